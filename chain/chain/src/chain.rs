@@ -1,3 +1,4 @@
+use std::cmp::min;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{Duration as TimeDuration, Instant};
@@ -20,6 +21,7 @@ use near_primitives::sharding::{
 };
 use near_primitives::transaction::{
     ExecutionOutcome, ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof, ExecutionStatus,
+    SignedTransaction,
 };
 use near_primitives::types::{
     AccountId, Balance, BlockExtra, BlockIndex, ChunkExtra, EpochId, Gas, ShardId, ValidatorStake,
@@ -35,7 +37,9 @@ use crate::error::{Error, ErrorKind};
 use crate::finality::{ApprovalVerificationError, FinalityGadget, FinalityGadgetQuorums};
 use crate::lightclient::get_epoch_block_producers_view;
 use crate::metrics;
-use crate::store::{ChainStore, ChainStoreAccess, ChainStoreUpdate, ShardInfo, StateSyncInfo};
+use crate::store::{
+    ChainStore, ChainStoreAccess, ChainStoreUpdate, GCMode, ShardInfo, StateSyncInfo,
+};
 use crate::types::{
     AcceptedBlock, ApplyTransactionResult, Block, BlockHeader, BlockStatus, Provenance,
     ReceiptList, ReceiptProofResponse, ReceiptResponse, RootProof, RuntimeAdapter,
@@ -60,6 +64,9 @@ const ACCEPTABLE_TIME_DIFFERENCE: i64 = 12 * 10;
 /// Over this number of blocks in advance if we are not chunk producer - route tx to upcoming validators.
 pub const TX_ROUTING_HEIGHT_HORIZON: BlockIndex = 4;
 
+/// Maximum number of heights garbage collected at once, to bound the time spent in a single call.
+const GC_HEIGHTS_LIMIT: BlockIndex = 100;
+
 /// The multiplier of the stake percentage when computing block weight
 pub const WEIGHT_MULTIPLIER: u128 = 1_000_000_000;
 
@@ -315,6 +322,7 @@ impl Chain {
                         genesis.header.inner_lite.timestamp,
                     );
                     store_update.save_head(&head)?;
+                    store_update.save_body_tail(&head);
                     store_update.save_sync_head(&head);
 
                     store_update.merge(state_store_update);
@@ -1778,6 +1786,117 @@ impl Chain {
         Ok(FinalExecutionOutcomeView { status, transaction: outcomes.pop().unwrap(), receipts })
     }
 
+    /// Returns outcomes of all transactions and receipts executed in the given block on the
+    /// shards this node tracks, each paired with the account it was executed on.
+    pub fn get_block_execution_outcomes(
+        &mut self,
+        block_hash: &CryptoHash,
+    ) -> Result<Vec<(AccountId, ExecutionOutcomeWithIdAndProof)>, Error> {
+        let mut result = vec![];
+        for shard_id in 0..self.runtime_adapter.num_shards() {
+            for (id, receiver_id) in self.store.get_outcome_receivers(block_hash, shard_id)? {
+                let outcome = self.store.get_execution_outcome(&id)?;
+                result.push((receiver_id, outcome.clone()));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Height of the first block of the epoch `num_epochs_to_keep - 1` epochs before the epoch of
+    /// the head. Data of the blocks below it can be garbage collected.
+    fn get_gc_stop_height(&mut self, num_epochs_to_keep: u64) -> Result<BlockIndex, Error> {
+        let genesis_height = self.genesis.inner_lite.height;
+        let head = self.head()?;
+        let mut epoch_start_height =
+            self.runtime_adapter.get_epoch_start_height(&head.last_block_hash)?;
+        for _ in 1..num_epochs_to_keep {
+            if epoch_start_height <= genesis_height + 1 {
+                return Ok(genesis_height);
+            }
+            let prev_hash = self.get_header_by_height(epoch_start_height)?.prev_hash;
+            epoch_start_height = self.runtime_adapter.get_epoch_start_height(&prev_hash)?;
+        }
+        Ok(epoch_start_height)
+    }
+
+    /// Garbage collects blocks, chunks, receipts, outcomes and state that are more than
+    /// `num_epochs_to_keep` epochs old. Forks below that height are discarded entirely.
+    /// Processes at most `GC_HEIGHTS_LIMIT` heights per call, advancing the chain tail.
+    pub fn clear_old_data(&mut self, num_epochs_to_keep: u64) -> Result<(), Error> {
+        let gc_stop_height = self.get_gc_stop_height(num_epochs_to_keep)?;
+        // Nodes started before garbage collection existed have no tail saved.
+        let tail_height = match self.store.tail() {
+            Ok(tail) => tail.height,
+            Err(err) => match err.kind() {
+                ErrorKind::DBNotFoundErr(_) => self.genesis.inner_lite.height,
+                _ => return Err(err),
+            },
+        };
+        let trie = self.runtime_adapter.get_trie();
+        for height in tail_height + 1..min(gc_stop_height, tail_height + 1 + GC_HEIGHTS_LIMIT) {
+            let canonical_hash = match self.store.get_block_hash_by_height(height) {
+                Ok(hash) => Some(hash),
+                Err(err) => match err.kind() {
+                    ErrorKind::DBNotFoundErr(_) => None,
+                    _ => return Err(err),
+                },
+            };
+            let mut block_hashes = self.store.get_all_block_hashes_by_height(height)?;
+            // Databases created before blocks were indexed by height only know the canonical ones.
+            if let Some(canonical_hash) = canonical_hash {
+                if !block_hashes.contains(&canonical_hash)
+                    && self.store.block_exists(&canonical_hash)?
+                {
+                    block_hashes.push(canonical_hash);
+                }
+            }
+            // Forks go first, see `TrieChanges` on the order of releasing states.
+            block_hashes.sort_by_key(|hash| Some(*hash) == canonical_hash);
+            for block_hash in block_hashes {
+                // Forks at this height may be cleared already, if the garbage collection stopped
+                // at the canonical block.
+                if !self.store.block_exists(&block_hash)? {
+                    continue;
+                }
+                let gc_mode = if Some(block_hash) == canonical_hash {
+                    GCMode::Canonical
+                } else {
+                    GCMode::Fork
+                };
+                let mut chain_store_update = self.store.store_update();
+                if let Err(err) =
+                    chain_store_update.clear_block_data(trie.clone(), &block_hash, gc_mode)
+                {
+                    if let ErrorKind::GCError(_) = err.kind() {
+                        // The tail stays below this block, so its data is kept as is.
+                        error!(
+                            target: "chain",
+                            "Stopped garbage collection at block {}: {}",
+                            block_hash,
+                            err
+                        );
+                        return Ok(());
+                    }
+                    return Err(err);
+                }
+                chain_store_update.commit()?;
+            }
+            let mut chain_store_update = self.store.store_update();
+            chain_store_update.clear_block_hashes_by_height(height);
+            if let Some(canonical_hash) = canonical_hash {
+                let header = chain_store_update.get_block_header(&canonical_hash)?.clone();
+                let prev_timestamp = chain_store_update
+                    .get_block_header(&header.prev_hash)
+                    .map(|prev_header| prev_header.inner_lite.timestamp)
+                    .unwrap_or(header.inner_lite.timestamp);
+                chain_store_update
+                    .save_body_tail(&Tip::from_header_and_prev_timestamp(&header, prev_timestamp));
+            }
+            chain_store_update.commit()?;
+        }
+        Ok(())
+    }
+
     /// Find a validator that is responsible for a given shard to forward requests to
     pub fn find_validator_for_forwarding(&self, shard_id: ShardId) -> Result<AccountId, Error> {
         let head = self.head()?;
@@ -2064,9 +2183,11 @@ impl<'a> ChainUpdate<'a> {
                 return Ok(true);
             }
         }
-        for part_id in 0..self.runtime_adapter.num_total_parts(&parent_hash) {
-            if &Some(self.runtime_adapter.get_part_owner(&parent_hash, part_id as u64)?) == me {
-                return Ok(true);
+        for shard_id in 0..self.runtime_adapter.num_shards() {
+            for part_id in 0..self.runtime_adapter.num_total_parts(&parent_hash, shard_id) {
+                if &Some(self.runtime_adapter.get_part_owner(&parent_hash, part_id as u64)?) == me {
+                    return Ok(true);
+                }
             }
         }
         Ok(false)
@@ -2337,6 +2458,11 @@ impl<'a> ChainUpdate<'a> {
 
                     let (outcome_root, outcome_paths) =
                         ApplyTransactionResult::compute_outcomes_proof(&apply_result.outcomes);
+                    let outcome_receivers = collect_outcome_receivers(
+                        &chunk.transactions,
+                        &receipts,
+                        &apply_result.outcomes,
+                    );
 
                     self.chain_store_update.save_trie_changes(apply_result.trie_changes);
                     // Save state root after applying transactions.
@@ -2372,6 +2498,16 @@ impl<'a> ChainUpdate<'a> {
                         outgoing_receipts,
                     );
                     // Save receipt and transaction results.
+                    self.chain_store_update.save_outcome_receivers(
+                        &block.hash(),
+                        shard_id,
+                        outcome_receivers,
+                    );
+                    self.chain_store_update.save_state_changes(
+                        &block.hash(),
+                        shard_id,
+                        apply_result.state_changes,
+                    );
                     self.chain_store_update.save_outcomes_with_proofs(
                         &block.hash(),
                         apply_result.outcomes,
@@ -2402,6 +2538,11 @@ impl<'a> ChainUpdate<'a> {
                         .map_err(|e| ErrorKind::Other(e.to_string()))?;
 
                     self.chain_store_update.save_trie_changes(apply_result.trie_changes);
+                    self.chain_store_update.save_state_changes(
+                        &block.hash(),
+                        shard_id,
+                        apply_result.state_changes,
+                    );
                     new_extra.state_root = apply_result.new_root;
 
                     self.chain_store_update.save_chunk_extra(&block.hash(), shard_id, new_extra);
@@ -3083,6 +3224,8 @@ impl<'a> ChainUpdate<'a> {
 
         let (outcome_root, outcome_proofs) =
             ApplyTransactionResult::compute_outcomes_proof(&apply_result.outcomes);
+        let outcome_receivers =
+            collect_outcome_receivers(&chunk.transactions, &receipts, &apply_result.outcomes);
 
         self.chain_store_update.save_chunk(&chunk.chunk_hash, chunk.clone());
 
@@ -3110,6 +3253,16 @@ impl<'a> ChainUpdate<'a> {
             outgoing_receipts,
         );
         // Saving transaction results.
+        self.chain_store_update.save_outcome_receivers(
+            &block_header.hash,
+            shard_id,
+            outcome_receivers,
+        );
+        self.chain_store_update.save_state_changes(
+            &block_header.hash,
+            shard_id,
+            apply_result.state_changes,
+        );
         self.chain_store_update.save_outcomes_with_proofs(
             &block_header.hash,
             apply_result.outcomes,
@@ -3165,6 +3318,11 @@ impl<'a> ChainUpdate<'a> {
         )?;
 
         self.chain_store_update.save_trie_changes(apply_result.trie_changes);
+        self.chain_store_update.save_state_changes(
+            &block_header.hash(),
+            shard_id,
+            apply_result.state_changes,
+        );
         chunk_extra.state_root = apply_result.new_root;
 
         self.chain_store_update.save_chunk_extra(&block_header.hash(), shard_id, chunk_extra);
@@ -3225,3 +3383,36 @@ pub fn collect_receipts_from_response(
     let receipt_proofs = &receipt_proof_response.iter().map(|x| x.1.clone()).flatten().collect();
     collect_receipts(receipt_proofs)
 }
+
+/// Pairs ids of the outcomes with the accounts they were executed on.
+/// Local receipts are not part of the applied receipts, but they always go to the receiver of the
+/// transaction that produced them.
+pub fn collect_outcome_receivers(
+    transactions: &[SignedTransaction],
+    receipts: &[Receipt],
+    outcomes: &[ExecutionOutcomeWithId],
+) -> Vec<(CryptoHash, AccountId)> {
+    let mut receivers = HashMap::new();
+    for transaction in transactions {
+        let tx_hash = transaction.get_hash();
+        receivers.insert(tx_hash, transaction.transaction.receiver_id.clone());
+    }
+    for receipt in receipts {
+        receivers.insert(receipt.receipt_id, receipt.receiver_id.clone());
+    }
+    for outcome_with_id in outcomes {
+        if let Some(receiver_id) = receivers.get(&outcome_with_id.id).cloned() {
+            for receipt_id in outcome_with_id.outcome.receipt_ids.iter() {
+                receivers.entry(*receipt_id).or_insert_with(|| receiver_id.clone());
+            }
+        }
+    }
+    outcomes
+        .iter()
+        .filter_map(|outcome_with_id| {
+            receivers
+                .get(&outcome_with_id.id)
+                .map(|receiver_id| (outcome_with_id.id, receiver_id.clone()))
+        })
+        .collect()
+}
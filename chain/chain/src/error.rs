@@ -151,6 +151,9 @@ pub enum ErrorKind {
     /// Storage error. Used for internal passing the error.
     #[fail(display = "Storage Error")]
     StorageError,
+    /// Data of a block can't be garbage collected, as the state it releases is inconsistent.
+    #[fail(display = "GC Error: {}", _0)]
+    GCError(String),
     /// Anything else
     #[fail(display = "Other Error: {}", _0)]
     Other(String),
@@ -198,6 +201,7 @@ impl Error {
             | ErrorKind::EpochOutOfBounds
             | ErrorKind::ChallengedBlockOnChain
             | ErrorKind::StorageError
+            | ErrorKind::GCError(_)
             | ErrorKind::DBNotFoundErr(_) => false,
             ErrorKind::InvalidBlockPastTime(_, _)
             | ErrorKind::InvalidBlockFutureTime(_)
@@ -239,7 +243,10 @@ impl Error {
 
     pub fn is_error(&self) -> bool {
         match self.kind() {
-            ErrorKind::IOErr(_) | ErrorKind::Other(_) | ErrorKind::DBNotFoundErr(_) => true,
+            ErrorKind::IOErr(_)
+            | ErrorKind::Other(_)
+            | ErrorKind::GCError(_)
+            | ErrorKind::DBNotFoundErr(_) => true,
             _ => false,
         }
     }
@@ -1,11 +1,15 @@
+use std::cmp::max;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::TryFrom;
+use std::fs;
 use std::io;
+use std::path::Path;
 use std::sync::Arc;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use cached::{Cached, SizedCache};
 use chrono::Utc;
+use log::error;
 
 use near_primitives::hash::CryptoHash;
 use near_primitives::receipt::Receipt;
@@ -16,17 +20,21 @@ use near_primitives::transaction::{ExecutionOutcomeWithId, ExecutionOutcomeWithI
 use near_primitives::types::{AccountId, BlockExtra, BlockIndex, ChunkExtra, EpochId, ShardId};
 use near_primitives::utils::{index_to_bytes, to_timestamp};
 use near_store::{
-    read_with_cache, ColBlock, ColBlockExtra, ColBlockHeader, ColBlockIndex, ColBlockMisc,
-    ColBlockPerHeight, ColBlocksToCatchup, ColChallengedBlocks, ColChunkExtra, ColChunks,
-    ColEpochLightClientBlocks, ColIncomingReceipts, ColInvalidChunks, ColLastApprovalPerAccount,
-    ColLastBlockWithNewChunk, ColMyLastApprovalsPerChain, ColNextBlockHashes,
-    ColNextBlockWithNewChunk, ColOutgoingReceipts, ColPartialChunks, ColReceiptIdToShardId,
-    ColStateDlInfos, ColTransactionResult, Store, StoreUpdate, WrappedTrieChanges,
+    column_names, detect_backend, read_with_cache, ColBlock, ColBlockExtra, ColBlockHashesByHeight,
+    ColBlockHeader, ColBlockIndex, ColBlockMisc, ColBlockPerHeight, ColBlocksToCatchup,
+    ColChallengedBlocks, ColChunkExtra, ColChunks, ColEpochLightClientBlocks, ColIncomingReceipts,
+    ColInvalidChunks, ColLastApprovalPerAccount, ColLastBlockWithNewChunk,
+    ColMyLastApprovalsPerChain, ColNextBlockHashes, ColNextBlockWithNewChunk,
+    ColOutcomesByBlockHash, ColOutgoingReceipts, ColPartialChunks, ColReceiptIdToShardId,
+    ColStateChanges, ColStateDlInfos, ColTransactionResult, ColTrieChanges, SnapshotManifest,
+    Store, StoreUpdate, Trie, TrieChanges, WrappedTrieChanges,
 };
 
 use crate::byzantine_assert;
 use crate::error::{Error, ErrorKind};
-use crate::types::{Block, BlockHeader, LatestKnown, ReceiptProofResponse, ReceiptResponse, Tip};
+use crate::types::{
+    Block, BlockHeader, LatestKnown, ReceiptProofResponse, ReceiptResponse, StateChanges, Tip,
+};
 use near_primitives::block::{Approval, Weight};
 use near_primitives::errors::InvalidTxError;
 use near_primitives::merkle::MerklePath;
@@ -54,6 +62,15 @@ fn get_block_shard_id(block_hash: &CryptoHash, shard_id: ShardId) -> Vec<u8> {
     res
 }
 
+/// How the state of a garbage collected block is released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GCMode {
+    /// Block is on the canonical chain: the state of its parent is not needed anymore.
+    Canonical,
+    /// Block is on a fork that will never be canonical: its own state is not needed.
+    Fork,
+}
+
 /// Contains the information that is used to sync state for shards as epochs switch
 #[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize)]
 pub struct StateSyncInfo {
@@ -353,6 +370,8 @@ pub struct ChainStore {
     next_block_with_new_chunk: SizedCache<Vec<u8>, CryptoHash>,
     /// Shard id to last block that contains a new chunk for this shard.
     last_block_with_new_chunk: SizedCache<Vec<u8>, CryptoHash>,
+    /// Archive nodes are never garbage collected, so they don't keep trie changes around.
+    archive: bool,
 }
 
 pub fn option_to_not_found<T>(res: io::Result<Option<T>>, field_name: &str) -> Result<T, Error> {
@@ -388,13 +407,125 @@ impl ChainStore {
             receipt_id_to_shard_id: SizedCache::with_size(CHUNK_CACHE_SIZE),
             next_block_with_new_chunk: SizedCache::with_size(CHUNK_CACHE_SIZE),
             last_block_with_new_chunk: SizedCache::with_size(CHUNK_CACHE_SIZE),
+            archive: false,
         }
     }
 
+    pub fn set_archive(&mut self, archive: bool) {
+        self.archive = archive;
+    }
+
     pub fn owned_store(&self) -> Arc<Store> {
         self.store.clone()
     }
 
+    /// Takes a snapshot of the whole store at `path` without stopping the node, and writes its
+    /// manifest. The snapshot is cut at the last final block: blocks above it may still end up
+    /// on an abandoned fork, so they are removed from the snapshot together with their state.
+    /// Fails on archive nodes if there are blocks above the final one, as archive nodes don't keep
+    /// the trie changes needed to remove their state.
+    pub fn create_snapshot(&self, chain_id: &str, path: &Path) -> Result<SnapshotManifest, Error> {
+        let snapshot_store = self.store.checkpoint(path)?;
+        let result = Self::cut_snapshot(snapshot_store, chain_id, path);
+        if result.is_err() {
+            if let Err(err) = fs::remove_dir_all(path) {
+                error!(target: "chain", "Failed to remove {}: {}", path.display(), err);
+            }
+        }
+        result
+    }
+
+    fn cut_snapshot(
+        snapshot_store: Arc<Store>,
+        chain_id: &str,
+        path: &Path,
+    ) -> Result<SnapshotManifest, Error> {
+        let mut snapshot = ChainStore::new(snapshot_store.clone());
+        let head = snapshot.head()?;
+        let mut final_hash =
+            snapshot.get_block_header(&head.last_block_hash)?.inner_rest.last_quorum_pre_commit;
+        if final_hash == CryptoHash::default() {
+            // Until some block is final, only the genesis is.
+            final_hash = head.last_block_hash;
+            loop {
+                let prev_hash = snapshot.get_block_header(&final_hash)?.prev_hash;
+                if prev_hash == CryptoHash::default() {
+                    break;
+                }
+                final_hash = prev_hash;
+            }
+        }
+        let final_header = snapshot.get_block_header(&final_hash)?.clone();
+        let final_prev_timestamp = snapshot
+            .get_block_header(&final_header.prev_hash)
+            .map(|prev_header| prev_header.inner_lite.timestamp)
+            .unwrap_or(final_header.inner_lite.timestamp);
+        let final_tip = Tip::from_header_and_prev_timestamp(&final_header, final_prev_timestamp);
+
+        // Lighter forks may go above all the heads.
+        let max_block_height = snapshot
+            .store
+            .iter(ColBlockHashesByHeight)
+            .map(|(key, _)| {
+                let mut height = [0; 8];
+                height.copy_from_slice(&key);
+                BlockIndex::from_le_bytes(height)
+            })
+            .max()
+            .unwrap_or_default();
+        let max_height = max(
+            max(head.height, snapshot.header_head()?.height),
+            max(snapshot.get_latest_known()?.height, max_block_height),
+        );
+        let mut cut_blocks = vec![];
+        for height in final_tip.height + 1..=max_height {
+            for block_hash in snapshot.get_all_block_hashes_by_height(height)? {
+                let num_shards = snapshot.get_block(&block_hash)?.chunks.len() as ShardId;
+                let mut has_trie_changes = false;
+                for shard_id in 0..num_shards {
+                    let block_shard_id = get_block_shard_id(&block_hash, shard_id);
+                    has_trie_changes |= snapshot.store.exists(ColTrieChanges, &block_shard_id)?;
+                }
+                cut_blocks.push((block_hash, has_trie_changes));
+            }
+        }
+        // Archive nodes never save trie changes, so the state of the cut blocks can't be removed.
+        if !cut_blocks.is_empty()
+            && cut_blocks.iter().all(|(_, has_trie_changes)| !has_trie_changes)
+        {
+            return Err(ErrorKind::Other(
+                "Archive nodes don't keep trie changes to cut a snapshot at the final block"
+                    .to_string(),
+            )
+            .into());
+        }
+
+        let trie = Arc::new(Trie::new(snapshot_store));
+        for (block_hash, _) in cut_blocks {
+            let mut store_update = snapshot.store_update();
+            store_update.clear_block_data(trie.clone(), &block_hash, GCMode::Fork)?;
+            store_update.commit()?;
+        }
+        let mut store_update = snapshot.store_update();
+        for height in final_tip.height + 1..=max_height {
+            store_update.clear_block_hashes_by_height(height);
+        }
+        store_update.reset_heads(&final_tip, max_height)?;
+        store_update.commit()?;
+
+        let manifest = SnapshotManifest {
+            chain_id: chain_id.to_string(),
+            backend: detect_backend(path),
+            head_height: final_tip.height,
+            head_hash: final_hash,
+            final_height: final_tip.height,
+            final_hash,
+            columns: column_names(),
+        };
+        manifest.write(path)?;
+        Ok(manifest)
+    }
+
     pub fn store_update(&mut self) -> ChainStoreUpdate {
         ChainStoreUpdate::new(self)
     }
@@ -436,6 +567,40 @@ impl ChainStore {
         }
     }
 
+    /// Ids of the outcomes computed for given shard in the block together with the account each
+    /// of them was executed on. Empty if the shard was not applied by this node.
+    pub fn get_outcome_receivers(
+        &self,
+        block_hash: &CryptoHash,
+        shard_id: ShardId,
+    ) -> Result<Vec<(CryptoHash, AccountId)>, Error> {
+        Ok(self
+            .store
+            .get_ser(ColOutcomesByBlockHash, &get_block_shard_id(block_hash, shard_id))?
+            .unwrap_or_default())
+    }
+
+    /// Changes of the subscribed state prefixes of the given shard in the block. Empty if the shard
+    /// was not applied by this node or nothing under the prefixes has changed.
+    pub fn get_state_changes(
+        &self,
+        block_hash: &CryptoHash,
+        shard_id: ShardId,
+    ) -> Result<StateChanges, Error> {
+        Ok(self
+            .store
+            .get_ser(ColStateChanges, &get_block_shard_id(block_hash, shard_id))?
+            .unwrap_or_default())
+    }
+
+    /// Hashes of all the blocks at the given height which bodies are stored, including forks.
+    pub fn get_all_block_hashes_by_height(
+        &self,
+        height: BlockIndex,
+    ) -> Result<Vec<CryptoHash>, Error> {
+        Ok(self.store.get_ser(ColBlockHashesByHeight, &index_to_bytes(height))?.unwrap_or_default())
+    }
+
     pub fn check_blocks_on_same_chain(
         &mut self,
         cur_header: &BlockHeader,
@@ -863,6 +1028,8 @@ struct ChainStoreCacheUpdate {
     outgoing_receipts: HashMap<(CryptoHash, ShardId), Vec<Receipt>>,
     incoming_receipts: HashMap<(CryptoHash, ShardId), Vec<ReceiptProof>>,
     outcomes: HashMap<CryptoHash, ExecutionOutcomeWithIdAndProof>,
+    outcome_receivers: HashMap<(CryptoHash, ShardId), Vec<(CryptoHash, AccountId)>>,
+    state_changes: HashMap<(CryptoHash, ShardId), StateChanges>,
     invalid_chunks: HashMap<ChunkHash, EncodedShardChunk>,
     receipt_id_to_shard_id: HashMap<CryptoHash, ShardId>,
     next_block_with_new_chunk: HashMap<(CryptoHash, ShardId), CryptoHash>,
@@ -888,6 +1055,8 @@ impl ChainStoreCacheUpdate {
             outgoing_receipts: HashMap::default(),
             incoming_receipts: HashMap::default(),
             outcomes: Default::default(),
+            outcome_receivers: HashMap::default(),
+            state_changes: HashMap::default(),
             invalid_chunks: Default::default(),
             receipt_id_to_shard_id: Default::default(),
             next_block_with_new_chunk: Default::default(),
@@ -1371,6 +1540,28 @@ impl<'a> ChainStoreUpdate<'a> {
         Ok(())
     }
 
+    /// Moves all the heads and the latest known height down to the given tip, and forgets the
+    /// canonical blocks above it up to `max_height`.
+    pub fn reset_heads(&mut self, t: &Tip, max_height: BlockIndex) -> Result<(), Error> {
+        for height in t.height + 1..=max_height {
+            self.chain_store_cache_update.block_index.insert(height, None);
+        }
+        self.head = Some(t.clone());
+        self.header_head = Some(t.clone());
+        self.sync_head = Some(t.clone());
+        let mut store_update = self.store().store_update();
+        store_update.delete(ColNextBlockHashes, t.last_block_hash.as_ref());
+        self.chain_store.next_block_hashes.cache_remove(&t.last_block_hash.as_ref().to_vec());
+        store_update.set_ser(
+            ColBlockMisc,
+            LATEST_KNOWN_KEY,
+            &LatestKnown { height: t.height, seen: to_timestamp(Utc::now()) },
+        )?;
+        self.chain_store.latest_known = None;
+        self.merge(store_update);
+        Ok(())
+    }
+
     /// Save "sync" head.
     pub fn save_sync_head(&mut self, t: &Tip) {
         self.sync_head = Some(t.clone());
@@ -1489,6 +1680,33 @@ impl<'a> ChainStoreUpdate<'a> {
         }
     }
 
+    /// Saves ids of the outcomes computed for the shard in the given block, each paired with the
+    /// account the transaction or receipt was executed on.
+    pub fn save_outcome_receivers(
+        &mut self,
+        block_hash: &CryptoHash,
+        shard_id: ShardId,
+        outcome_receivers: Vec<(CryptoHash, AccountId)>,
+    ) {
+        self.chain_store_cache_update
+            .outcome_receivers
+            .insert((*block_hash, shard_id), outcome_receivers);
+    }
+
+    /// Saves changes of the subscribed state prefixes of the shard in the given block.
+    pub fn save_state_changes(
+        &mut self,
+        block_hash: &CryptoHash,
+        shard_id: ShardId,
+        state_changes: StateChanges,
+    ) {
+        if !state_changes.is_empty() {
+            self.chain_store_cache_update
+                .state_changes
+                .insert((*block_hash, shard_id), state_changes);
+        }
+    }
+
     pub fn save_outcome_with_proof(
         &mut self,
         id: CryptoHash,
@@ -1542,6 +1760,91 @@ impl<'a> ChainStoreUpdate<'a> {
         self.chain_store_cache_update.last_block_with_new_chunk.insert(shard_id, block_hash);
     }
 
+    /// Removes the body of the given block together with the data computed while applying it.
+    /// Depending on `gc_mode` either the state before the block or the state of the block itself
+    /// is released. Must be committed before the next block is cleared, as trie refcounts are
+    /// read from the storage.
+    pub fn clear_block_data(
+        &mut self,
+        trie: Arc<Trie>,
+        block_hash: &CryptoHash,
+        gc_mode: GCMode,
+    ) -> Result<(), Error> {
+        let block = self.get_block(block_hash)?.clone();
+        let height = block.header.inner_lite.height;
+        let mut store_update = self.store().store_update();
+        for (shard_id, chunk_header) in block.chunks.iter().enumerate() {
+            let shard_id = shard_id as ShardId;
+            let block_shard_id = get_block_shard_id(block_hash, shard_id);
+            if let Some(trie_changes) =
+                self.store().get_ser::<TrieChanges>(ColTrieChanges, &block_shard_id)?
+            {
+                let trie_changes =
+                    WrappedTrieChanges::new(trie.clone(), trie_changes, shard_id, *block_hash);
+                match gc_mode {
+                    GCMode::Canonical => trie_changes.deletions_into(&mut store_update),
+                    GCMode::Fork => trie_changes.revert_insertions_into(&mut store_update),
+                }
+                .map_err(|err| ErrorKind::GCError(err.to_string()))?;
+                store_update.delete(ColTrieChanges, &block_shard_id);
+            }
+            // Outcomes are keyed by transaction or receipt id only, so the same keys may be used
+            // by the outcomes of the same transactions and receipts in other blocks. Only the
+            // outcomes saved for this block are deleted.
+            for (outcome_id, _) in self.chain_store.get_outcome_receivers(block_hash, shard_id)? {
+                let outcome = self.store().get_ser::<ExecutionOutcomeWithIdAndProof>(
+                    ColTransactionResult,
+                    outcome_id.as_ref(),
+                )?;
+                if outcome.map_or(false, |outcome| outcome.block_hash == *block_hash) {
+                    store_update.delete(ColTransactionResult, outcome_id.as_ref());
+                    self.chain_store.outcomes.cache_remove(&outcome_id.into());
+                }
+                if gc_mode == GCMode::Canonical {
+                    store_update.delete(ColReceiptIdToShardId, outcome_id.as_ref());
+                    self.chain_store.receipt_id_to_shard_id.cache_remove(&outcome_id.into());
+                }
+            }
+            for col in [
+                ColChunkExtra,
+                ColOutgoingReceipts,
+                ColIncomingReceipts,
+                ColOutcomesByBlockHash,
+                ColNextBlockWithNewChunk,
+                ColStateChanges,
+            ]
+            .iter()
+            {
+                store_update.delete(*col, &block_shard_id);
+            }
+            self.chain_store.chunk_extras.cache_remove(&block_shard_id);
+            self.chain_store.outgoing_receipts.cache_remove(&block_shard_id);
+            self.chain_store.incoming_receipts.cache_remove(&block_shard_id);
+            self.chain_store.next_block_with_new_chunk.cache_remove(&block_shard_id);
+            if chunk_header.height_included == height {
+                let chunk_hash = chunk_header.chunk_hash();
+                store_update.delete(ColChunks, chunk_hash.as_ref());
+                store_update.delete(ColPartialChunks, chunk_hash.as_ref());
+                self.chain_store.chunks.cache_remove(&chunk_hash.as_ref().to_vec());
+                self.chain_store.partial_chunks.cache_remove(&chunk_hash.as_ref().to_vec());
+            }
+        }
+        store_update.delete(ColBlock, block_hash.as_ref());
+        store_update.delete(ColBlockExtra, block_hash.as_ref());
+        store_update.delete(ColChallengedBlocks, block_hash.as_ref());
+        self.chain_store.blocks.cache_remove(&block_hash.into());
+        self.chain_store.block_extras.cache_remove(&block_hash.into());
+        self.merge(store_update);
+        Ok(())
+    }
+
+    /// Forgets the blocks at the given height, once all of them are garbage collected.
+    pub fn clear_block_hashes_by_height(&mut self, height: BlockIndex) {
+        let mut store_update = self.store().store_update();
+        store_update.delete(ColBlockHashesByHeight, &index_to_bytes(height));
+        self.merge(store_update);
+    }
+
     /// Merge another StoreUpdate into this one
     pub fn merge(&mut self, store_update: StoreUpdate) {
         self.store_updates.push(store_update);
@@ -1583,6 +1886,21 @@ impl<'a> ChainStoreUpdate<'a> {
         for hash in self.chain_store_cache_update.deleted_blocks.iter() {
             store_update.delete(ColBlock, hash.as_ref());
         }
+        let mut block_hashes_by_height: HashMap<BlockIndex, Vec<CryptoHash>> = HashMap::new();
+        for (hash, block) in self.chain_store_cache_update.blocks.iter() {
+            let height = block.header.inner_lite.height;
+            if !block_hashes_by_height.contains_key(&height) {
+                let hashes = self.chain_store.get_all_block_hashes_by_height(height)?;
+                block_hashes_by_height.insert(height, hashes);
+            }
+            let hashes = block_hashes_by_height.get_mut(&height).unwrap();
+            if !hashes.contains(hash) {
+                hashes.push(*hash);
+            }
+        }
+        for (height, hashes) in block_hashes_by_height.iter() {
+            store_update.set_ser(ColBlockHashesByHeight, &index_to_bytes(*height), hashes)?;
+        }
         for (hash, header) in self.chain_store_cache_update.headers.iter() {
             let map = match self.chain_store.get_any_block_hash_by_height(header.inner_lite.height)
             {
@@ -1680,6 +1998,24 @@ impl<'a> ChainStoreUpdate<'a> {
         for (hash, outcome) in self.chain_store_cache_update.outcomes.iter() {
             store_update.set_ser(ColTransactionResult, hash.as_ref(), outcome)?;
         }
+        for ((block_hash, shard_id), outcome_receivers) in
+            self.chain_store_cache_update.outcome_receivers.iter()
+        {
+            store_update.set_ser(
+                ColOutcomesByBlockHash,
+                &get_block_shard_id(block_hash, *shard_id),
+                outcome_receivers,
+            )?;
+        }
+        for ((block_hash, shard_id), state_changes) in
+            self.chain_store_cache_update.state_changes.iter()
+        {
+            store_update.set_ser(
+                ColStateChanges,
+                &get_block_shard_id(block_hash, *shard_id),
+                state_changes,
+            )?;
+        }
         for (receipt_id, shard_id) in self.chain_store_cache_update.receipt_id_to_shard_id.iter() {
             store_update.set_ser(ColReceiptIdToShardId, receipt_id.as_ref(), shard_id)?;
         }
@@ -1704,7 +2040,14 @@ impl<'a> ChainStoreUpdate<'a> {
             trie_changes
                 .insertions_into(&mut store_update)
                 .map_err(|err| ErrorKind::Other(err.to_string()))?;
-            // TODO: save deletions separately for garbage collection.
+            // Deletions are applied once the state before this block is garbage collected.
+            if !self.chain_store.archive {
+                store_update.set_ser(
+                    ColTrieChanges,
+                    &get_block_shard_id(trie_changes.block_hash(), trie_changes.shard_id()),
+                    trie_changes.trie_changes(),
+                )?;
+            }
         }
         let mut affected_catchup_blocks = HashSet::new();
         for (prev_hash, hash) in self.remove_blocks_to_catchup.drain(..) {
@@ -1805,6 +2148,7 @@ impl<'a> ChainStoreUpdate<'a> {
             outgoing_receipts,
             incoming_receipts,
             outcomes,
+            outcome_receivers: _,
             invalid_chunks,
             receipt_id_to_shard_id,
             next_block_with_new_chunk,
@@ -31,7 +31,8 @@ use near_primitives::types::{
     ValidatorStake,
 };
 use near_primitives::views::{
-    AccessKeyInfoView, AccessKeyList, EpochValidatorInfo, QueryResponse, QueryResponseKind,
+    AccessKeyInfoView, AccessKeyList, EpochValidatorInfo, ExecutionOutcomeWithIdView,
+    QueryResponse, QueryResponseKind, SimulationResultView,
 };
 use near_store::test_utils::create_test_store;
 use near_store::{
@@ -262,6 +263,10 @@ impl RuntimeAdapter for KeyValueRuntime {
         )
     }
 
+    fn get_trie(&self) -> Arc<Trie> {
+        self.trie.clone()
+    }
+
     fn verify_block_signature(&self, header: &BlockHeader) -> Result<(), Error> {
         let validators = &self.validators
             [self.get_epoch_and_valset(header.prev_hash).map_err(|err| err.to_string())?.1];
@@ -347,12 +352,12 @@ impl RuntimeAdapter for KeyValueRuntime {
         self.num_shards
     }
 
-    fn num_total_parts(&self, parent_hash: &CryptoHash) -> usize {
+    fn num_total_parts(&self, parent_hash: &CryptoHash, shard_id: ShardId) -> usize {
         let height = self.get_prev_height(parent_hash).unwrap();
-        1 + self.num_data_parts(parent_hash) * (2 + (height as usize) % 2)
+        1 + self.num_data_parts(parent_hash, shard_id) * (2 + (height as usize) % 2)
     }
 
-    fn num_data_parts(&self, parent_hash: &CryptoHash) -> usize {
+    fn num_data_parts(&self, parent_hash: &CryptoHash, _shard_id: ShardId) -> usize {
         let height = self.get_prev_height(parent_hash).unwrap();
         // Test changing number of data parts
         12 + 2 * ((height as usize) % 4)
@@ -366,8 +371,9 @@ impl RuntimeAdapter for KeyValueRuntime {
         let validators = &self.validators[self.get_epoch_and_valset(*parent_hash)?.1];
         // if we don't use data_parts and total_parts as part of the formula here, the part owner
         //     would not depend on height, and tests wouldn't catch passing wrong height here
-        let idx =
-            part_id as usize + self.num_data_parts(parent_hash) + self.num_total_parts(parent_hash);
+        let idx = part_id as usize
+            + self.num_data_parts(parent_hash, 0)
+            + self.num_total_parts(parent_hash, 0);
         Ok(validators[idx as usize % validators.len()].account_id.clone())
     }
 
@@ -478,7 +484,7 @@ impl RuntimeAdapter for KeyValueRuntime {
         _block_index: BlockIndex,
         _block_timestamp: u64,
         _prev_block_hash: &CryptoHash,
-        _block_hash: &CryptoHash,
+        block_hash: &CryptoHash,
         receipts: &[Receipt],
         transactions: &[SignedTransaction],
         _last_validator_proposals: &[ValidatorStake],
@@ -602,6 +608,7 @@ impl RuntimeAdapter for KeyValueRuntime {
                         logs: vec![],
                         receipt_ids: new_receipt_hashes,
                         gas_burnt: 0,
+                        events: vec![],
                     },
                 });
             }
@@ -632,6 +639,8 @@ impl RuntimeAdapter for KeyValueRuntime {
             trie_changes: WrappedTrieChanges::new(
                 self.trie.clone(),
                 TrieChanges::empty(state_root),
+                shard_id,
+                *block_hash,
             ),
             new_root: state_root,
             outcomes: tx_results,
@@ -642,6 +651,7 @@ impl RuntimeAdapter for KeyValueRuntime {
             total_validator_reward: 0,
             total_balance_burnt: 0,
             proof: None,
+            state_changes: vec![],
         })
     }
 
@@ -672,6 +682,7 @@ impl RuntimeAdapter for KeyValueRuntime {
         _block_hash: &CryptoHash,
         path: Vec<&str>,
         _data: &[u8],
+        _include_proof: bool,
     ) -> Result<QueryResponse, Box<dyn std::error::Error>> {
         match path[0] {
             "account" => {
@@ -692,6 +703,7 @@ impl RuntimeAdapter for KeyValueRuntime {
                         .into(),
                     ),
                     block_height,
+                    proof: None,
                 })
             }
             "access_key" if path.len() == 2 => Ok(QueryResponse {
@@ -702,10 +714,12 @@ impl RuntimeAdapter for KeyValueRuntime {
                     }],
                 }),
                 block_height,
+                proof: None,
             }),
             "access_key" if path.len() == 3 => Ok(QueryResponse {
                 kind: QueryResponseKind::AccessKey(AccessKey::full_access().into()),
                 block_height,
+                proof: None,
             }),
             _ => {
                 panic!("RuntimeAdapter.query mockup received unexpected query: {:?}", path);
@@ -713,6 +727,34 @@ impl RuntimeAdapter for KeyValueRuntime {
         }
     }
 
+    fn simulate_transaction(
+        &self,
+        _shard_id: ShardId,
+        _state_root: &StateRoot,
+        _block_index: BlockIndex,
+        _block_timestamp: u64,
+        _gas_price: Balance,
+        transaction: &SignedTransaction,
+    ) -> Result<SimulationResultView, Box<dyn std::error::Error>> {
+        Ok(SimulationResultView {
+            outcomes: vec![ExecutionOutcomeWithIdView {
+                id: transaction.get_hash(),
+                outcome: ExecutionOutcome {
+                    status: ExecutionStatus::SuccessValue(vec![]),
+                    logs: vec![],
+                    receipt_ids: vec![],
+                    gas_burnt: 0,
+                    events: vec![],
+                }
+                .into(),
+                proof: vec![],
+            }],
+            gas_burnt: 0,
+            logs: vec![],
+            unprocessed_receipts: vec![],
+        })
+    }
+
     fn obtain_state_part(&self, state_root: &StateRoot, part_id: u64, num_parts: u64) -> Vec<u8> {
         assert!(part_id < num_parts);
         let state = self.state.read().unwrap().get(&state_root).unwrap().clone();
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 
@@ -15,8 +16,8 @@ use near_primitives::types::{
     AccountId, Balance, BlockIndex, EpochId, Gas, MerkleHash, ShardId, StateRoot, StateRootNode,
     ValidatorStake,
 };
-use near_primitives::views::{EpochValidatorInfo, QueryResponse};
-use near_store::{PartialStorage, StoreUpdate, WrappedTrieChanges};
+use near_primitives::views::{EpochValidatorInfo, QueryResponse, SimulationResultView};
+use near_store::{PartialStorage, StoreUpdate, Trie, WrappedTrieChanges};
 
 use crate::chain::WEIGHT_MULTIPLIER;
 use crate::error::Error;
@@ -82,6 +83,10 @@ pub struct AcceptedBlock {
 /// Map of shard to list of receipts to send to it.
 pub type ReceiptResult = HashMap<ShardId, Vec<Receipt>>;
 
+/// Changes of the state keys under the subscribed prefixes, sorted by key. `None` means the key
+/// was deleted.
+pub type StateChanges = Vec<(Vec<u8>, Option<Vec<u8>>)>;
+
 pub struct ApplyTransactionResult {
     pub trie_changes: WrappedTrieChanges,
     pub new_root: StateRoot,
@@ -93,6 +98,7 @@ pub struct ApplyTransactionResult {
     pub total_validator_reward: Balance,
     pub total_balance_burnt: Balance,
     pub proof: Option<PartialStorage>,
+    pub state_changes: StateChanges,
 }
 
 impl ApplyTransactionResult {
@@ -116,6 +122,9 @@ pub trait RuntimeAdapter: Send + Sync {
     /// StoreUpdate can be discarded if the chain past the genesis.
     fn genesis_state(&self) -> (StoreUpdate, Vec<StateRoot>);
 
+    /// Trie over the state of all shards, used to release state of old blocks.
+    fn get_trie(&self) -> Arc<Trie>;
+
     /// Verify block producer validity
     fn verify_block_signature(&self, header: &BlockHeader) -> Result<(), Error>;
 
@@ -235,9 +244,11 @@ pub trait RuntimeAdapter: Send + Sync {
     /// Get current number of shards.
     fn num_shards(&self) -> ShardId;
 
-    fn num_total_parts(&self, parent_hash: &CryptoHash) -> usize;
+    /// Number of parts the chunks of the given shard are encoded into.
+    fn num_total_parts(&self, parent_hash: &CryptoHash, shard_id: ShardId) -> usize;
 
-    fn num_data_parts(&self, parent_hash: &CryptoHash) -> usize;
+    /// Number of parts enough to reconstruct the chunks of the given shard.
+    fn num_data_parts(&self, parent_hash: &CryptoHash, shard_id: ShardId) -> usize;
 
     /// Account Id to Shard Id mapping, given current number of shards.
     fn account_id_to_shard_id(&self, account_id: &AccountId) -> ShardId;
@@ -378,7 +389,8 @@ pub trait RuntimeAdapter: Send + Sync {
         challenges_result: &ChallengesResult,
     ) -> Result<ApplyTransactionResult, Error>;
 
-    /// Query runtime with given `path` and `data`.
+    /// Query runtime with given `path` and `data`. With `include_proof`, the response has the trie
+    /// nodes read to answer the query.
     fn query(
         &self,
         state_root: &StateRoot,
@@ -387,8 +399,21 @@ pub trait RuntimeAdapter: Send + Sync {
         block_hash: &CryptoHash,
         path_parts: Vec<&str>,
         data: &[u8],
+        include_proof: bool,
     ) -> Result<QueryResponse, Box<dyn std::error::Error>>;
 
+    /// Executes the transaction on top of the given state of the shard, together with all the
+    /// receipts it produces for the accounts of the same shard. Changes to the state are dropped.
+    fn simulate_transaction(
+        &self,
+        shard_id: ShardId,
+        state_root: &StateRoot,
+        block_index: BlockIndex,
+        block_timestamp: u64,
+        gas_price: Balance,
+        transaction: &SignedTransaction,
+    ) -> Result<SimulationResultView, Box<dyn std::error::Error>>;
+
     fn get_validator_info(&self, block_hash: &CryptoHash) -> Result<EpochValidatorInfo, Error>;
 
     /// Get the part of the state from given state root.
@@ -559,7 +584,7 @@ mod tests {
     use super::*;
     use crate::Chain;
     use near_primitives::merkle::verify_path;
-    use near_primitives::transaction::{ExecutionOutcome, ExecutionStatus};
+    use near_primitives::transaction::{ContractEvent, ExecutionOutcome, ExecutionStatus};
 
     #[test]
     fn test_block_produce() {
@@ -610,6 +635,7 @@ mod tests {
                 logs: vec!["outcome1".to_string()],
                 receipt_ids: vec![hash(&[1])],
                 gas_burnt: 100,
+                events: vec![],
             },
         };
         let outcome2 = ExecutionOutcomeWithId {
@@ -619,6 +645,12 @@ mod tests {
                 logs: vec!["outcome2".to_string()],
                 receipt_ids: vec![],
                 gas_burnt: 0,
+                events: vec![ContractEvent {
+                    account_id: "alice.near".to_string(),
+                    name: "outcome2".to_string(),
+                    topics: vec![[1; 32]],
+                    data: vec![],
+                }],
             },
         };
         let outcomes = vec![outcome1, outcome2];
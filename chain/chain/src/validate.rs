@@ -272,11 +272,11 @@ fn validate_chunk_proofs_challenge(
     }
     // Temporary holds the decoded chunk, since we use a reference below to avoid cloning it.
     let tmp_chunk;
+    let data_parts = runtime_adapter
+        .num_data_parts(&chunk_header.inner.prev_block_hash, chunk_header.inner.shard_id);
     let chunk_ref = match &chunk_proofs.chunk {
         MaybeEncodedShardChunk::Encoded(encoded_chunk) => {
-            match encoded_chunk
-                .decode_chunk(runtime_adapter.num_data_parts(&chunk_header.inner.prev_block_hash))
-            {
+            match encoded_chunk.decode_chunk(data_parts) {
                 Ok(chunk) => {
                     tmp_chunk = Some(chunk);
                     tmp_chunk.as_ref().unwrap()
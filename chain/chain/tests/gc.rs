@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use near_chain::test_utils::setup;
+use near_chain::{Block, Chain, ChainStoreAccess, Provenance, RuntimeAdapter};
+use near_crypto::Signer;
+use near_primitives::test_utils::init_test_logger;
+use near_primitives::types::StateRoot;
+use near_store::test_utils::create_test_store;
+use near_store::{ColState, Trie, WrappedTrieChanges};
+
+#[test]
+fn gc_old_blocks() {
+    init_test_logger();
+    let (mut chain, _, signer) = setup();
+    let mut hashes = vec![chain.genesis().hash()];
+    for _ in 0..30 {
+        let prev = chain.get_block(&chain.head_header().unwrap().hash()).unwrap().clone();
+        let block = Block::empty(&prev, &*signer);
+        hashes.push(block.hash());
+        chain.process_block(&None, block, Provenance::PRODUCED, |_| {}, |_| {}, |_| {}).unwrap();
+    }
+    chain.clear_old_data(1).unwrap();
+    let tail = chain.store().tail().unwrap().height;
+    assert!(tail > 0 && tail < 30);
+    for (height, hash) in hashes.iter().enumerate().skip(1) {
+        // Headers are kept for header sync and light clients.
+        assert!(chain.get_block_header(hash).is_ok());
+        assert_eq!(chain.get_block(hash).is_ok(), height as u64 > tail);
+    }
+    assert!(chain.get_block(&hashes[0]).is_ok());
+    assert_eq!(chain.head().unwrap().height, 30);
+}
+
+/// Processes `num_blocks` blocks on top of `prev`. The state of each block is the state of its
+/// previous block with `key` set to the height of the block, so that the states of forks share
+/// nodes with the canonical ones. Returns the blocks together with their state roots.
+fn process_blocks(
+    chain: &mut Chain,
+    trie: &Arc<Trie>,
+    signer: &dyn Signer,
+    prev: &Block,
+    prev_root: StateRoot,
+    num_blocks: usize,
+    key: &[u8],
+) -> Vec<(Block, StateRoot)> {
+    let mut prev = (prev.clone(), prev_root);
+    let mut blocks = vec![];
+    for _ in 0..num_blocks {
+        let block = Block::empty(&prev.0, signer);
+        chain
+            .process_block(&None, block.clone(), Provenance::PRODUCED, |_| {}, |_| {}, |_| {})
+            .unwrap();
+        let value = block.header.inner_lite.height.to_le_bytes().to_vec();
+        let trie_changes =
+            trie.update(&prev.1, vec![(key.to_vec(), Some(value))].into_iter()).unwrap();
+        let root = trie_changes.new_root;
+        let mut store_update = chain.mut_store().store_update();
+        store_update.save_trie_changes(WrappedTrieChanges::new(
+            trie.clone(),
+            trie_changes,
+            0,
+            block.hash(),
+        ));
+        store_update.commit().unwrap();
+        prev = (block, root);
+        blocks.push(prev.clone());
+    }
+    blocks
+}
+
+/// Trie nodes stored for all the states.
+fn stored_nodes(chain: &Chain) -> HashSet<Vec<u8>> {
+    chain.store().store().iter(ColState).map(|(key, _)| key.to_vec()).collect()
+}
+
+/// Trie nodes of the given states, found by writing them from scratch into an empty store.
+fn state_nodes(trie: &Trie, roots: &[StateRoot]) -> HashSet<Vec<u8>> {
+    let store = create_test_store();
+    let new_trie = Arc::new(Trie::new(store.clone()));
+    for root in roots {
+        let changes = trie.iter(root).unwrap().map(|item| {
+            let (key, value) = item.unwrap();
+            (key, Some(value))
+        });
+        let (store_update, new_root) = new_trie
+            .update(&Trie::empty_root(), changes.collect::<Vec<_>>().into_iter())
+            .unwrap()
+            .into(new_trie.clone())
+            .unwrap();
+        store_update.commit().unwrap();
+        assert_eq!(new_root, *root);
+    }
+    store.iter(ColState).map(|(key, _)| key.to_vec()).collect()
+}
+
+/// Checks that the canonical states from the tail up are readable and returns their roots.
+fn check_canonical_states(
+    chain: &Chain,
+    trie: &Trie,
+    canonical: &[(Block, StateRoot)],
+) -> Vec<StateRoot> {
+    let tail = chain.store().tail().unwrap().height;
+    let mut roots = vec![];
+    for (block, root) in canonical.iter() {
+        let height = block.header.inner_lite.height;
+        assert_eq!(chain.get_block(&block.hash()).is_ok(), height > tail);
+        if height >= tail {
+            assert_eq!(trie.get(root, b"main").unwrap(), Some(height.to_le_bytes().to_vec()));
+            assert_eq!(trie.get(root, b"fork").unwrap(), None);
+            roots.push(*root);
+        }
+    }
+    roots
+}
+
+#[test]
+fn gc_forks_below_gc_height() {
+    init_test_logger();
+    let (mut chain, runtime, signer) = setup();
+    let trie = runtime.get_trie();
+    let genesis = chain.get_block(&chain.genesis().hash()).unwrap().clone();
+    let canonical =
+        process_blocks(&mut chain, &trie, &*signer, &genesis, Trie::empty_root(), 30, b"main");
+    let mut forks = vec![];
+    for base in [1, 3].iter() {
+        let (prev, prev_root) = &canonical[*base];
+        forks.extend(process_blocks(&mut chain, &trie, &*signer, prev, *prev_root, 2, b"fork"));
+    }
+    assert_eq!(chain.head().unwrap().last_block_hash, canonical[29].0.hash());
+
+    chain.clear_old_data(1).unwrap();
+    let tail = chain.store().tail().unwrap().height;
+    assert!(forks.iter().all(|(block, _)| block.header.inner_lite.height <= tail));
+    for (block, _) in forks.iter() {
+        assert!(chain.get_block(&block.hash()).is_err());
+    }
+    let roots = check_canonical_states(&chain, &trie, &canonical);
+    assert_eq!(stored_nodes(&chain), state_nodes(&trie, &roots));
+}
+
+#[test]
+fn gc_forks_across_gc_height() {
+    init_test_logger();
+    let (mut chain, runtime, signer) = setup();
+    let trie = runtime.get_trie();
+    let genesis = chain.get_block(&chain.genesis().hash()).unwrap().clone();
+    let mut canonical =
+        process_blocks(&mut chain, &trie, &*signer, &genesis, Trie::empty_root(), 30, b"main");
+    // Forks of 3 blocks from every other height, so that some of them cross the tail.
+    let mut forks = vec![];
+    for base in (1..26).step_by(2) {
+        let (prev, prev_root) = &canonical[base];
+        forks.push(process_blocks(&mut chain, &trie, &*signer, prev, *prev_root, 3, b"fork"));
+    }
+    assert_eq!(chain.head().unwrap().last_block_hash, canonical[29].0.hash());
+
+    chain.clear_old_data(1).unwrap();
+    let tail = chain.store().tail().unwrap().height;
+    assert!(forks.iter().any(|fork| {
+        fork.first().unwrap().0.header.inner_lite.height <= tail
+            && fork.last().unwrap().0.header.inner_lite.height > tail
+    }));
+    for (block, _) in forks.iter().flatten() {
+        assert_eq!(chain.get_block(&block.hash()).is_ok(), block.header.inner_lite.height > tail);
+    }
+    check_canonical_states(&chain, &trie, &canonical);
+
+    // Once the rest of the forks is below the tail, only the canonical states are left.
+    let (head, head_root) = canonical.last().unwrap().clone();
+    canonical.extend(process_blocks(&mut chain, &trie, &*signer, &head, head_root, 30, b"main"));
+    chain.clear_old_data(1).unwrap();
+    assert!(chain.store().tail().unwrap().height > 30);
+    for (block, _) in forks.iter().flatten() {
+        assert!(chain.get_block(&block.hash()).is_err());
+    }
+    let roots = check_canonical_states(&chain, &trie, &canonical);
+    assert_eq!(stored_nodes(&chain), state_nodes(&trie, &roots));
+}
@@ -7,7 +7,7 @@ use near_primitives::sharding::{
 };
 use near_primitives::types::{BlockIndex, ShardId};
 
-const HEIGHT_HORIZON: u64 = 1024;
+pub(crate) const HEIGHT_HORIZON: u64 = 1024;
 const MAX_HEIGHTS_AHEAD: u64 = 5;
 const CHUNK_HEADER_HEIGHT_HORIZON: u64 = 10;
 const NUM_BLOCK_HASH_TO_CHUNK_HEADER: usize = 30;
@@ -123,11 +123,13 @@ impl EncodedChunksCache {
         }
     }
 
+    /// Removes the chunks that are not requested and fell outside of the horizon, returns them.
     pub fn update_largest_seen_height<T>(
         &mut self,
         new_height: BlockIndex,
         requested_chunks: &HashMap<ChunkHash, T>,
-    ) {
+    ) -> Vec<ChunkHash> {
+        let mut removed = vec![];
         let old_largest_seen_height = self.largest_seen_height;
         self.largest_seen_height = new_height;
         for height in old_largest_seen_height.saturating_sub(HEIGHT_HORIZON)
@@ -137,10 +139,12 @@ impl EncodedChunksCache {
                 for chunk_hash in chunks_to_remove {
                     if !requested_chunks.contains_key(&chunk_hash) {
                         self.encoded_chunks.remove(&chunk_hash);
+                        removed.push(chunk_hash);
                     }
                 }
             }
         }
+        removed
     }
 
     pub fn insert_chunk_header(&mut self, shard_id: ShardId, header: ShardChunkHeader) {
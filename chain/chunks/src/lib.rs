@@ -1,9 +1,12 @@
 extern crate log;
 
 use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use borsh::{BorshDeserialize, BorshSerialize};
+use cached::{Cached, SizedCache};
 use log::{debug, error};
 use rand::seq::SliceRandom;
 
@@ -15,7 +18,9 @@ use near_chain::{
 use near_crypto::Signer;
 use near_network::types::{NetworkAdapter, PartialEncodedChunkRequestMsg};
 use near_network::NetworkRequests;
+use near_pool::types::{InsertTransactionResult, PoolConfig};
 use near_pool::{PoolIteratorWrapper, TransactionPool};
+use near_primitives::block::BlockHeader;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{merklize, verify_path, MerklePath};
 use near_primitives::receipt::Receipt;
@@ -27,8 +32,9 @@ use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{
     AccountId, Balance, BlockIndex, Gas, MerkleHash, ShardId, StateRoot, ValidatorStake,
 };
+use near_store::{ColPendingPartialChunks, Store};
 
-use crate::chunk_cache::{EncodedChunksCache, EncodedChunksCacheEntry};
+use crate::chunk_cache::{EncodedChunksCache, EncodedChunksCacheEntry, HEIGHT_HORIZON};
 pub use crate::types::Error;
 
 mod chunk_cache;
@@ -38,6 +44,8 @@ const CHUNK_REQUEST_RETRY_MS: u64 = 100;
 const CHUNK_REQUEST_SWITCH_TO_OTHERS_MS: u64 = 400;
 const CHUNK_REQUEST_SWITCH_TO_FULL_FETCH_MS: u64 = 3_000;
 const CHUNK_REQUEST_RETRY_MAX_MS: u64 = 100_000;
+/// Number of recent blocks for which the block producers that missed the approval are kept.
+const NUM_BLOCKS_FOR_OFFLINE_PART_OWNERS: usize = 30;
 
 #[derive(PartialEq, Eq)]
 pub enum ChunkStatus {
@@ -66,6 +74,35 @@ struct ChunkRequestInfo {
     last_requested: Instant,
 }
 
+/// Kinds of the rows of a chunk that is not assembled yet in `ColPendingPartialChunks`. Every row
+/// is keyed by the chunk hash, the kind and the part ordinal or the receipts' shard id, so that
+/// only the newly received parts and receipts are written.
+const PENDING_CHUNK_HEADER: u8 = 0;
+const PENDING_CHUNK_PART: u8 = 1;
+const PENDING_CHUNK_RECEIPTS: u8 = 2;
+
+/// Header of a chunk that is not assembled yet and whether it was requested.
+#[derive(BorshSerialize, BorshDeserialize)]
+struct PendingChunkHeader {
+    header: ShardChunkHeader,
+    requested: bool,
+}
+
+/// Rows of a chunk that is not assembled yet that are in the store.
+#[derive(Default)]
+struct PendingChunkRows {
+    requested: Option<bool>,
+    keys: HashSet<Vec<u8>>,
+}
+
+fn pending_chunk_key(chunk_hash: &ChunkHash, kind: u8, index: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(41);
+    key.extend_from_slice(chunk_hash.as_ref());
+    key.push(kind);
+    key.extend_from_slice(&index.to_le_bytes());
+    key
+}
+
 struct RequestPool {
     retry_duration: Duration,
     switch_to_others_duration: Duration,
@@ -101,7 +138,8 @@ impl RequestPool {
         self.requests.remove(chunk_hash);
     }
 
-    pub fn fetch(&mut self) -> Vec<(ChunkHash, ChunkRequestInfo)> {
+    /// Returns the requests to resend and the hashes of the chunks whose requests were evicted.
+    pub fn fetch(&mut self) -> (Vec<(ChunkHash, ChunkRequestInfo)>, Vec<ChunkHash>) {
         let mut removed_requests = HashSet::<ChunkHash>::default();
         let mut requests = Vec::new();
         for (chunk_hash, mut chunk_request) in self.requests.iter_mut() {
@@ -115,10 +153,10 @@ impl RequestPool {
                 requests.push((chunk_hash.clone(), chunk_request.clone()));
             }
         }
-        for chunk_hash in removed_requests {
-            self.requests.remove(&chunk_hash);
+        for chunk_hash in removed_requests.iter() {
+            self.requests.remove(chunk_hash);
         }
-        requests
+        (requests, removed_requests.into_iter().collect())
     }
 }
 
@@ -126,12 +164,18 @@ pub struct ShardsManager {
     me: Option<AccountId>,
 
     tx_pools: HashMap<ShardId, TransactionPool>,
+    pool_config: PoolConfig,
 
     runtime_adapter: Arc<dyn RuntimeAdapter>,
     network_adapter: Arc<dyn NetworkAdapter>,
+    store: Arc<Store>,
 
     encoded_chunks: EncodedChunksCache,
     requested_partial_encoded_chunks: RequestPool,
+    /// Rows in `ColPendingPartialChunks` of the chunks that are not assembled yet.
+    pending_chunk_rows: HashMap<ChunkHash, PendingChunkRows>,
+    /// Block producers that didn't approve a block, for the chunks built on top of it.
+    offline_part_owners: SizedCache<CryptoHash, HashSet<AccountId>>,
 }
 
 impl ShardsManager {
@@ -139,12 +183,16 @@ impl ShardsManager {
         me: Option<AccountId>,
         runtime_adapter: Arc<dyn RuntimeAdapter>,
         network_adapter: Arc<dyn NetworkAdapter>,
+        store: Arc<Store>,
+        pool_config: PoolConfig,
     ) -> Self {
-        Self {
+        let mut shards_manager = Self {
             me,
             tx_pools: HashMap::new(),
+            pool_config,
             runtime_adapter,
             network_adapter,
+            store,
             encoded_chunks: EncodedChunksCache::new(),
             requested_partial_encoded_chunks: RequestPool::new(
                 Duration::from_millis(CHUNK_REQUEST_RETRY_MS),
@@ -152,20 +200,260 @@ impl ShardsManager {
                 Duration::from_millis(CHUNK_REQUEST_SWITCH_TO_FULL_FETCH_MS),
                 Duration::from_millis(CHUNK_REQUEST_RETRY_MAX_MS),
             ),
+            pending_chunk_rows: HashMap::new(),
+            offline_part_owners: SizedCache::with_size(NUM_BLOCKS_FOR_OFFLINE_PART_OWNERS),
+        };
+        shards_manager.load_pending_chunks();
+        shards_manager
+    }
+
+    /// Restores the chunks that were being assembled before a restart, so that only the parts and
+    /// receipts still missing are requested. The rows of the chunks that were assembled or became
+    /// too old in the meantime are removed.
+    fn load_pending_chunks(&mut self) {
+        let store = self.store.clone();
+        let mut pending_chunks: HashMap<ChunkHash, (PartialEncodedChunk, bool, HashSet<Vec<u8>>)> =
+            HashMap::new();
+        let mut stale_keys = vec![];
+        for (key, value) in store.iter(ColPendingPartialChunks) {
+            if key.len() != 41 {
+                stale_keys.push(key.to_vec());
+                continue;
+            }
+            let chunk_hash = match CryptoHash::try_from(&key[..32]) {
+                Ok(hash) => ChunkHash(hash),
+                Err(_) => {
+                    stale_keys.push(key.to_vec());
+                    continue;
+                }
+            };
+            let (partial_chunk, requested, keys) =
+                pending_chunks.entry(chunk_hash.clone()).or_insert_with(|| {
+                    (
+                        PartialEncodedChunk {
+                            shard_id: 0,
+                            chunk_hash,
+                            header: None,
+                            parts: vec![],
+                            receipts: vec![],
+                        },
+                        false,
+                        HashSet::new(),
+                    )
+                });
+            keys.insert(key.to_vec());
+            let result = match key[32] {
+                PENDING_CHUNK_HEADER => PendingChunkHeader::try_from_slice(&value).map(|pending| {
+                    partial_chunk.shard_id = pending.header.inner.shard_id;
+                    partial_chunk.header = Some(pending.header);
+                    *requested = pending.requested;
+                }),
+                PENDING_CHUNK_PART => PartialEncodedChunkPart::try_from_slice(&value)
+                    .map(|part| partial_chunk.parts.push(part)),
+                PENDING_CHUNK_RECEIPTS => ReceiptProof::try_from_slice(&value)
+                    .map(|receipt| partial_chunk.receipts.push(receipt)),
+                _ => Ok(()),
+            };
+            if let Err(err) = result {
+                error!(target: "chunks", "Failed to load pending partial chunk {:?}: {}", key, err);
+            }
+        }
+
+        let mut chain_store = ChainStore::new(store.clone());
+        let head_height = chain_store.head().map(|tip| tip.height).unwrap_or_default();
+        for (chunk_hash, (partial_chunk, requested, keys)) in pending_chunks {
+            let header = match &partial_chunk.header {
+                Some(header) => header.clone(),
+                None => {
+                    stale_keys.extend(keys);
+                    continue;
+                }
+            };
+            let is_assembled = chain_store.get_chunk(&chunk_hash).is_ok()
+                || chain_store.is_invalid_chunk(&chunk_hash).map_or(false, |chunk| chunk.is_some());
+            if is_assembled || header.inner.height_created + HEIGHT_HORIZON < head_height {
+                stale_keys.extend(keys);
+                continue;
+            }
+            self.encoded_chunks.merge_in_partial_encoded_chunk(&partial_chunk);
+            if requested {
+                self.requested_partial_encoded_chunks.insert(
+                    chunk_hash.clone(),
+                    ChunkRequestInfo {
+                        height: header.inner.height_created,
+                        parent_hash: header.inner.prev_block_hash,
+                        shard_id: header.inner.shard_id,
+                        added: Instant::now(),
+                        last_requested: Instant::now(),
+                    },
+                );
+            }
+            self.pending_chunk_rows
+                .insert(chunk_hash, PendingChunkRows { requested: Some(requested), keys });
+        }
+
+        if !stale_keys.is_empty() {
+            let mut store_update = store.store_update();
+            for key in stale_keys {
+                store_update.delete(ColPendingPartialChunks, &key);
+            }
+            if let Err(err) = store_update.commit() {
+                error!(target: "chunks", "Failed to remove stale pending partial chunks: {}", err);
+            }
+        }
+    }
+
+    /// Persists the header of a chunk that is not assembled yet and the given parts and receipts
+    /// of it that are not in the store yet.
+    fn persist_pending_chunk(
+        &mut self,
+        chunk_hash: &ChunkHash,
+        parts: &[PartialEncodedChunkPart],
+        receipts: &[ReceiptProof],
+    ) {
+        let entry = match self.encoded_chunks.get(chunk_hash) {
+            Some(entry) => entry,
+            None => return,
+        };
+        let requested = self.requested_partial_encoded_chunks.contains_key(chunk_hash);
+        if !requested
+            && !self.encoded_chunks.height_within_horizon(entry.header.inner.height_created)
+        {
+            return;
+        }
+        let rows = self.pending_chunk_rows.entry(chunk_hash.clone()).or_default();
+        let mut store_update = self.store.store_update();
+        let mut new_keys = vec![];
+        let mut result = Ok(());
+        if rows.requested != Some(requested) {
+            let key = pending_chunk_key(chunk_hash, PENDING_CHUNK_HEADER, 0);
+            let pending = PendingChunkHeader { header: entry.header.clone(), requested };
+            result =
+                result.and_then(|_| store_update.set_ser(ColPendingPartialChunks, &key, &pending));
+            new_keys.push(key);
+        }
+        for part in parts.iter() {
+            let key = pending_chunk_key(chunk_hash, PENDING_CHUNK_PART, part.part_ord);
+            if !rows.keys.contains(&key) {
+                result =
+                    result.and_then(|_| store_update.set_ser(ColPendingPartialChunks, &key, part));
+                new_keys.push(key);
+            }
+        }
+        for receipt in receipts.iter() {
+            let key = pending_chunk_key(chunk_hash, PENDING_CHUNK_RECEIPTS, receipt.1.to_shard_id);
+            if !rows.keys.contains(&key) {
+                result = result
+                    .and_then(|_| store_update.set_ser(ColPendingPartialChunks, &key, receipt));
+                new_keys.push(key);
+            }
+        }
+        if new_keys.is_empty() {
+            return;
+        }
+        if let Err(err) = result.and_then(|_| store_update.commit()) {
+            error!(target: "chunks", "Failed to persist pending partial chunk {}: {}", chunk_hash.0, err);
+            return;
         }
+        rows.requested = Some(requested);
+        rows.keys.extend(new_keys);
+    }
+
+    fn remove_pending_chunk(&mut self, chunk_hash: &ChunkHash) {
+        self.remove_pending_chunks(vec![chunk_hash.clone()]);
+    }
+
+    /// Removes all the rows of the given chunks from the store in one update.
+    fn remove_pending_chunks(&mut self, chunk_hashes: Vec<ChunkHash>) {
+        let mut store_update = self.store.store_update();
+        let mut removed = false;
+        for chunk_hash in chunk_hashes {
+            if let Some(rows) = self.pending_chunk_rows.remove(&chunk_hash) {
+                for key in rows.keys {
+                    store_update.delete(ColPendingPartialChunks, &key);
+                    removed = true;
+                }
+            }
+        }
+        if !removed {
+            return;
+        }
+        if let Err(err) = store_update.commit() {
+            error!(target: "chunks", "Failed to remove pending partial chunks: {}", err);
+        }
+    }
+
+    /// Remembers the block producers whose approvals are missing in the block, so that the parts
+    /// of the chunks built on top of it owned by them are forwarded to online block producers.
+    pub fn record_block_approvals(&mut self, header: &BlockHeader) -> Result<(), Error> {
+        // Without any approvals, e.g. right after genesis, there is nothing to tell who is offline.
+        if header.inner_rest.approvals.is_empty() {
+            return Ok(());
+        }
+        let block_hash = header.hash();
+        let approvers = header
+            .inner_rest
+            .approvals
+            .iter()
+            .map(|approval| &approval.account_id)
+            .collect::<HashSet<_>>();
+        let epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(&block_hash)?;
+        let offline = self
+            .runtime_adapter
+            .get_epoch_block_producers(&epoch_id, &block_hash)?
+            .into_iter()
+            .map(|(validator_stake, _)| validator_stake.account_id)
+            .filter(|account_id| !approvers.contains(account_id))
+            .collect();
+        self.offline_part_owners.cache_set(block_hash, offline);
+        Ok(())
+    }
+
+    fn get_offline_part_owners(&mut self, parent_hash: &CryptoHash) -> HashSet<AccountId> {
+        self.offline_part_owners.cache_get(parent_hash).cloned().unwrap_or_default()
+    }
+
+    /// Returns the block producer that keeps the given part: its owner if it is online, otherwise
+    /// the owner of the closest following part that is online.
+    fn get_part_holder(
+        &self,
+        parent_hash: &CryptoHash,
+        shard_id: ShardId,
+        part_ord: u64,
+        offline_part_owners: &HashSet<AccountId>,
+    ) -> Result<AccountId, Error> {
+        let owner = self.runtime_adapter.get_part_owner(parent_hash, part_ord)?;
+        if !offline_part_owners.contains(&owner) {
+            return Ok(owner);
+        }
+        let num_total_parts = self.runtime_adapter.num_total_parts(parent_hash, shard_id) as u64;
+        for offset in 1..num_total_parts {
+            let candidate = self
+                .runtime_adapter
+                .get_part_owner(parent_hash, (part_ord + offset) % num_total_parts)?;
+            if !offline_part_owners.contains(&candidate) {
+                return Ok(candidate);
+            }
+        }
+        Ok(owner)
     }
 
     pub fn update_largest_seen_height(&mut self, new_height: BlockIndex) {
-        self.encoded_chunks.update_largest_seen_height(
+        let removed = self.encoded_chunks.update_largest_seen_height(
             new_height,
             &self.requested_partial_encoded_chunks.requests,
         );
+        self.remove_pending_chunks(removed);
     }
 
     pub fn get_pool_iterator(&mut self, shard_id: ShardId) -> Option<PoolIteratorWrapper> {
         self.tx_pools.get_mut(&shard_id).map(|pool| pool.pool_iterator())
     }
 
+    pub fn get_tx_pool(&self, shard_id: ShardId) -> Option<&TransactionPool> {
+        self.tx_pools.get(&shard_id)
+    }
+
     pub fn cares_about_shard_this_or_next_epoch(
         &self,
         account_id: Option<&AccountId>,
@@ -188,8 +476,6 @@ impl ShardsManager {
     ) -> Result<(), Error> {
         let mut bp_to_parts = HashMap::new();
 
-        let cache_entry = self.encoded_chunks.get(chunk_hash);
-
         let request_full = force_request_full
             || self.cares_about_shard_this_or_next_epoch(
                 self.me.as_ref(),
@@ -213,7 +499,10 @@ impl ShardsManager {
             }
         };
 
-        for part_ord in 0..self.runtime_adapter.num_total_parts(parent_hash) {
+        let offline_part_owners = self.get_offline_part_owners(parent_hash);
+        let cache_entry = self.encoded_chunks.get(chunk_hash);
+
+        for part_ord in 0..self.runtime_adapter.num_total_parts(parent_hash, shard_id) {
             let part_ord = part_ord as u64;
             if cache_entry.map_or(false, |cache_entry| cache_entry.parts.contains_key(&part_ord)) {
                 continue;
@@ -230,7 +519,10 @@ impl ShardsManager {
             };
 
             if need_to_fetch_part {
-                let fetch_from = self.runtime_adapter.get_part_owner(&parent_hash, part_ord)?;
+                // Parts of offline owners are fetched from the block producers they were
+                //     forwarded to.
+                let fetch_from =
+                    self.get_part_holder(&parent_hash, shard_id, part_ord, &offline_part_owners)?;
                 let fetch_from = if Some(&fetch_from) == self.me.as_ref() {
                     // If missing own part, request it from the chunk producer
                     shard_representative_account_id.clone()
@@ -345,6 +637,7 @@ impl ShardsManager {
                     added: Instant::now(),
                 },
             );
+            self.persist_pending_chunk(&chunk_hash, &[], &[]);
             self.request_partial_encoded_chunk(
                 height,
                 &parent_hash,
@@ -360,7 +653,8 @@ impl ShardsManager {
     /// Resends chunk requests if haven't received it within expected time.
     pub fn resend_chunk_requests(&mut self) -> Result<(), Error> {
         // Process chunk one part requests.
-        let requests = self.requested_partial_encoded_chunks.fetch();
+        let (requests, evicted) = self.requested_partial_encoded_chunks.fetch();
+        self.remove_pending_chunks(evicted);
         for (chunk_hash, chunk_request) in requests {
             match self.request_partial_encoded_chunk(
                 chunk_request.height,
@@ -392,11 +686,16 @@ impl ShardsManager {
         self.encoded_chunks.get_chunk_headers_for_block(&prev_block_hash)
     }
 
-    pub fn insert_transaction(&mut self, shard_id: ShardId, tx: SignedTransaction) {
+    pub fn insert_transaction(
+        &mut self,
+        shard_id: ShardId,
+        tx: SignedTransaction,
+    ) -> InsertTransactionResult {
+        let pool_config = &self.pool_config;
         self.tx_pools
             .entry(shard_id)
-            .or_insert_with(TransactionPool::default)
-            .insert_transaction(tx);
+            .or_insert_with(|| TransactionPool::new(pool_config.clone()))
+            .insert_transaction(tx)
     }
 
     pub fn remove_transactions(
@@ -414,12 +713,25 @@ impl ShardsManager {
         shard_id: ShardId,
         transactions: &Vec<SignedTransaction>,
     ) {
+        let pool_config = &self.pool_config;
         self.tx_pools
             .entry(shard_id)
-            .or_insert_with(TransactionPool::default)
+            .or_insert_with(|| TransactionPool::new(pool_config.clone()))
             .reintroduce_transactions(transactions.clone());
     }
 
+    /// Evicts transactions of all pools for which `is_expired` returns true given their
+    /// `block_hash`. Returns the number of evicted transactions.
+    pub fn remove_expired_transactions(
+        &mut self,
+        is_expired: &mut dyn FnMut(&CryptoHash) -> bool,
+    ) -> usize {
+        self.tx_pools
+            .values_mut()
+            .map(|pool| pool.remove_expired_transactions(|block_hash| is_expired(block_hash)))
+            .sum()
+    }
+
     pub fn receipts_recipient_filter(
         &self,
         from_shard_id: ShardId,
@@ -472,15 +784,13 @@ impl ShardsManager {
             return;
         };
 
-        let parts =
-            request.part_ords.iter().map(|part_ord| entry.parts.get(&part_ord)).collect::<Vec<_>>();
-
-        if parts.iter().any(|x| x.is_none()) {
-            debug!(target:"chunks", "Not responding, some parts are missing");
-            return;
-        }
-
-        let parts = parts.into_iter().map(|x| x.unwrap().clone()).collect::<Vec<_>>();
+        // Respond with the parts we have: any sufficient subset of parts is enough to reconstruct
+        //     the chunk, and the requester asks for the rest on retries.
+        let parts = request
+            .part_ords
+            .iter()
+            .filter_map(|part_ord| entry.parts.get(&part_ord).cloned())
+            .collect::<Vec<_>>();
 
         let receipts = request
             .tracking_shards
@@ -495,6 +805,11 @@ impl ShardsManager {
 
         let receipts = receipts.into_iter().map(|x| x.unwrap().clone()).collect::<Vec<_>>();
 
+        if parts.is_empty() && receipts.is_empty() && !request.part_ords.is_empty() {
+            debug!(target:"chunks", "Not responding, all parts are missing");
+            return;
+        }
+
         let partial_encoded_chunk = PartialEncodedChunk {
             shard_id: entry.header.inner.shard_id,
             chunk_hash: entry.header.chunk_hash(),
@@ -555,9 +870,10 @@ impl ShardsManager {
         mut encoded_chunk: EncodedShardChunk,
         chain_store: &mut ChainStore,
     ) -> Result<bool, Error> {
+        let shard_id = encoded_chunk.header.inner.shard_id;
         match ShardsManager::check_chunk_complete(
-            self.runtime_adapter.num_data_parts(&prev_block_hash),
-            self.runtime_adapter.num_total_parts(&prev_block_hash),
+            self.runtime_adapter.num_data_parts(&prev_block_hash, shard_id),
+            self.runtime_adapter.num_total_parts(&prev_block_hash, shard_id),
             &mut encoded_chunk,
         ) {
             ChunkStatus::Complete(merkle_paths) => {
@@ -568,6 +884,7 @@ impl ShardsManager {
             ChunkStatus::Invalid => {
                 let chunk_hash = encoded_chunk.header.chunk_hash();
                 self.encoded_chunks.remove(&chunk_hash);
+                self.remove_pending_chunk(&chunk_hash);
                 Err(Error::InvalidChunk)
             }
         }
@@ -642,7 +959,8 @@ impl ShardsManager {
             return Err(Error::InvalidChunkShardId);
         }
 
-        let num_total_parts = self.runtime_adapter.num_total_parts(&prev_block_hash);
+        let num_total_parts =
+            self.runtime_adapter.num_total_parts(&prev_block_hash, header.inner.shard_id);
         for part_info in partial_encoded_chunk.parts.iter() {
             // TODO: only validate parts we care about
             self.validate_part(header.inner.encoded_merkle_root, part_info, num_total_parts)?;
@@ -681,12 +999,13 @@ impl ShardsManager {
         let have_all_parts = self.has_all_parts(&prev_block_hash, entry)?;
         let have_all_receipts = self.has_all_receipts(&prev_block_hash, entry)?;
 
-        let can_reconstruct =
-            entry.parts.len() >= self.runtime_adapter.num_data_parts(&prev_block_hash);
+        let can_reconstruct = entry.parts.len()
+            >= self.runtime_adapter.num_data_parts(&prev_block_hash, header.inner.shard_id);
 
         if have_all_parts {
             self.encoded_chunks.insert_chunk_header(partial_encoded_chunk.shard_id, header.clone());
         }
+        let offline_part_owners = self.get_offline_part_owners(&prev_block_hash);
         let entry = self.encoded_chunks.get(&chunk_hash).unwrap();
 
         if have_all_parts && have_all_receipts {
@@ -699,7 +1018,11 @@ impl ShardsManager {
 
             if let Err(_) = chain_store.get_partial_chunk(&header.chunk_hash()) {
                 let mut store_update = chain_store.store_update();
-                self.persist_partial_chunk_for_data_availability(entry, &mut store_update);
+                self.persist_partial_chunk_for_data_availability(
+                    entry,
+                    &offline_part_owners,
+                    &mut store_update,
+                );
                 store_update.commit()?;
             }
 
@@ -710,6 +1033,7 @@ impl ShardsManager {
             if !cares_about_shard {
                 self.encoded_chunks.remove_from_cache_if_outside_horizon(&chunk_hash);
                 self.requested_partial_encoded_chunks.remove(&chunk_hash);
+                self.remove_pending_chunk(&chunk_hash);
                 return Ok(ProcessPartialEncodedChunkResult::HaveAllPartsAndReceipts(
                     prev_block_hash,
                 ));
@@ -717,10 +1041,9 @@ impl ShardsManager {
         }
 
         if can_reconstruct {
-            let mut encoded_chunk = EncodedShardChunk::from_header(
-                header,
-                self.runtime_adapter.num_total_parts(&prev_block_hash),
-            );
+            let num_total_parts =
+                self.runtime_adapter.num_total_parts(&prev_block_hash, header.inner.shard_id);
+            let mut encoded_chunk = EncodedShardChunk::from_header(header, num_total_parts);
 
             for (part_ord, part_entry) in entry.parts.iter() {
                 encoded_chunk.content.parts[*part_ord as usize] = Some(part_entry.part.clone());
@@ -739,6 +1062,11 @@ impl ShardsManager {
             return Ok(ProcessPartialEncodedChunkResult::HaveAllPartsAndReceipts(prev_block_hash));
         }
 
+        self.persist_pending_chunk(
+            &chunk_hash,
+            &partial_encoded_chunk.parts,
+            &partial_encoded_chunk.receipts,
+        );
         Ok(ProcessPartialEncodedChunkResult::NeedMoreOnePartsOrReceipts(header))
     }
 
@@ -755,6 +1083,20 @@ impl ShardsManager {
         Ok(Some(self.runtime_adapter.get_part_owner(prev_block_hash, part_ord)?) == self.me)
     }
 
+    /// Whether the part is kept for data availability: we either own it, or it was forwarded to us
+    ///     because its owner is offline.
+    fn keeps_part(
+        &self,
+        prev_block_hash: &CryptoHash,
+        shard_id: ShardId,
+        part_ord: u64,
+        offline_part_owners: &HashSet<AccountId>,
+    ) -> Result<bool, Error> {
+        let holder =
+            self.get_part_holder(prev_block_hash, shard_id, part_ord, offline_part_owners)?;
+        Ok(self.need_part(prev_block_hash, part_ord)? || Some(holder) == self.me)
+    }
+
     fn has_all_receipts(
         &self,
         prev_block_hash: &CryptoHash,
@@ -776,7 +1118,8 @@ impl ShardsManager {
         prev_block_hash: &CryptoHash,
         chunk_entry: &EncodedChunksCacheEntry,
     ) -> Result<bool, Error> {
-        for part_ord in 0..self.runtime_adapter.num_total_parts(&prev_block_hash) {
+        let shard_id = chunk_entry.header.inner.shard_id;
+        for part_ord in 0..self.runtime_adapter.num_total_parts(&prev_block_hash, shard_id) {
             let part_ord = part_ord as u64;
             if !chunk_entry.parts.contains_key(&part_ord) {
                 if self.need_part(&prev_block_hash, part_ord)? {
@@ -806,8 +1149,8 @@ impl ShardsManager {
         tx_root: CryptoHash,
         signer: &dyn Signer,
     ) -> Result<(EncodedShardChunk, Vec<MerklePath>), Error> {
-        let total_parts = self.runtime_adapter.num_total_parts(&prev_block_hash);
-        let data_parts = self.runtime_adapter.num_data_parts(&prev_block_hash);
+        let total_parts = self.runtime_adapter.num_total_parts(&prev_block_hash, shard_id);
+        let data_parts = self.runtime_adapter.num_data_parts(&prev_block_hash, shard_id);
         EncodedShardChunk::new(
             prev_block_hash,
             prev_state_root,
@@ -834,19 +1177,23 @@ impl ShardsManager {
     pub fn persist_partial_chunk_for_data_availability(
         &self,
         chunk_entry: &EncodedChunksCacheEntry,
+        offline_part_owners: &HashSet<AccountId>,
         store_update: &mut ChainStoreUpdate,
     ) {
         let prev_block_hash = chunk_entry.header.inner.prev_block_hash;
+        let shard_id = chunk_entry.header.inner.shard_id;
         let partial_chunk = PartialEncodedChunk {
-            shard_id: chunk_entry.header.inner.shard_id,
+            shard_id,
             chunk_hash: chunk_entry.header.chunk_hash().clone(),
             header: Some(chunk_entry.header.clone()),
             parts: chunk_entry
                 .parts
                 .iter()
                 .filter_map(|(part_ord, part_entry)| {
-                    if let Ok(need_part) = self.need_part(&prev_block_hash, *part_ord) {
-                        if need_part {
+                    if let Ok(keep_part) =
+                        self.keeps_part(&prev_block_hash, shard_id, *part_ord, offline_part_owners)
+                    {
+                        if keep_part {
                             Some(part_entry.clone())
                         } else {
                             None
@@ -881,10 +1228,12 @@ impl ShardsManager {
         let chunk_hash = encoded_chunk.header.chunk_hash();
 
         let mut store_update = chain_store.store_update();
+        let num_data_parts = self.runtime_adapter.num_data_parts(
+            &encoded_chunk.header.inner.prev_block_hash,
+            encoded_chunk.header.inner.shard_id,
+        );
         if let Ok(shard_chunk) = encoded_chunk
-            .decode_chunk(
-                self.runtime_adapter.num_data_parts(&encoded_chunk.header.inner.prev_block_hash),
-            )
+            .decode_chunk(num_data_parts)
             .map_err(|err| Error::from(err))
             .and_then(|shard_chunk| {
                 if !validate_chunk_proofs(&shard_chunk, &*self.runtime_adapter) {
@@ -907,6 +1256,7 @@ impl ShardsManager {
             store_update.commit()?;
 
             self.requested_partial_encoded_chunks.remove(&chunk_hash);
+            self.remove_pending_chunk(&chunk_hash);
 
             return Ok(());
         } else {
@@ -916,6 +1266,7 @@ impl ShardsManager {
             store_update.commit()?;
             self.encoded_chunks.remove(&chunk_hash);
             self.requested_partial_encoded_chunks.remove(&chunk_hash);
+            self.remove_pending_chunk(&chunk_hash);
             return Err(Error::InvalidChunk);
         }
     }
@@ -963,7 +1314,13 @@ impl ShardsManager {
         };
 
         // Save the partial chunk for data availability
-        self.persist_partial_chunk_for_data_availability(&cache_entry, store_update);
+        let offline_part_owners =
+            self.get_offline_part_owners(&encoded_chunk.header.inner.prev_block_hash);
+        self.persist_partial_chunk_for_data_availability(
+            &cache_entry,
+            &offline_part_owners,
+            store_update,
+        );
 
         // Save this chunk into encoded_chunks.
         self.encoded_chunks.insert(cache_entry.header.chunk_hash().clone(), cache_entry);
@@ -986,13 +1343,22 @@ impl ShardsManager {
         assert_eq!(encoded_chunk.header.inner.outgoing_receipts_root, outgoing_receipts_root);
 
         let mut block_producer_mapping = HashMap::new();
+        let offline_part_owners = self.get_offline_part_owners(&prev_block_hash);
 
-        for part_ord in 0..self.runtime_adapter.num_total_parts(&prev_block_hash) {
+        for part_ord in 0..self.runtime_adapter.num_total_parts(&prev_block_hash, shard_id) {
             let part_ord = part_ord as u64;
             let to_whom = self.runtime_adapter.get_part_owner(&prev_block_hash, part_ord).unwrap();
 
             let entry = block_producer_mapping.entry(to_whom.clone()).or_insert_with(|| vec![]);
             entry.push(part_ord);
+
+            // The owner still gets its part in case it comes back online, but the part is also
+            //     forwarded to a block producer that is online to keep the chunk available.
+            let holder =
+                self.get_part_holder(&prev_block_hash, shard_id, part_ord, &offline_part_owners)?;
+            if holder != to_whom {
+                block_producer_mapping.entry(holder).or_insert_with(|| vec![]).push(part_ord);
+            }
         }
 
         for (to_whom, part_ords) in block_producer_mapping {
@@ -19,6 +19,7 @@ use near_chain::{
 use near_chunks::{ProcessPartialEncodedChunkResult, ShardsManager};
 use near_network::types::{PeerId, ReasonForBan};
 use near_network::{FullPeerInfo, NetworkAdapter, NetworkClientResponses, NetworkRequests};
+use near_pool::types::InsertTransactionResult;
 use near_primitives::block::{Approval, ApprovalMessage, Block, BlockHeader};
 use near_primitives::challenge::{Challenge, ChallengeBody};
 use near_primitives::hash::CryptoHash;
@@ -75,11 +76,14 @@ impl Client {
         network_adapter: Arc<dyn NetworkAdapter>,
         block_producer: Option<BlockProducer>,
     ) -> Result<Self, Error> {
-        let chain = Chain::new(store.clone(), runtime_adapter.clone(), &chain_genesis)?;
+        let mut chain = Chain::new(store.clone(), runtime_adapter.clone(), &chain_genesis)?;
+        chain.mut_store().set_archive(config.archive);
         let shards_mgr = ShardsManager::new(
             block_producer.as_ref().map(|x| x.account_id.clone()),
             runtime_adapter.clone(),
             network_adapter.clone(),
+            store.clone(),
+            config.tx_pool.clone(),
         );
         let sync_status = SyncStatus::AwaitingPeers;
         let header_sync = HeaderSync::new(
@@ -156,6 +160,22 @@ impl Client {
         }
     }
 
+    /// Evicts pool transactions that can't be included on top of the given head anymore: their
+    /// `block_hash` is past the transaction validity period or is not on the chain of the head.
+    fn remove_expired_transactions(&mut self, head: &BlockHeader) {
+        let Self { chain, shards_mgr, .. } = self;
+        let transaction_validity_period = chain.transaction_validity_period;
+        let num_evicted = shards_mgr.remove_expired_transactions(&mut |block_hash| {
+            chain
+                .mut_store()
+                .check_blocks_on_same_chain(head, block_hash, transaction_validity_period)
+                .is_err()
+        });
+        if num_evicted > 0 {
+            debug!(target: "client", "Evicted {} expired transactions from the pool", num_evicted);
+        }
+    }
+
     /// Produce block if we are block producer for given `next_height` index.
     /// Either returns produced block (not applied) or error.
     pub fn produce_block(
@@ -684,8 +704,18 @@ impl Client {
             });
         }
 
+        if let Err(err) = self.shards_mgr.record_block_approvals(&block.header) {
+            error!(target: "client", "Failed to record approvals of block {}: {}", block_hash, err);
+        }
+
         if status.is_new_head() {
             self.shards_mgr.update_largest_seen_height(block.header.inner_lite.height);
+            self.remove_expired_transactions(&block.header);
+            if !self.config.archive {
+                if let Err(err) = self.chain.clear_old_data(self.config.gc_num_epochs_to_keep) {
+                    error!(target: "client", "Failed to garbage collect old chain data: {}", err);
+                }
+            }
         }
 
         if let Some(bp) = self.block_producer.clone() {
@@ -1013,7 +1043,12 @@ impl Client {
                     me,
                     shard_id
                 );
-                self.shards_mgr.insert_transaction(shard_id, tx.clone());
+                match self.shards_mgr.insert_transaction(shard_id, tx.clone()) {
+                    InsertTransactionResult::Success | InsertTransactionResult::Duplicate => {}
+                    result => {
+                        debug!(target: "client", "Transaction {} is not added to the pool: {:?}", tx.get_hash(), result);
+                    }
+                }
 
                 if active_validator {
                     NetworkClientResponses::ValidTx
@@ -5,11 +5,12 @@ use std::sync::{Arc, RwLock};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use actix::{Actor, Addr, AsyncContext, Context, Handler};
+use actix::prelude::SendError;
+use actix::{Actor, Addr, AsyncContext, Context, Handler, Recipient};
 use chrono::{DateTime, Utc};
 use log::{debug, error, info, warn};
 
-use near_chain::types::{AcceptedBlock, ShardStateSyncResponse};
+use near_chain::types::{AcceptedBlock, BlockStatus, ShardStateSyncResponse};
 use near_chain::{
     byzantine_assert, Block, BlockHeader, ChainGenesis, ChainStoreAccess, ErrorKind, Provenance,
     RuntimeAdapter,
@@ -24,7 +25,7 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::types::{BlockIndex, EpochId};
 use near_primitives::unwrap_or_return;
 use near_primitives::utils::{from_timestamp, to_timestamp};
-use near_primitives::views::ValidatorInfo;
+use near_primitives::views::{PendingTransactionView, TransactionPoolStatsView, ValidatorInfo};
 use near_store::Store;
 use near_telemetry::TelemetryActor;
 
@@ -32,8 +33,10 @@ use crate::client::Client;
 use crate::info::InfoHelper;
 use crate::sync::{most_weight_peer, StateSyncResult};
 use crate::types::{
-    BlockProducer, ClientConfig, Error, GetNetworkInfo, NetworkInfoResponse, ShardSyncDownload,
-    ShardSyncStatus, Status, StatusSyncInfo, SyncStatus,
+    BlockProducer, ChainEvent, ChainEvents, ClientConfig, Error, GetNetworkInfo,
+    GetPendingTransactions, GetTransactionPoolStats, NetworkInfoResponse, ShardSyncDownload,
+    ShardSyncStatus, Status, StatusSyncInfo, SubscribeChainEvents, SyncStatus,
+    MAX_PENDING_TRANSACTIONS,
 };
 use crate::{sync, StatusResponse};
 use near_chain::test_utils::format_hash;
@@ -42,6 +45,12 @@ use std::cmp::Ordering;
 /// Multiplier on `max_block_time` to wait until deciding that chain stalled.
 const STATUS_WAIT_TIME_MULTIPLIER: u64 = 10;
 
+/// Recipient of chain events, with the number of batches it has missed since the last delivery.
+struct ChainEventSubscriber {
+    recipient: Recipient<ChainEvents>,
+    dropped: u64,
+}
+
 pub struct ClientActor {
     client: Client,
     network_adapter: Arc<dyn NetworkAdapter>,
@@ -55,6 +64,10 @@ pub struct ClientActor {
     last_validator_announce_time: Option<Instant>,
     /// Info helper.
     info_helper: InfoHelper,
+    /// Subscribers to the events of accepted blocks.
+    chain_event_subscribers: Vec<ChainEventSubscriber>,
+    /// Last final block that was sent to subscribers.
+    last_final_block_hash: CryptoHash,
 }
 
 fn wait_until_genesis(genesis_time: &DateTime<Utc>) {
@@ -106,10 +119,13 @@ impl ClientActor {
                 received_bytes_per_sec: 0,
                 sent_bytes_per_sec: 0,
                 known_producers: vec![],
+                peer_reputations: HashMap::new(),
             },
             last_validator_announce_height: None,
             last_validator_announce_time: None,
             info_helper,
+            chain_event_subscribers: vec![],
+            last_final_block_hash: CryptoHash::default(),
         })
     }
     fn check_signature_account_announce(
@@ -510,10 +526,74 @@ impl Handler<GetNetworkInfo> for ClientActor {
             sent_bytes_per_sec: self.network_info.sent_bytes_per_sec,
             received_bytes_per_sec: self.network_info.received_bytes_per_sec,
             known_producers: self.network_info.known_producers.clone(),
+            peer_reputations: self.network_info.peer_reputations.clone(),
         })
     }
 }
 
+impl Handler<GetPendingTransactions> for ClientActor {
+    type Result = Result<Vec<PendingTransactionView>, String>;
+
+    fn handle(&mut self, msg: GetPendingTransactions, _: &mut Context<Self>) -> Self::Result {
+        let shard_ids = match (msg.shard_id, &msg.signer_id) {
+            (Some(shard_id), _) => vec![shard_id],
+            (None, Some(signer_id)) => {
+                vec![self.client.runtime_adapter.account_id_to_shard_id(signer_id)]
+            }
+            (None, None) => (0..self.client.runtime_adapter.num_shards()).collect(),
+        };
+        let mut result = vec![];
+        for shard_id in shard_ids {
+            if let Some(pool) = self.client.shards_mgr.get_tx_pool(shard_id) {
+                for (tx, age) in pool.pending_transactions(msg.signer_id.as_ref()) {
+                    result.push(PendingTransactionView {
+                        hash: tx.get_hash(),
+                        shard_id,
+                        signer_id: tx.transaction.signer_id.clone(),
+                        public_key: tx.transaction.public_key.clone(),
+                        nonce: tx.transaction.nonce,
+                        receiver_id: tx.transaction.receiver_id.clone(),
+                        age_ms: age.as_millis() as u64,
+                    });
+                }
+            }
+        }
+        result.sort_by(|a, b| {
+            (a.shard_id, &a.signer_id, a.nonce).cmp(&(b.shard_id, &b.signer_id, b.nonce))
+        });
+        result.truncate(msg.limit.map_or(MAX_PENDING_TRANSACTIONS, |limit| {
+            std::cmp::min(limit, MAX_PENDING_TRANSACTIONS)
+        }));
+        Ok(result)
+    }
+}
+
+impl Handler<GetTransactionPoolStats> for ClientActor {
+    type Result = Result<Vec<TransactionPoolStatsView>, String>;
+
+    fn handle(&mut self, _: GetTransactionPoolStats, _: &mut Context<Self>) -> Self::Result {
+        Ok((0..self.client.runtime_adapter.num_shards())
+            .filter_map(|shard_id| {
+                self.client.shards_mgr.get_tx_pool(shard_id).map(|pool| TransactionPoolStatsView {
+                    shard_id,
+                    num_transactions: pool.len() as u64,
+                    num_groups: pool.num_groups() as u64,
+                    num_evicted: pool.num_evicted,
+                })
+            })
+            .collect())
+    }
+}
+
+impl Handler<SubscribeChainEvents> for ClientActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribeChainEvents, _: &mut Context<Self>) -> Self::Result {
+        self.chain_event_subscribers
+            .push(ChainEventSubscriber { recipient: msg.recipient, dropped: 0 });
+    }
+}
+
 impl ClientActor {
     fn sign_announce_account(&self, epoch_id: &EpochId) -> Result<Signature, ()> {
         if let Some(block_producer) = self.client.block_producer.as_ref() {
@@ -734,6 +814,54 @@ impl ClientActor {
 
             self.info_helper.block_processed(gas_used, gas_limit);
             self.check_send_announce_account(accepted_block.hash);
+            self.send_chain_events(&accepted_block.hash, &accepted_block.status);
+        }
+    }
+
+    /// Sends events of the accepted block to the subscribers.
+    /// Subscribers with full mailboxes skip the block, closed ones are removed.
+    fn send_chain_events(&mut self, block_hash: &CryptoHash, status: &BlockStatus) {
+        if self.chain_event_subscribers.is_empty() {
+            return;
+        }
+        let block = unwrap_or_return!(self.client.chain.get_block(block_hash)).clone();
+        let mut events = vec![];
+        if status.is_new_head() {
+            events.push(ChainEvent::NewHead(block.clone().into()));
+            let final_block_hash = block.header.inner_rest.last_quorum_pre_commit;
+            if final_block_hash != CryptoHash::default()
+                && final_block_hash != self.last_final_block_hash
+            {
+                self.last_final_block_hash = final_block_hash;
+                let final_block =
+                    unwrap_or_return!(self.client.chain.get_block(&final_block_hash)).clone();
+                events.push(ChainEvent::FinalBlock(final_block.into()));
+            }
+        }
+        for chunk_header in block.chunks.iter() {
+            if chunk_header.height_included == block.header.inner_lite.height {
+                events.push(ChainEvent::NewChunk {
+                    block_hash: *block_hash,
+                    chunk: chunk_header.clone().into(),
+                });
+            }
+        }
+        let outcomes =
+            unwrap_or_return!(self.client.chain.get_block_execution_outcomes(block_hash));
+        events.extend(outcomes.into_iter().map(|(receiver_id, outcome)| {
+            ChainEvent::ExecutionOutcome { receiver_id, outcome: outcome.into() }
+        }));
+
+        for mut subscriber in self.chain_event_subscribers.drain(..).collect::<Vec<_>>() {
+            match subscriber
+                .recipient
+                .try_send(ChainEvents { events: events.clone(), dropped: subscriber.dropped })
+            {
+                Ok(()) => subscriber.dropped = 0,
+                Err(SendError::Full(_)) => subscriber.dropped += 1,
+                Err(SendError::Closed(_)) => continue,
+            }
+            self.chain_event_subscribers.push(subscriber);
         }
     }
 
@@ -4,8 +4,11 @@ extern crate lazy_static;
 pub use crate::client::Client;
 pub use crate::client_actor::ClientActor;
 pub use crate::types::{
-    BlockProducer, ClientConfig, Error, GetBlock, GetChunk, GetGasPrice, GetNetworkInfo,
-    GetNextLightClientBlock, GetValidatorInfo, Query, Status, StatusResponse, SyncStatus, TxStatus,
+    BlockProducer, ChainEvent, ChainEvents, ClientConfig, CreateSnapshot, Error, GetBlock,
+    GetChunk, GetContractEvents, GetGasPrice, GetLightClientProof, GetNetworkInfo,
+    GetNextLightClientBlock, GetPendingTransactions, GetStateChanges, GetTransactionPoolStats,
+    GetValidatorInfo, Query, SimulateTx, Status, StatusResponse, SubscribeChainEvents, SyncStatus,
+    TxStatus,
 };
 pub use crate::view_client::ViewClientActor;
 
@@ -5,16 +5,20 @@ use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 use actix::actors::mocker::Mocker;
-use actix::{Actor, Addr, AsyncContext, Context};
+use actix::{Actor, Addr, AsyncContext, Context, MailboxError};
 use chrono::{DateTime, Utc};
 use futures::future;
-use futures::future::Future;
+use futures::future::{Either, Future};
+use futures::stream::{self, Stream};
 use rand::{thread_rng, Rng};
 
 use near_chain::test_utils::KeyValueRuntime;
 use near_chain::{Chain, ChainGenesis, Provenance, RuntimeAdapter};
 use near_crypto::{InMemorySigner, KeyType, PublicKey};
-use near_network::types::{AccountOrPeerIdOrHash, NetworkInfo, PeerChainInfo};
+use near_network::recorder::{Direction, RecordedMessage};
+use near_network::types::{
+    AccountOrPeerIdOrHash, NetworkInfo, NetworkViewClientResponses, PeerChainInfo, PeerMessage,
+};
 use near_network::{
     FullPeerInfo, NetworkAdapter, NetworkClientMessages, NetworkClientResponses, NetworkRecipient,
     NetworkRequests, NetworkResponses, PeerInfo, PeerManagerActor,
@@ -314,6 +318,7 @@ pub fn setup_mock_all_validators(
                             sent_bytes_per_sec: 0,
                             received_bytes_per_sec: 0,
                             known_producers: vec![],
+                            peer_reputations: HashMap::new(),
                         };
                         client_addr.do_send(NetworkClientMessages::NetworkInfo(info));
                     }
@@ -642,6 +647,58 @@ pub fn setup_mock_all_validators(
     (value.clone().unwrap(), ret)
 }
 
+/// Response of the client or the view client to a replayed message.
+pub enum ReplayResponse {
+    Client(NetworkClientResponses),
+    ViewClient(NetworkViewClientResponses),
+}
+
+/// Feeds the messages received from the peers in a network recording to the client and view
+/// client, e.g. set up with `setup_mock`. Messages are sent one at a time in the recorded order,
+/// each after the previous one is processed, so the replay doesn't depend on timing. Messages
+/// handled by the network layer itself are skipped. Resolves to the responses in the same order.
+pub fn replay_recording(
+    client: Addr<ClientActor>,
+    view_client: Addr<ViewClientActor>,
+    recording: Vec<RecordedMessage>,
+) -> impl Future<Item = Vec<ReplayResponse>, Error = MailboxError> {
+    // Blocks requested from each peer, to tell the client whether a received block was requested.
+    let mut requested_blocks = HashSet::new();
+    let mut messages = vec![];
+    for recorded in recording {
+        let RecordedMessage { peer_id, direction, message, .. } = recorded;
+        match direction {
+            Direction::Outbound => {
+                if let PeerMessage::BlockRequest(hash) = message {
+                    requested_blocks.insert((peer_id, hash));
+                }
+            }
+            Direction::Inbound => {
+                if message.is_view_client_message() {
+                    messages.extend(message.into_view_client_message().map(Either::B));
+                } else if message.is_client_message() {
+                    let was_requested = match &message {
+                        PeerMessage::Block(block) => {
+                            requested_blocks.contains(&(peer_id.clone(), block.hash()))
+                        }
+                        _ => false,
+                    };
+                    messages
+                        .extend(message.into_client_message(peer_id, was_requested).map(Either::A));
+                }
+            }
+        }
+    }
+    stream::iter_ok(messages)
+        .and_then(move |message| match message {
+            Either::A(message) => Either::A(client.send(message).map(ReplayResponse::Client)),
+            Either::B(message) => {
+                Either::B(view_client.send(message).map(ReplayResponse::ViewClient))
+            }
+        })
+        .collect()
+}
+
 /// Sets up ClientActor and ViewClientActor without network.
 pub fn setup_no_network(
     validators: Vec<&'static str>,
@@ -1,24 +1,32 @@
 use std::cmp::min;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
-use actix::Message;
+use actix::{Message, Recipient};
 use chrono::{DateTime, Utc};
 use serde_derive::{Deserialize, Serialize};
 
 use near_crypto::{InMemorySigner, Signer};
-use near_network::types::AccountOrPeerIdOrHash;
+use near_network::types::{AccountOrPeerIdOrHash, PeerId};
 use near_network::PeerInfo;
+use near_pool::types::PoolConfig;
 use near_primitives::hash::CryptoHash;
 use near_primitives::sharding::ChunkHash;
-use near_primitives::types::{AccountId, BlockIndex, ShardId, ValidatorId, Version};
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::{
+    AccountId, BlockIdOrFinality, BlockIndex, ShardId, ValidatorId, Version,
+};
 use near_primitives::utils::generate_random_string;
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
-    LightClientBlockView, QueryResponse,
+    BlockView, ChunkHeaderView, ChunkView, ContractEventWithOutcomeView, EpochValidatorInfo,
+    ExecutionOutcomeWithIdView, FinalExecutionOutcomeView, GasPriceView, LightClientBlockView,
+    LightClientProofView, PendingTransactionView, QueryResponse, SimulationResultView,
+    StateChangesView, TransactionPoolStatsView,
 };
 pub use near_primitives::views::{StatusResponse, StatusSyncInfo};
+use near_store::SnapshotManifest;
 
 /// Combines errors coming from chain, tx pool and block producer.
 #[derive(Debug)]
@@ -135,6 +143,12 @@ pub struct ClientConfig {
     pub tracked_accounts: Vec<AccountId>,
     /// Shards that this client tracks
     pub tracked_shards: Vec<ShardId>,
+    /// Keep all the history instead of garbage collecting old blocks and state.
+    pub archive: bool,
+    /// Number of epochs of blocks and state to keep when not archiving.
+    pub gc_num_epochs_to_keep: u64,
+    /// Limits and ordering of the transaction pools.
+    pub tx_pool: PoolConfig,
 }
 
 impl ClientConfig {
@@ -183,6 +197,9 @@ impl ClientConfig {
             block_header_fetch_horizon: 50,
             tracked_accounts: vec![],
             tracked_shards: vec![],
+            archive: false,
+            gc_num_epochs_to_keep: 5,
+            tx_pool: PoolConfig::default(),
         }
     }
 }
@@ -284,11 +301,33 @@ pub struct Query {
     pub path: String,
     pub data: Vec<u8>,
     pub id: String,
+    /// Block whose state is queried.
+    pub block_id_or_finality: BlockIdOrFinality,
+    /// Whether the response should have the proof of the result.
+    pub include_proof: bool,
 }
 
 impl Query {
+    /// Query against the state of the current head.
     pub fn new(path: String, data: Vec<u8>) -> Self {
-        Query { path, data, id: generate_random_string(10) }
+        Self::new_at(BlockIdOrFinality::latest(), path, data)
+    }
+
+    /// Query against the state of the given block.
+    pub fn new_at(block_id_or_finality: BlockIdOrFinality, path: String, data: Vec<u8>) -> Self {
+        Query {
+            path,
+            data,
+            id: generate_random_string(10),
+            block_id_or_finality,
+            include_proof: false,
+        }
+    }
+
+    /// Asks for the trie nodes read to answer the query, see `near_store::verify_state_proof`.
+    pub fn with_proof(mut self) -> Self {
+        self.include_proof = true;
+        self
     }
 }
 
@@ -312,12 +351,44 @@ impl Message for GetNextLightClientBlock {
     type Result = Result<Option<LightClientBlockView>, String>;
 }
 
+/// Proof that the outcome of the transaction or receipt with given id is included into a block.
+pub struct GetLightClientProof {
+    pub id: CryptoHash,
+}
+
+impl Message for GetLightClientProof {
+    type Result = Result<LightClientProofView, String>;
+}
+
 pub struct GetNetworkInfo {}
 
 impl Message for GetNetworkInfo {
     type Result = Result<NetworkInfoResponse, String>;
 }
 
+/// Maximum number of pending transactions returned by `GetPendingTransactions`.
+pub const MAX_PENDING_TRANSACTIONS: usize = 1000;
+
+/// Transactions waiting in the pools of the node, of the given shard or signed by the given
+/// account. Without a shard the shard of the signer is used, or all shards without a signer.
+/// At most `limit` transactions are returned, capped by `MAX_PENDING_TRANSACTIONS`.
+pub struct GetPendingTransactions {
+    pub shard_id: Option<ShardId>,
+    pub signer_id: Option<AccountId>,
+    pub limit: Option<usize>,
+}
+
+impl Message for GetPendingTransactions {
+    type Result = Result<Vec<PendingTransactionView>, String>;
+}
+
+/// Statistics of the transaction pools of the node, one entry per shard that has a pool.
+pub struct GetTransactionPoolStats {}
+
+impl Message for GetTransactionPoolStats {
+    type Result = Result<Vec<TransactionPoolStatsView>, String>;
+}
+
 pub enum GetGasPrice {
     Height(BlockIndex),
     Hash(CryptoHash),
@@ -328,6 +399,52 @@ impl Message for GetGasPrice {
     type Result = Result<GasPriceView, String>;
 }
 
+/// Events emitted by contracts in the blocks with heights in the given range, both inclusive,
+/// optionally only by the given contract or only with the given topic. Only the shards tracked by
+/// the node are searched.
+pub struct GetContractEvents {
+    pub from_height: BlockIndex,
+    pub to_height: BlockIndex,
+    pub account_id: Option<AccountId>,
+    pub topic: Option<CryptoHash>,
+}
+
+impl Message for GetContractEvents {
+    type Result = Result<Vec<ContractEventWithOutcomeView>, String>;
+}
+
+/// Changes of the subscribed state prefixes in the blocks with heights in the given range, both
+/// inclusive, optionally only of the keys with the given prefix.
+pub struct GetStateChanges {
+    pub from_height: BlockIndex,
+    pub to_height: BlockIndex,
+    pub key_prefix: Option<Vec<u8>>,
+}
+
+impl Message for GetStateChanges {
+    type Result = Result<Vec<StateChangesView>, String>;
+}
+
+/// Executes the transaction on top of the state of the head, as if it was included in the next
+/// block, without sending it to the pool.
+pub struct SimulateTx {
+    pub tx: SignedTransaction,
+}
+
+impl Message for SimulateTx {
+    type Result = Result<SimulationResultView, String>;
+}
+
+/// Takes a snapshot of the database of the running node at the given path.
+pub struct CreateSnapshot {
+    pub chain_id: String,
+    pub path: PathBuf,
+}
+
+impl Message for CreateSnapshot {
+    type Result = Result<SnapshotManifest, String>;
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct NetworkInfoResponse {
     pub active_peers: Vec<PeerInfo>,
@@ -337,6 +454,8 @@ pub struct NetworkInfoResponse {
     pub received_bytes_per_sec: u64,
     /// Accounts of known block and chunk producers from routing table.
     pub known_producers: Vec<AccountId>,
+    /// Reputation of the active peers.
+    pub peer_reputations: HashMap<PeerId, i64>,
 }
 
 /// Status of given transaction including all the subsequent receipts.
@@ -356,3 +475,37 @@ pub struct GetValidatorInfo {
 impl Message for GetValidatorInfo {
     type Result = Result<EpochValidatorInfo, String>;
 }
+
+/// Change of the chain observed by the client that can be streamed to subscribers.
+#[derive(Clone, Debug)]
+pub enum ChainEvent {
+    /// Block became the new head of the chain.
+    NewHead(BlockView),
+    /// Block became the last final block.
+    FinalBlock(BlockView),
+    /// Chunk with given header was included into the block.
+    NewChunk { block_hash: CryptoHash, chunk: ChunkHeaderView },
+    /// Transaction or receipt executed on `receiver_id` in the block.
+    ExecutionOutcome { receiver_id: AccountId, outcome: ExecutionOutcomeWithIdView },
+}
+
+/// Events produced while processing a single accepted block.
+pub struct ChainEvents {
+    pub events: Vec<ChainEvent>,
+    /// Number of previous batches that were dropped because the subscriber was not keeping up.
+    pub dropped: u64,
+}
+
+impl Message for ChainEvents {
+    type Result = ();
+}
+
+/// Registers recipient for all the events the client observes.
+/// The recipient is removed once its mailbox is closed.
+pub struct SubscribeChainEvents {
+    pub recipient: Recipient<ChainEvents>,
+}
+
+impl Message for SubscribeChainEvents {
+    type Result = ();
+}
@@ -2,25 +2,34 @@
 //! Useful for querying from RPC.
 
 use std::sync::Arc;
+use std::thread;
 
-use actix::{Actor, Context, Handler};
-use log::{error, warn};
+use actix::{Actor, Context, Handler, ResponseFuture};
+use borsh::BorshSerialize;
+use futures::sync::oneshot;
+use futures::{future, Future};
+use log::{error, info, warn};
 
-use near_chain::{Chain, ChainGenesis, ChainStoreAccess, ErrorKind, RuntimeAdapter};
-use near_primitives::types::AccountId;
+use near_chain::{Chain, ChainGenesis, ChainStore, ChainStoreAccess, ErrorKind, RuntimeAdapter};
+use near_primitives::block::BlockHeader;
+use near_primitives::types::{AccountId, BlockId, BlockIdOrFinality, BlockIndex, Finality};
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, FinalExecutionStatus,
-    GasPriceView, LightClientBlockView, QueryResponse,
+    BlockView, ChunkView, ContractEventWithOutcomeView, EpochValidatorInfo,
+    FinalExecutionOutcomeView, FinalExecutionStatus, GasPriceView, LightClientBlockView,
+    LightClientProofView, QueryResponse, SimulationResultView, StateChangeView, StateChangesView,
 };
-use near_store::Store;
+use near_store::{SnapshotManifest, Store};
 
-use crate::types::{Error, GetBlock, GetGasPrice, Query, TxStatus};
-use crate::{GetChunk, GetNextLightClientBlock, GetValidatorInfo};
+use crate::types::{
+    CreateSnapshot, Error, GetBlock, GetContractEvents, GetGasPrice, GetStateChanges, Query,
+    SimulateTx, TxStatus,
+};
+use crate::{GetChunk, GetLightClientProof, GetNextLightClientBlock, GetValidatorInfo};
 use cached::{Cached, SizedCache};
 use near_network::types::{NetworkViewClientMessages, NetworkViewClientResponses};
 use near_network::{NetworkAdapter, NetworkRequests};
 use near_primitives::hash::CryptoHash;
-use near_primitives::merkle::verify_path;
+use near_primitives::merkle::{merklize, verify_path};
 use std::hash::Hash;
 use std::time::{Duration, Instant};
 
@@ -28,6 +37,10 @@ use std::time::{Duration, Instant};
 const QUERY_REQUEST_LIMIT: usize = 500;
 /// Waiting time between requests, in ms
 const REQUEST_WAIT_TIME: u64 = 1000;
+/// Max number of blocks searched by a single request for contract events.
+const MAX_CONTRACT_EVENTS_BLOCK_RANGE: BlockIndex = 1000;
+/// Max number of blocks searched by a single request for state changes.
+const MAX_STATE_CHANGES_BLOCK_RANGE: BlockIndex = 100;
 
 /// View client provides currently committed (to the storage) view of the current chain and state.
 pub struct ViewClientActor {
@@ -79,12 +92,53 @@ impl ViewClientActor {
         need_request
     }
 
+    /// Resolves the header of the block whose state is queried.
+    fn get_query_header(
+        &mut self,
+        block_id_or_finality: &BlockIdOrFinality,
+    ) -> Result<BlockHeader, String> {
+        let header = match block_id_or_finality {
+            BlockIdOrFinality::BlockId(BlockId::Height(height)) => {
+                self.chain.get_header_by_height(*height)
+            }
+            BlockIdOrFinality::BlockId(BlockId::Hash(block_hash)) => {
+                self.chain.get_block_header(block_hash)
+            }
+            BlockIdOrFinality::Finality(Finality::Optimistic) => self.chain.head_header(),
+            BlockIdOrFinality::Finality(Finality::Final) => {
+                let final_block_hash = self
+                    .chain
+                    .head_header()
+                    .map_err(|e| e.to_string())?
+                    .inner_rest
+                    .last_quorum_pre_commit;
+                if final_block_hash == CryptoHash::default() {
+                    Ok(self.chain.genesis())
+                } else {
+                    self.chain.get_block_header(&final_block_hash)
+                }
+            }
+        };
+        header.map(Clone::clone).map_err(|e| match e.kind() {
+            ErrorKind::DBNotFoundErr(_) => format!("Block {:?} is unknown", block_id_or_finality),
+            _ => e.to_string(),
+        })
+    }
+
     fn handle_query(&mut self, msg: Query) -> Result<Option<QueryResponse>, String> {
         if let Some(response) = self.query_responses.cache_remove(&msg.id) {
             self.query_requests.cache_remove(&msg.id);
             return response.map(Some);
         }
-        let header = self.chain.head_header().map_err(|e| e.to_string())?.clone();
+        let header = self.get_query_header(&msg.block_id_or_finality)?;
+        if let Ok(tail) = self.chain.store().tail() {
+            if header.inner_lite.height <= tail.height {
+                return Err(format!(
+                    "State at block {} has been garbage collected, the oldest available height is {}",
+                    header.hash, tail.height
+                ));
+            }
+        }
         let path_parts: Vec<&str> = msg.path.split('/').collect();
         if path_parts.len() <= 1 {
             return Err("Not enough query parameters provided".to_string());
@@ -105,6 +159,7 @@ impl ViewClientActor {
                         &header.hash,
                         path_parts.clone(),
                         &msg.data,
+                        msg.include_proof,
                     )
                     .map(Some)
                     .map_err(|e| e.to_string())
@@ -116,6 +171,14 @@ impl ViewClientActor {
                         warn!(target: "client", "Getting chunk extra failed: {}", e.to_string());
                     }
                 }
+                // Peers answer queries against their own head without proofs, so only those can
+                // be routed.
+                if msg.block_id_or_finality != BlockIdOrFinality::latest() || msg.include_proof {
+                    return Err(format!(
+                        "State of shard {} at block {} is not available: it was garbage collected or the shard is not tracked",
+                        shard_id, header.hash
+                    ));
+                }
                 // route request
                 if Self::need_request(msg.id.clone(), &mut self.query_requests) {
                     let validator = self
@@ -328,6 +391,59 @@ impl Handler<GetNextLightClientBlock> for ViewClientActor {
     }
 }
 
+/// Returns the proof that the outcome of a transaction or receipt is included into the first block
+/// after the one it was executed in that has a new chunk of its shard, since the outcome root of
+/// a chunk is only committed to by the next chunk of the shard.
+impl Handler<GetLightClientProof> for ViewClientActor {
+    type Result = Result<LightClientProofView, String>;
+
+    fn handle(&mut self, msg: GetLightClientProof, _: &mut Context<Self>) -> Self::Result {
+        let outcome_with_proof =
+            self.chain.get_execution_outcome(&msg.id).map_err(|err| err.to_string())?.clone();
+        let outcome_hashes = outcome_with_proof.outcome_with_id.to_hashes();
+        let block_hash = outcome_with_proof.block_hash;
+
+        // Find the shard whose outcome root the outcome proof leads to.
+        let mut outcome_shard_id = None;
+        for shard_id in 0..self.runtime_adapter.num_shards() {
+            if let Ok(chunk_extra) = self.chain.get_chunk_extra(&block_hash, shard_id) {
+                if verify_path(chunk_extra.outcome_root, &outcome_with_proof.proof, &outcome_hashes)
+                {
+                    outcome_shard_id = Some(shard_id);
+                    break;
+                }
+            }
+        }
+        let shard_id = outcome_shard_id
+            .ok_or_else(|| format!("Outcome root of {} is not tracked by the node", msg.id))?;
+
+        let next_block_hash = match self
+            .chain
+            .get_next_block_hash_with_new_chunk(&block_hash, shard_id)
+            .map_err(|err| err.to_string())?
+        {
+            Some(next_block_hash) => *next_block_hash,
+            None => return Err(format!("Outcome of {} is not included into a block yet", msg.id)),
+        };
+        let block = self.chain.get_block(&next_block_hash).map_err(|err| err.to_string())?;
+        let (_, outcome_root_paths) = merklize(
+            &block.chunks.iter().map(|chunk| chunk.inner.outcome_root).collect::<Vec<_>>(),
+        );
+        let outcome_root_proof = outcome_root_paths[shard_id as usize].clone();
+        let block_header_lite = block.header.clone().into();
+
+        Ok(LightClientProofView {
+            raw_outcome: outcome_with_proof
+                .outcome_with_id
+                .try_to_vec()
+                .map_err(|err| err.to_string())?,
+            outcome_proof: outcome_with_proof.into(),
+            outcome_root_proof,
+            block_header_lite,
+        })
+    }
+}
+
 impl Handler<NetworkViewClientMessages> for ViewClientActor {
     type Result = NetworkViewClientResponses;
 
@@ -348,7 +464,13 @@ impl Handler<NetworkViewClientMessages> for ViewClientActor {
                 NetworkViewClientResponses::NoResponse
             }
             NetworkViewClientMessages::Query { path, data, id } => {
-                let query = Query { path, data, id: id.clone() };
+                let query = Query {
+                    path,
+                    data,
+                    id: id.clone(),
+                    block_id_or_finality: BlockIdOrFinality::latest(),
+                    include_proof: false,
+                };
                 match self.handle_query(query) {
                     Ok(Some(r)) => {
                         NetworkViewClientResponses::QueryResponse { response: Ok(r), id }
@@ -405,6 +527,176 @@ impl Handler<NetworkViewClientMessages> for ViewClientActor {
     }
 }
 
+/// Snapshots are taken on a dedicated thread, as cutting them at the final block may take a
+/// while and the view client would stop answering queries meanwhile.
+impl Handler<CreateSnapshot> for ViewClientActor {
+    type Result = ResponseFuture<SnapshotManifest, String>;
+
+    fn handle(&mut self, msg: CreateSnapshot, _ctx: &mut Self::Context) -> Self::Result {
+        let chain_store = ChainStore::new(self.chain.store().owned_store());
+        let (sender, receiver) = oneshot::channel();
+        let spawned = thread::Builder::new().name("snapshot".to_string()).spawn(move || {
+            let result = chain_store
+                .create_snapshot(&msg.chain_id, &msg.path)
+                .map_err(|err| format!("Failed to create a snapshot: {}", err));
+            if let Ok(manifest) = &result {
+                info!(
+                    target: "client",
+                    "Created snapshot at {} with head #{} {}",
+                    msg.path.display(),
+                    manifest.head_height,
+                    manifest.head_hash
+                );
+            }
+            let _ = sender.send(result);
+        });
+        if let Err(err) = spawned {
+            return Box::new(future::err(format!("Failed to start taking a snapshot: {}", err)));
+        }
+        Box::new(
+            receiver
+                .map_err(|_| "Snapshot thread stopped without a result".to_string())
+                .and_then(|result| result),
+        )
+    }
+}
+
+impl Handler<GetContractEvents> for ViewClientActor {
+    type Result = Result<Vec<ContractEventWithOutcomeView>, String>;
+
+    fn handle(&mut self, msg: GetContractEvents, _ctx: &mut Self::Context) -> Self::Result {
+        if msg.to_height < msg.from_height
+            || msg.to_height - msg.from_height >= MAX_CONTRACT_EVENTS_BLOCK_RANGE
+        {
+            return Err(format!(
+                "Invalid block range {}..{}, at most {} blocks can be searched at once",
+                msg.from_height, msg.to_height, MAX_CONTRACT_EVENTS_BLOCK_RANGE
+            ));
+        }
+        let mut events = vec![];
+        for height in msg.from_height..=msg.to_height {
+            let block_hash = match self.chain.get_header_by_height(height) {
+                Ok(header) => header.hash,
+                Err(err) => match err.kind() {
+                    ErrorKind::DBNotFoundErr(_) => continue,
+                    _ => return Err(err.to_string()),
+                },
+            };
+            for shard_id in 0..self.runtime_adapter.num_shards() {
+                let outcome_receivers = self
+                    .chain
+                    .store()
+                    .get_outcome_receivers(&block_hash, shard_id)
+                    .map_err(|err| err.to_string())?;
+                for (outcome_id, receiver_id) in outcome_receivers {
+                    if msg
+                        .account_id
+                        .as_ref()
+                        .map_or(false, |account_id| account_id != &receiver_id)
+                    {
+                        continue;
+                    }
+                    let outcome =
+                        self.chain.get_execution_outcome(&outcome_id).map_err(|e| e.to_string())?;
+                    for event in outcome.outcome_with_id.outcome.events.iter() {
+                        if msg
+                            .topic
+                            .as_ref()
+                            .map_or(false, |topic| !event.topics.contains(&(topic.0).0))
+                        {
+                            continue;
+                        }
+                        events.push(ContractEventWithOutcomeView {
+                            block_height: height,
+                            block_hash,
+                            outcome_id,
+                            event: event.clone().into(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(events)
+    }
+}
+
+impl Handler<GetStateChanges> for ViewClientActor {
+    type Result = Result<Vec<StateChangesView>, String>;
+
+    fn handle(&mut self, msg: GetStateChanges, _ctx: &mut Self::Context) -> Self::Result {
+        if msg.to_height < msg.from_height
+            || msg.to_height - msg.from_height >= MAX_STATE_CHANGES_BLOCK_RANGE
+        {
+            return Err(format!(
+                "Invalid block range {}..{}, at most {} blocks can be searched at once",
+                msg.from_height, msg.to_height, MAX_STATE_CHANGES_BLOCK_RANGE
+            ));
+        }
+        let mut result = vec![];
+        for height in msg.from_height..=msg.to_height {
+            let block_hash = match self.chain.get_header_by_height(height) {
+                Ok(header) => header.hash,
+                Err(err) => match err.kind() {
+                    ErrorKind::DBNotFoundErr(_) => continue,
+                    _ => return Err(err.to_string()),
+                },
+            };
+            for shard_id in 0..self.runtime_adapter.num_shards() {
+                let changes: Vec<_> = self
+                    .chain
+                    .store()
+                    .get_state_changes(&block_hash, shard_id)
+                    .map_err(|err| err.to_string())?
+                    .into_iter()
+                    .filter(|(key, _)| {
+                        msg.key_prefix.as_ref().map_or(true, |prefix| key.starts_with(prefix))
+                    })
+                    .map(|(key, value)| StateChangeView { key, value })
+                    .collect();
+                if !changes.is_empty() {
+                    result.push(StateChangesView {
+                        block_height: height,
+                        block_hash,
+                        shard_id,
+                        changes,
+                    });
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Simulates the transaction on top of the state of the head. Only the shard of the signer is
+/// simulated, so the node must track it; receipts to other shards are returned unprocessed.
+impl Handler<SimulateTx> for ViewClientActor {
+    type Result = Result<SimulationResultView, String>;
+
+    fn handle(&mut self, msg: SimulateTx, _ctx: &mut Self::Context) -> Self::Result {
+        let header = self.chain.head_header().map_err(|e| e.to_string())?.clone();
+        let shard_id = self.runtime_adapter.account_id_to_shard_id(&msg.tx.transaction.signer_id);
+        let state_root = match self.chain.get_chunk_extra(&header.hash, shard_id) {
+            Ok(chunk_extra) => chunk_extra.state_root,
+            Err(_) => {
+                return Err(format!(
+                    "State of shard {} at block {} is not available: the shard is not tracked",
+                    shard_id, header.hash
+                ))
+            }
+        };
+        self.runtime_adapter
+            .simulate_transaction(
+                shard_id,
+                &state_root,
+                header.inner_lite.height + 1,
+                header.inner_lite.timestamp,
+                header.inner_rest.gas_price,
+                &msg.tx,
+            )
+            .map_err(|e| e.to_string())
+    }
+}
+
 impl Handler<GetGasPrice> for ViewClientActor {
     type Result = Result<GasPriceView, String>;
 
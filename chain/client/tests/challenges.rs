@@ -173,10 +173,14 @@ fn create_chunk(
         .unwrap();
     if let Some(transactions) = replace_transactions {
         // The best way it to decode chunk, replace transactions and then recreate encoded chunk.
-        let total_parts =
-            client.chain.runtime_adapter.num_total_parts(&chunk.header.inner.prev_block_hash);
-        let data_parts =
-            client.chain.runtime_adapter.num_data_parts(&chunk.header.inner.prev_block_hash);
+        let total_parts = client
+            .chain
+            .runtime_adapter
+            .num_total_parts(&chunk.header.inner.prev_block_hash, chunk.header.inner.shard_id);
+        let data_parts = client
+            .chain
+            .runtime_adapter
+            .num_data_parts(&chunk.header.inner.prev_block_hash, chunk.header.inner.shard_id);
         let decoded_chunk = chunk.decode_chunk(data_parts).unwrap();
 
         let (tx_root, _) = merklize(&transactions);
@@ -256,12 +260,10 @@ fn test_verify_chunk_invalid_proofs_challenge_decoded_chunk() {
     let (encoded_chunk, _merkle_paths, _receipts, block) =
         create_invalid_proofs_chunk(&mut env.clients[0]);
     let chunk = encoded_chunk
-        .decode_chunk(
-            env.clients[0]
-                .chain
-                .runtime_adapter
-                .num_data_parts(&encoded_chunk.header.inner.prev_block_hash),
-        )
+        .decode_chunk(env.clients[0].chain.runtime_adapter.num_data_parts(
+            &encoded_chunk.header.inner.prev_block_hash,
+            encoded_chunk.header.inner.shard_id,
+        ))
         .unwrap();
 
     let challenge_result = challenge(
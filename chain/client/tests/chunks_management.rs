@@ -1,21 +1,27 @@
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
 
 use actix::{Addr, System};
 use futures::{future, Future};
 use log::info;
 
-use near_chain::ChainGenesis;
+use near_chain::{ChainGenesis, ChainStoreAccess};
 use near_client::test_utils::{setup_mock_all_validators, TestEnv};
-use near_client::{ClientActor, GetBlock, ViewClientActor};
+use near_client::{Client, ClientActor, GetBlock, ViewClientActor};
+use near_crypto::{InMemorySigner, KeyType};
 use near_network::types::PartialEncodedChunkRequestMsg;
 use near_network::{NetworkClientMessages, NetworkRequests, NetworkResponses, PeerInfo};
-use near_primitives::block::BlockHeader;
+use near_primitives::block::{Approval, BlockHeader};
 use near_primitives::hash::CryptoHash;
+use near_primitives::merkle::{merklize, MerklePath};
+use near_primitives::sharding::EncodedShardChunk;
 use near_primitives::test_utils::init_integration_logger;
 use near_primitives::test_utils::{heavy_test, init_test_logger};
 use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::StateRoot;
 
 #[test]
 fn chunks_produced_and_distributed_all_in_all_shards() {
@@ -250,3 +256,147 @@ fn test_request_chunk_restart() {
         assert!(false);
     }
 }
+
+/// Requests for parts are answered with the parts that are known, even if some are missing.
+#[test]
+fn test_request_chunk_partial_response() {
+    init_test_logger();
+    let mut env = TestEnv::new(ChainGenesis::test(), 1, 1);
+    for i in 1..4 {
+        env.produce_block(0, i);
+        env.network_adapters[0].pop();
+    }
+    let block1 = env.clients[0].chain.get_block_by_height(3).unwrap().clone();
+    let request = PartialEncodedChunkRequestMsg {
+        chunk_hash: block1.chunks[0].chunk_hash(),
+        part_ords: vec![0, 1_000],
+        tracking_shards: HashSet::default(),
+    };
+    let client = &mut env.clients[0];
+    client.shards_mgr.process_partial_encoded_chunk_request(
+        request,
+        CryptoHash::default(),
+        client.chain.mut_store(),
+    );
+    let response = env.network_adapters[0].pop().unwrap();
+    if let NetworkRequests::PartialEncodedChunkResponse { partial_encoded_chunk, .. } = response {
+        assert_eq!(partial_encoded_chunk.parts.len(), 1);
+        assert_eq!(partial_encoded_chunk.parts[0].part_ord, 0);
+    } else {
+        panic!("Unexpected response {:?}", response);
+    }
+}
+
+/// Parts owned by a block producer that missed its approval are also sent to an online one.
+#[test]
+fn test_forward_parts_of_offline_owner() {
+    init_test_logger();
+    let mut env = TestEnv::new(ChainGenesis::test(), 1, 3);
+    let client = &mut env.clients[0];
+    let genesis = client.chain.get_block_by_height(0).unwrap().clone();
+    let genesis_hash = genesis.hash();
+
+    // Only test0 and test2 approved the block, so test1 is considered offline.
+    let mut header = genesis.header.clone();
+    header.inner_rest.approvals = vec!["test0", "test2"]
+        .into_iter()
+        .map(|account_id| {
+            let signer = InMemorySigner::from_seed(account_id, KeyType::ED25519, account_id);
+            Approval::new(genesis_hash, genesis_hash, &signer, account_id.to_string())
+        })
+        .collect();
+    client.shards_mgr.record_block_approvals(&header).unwrap();
+
+    let (encoded_chunk, merkle_paths) = create_chunk_on_genesis(client);
+    let chunk_hash = encoded_chunk.header.chunk_hash();
+    client
+        .shards_mgr
+        .distribute_encoded_chunk(encoded_chunk, merkle_paths, vec![], client.chain.mut_store())
+        .unwrap();
+
+    let mut sent_parts = HashMap::new();
+    while let Some(request) = env.network_adapters[0].pop() {
+        if let NetworkRequests::PartialEncodedChunkMessage { account_id, partial_encoded_chunk } =
+            request
+        {
+            let part_ords = partial_encoded_chunk.parts.iter().map(|part| part.part_ord);
+            sent_parts.insert(account_id, part_ords.collect::<HashSet<_>>());
+        }
+    }
+    let kept_parts = client
+        .chain
+        .mut_store()
+        .get_partial_chunk(&chunk_hash)
+        .unwrap()
+        .parts
+        .iter()
+        .map(|part| part.part_ord)
+        .collect::<HashSet<_>>();
+
+    let runtime_adapter = client.chain.runtime_adapter.clone();
+    let mut num_offline_parts = 0;
+    for part_ord in 0..runtime_adapter.num_total_parts(&genesis_hash, 0) as u64 {
+        if runtime_adapter.get_part_owner(&genesis_hash, part_ord).unwrap() == "test1" {
+            num_offline_parts += 1;
+            assert!(sent_parts["test1"].contains(&part_ord));
+            assert!(sent_parts["test2"].contains(&part_ord) || kept_parts.contains(&part_ord));
+        }
+    }
+    assert!(num_offline_parts > 0);
+}
+
+/// Parts received for a chunk that is still requested are not requested again after a restart.
+#[test]
+fn test_pending_chunk_restart() {
+    init_test_logger();
+    let mut env = TestEnv::new(ChainGenesis::test(), 1, 1);
+    let (encoded_chunk, merkle_paths) = create_chunk_on_genesis(&mut env.clients[0]);
+    let partial_encoded_chunk =
+        encoded_chunk.create_partial_encoded_chunk(vec![0], true, vec![], &merkle_paths);
+    let client = &mut env.clients[0];
+    client.shards_mgr.request_chunks(vec![encoded_chunk.header.clone()]).unwrap();
+    client.process_partial_encoded_chunk(partial_encoded_chunk).unwrap();
+    while env.network_adapters[0].pop().is_some() {}
+
+    env.restart(0);
+    thread::sleep(Duration::from_millis(200));
+    env.clients[0].shards_mgr.resend_chunk_requests().unwrap();
+    let mut num_requests = 0;
+    while let Some(request) = env.network_adapters[0].pop() {
+        if let NetworkRequests::PartialEncodedChunkRequest { request, .. } = request {
+            assert_eq!(request.chunk_hash, encoded_chunk.header.chunk_hash());
+            assert!(!request.part_ords.is_empty());
+            assert!(!request.part_ords.contains(&0));
+            num_requests += 1;
+        }
+    }
+    assert!(num_requests > 0);
+}
+
+fn create_chunk_on_genesis(client: &mut Client) -> (EncodedShardChunk, Vec<MerklePath>) {
+    let genesis_hash = client.chain.get_block_by_height(0).unwrap().hash();
+    let signer = InMemorySigner::from_seed("test0", KeyType::ED25519, "test0");
+    let (receipts_root, _) = merklize(&client.chain.runtime_adapter.build_receipts_hashes(&vec![]));
+    let (tx_root, _) = merklize(&Vec::<SignedTransaction>::new());
+    client
+        .shards_mgr
+        .create_encoded_shard_chunk(
+            genesis_hash,
+            StateRoot::default(),
+            CryptoHash::default(),
+            1,
+            0,
+            0,
+            1_000,
+            0,
+            0,
+            0,
+            vec![],
+            vec![],
+            &vec![],
+            receipts_root,
+            tx_root,
+            &signer,
+        )
+        .unwrap()
+}
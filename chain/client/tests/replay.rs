@@ -0,0 +1,58 @@
+use actix::System;
+use futures::{future, Future};
+
+use near_client::test_utils::{replay_recording, setup_mock, ReplayResponse};
+use near_client::GetBlock;
+use near_network::recorder::{Direction, RecordedMessage};
+use near_network::types::{PeerId, PeerMessage};
+use near_network::{NetworkClientResponses, NetworkResponses};
+use near_primitives::hash::hash;
+use near_primitives::test_utils::init_test_logger;
+
+/// Replays recorded block requests to the client, which answers the one for a known block.
+#[test]
+fn replay_block_requests() {
+    init_test_logger();
+    System::run(|| {
+        let (client, view_client) = setup_mock(
+            vec!["test"],
+            "other",
+            true,
+            Box::new(|_, _, _| NetworkResponses::NoResponse),
+        );
+        actix::spawn(view_client.send(GetBlock::Best).then(move |res| {
+            let block_hash = res.unwrap().unwrap().header.hash;
+            let peer_id = PeerId::random();
+            let recorded = |direction, message| RecordedMessage {
+                timestamp: 0,
+                peer_id: peer_id.clone(),
+                direction,
+                message,
+            };
+            let recording = vec![
+                recorded(Direction::Inbound, PeerMessage::BlockRequest(block_hash)),
+                // Messages sent to the peer and messages for the network layer are not replayed.
+                recorded(Direction::Outbound, PeerMessage::BlockRequest(hash(&[1]))),
+                recorded(Direction::Inbound, PeerMessage::PeersRequest),
+                recorded(Direction::Inbound, PeerMessage::BlockRequest(hash(&[1]))),
+            ];
+            replay_recording(client, view_client, recording).then(move |res| {
+                let responses = res.unwrap();
+                assert_eq!(responses.len(), 2);
+                match &responses[0] {
+                    ReplayResponse::Client(NetworkClientResponses::Block(block)) => {
+                        assert_eq!(block.hash(), block_hash)
+                    }
+                    _ => panic!("Invalid response"),
+                }
+                match &responses[1] {
+                    ReplayResponse::Client(NetworkClientResponses::NoResponse) => {}
+                    _ => panic!("Invalid response"),
+                }
+                System::current().stop();
+                future::ok(())
+            })
+        }));
+    })
+    .unwrap();
+}
@@ -6,23 +6,19 @@ use serde::Deserialize;
 use serde::Serialize;
 
 use near_primitives::hash::CryptoHash;
-use near_primitives::types::{BlockIndex, ShardId};
+pub use near_primitives::types::BlockId;
+use near_primitives::types::{BlockIdOrFinality, BlockIndex, ShardId};
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
-    QueryResponse, StatusResponse,
+    BlockView, ChunkView, ContractEventWithOutcomeView, EpochValidatorInfo,
+    FinalExecutionOutcomeView, GasPriceView, LightClientBlockView, LightClientProofView,
+    PendingTransactionView, QueryResponse, SimulationResultView, StateChangesView, StatusResponse,
+    TransactionPoolStatsView,
 };
 
 use crate::message::{from_slice, Message, RpcError};
 
 pub mod message;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum BlockId {
-    Height(BlockIndex),
-    Hash(CryptoHash),
-}
-
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ChunkId {
@@ -191,9 +187,99 @@ jsonrpc_client!(pub struct JsonRpcClient {
     pub fn block(&mut self, id: BlockId) -> RpcRequest<BlockView>;
     pub fn chunk(&mut self, id: ChunkId) -> RpcRequest<ChunkView>;
     pub fn validators(&mut self, block_hash: String) -> RpcRequest<EpochValidatorInfo>;
+    pub fn next_light_client_block(
+        &mut self,
+        last_block_hash: String
+    ) -> RpcRequest<Option<LightClientBlockView>>;
+    pub fn light_client_proof(&mut self, id: String) -> RpcRequest<LightClientProofView>;
     pub fn gas_price(&mut self, id: Option<BlockId>) -> RpcRequest<GasPriceView>;
+    pub fn simulate_tx(&mut self, tx: String) -> RpcRequest<SimulationResultView>;
+    pub fn contract_events(
+        &mut self,
+        from_height: BlockIndex,
+        to_height: BlockIndex,
+        account_id: Option<String>,
+        topic: Option<CryptoHash>
+    ) -> RpcRequest<Vec<ContractEventWithOutcomeView>>;
+    pub fn changes(
+        &mut self,
+        from_height: BlockIndex,
+        to_height: BlockIndex,
+        key_prefix: Option<String>
+    ) -> RpcRequest<Vec<StateChangesView>>;
+    pub fn pending_transactions(
+        &mut self,
+        shard_id: Option<ShardId>,
+        signer_id: Option<String>,
+        limit: Option<usize>
+    ) -> RpcRequest<Vec<PendingTransactionView>>;
+    pub fn tx_pool_stats(&mut self) -> RpcRequest<Vec<TransactionPoolStatsView>>;
+    pub fn adm_create_snapshot(&mut self, name: String) -> RpcRequest<serde_json::Value>;
 });
 
+impl JsonRpcClient {
+    /// Queries the state of the given block instead of the head.
+    pub fn query_at(
+        &mut self,
+        path: String,
+        data: String,
+        block_id_or_finality: BlockIdOrFinality,
+    ) -> RpcRequest<QueryResponse> {
+        call_method(&self.client, &self.server_addr, "query", (path, data, block_id_or_finality))
+    }
+
+    /// Queries the state of the given block, with the proof of the result.
+    pub fn query_with_proof(
+        &mut self,
+        path: String,
+        data: String,
+        block_id_or_finality: BlockIdOrFinality,
+    ) -> RpcRequest<QueryResponse> {
+        call_method(
+            &self.client,
+            &self.server_addr,
+            "query",
+            (path, data, block_id_or_finality, true),
+        )
+    }
+
+    /// Returns the description of all methods served by the node.
+    pub fn discover(&mut self) -> RpcRequest<serde_json::Value> {
+        call_method(&self.client, &self.server_addr, "rpc.discover", expand_params!())
+    }
+
+    /// Sends the given requests as a single batch. Responses come back in the order of requests.
+    /// Notifications get no responses, so a batch of notifications only results in no responses.
+    pub fn batch(&mut self, requests: Vec<Message>) -> RpcRequest<Vec<Message>> {
+        Box::new(
+            self.client
+                .post(&self.server_addr)
+                .header("Content-Type", "application/json")
+                .send_json(&Message::Batch(requests))
+                .map_err(|_| RpcError::invalid_request())
+                .and_then(|mut response| {
+                    response.body().then(|body| match body {
+                        Ok(ref bytes) if bytes.is_empty() => Ok(Message::Batch(vec![])),
+                        Ok(bytes) => from_slice(&bytes).map_err(|err| {
+                            RpcError::parse_error(format!("Error {:?} in {:?}", err, bytes))
+                        }),
+                        Err(err) => Err(RpcError::parse_error(format!(
+                            "Failed to retrieve payload: {:?}",
+                            err
+                        ))),
+                    })
+                })
+                .and_then(|message| match message {
+                    Message::Batch(responses) => Ok(responses),
+                    Message::Response(resp) => {
+                        resp.result.and_then(|_| Err(RpcError::invalid_request()))
+                    }
+                    _ => Err(RpcError::invalid_request()),
+                }),
+        )
+    }
+}
+
 fn create_client() -> Client {
     Client::build()
         .timeout(CONNECT_TIMEOUT)
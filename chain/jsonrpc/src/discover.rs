@@ -0,0 +1,393 @@
+//! Description of the JSON-RPC API served by `JsonRpcHandler`, returned by `rpc.discover`.
+//! Follows the layout of an OpenRPC document, so that client SDKs can be generated from it.
+//! Every method dispatched in `JsonRpcHandler::process_request` must be listed here.
+
+use serde_json::{json, Value};
+
+/// Version of the described API. Bump when methods, params or results change.
+const API_VERSION: &str = "0.7.0";
+
+fn param(name: &str, description: &str, schema: Value) -> Value {
+    json!({ "name": name, "description": description, "required": true, "schema": schema })
+}
+
+fn optional_param(name: &str, description: &str, schema: Value) -> Value {
+    json!({ "name": name, "description": description, "required": false, "schema": schema })
+}
+
+fn method(name: &str, summary: &str, params: Vec<Value>, result: Value) -> Value {
+    json!({
+        "name": name,
+        "summary": summary,
+        "paramStructure": "by-position",
+        "params": params,
+        "result": { "name": format!("{}_result", name), "schema": result },
+    })
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn object(required: &[&str]) -> Value {
+    json!({ "type": "object", "required": required })
+}
+
+fn components() -> Value {
+    json!({
+        "schemas": {
+            "CryptoHash": {
+                "type": "string",
+                "description": "Base58 encoded 32 bytes hash",
+            },
+            "AccountId": {
+                "type": "string",
+                "description": "Account name, e.g. `alice.near`",
+            },
+            "BlockId": {
+                "description": "Block height or block hash",
+                "oneOf": [
+                    { "type": "integer", "minimum": 0 },
+                    { "$ref": "#/components/schemas/CryptoHash" },
+                ],
+            },
+            "BlockIdOrFinality": {
+                "description": "Block height or hash, or finality of the latest block",
+                "oneOf": [
+                    { "$ref": "#/components/schemas/BlockId" },
+                    { "type": "string", "enum": ["optimistic", "final"] },
+                ],
+            },
+            "ChunkId": {
+                "description": "Chunk hash, or a pair of block id and shard id",
+                "oneOf": [
+                    { "$ref": "#/components/schemas/CryptoHash" },
+                    {
+                        "type": "array",
+                        "items": [
+                            { "$ref": "#/components/schemas/BlockId" },
+                            { "type": "integer", "minimum": 0 },
+                        ],
+                    },
+                ],
+            },
+            "FinalExecutionOutcome": object(&["status", "transaction", "receipts"]),
+            "BlockView": object(&["header", "chunks"]),
+            "ChunkView": object(&["header", "transactions", "receipts"]),
+            "EpochValidatorInfo": object(&[
+                "current_validators",
+                "next_validators",
+                "current_fishermen",
+                "next_fishermen",
+            ]),
+            "QueryResponse": {
+                "type": "object",
+                "required": ["block_height"],
+                "properties": { "proof": { "$ref": "#/components/schemas/StateProofView" } },
+            },
+            "StateProofView": {
+                "type": "object",
+                "required": ["state_root", "nodes"],
+                "description": "Trie nodes read to answer a query, each an array of bytes",
+            },
+            "StatusResponse": object(&["version", "chain_id", "rpc_addr", "validators", "sync_info"]),
+            "LightClientBlockView": object(&["inner_lite", "inner_rest_hash", "prev_hash"]),
+            "LightClientProof": {
+                "type": "object",
+                "required": [
+                    "outcome_proof",
+                    "raw_outcome",
+                    "outcome_root_proof",
+                    "block_header_lite",
+                ],
+                "description": "Outcome with the merkle path to the outcome root of its chunk, \
+                    the base64 encoded borsh of the outcome, the merkle path from the chunk \
+                    outcome root to the block outcome root, and the lite header of the block",
+            },
+            "NetworkInfo": object(&["active_peers", "num_active_peers", "peer_max_count"]),
+            "SnapshotManifest": object(&[
+                "chain_id",
+                "backend",
+                "head_height",
+                "head_hash",
+                "final_height",
+                "final_hash",
+                "columns",
+            ]),
+            "SimulationResultView": {
+                "type": "object",
+                "required": ["outcomes", "gas_burnt", "logs", "unprocessed_receipts"],
+                "description": "Outcomes of the transaction and of its receipts in the shard of \
+                    the signer; receipts to other shards are returned unprocessed",
+            },
+            "ContractEventWithOutcome": {
+                "type": "object",
+                "required": [
+                    "block_height",
+                    "block_hash",
+                    "outcome_id",
+                    "account_id",
+                    "name",
+                    "topics",
+                    "data",
+                ],
+                "description": "Event emitted by a contract; topics are base58 encoded 32 bytes, \
+                    data is base64 encoded",
+            },
+            "StateChanges": {
+                "type": "object",
+                "required": ["block_height", "block_hash", "shard_id", "changes"],
+                "description": "Changes of the subscribed state keys of a shard in a block; \
+                    every change has a base64 encoded `key` and `value`, null if deleted",
+            },
+            "PendingTransaction": object(&[
+                "hash",
+                "shard_id",
+                "signer_id",
+                "public_key",
+                "nonce",
+                "receiver_id",
+                "age_ms",
+            ]),
+            "TransactionPoolStats": object(&[
+                "shard_id",
+                "num_transactions",
+                "num_groups",
+                "num_evicted",
+            ]),
+            "GasPriceView": {
+                "type": "object",
+                "required": ["gas_price"],
+                "properties": { "gas_price": { "type": "string", "description": "Decimal u128" } },
+            },
+        }
+    })
+}
+
+/// Methods of the API in the order they are dispatched.
+fn methods() -> Vec<Value> {
+    let signed_tx = || {
+        param(
+            "signed_tx",
+            "Borsh serialized `SignedTransaction`, base64 encoded",
+            json!({ "type": "string" }),
+        )
+    };
+    vec![
+        method(
+            "broadcast_tx_async",
+            "Sends a transaction and returns its hash immediately",
+            vec![signed_tx()],
+            schema_ref("CryptoHash"),
+        ),
+        method(
+            "broadcast_tx_commit",
+            "Sends a transaction and waits until it is executed",
+            vec![signed_tx()],
+            schema_ref("FinalExecutionOutcome"),
+        ),
+        method(
+            "validators",
+            "Returns validators and fishermen of the epoch of the given block",
+            vec![param("block_hash", "Hash of the block", schema_ref("CryptoHash"))],
+            schema_ref("EpochValidatorInfo"),
+        ),
+        method(
+            "query",
+            "Queries account, access keys, contract state or calls a view function",
+            vec![
+                param(
+                    "path",
+                    "Query path, e.g. `account/<account_id>` or `call/<account_id>/<method>`. \
+                     `contract/<account_id>/<limit>/<start_key>` returns at most `limit` (1 to \
+                     1000) values starting from the base58 encoded `start_key`, and the key of \
+                     the next page",
+                    json!({ "type": "string" }),
+                ),
+                param("data", "Base58 encoded query data", json!({ "type": "string" })),
+                optional_param(
+                    "block_id_or_finality",
+                    "Block whose state is queried, the head by default",
+                    schema_ref("BlockIdOrFinality"),
+                ),
+                optional_param(
+                    "include_proof",
+                    "Whether to return the trie nodes proving the result against the state root, \
+                     only for `account`, `contract` and `access_key/<account_id>/<public_key>`",
+                    json!({ "type": "boolean" }),
+                ),
+            ],
+            schema_ref("QueryResponse"),
+        ),
+        method("health", "Returns null if the node is healthy", vec![], json!({ "type": "null" })),
+        method("status", "Returns status of the node", vec![], schema_ref("StatusResponse")),
+        method(
+            "tx",
+            "Returns the execution outcome of a transaction, waiting until it is final",
+            vec![
+                param("tx_hash", "Hash of the transaction", schema_ref("CryptoHash")),
+                param("sender_id", "Signer of the transaction", schema_ref("AccountId")),
+            ],
+            schema_ref("FinalExecutionOutcome"),
+        ),
+        method(
+            "block",
+            "Returns the block with the given height or hash",
+            vec![param("block_id", "Height or hash of the block", schema_ref("BlockId"))],
+            schema_ref("BlockView"),
+        ),
+        method(
+            "chunk",
+            "Returns the chunk with the given hash, or the chunk of a shard in a block",
+            vec![param("chunk_id", "Chunk hash or [block_id, shard_id]", schema_ref("ChunkId"))],
+            schema_ref("ChunkView"),
+        ),
+        method(
+            "next_light_client_block",
+            "Returns the next light client block after the given one, if any",
+            vec![param(
+                "last_block_hash",
+                "Hash of the last block known to the light client",
+                schema_ref("CryptoHash"),
+            )],
+            json!({ "oneOf": [schema_ref("LightClientBlockView"), { "type": "null" }] }),
+        ),
+        method(
+            "light_client_proof",
+            "Returns the proof that the outcome of a transaction or receipt is included into the \
+             first block that has the next chunk of its shard",
+            vec![param(
+                "id",
+                "Hash of the transaction or id of the receipt",
+                schema_ref("CryptoHash"),
+            )],
+            schema_ref("LightClientProof"),
+        ),
+        method(
+            "network_info",
+            "Returns active peers and network throughput",
+            vec![],
+            schema_ref("NetworkInfo"),
+        ),
+        method(
+            "pending_transactions",
+            "Returns transactions waiting in the pools of the node, of the given shard or signed \
+             by the given account, or of all shards",
+            vec![
+                optional_param(
+                    "shard_id",
+                    "Only return transactions of this shard, defaults to the shard of the signer",
+                    json!({ "oneOf": [{ "type": "integer" }, { "type": "null" }] }),
+                ),
+                optional_param(
+                    "signer_id",
+                    "Only return transactions signed by this account",
+                    json!({ "oneOf": [schema_ref("AccountId"), { "type": "null" }] }),
+                ),
+                optional_param(
+                    "limit",
+                    "Maximum number of transactions to return, at most 1000",
+                    json!({ "oneOf": [{ "type": "integer" }, { "type": "null" }] }),
+                ),
+            ],
+            json!({ "type": "array", "items": schema_ref("PendingTransaction") }),
+        ),
+        method(
+            "tx_pool_stats",
+            "Returns the size, number of groups and evictions of the transaction pool of every \
+             shard",
+            vec![],
+            json!({ "type": "array", "items": schema_ref("TransactionPoolStats") }),
+        ),
+        method(
+            "gas_price",
+            "Returns gas price at the given block, or at the head if omitted",
+            vec![optional_param(
+                "block_id",
+                "Height or hash of the block",
+                json!({ "oneOf": [schema_ref("BlockId"), { "type": "null" }] }),
+            )],
+            schema_ref("GasPriceView"),
+        ),
+        method(
+            "simulate_tx",
+            "Executes a transaction on top of the head state without sending it to the network",
+            vec![signed_tx()],
+            schema_ref("SimulationResultView"),
+        ),
+        method(
+            "contract_events",
+            "Returns events emitted by contracts in the blocks of the given height range, \
+             searching only the shards tracked by the node",
+            vec![
+                param("from_height", "First height of the range", json!({ "type": "integer" })),
+                param(
+                    "to_height",
+                    "Last height of the range, at most 1000 blocks after the first one",
+                    json!({ "type": "integer" }),
+                ),
+                optional_param(
+                    "account_id",
+                    "Only return events of this contract",
+                    json!({ "oneOf": [schema_ref("AccountId"), { "type": "null" }] }),
+                ),
+                optional_param(
+                    "topic",
+                    "Only return events with this topic",
+                    json!({ "oneOf": [schema_ref("CryptoHash"), { "type": "null" }] }),
+                ),
+            ],
+            json!({ "type": "array", "items": schema_ref("ContractEventWithOutcome") }),
+        ),
+        method(
+            "changes",
+            "Returns changes of the state prefixes the node is subscribed to in its config, in \
+             the blocks of the given height range, searching only the shards tracked by the node",
+            vec![
+                param("from_height", "First height of the range", json!({ "type": "integer" })),
+                param(
+                    "to_height",
+                    "Last height of the range, at most 100 blocks after the first one",
+                    json!({ "type": "integer" }),
+                ),
+                optional_param(
+                    "key_prefix",
+                    "Only return changes of the keys with this base64 encoded prefix",
+                    json!({ "oneOf": [{ "type": "string" }, { "type": "null" }] }),
+                ),
+            ],
+            json!({ "type": "array", "items": schema_ref("StateChanges") }),
+        ),
+        method(
+            "adm_create_snapshot",
+            "Takes a snapshot of the database of the running node at its last final block. Only \
+             served on `admin_addr` and when `snapshots_dir` is set in the RPC config",
+            vec![param(
+                "name",
+                "Name of the snapshot directory inside `snapshots_dir`",
+                json!({ "type": "string" }),
+            )],
+            schema_ref("SnapshotManifest"),
+        ),
+        method(
+            "rpc.discover",
+            "Returns this document",
+            vec![],
+            json!({ "type": "object", "required": ["openrpc", "info", "methods"] }),
+        ),
+    ]
+}
+
+/// OpenRPC-like document describing every JSON-RPC method of the node.
+pub(crate) fn api_description() -> Value {
+    json!({
+        "openrpc": "1.2.4",
+        "info": {
+            "title": "NEAR JSON-RPC",
+            "version": API_VERSION,
+            "description": "Requests may be sent one by one or as a JSON-RPC 2.0 batch array",
+        },
+        "methods": methods(),
+        "components": components(),
+    })
+}
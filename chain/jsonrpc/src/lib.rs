@@ -1,6 +1,8 @@
 extern crate prometheus;
 
+use std::cmp::max;
 use std::convert::TryFrom;
+use std::path::PathBuf;
 use std::string::FromUtf8Error;
 use std::time::Duration;
 
@@ -9,17 +11,21 @@ use actix_cors::Cors;
 use actix_web::{http, middleware, web, App, Error as HttpError, HttpResponse, HttpServer};
 use borsh::BorshDeserialize;
 use futures::future::Future;
-use futures03::{compat::Future01CompatExt as _, FutureExt as _, TryFutureExt as _};
+use futures03::{
+    compat::Future01CompatExt as _, FutureExt as _, StreamExt as _, TryFutureExt as _,
+};
 use serde::de::DeserializeOwned;
 use serde_derive::{Deserialize, Serialize};
 use serde_json::Value;
 
 use async_utils::{delay, timeout};
 use message::Message;
-use message::{Request, RpcError};
+use message::RpcError;
 use near_client::{
-    ClientActor, GetBlock, GetChunk, GetGasPrice, GetNetworkInfo, GetNextLightClientBlock,
-    GetValidatorInfo, Query, Status, TxStatus, ViewClientActor,
+    ClientActor, CreateSnapshot, GetBlock, GetChunk, GetContractEvents, GetGasPrice,
+    GetLightClientProof, GetNetworkInfo, GetNextLightClientBlock, GetPendingTransactions,
+    GetStateChanges, GetTransactionPoolStats, GetValidatorInfo, Query, SimulateTx, Status,
+    TxStatus, ViewClientActor,
 };
 pub use near_jsonrpc_client as client;
 use near_jsonrpc_client::{message, BlockId, ChunkId};
@@ -28,11 +34,15 @@ use near_network::{NetworkClientMessages, NetworkClientResponses};
 use near_primitives::hash::CryptoHash;
 use near_primitives::serialize::{from_base, from_base64, BaseEncode};
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, BlockIdOrFinality, BlockIndex, ShardId};
 use near_primitives::views::{ExecutionErrorView, FinalExecutionStatus};
 
+mod discover;
 mod metrics;
 pub mod test_utils;
+mod websocket;
+
+pub use websocket::{Subscription, WebSocketConfig};
 
 /// Maximum byte size of the json payload.
 const JSON_PAYLOAD_MAX_SIZE: usize = 2 * 1024 * 1024;
@@ -53,11 +63,36 @@ impl Default for RpcPollingConfig {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct RpcBatchConfig {
+    /// Maximum number of requests in a single batch.
+    pub max_batch_size: usize,
+    /// Maximum number of requests of a batch processed concurrently.
+    pub max_parallelism: usize,
+}
+
+impl Default for RpcBatchConfig {
+    fn default() -> Self {
+        Self { max_batch_size: 100, max_parallelism: 10 }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct RpcConfig {
     pub addr: String,
     pub cors_allowed_origins: Vec<String>,
     pub polling_config: RpcPollingConfig,
+    #[serde(default)]
+    pub batch_config: RpcBatchConfig,
+    #[serde(default)]
+    pub websocket: WebSocketConfig,
+    /// Address of the listener serving admin methods, in addition to the regular ones. Admin
+    /// methods are never served on `addr`, so it should only be reachable by the operator.
+    #[serde(default)]
+    pub admin_addr: Option<String>,
+    /// Directory where `adm_create_snapshot` puts database snapshots.
+    #[serde(default)]
+    pub snapshots_dir: Option<String>,
 }
 
 impl Default for RpcConfig {
@@ -66,6 +101,10 @@ impl Default for RpcConfig {
             addr: "0.0.0.0:3030".to_owned(),
             cors_allowed_origins: vec!["*".to_owned()],
             polling_config: Default::default(),
+            batch_config: Default::default(),
+            websocket: Default::default(),
+            admin_addr: None,
+            snapshots_dir: None,
         }
     }
 }
@@ -118,6 +157,66 @@ fn parse_hash(params: Option<Value>) -> Result<CryptoHash, RpcError> {
     })
 }
 
+/// Parses `[path, data]` or `[path, data, block_id_or_finality]` params of `query`.
+fn parse_query_params(
+    params: Option<Value>,
+) -> Result<(String, String, BlockIdOrFinality, bool), RpcError> {
+    let num_params = match &params {
+        Some(Value::Array(values)) => values.len(),
+        _ => 0,
+    };
+    match num_params {
+        4 => parse_params::<(String, String, BlockIdOrFinality, bool)>(params),
+        3 => parse_params::<(String, String, BlockIdOrFinality)>(params)
+            .map(|(path, data, block_id_or_finality)| (path, data, block_id_or_finality, false)),
+        _ => parse_params::<(String, String)>(params)
+            .map(|(path, data)| (path, data, BlockIdOrFinality::latest(), false)),
+    }
+}
+
+/// Parses `[from_height, to_height, account_id?, topic?]` params of `contract_events`.
+fn parse_contract_events_params(
+    mut params: Option<Value>,
+) -> Result<(BlockIndex, BlockIndex, Option<AccountId>, Option<CryptoHash>), RpcError> {
+    if let Some(Value::Array(values)) = &mut params {
+        while values.len() < 4 {
+            values.push(Value::Null);
+        }
+    }
+    parse_params(params)
+}
+
+/// Parses `[from_height, to_height, key_prefix?]` params of `changes`, the prefix is base64 encoded.
+fn parse_changes_params(
+    mut params: Option<Value>,
+) -> Result<(BlockIndex, BlockIndex, Option<Vec<u8>>), RpcError> {
+    if let Some(Value::Array(values)) = &mut params {
+        while values.len() < 3 {
+            values.push(Value::Null);
+        }
+    }
+    let (from_height, to_height, key_prefix) =
+        parse_params::<(BlockIndex, BlockIndex, Option<String>)>(params)?;
+    let key_prefix = key_prefix.map(from_base64_or_parse_err).transpose()?;
+    Ok((from_height, to_height, key_prefix))
+}
+
+/// Parses `[shard_id?, signer_id?, limit?]` params of `pending_transactions`.
+fn parse_pending_transactions_params(
+    mut params: Option<Value>,
+) -> Result<(Option<ShardId>, Option<AccountId>, Option<usize>), RpcError> {
+    match &mut params {
+        None => return Ok((None, None, None)),
+        Some(Value::Array(values)) => {
+            while values.len() < 3 {
+                values.push(Value::Null);
+            }
+        }
+        _ => {}
+    }
+    parse_params(params)
+}
+
 fn convert_mailbox_error(e: MailboxError) -> ExecutionErrorView {
     ExecutionErrorView { error_message: e.to_string(), error_type: "MailBoxError".to_string() }
 }
@@ -133,34 +232,93 @@ struct JsonRpcHandler {
     client_addr: Addr<ClientActor>,
     view_client_addr: Addr<ViewClientActor>,
     polling_config: RpcPollingConfig,
+    batch_config: RpcBatchConfig,
+    /// Whether the handler serves the admin listener.
+    admin: bool,
+    snapshots_dir: Option<PathBuf>,
 }
 
 impl JsonRpcHandler {
-    pub async fn process(&self, message: Message) -> Result<Message, HttpError> {
+    /// Returns `None` if there is nothing to respond with, as all the messages were notifications.
+    pub async fn process(&self, message: Message) -> Result<Option<Message>, HttpError> {
+        match message {
+            Message::Batch(messages) => Ok(self.process_batch(messages).await),
+            message => Ok(self.process_message(message).await),
+        }
+    }
+
+    /// Notifications are processed like requests, but get no response.
+    async fn process_message(&self, message: Message) -> Option<Message> {
         let id = message.id();
         match message {
-            Message::Request(request) => {
-                Ok(Message::response(id, self.process_request(request).await))
+            Message::Request(request) => Some(Message::response(
+                id,
+                self.process_request(request.method, request.params).await,
+            )),
+            Message::Notification(notification) => {
+                let _ = self.process_request(notification.method, notification.params).await;
+                None
             }
-            _ => Ok(Message::error(RpcError::invalid_request())),
+            _ => Some(Message::error(RpcError::invalid_request())),
         }
     }
 
-    async fn process_request(&self, request: Request) -> Result<Value, RpcError> {
-        match request.method.as_ref() {
-            "broadcast_tx_async" => self.send_tx_async(request.params).await,
-            "broadcast_tx_commit" => self.send_tx_commit(request.params).await,
-            "validators" => self.validators(request.params).await,
-            "query" => self.query(request.params).await,
+    /// Processes requests of a batch concurrently, at most `max_parallelism` at a time.
+    /// Responses are returned in the order of the requests.
+    async fn process_batch(&self, messages: Vec<Message>) -> Option<Message> {
+        if messages.is_empty() {
+            return Some(Message::error(RpcError::invalid_request()));
+        }
+        if messages.len() > self.batch_config.max_batch_size {
+            return Some(Message::error(RpcError::server_error(Some(format!(
+                "Batch size {} exceeds the limit of {}",
+                messages.len(),
+                self.batch_config.max_batch_size
+            )))));
+        }
+        near_metrics::inc_counter_by(&metrics::HTTP_RPC_BATCH_REQUEST_COUNT, messages.len() as i64);
+        let responses = futures03::stream::iter(messages)
+            .map(|message| self.process_message(message))
+            .buffered(max(self.batch_config.max_parallelism, 1))
+            .filter_map(futures03::future::ready)
+            .collect::<Vec<_>>()
+            .await;
+        if responses.is_empty() {
+            None
+        } else {
+            Some(Message::Batch(responses))
+        }
+    }
+
+    async fn process_request(
+        &self,
+        method: String,
+        params: Option<Value>,
+    ) -> Result<Value, RpcError> {
+        match method.as_ref() {
+            "broadcast_tx_async" => self.send_tx_async(params).await,
+            "broadcast_tx_commit" => self.send_tx_commit(params).await,
+            "validators" => self.validators(params).await,
+            "query" => self.query(params).await,
             "health" => self.health().await,
             "status" => self.status().await,
-            "tx" => self.tx_status(request.params).await,
-            "block" => self.block(request.params).await,
-            "chunk" => self.chunk(request.params).await,
-            "next_light_client_block" => self.next_light_client_block(request.params).await,
+            "tx" => self.tx_status(params).await,
+            "block" => self.block(params).await,
+            "chunk" => self.chunk(params).await,
+            "next_light_client_block" => self.next_light_client_block(params).await,
+            "light_client_proof" => self.light_client_proof(params).await,
             "network_info" => self.network_info().await,
-            "gas_price" => self.gas_price(request.params).await,
-            _ => Err(RpcError::method_not_found(request.method)),
+            "pending_transactions" => self.pending_transactions(params).await,
+            "tx_pool_stats" => self.tx_pool_stats().await,
+            "gas_price" => self.gas_price(params).await,
+            "simulate_tx" => self.simulate_tx(params).await,
+            "contract_events" => self.contract_events(params).await,
+            "changes" => self.changes(params).await,
+            "adm_create_snapshot" if self.admin && self.snapshots_dir.is_some() => {
+                self.create_snapshot(params).await
+            }
+            "rpc.discover" => Ok(discover::api_description()),
+            _ => Err(RpcError::method_not_found(method)),
         }
     }
 
@@ -248,7 +406,7 @@ impl JsonRpcHandler {
     }
 
     async fn query(&self, params: Option<Value>) -> Result<Value, RpcError> {
-        let (path, data) = parse_params::<(String, String)>(params)?;
+        let (path, data, block_id_or_finality, include_proof) = parse_query_params(params)?;
         let data = from_base_or_parse_err(data)?;
         let query_data_size = path.len() + data.len();
         if query_data_size > QUERY_DATA_MAX_SIZE {
@@ -257,7 +415,10 @@ impl JsonRpcHandler {
                 query_data_size
             ))));
         }
-        let query = Query::new(path, data);
+        let mut query = Query::new_at(block_id_or_finality, path, data);
+        if include_proof {
+            query = query.with_proof();
+        }
         timeout(self.polling_config.polling_timeout, async {
             loop {
                 let result = self.view_client_addr.send(query.clone()).compat().await;
@@ -325,10 +486,31 @@ impl JsonRpcHandler {
         )
     }
 
+    /// Returns the proof that the outcome of a transaction or receipt is included into a block.
+    async fn light_client_proof(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let (id,) = parse_params::<(CryptoHash,)>(params)?;
+        jsonify(self.view_client_addr.send(GetLightClientProof { id }).compat().await)
+    }
+
     async fn network_info(&self) -> Result<Value, RpcError> {
         jsonify(self.client_addr.send(GetNetworkInfo {}).compat().await)
     }
 
+    /// Returns transactions waiting in the pools of the node, of a shard or of a signer.
+    async fn pending_transactions(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let (shard_id, signer_id, limit) = parse_pending_transactions_params(params)?;
+        jsonify(
+            self.client_addr
+                .send(GetPendingTransactions { shard_id, signer_id, limit })
+                .compat()
+                .await,
+        )
+    }
+
+    async fn tx_pool_stats(&self) -> Result<Value, RpcError> {
+        jsonify(self.client_addr.send(GetTransactionPoolStats {}).compat().await)
+    }
+
     async fn gas_price(&self, params: Option<Value>) -> Result<Value, RpcError> {
         let (block_id,) = parse_params::<(Option<BlockId>,)>(params)?;
         let gas_price_request = match block_id {
@@ -339,6 +521,50 @@ impl JsonRpcHandler {
         jsonify(self.view_client_addr.send(gas_price_request).compat().await)
     }
 
+    /// Executes the transaction on top of the head state without submitting it.
+    async fn simulate_tx(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let tx = parse_tx(params)?;
+        jsonify(self.view_client_addr.send(SimulateTx { tx }).compat().await)
+    }
+
+    /// Returns events emitted by contracts in the given range of block heights.
+    async fn contract_events(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let (from_height, to_height, account_id, topic) = parse_contract_events_params(params)?;
+        jsonify(
+            self.view_client_addr
+                .send(GetContractEvents { from_height, to_height, account_id, topic })
+                .compat()
+                .await,
+        )
+    }
+
+    /// Returns changes of the subscribed state prefixes in the given range of block heights.
+    async fn changes(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let (from_height, to_height, key_prefix) = parse_changes_params(params)?;
+        jsonify(
+            self.view_client_addr
+                .send(GetStateChanges { from_height, to_height, key_prefix })
+                .compat()
+                .await,
+        )
+    }
+
+    /// Takes a snapshot of the database under the given name in the snapshots directory.
+    async fn create_snapshot(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let (name,) = parse_params::<(String,)>(params)?;
+        if name.is_empty() || name.starts_with('.') || name.contains(|c| c == '/' || c == '\\') {
+            return Err(RpcError::invalid_params(Some(format!("Invalid snapshot name {}", name))));
+        }
+        let path = self.snapshots_dir.as_ref().expect("Admin methods are enabled").join(name);
+        let chain_id = match self.client_addr.send(Status { is_health_check: false }).compat().await
+        {
+            Ok(Ok(status)) => status.chain_id,
+            Ok(Err(err)) => return Err(RpcError::new(-32_001, err, None)),
+            Err(err) => return Err(RpcError::server_error(Some(err.to_string()))),
+        };
+        jsonify(self.view_client_addr.send(CreateSnapshot { chain_id, path }).compat().await)
+    }
+
     pub async fn metrics(&self) -> Result<String, FromUtf8Error> {
         // Gather metrics and return them as a String
         let mut buffer = vec![];
@@ -366,8 +592,10 @@ fn rpc_handler(
     near_metrics::inc_counter(&metrics::HTTP_RPC_REQUEST_COUNT);
 
     let response = async move {
-        let message = handler.process(message.0).await?;
-        Ok(HttpResponse::Ok().json(message))
+        match handler.process(message.0).await? {
+            Some(message) => Ok(HttpResponse::Ok().json(message)),
+            None => Ok(HttpResponse::Ok().finish()),
+        }
     };
     response.boxed().compat()
 }
@@ -430,16 +658,31 @@ pub fn start_http(
     client_addr: Addr<ClientActor>,
     view_client_addr: Addr<ViewClientActor>,
 ) {
-    let RpcConfig { addr, polling_config, cors_allowed_origins } = config;
+    let RpcConfig {
+        addr,
+        polling_config,
+        batch_config,
+        cors_allowed_origins,
+        websocket,
+        admin_addr,
+        snapshots_dir,
+    } = config;
+    let snapshots_dir = snapshots_dir.map(PathBuf::from);
+    let handler = move |admin: bool| JsonRpcHandler {
+        client_addr: client_addr.clone(),
+        view_client_addr: view_client_addr.clone(),
+        polling_config,
+        batch_config,
+        admin,
+        snapshots_dir: snapshots_dir.clone(),
+    };
+    let public_handler = handler.clone();
     HttpServer::new(move || {
         App::new()
             .wrap(get_cors(&cors_allowed_origins))
-            .data(JsonRpcHandler {
-                client_addr: client_addr.clone(),
-                view_client_addr: view_client_addr.clone(),
-                polling_config,
-            })
+            .data(public_handler(false))
             .data(web::JsonConfig::default().limit(JSON_PAYLOAD_MAX_SIZE))
+            .data(websocket)
             .wrap(middleware::Logger::default())
             .service(web::resource("/").route(web::post().to_async(rpc_handler)))
             .service(
@@ -451,10 +694,26 @@ pub fn start_http(
                 web::resource("/network_info").route(web::get().to_async(network_info_handler)),
             )
             .service(web::resource("/metrics").route(web::get().to_async(prometheus_handler)))
+            .service(web::resource("/ws").route(web::get().to(websocket::websocket_handler)))
     })
     .bind(addr)
     .unwrap()
     .workers(4)
     .shutdown_timeout(5)
     .start();
+
+    if let Some(admin_addr) = admin_addr {
+        HttpServer::new(move || {
+            App::new()
+                .data(handler(true))
+                .data(web::JsonConfig::default().limit(JSON_PAYLOAD_MAX_SIZE))
+                .wrap(middleware::Logger::default())
+                .service(web::resource("/").route(web::post().to_async(rpc_handler)))
+        })
+        .bind(admin_addr)
+        .unwrap()
+        .workers(1)
+        .shutdown_timeout(5)
+        .start();
+    }
 }
@@ -1,5 +1,5 @@
 use lazy_static::lazy_static;
-use near_metrics::IntCounter;
+use near_metrics::{IntCounter, IntGauge};
 
 lazy_static! {
     pub static ref PROMETHEUS_REQUEST_COUNT: near_metrics::Result<IntCounter> =
@@ -12,9 +12,24 @@ lazy_static! {
             "http_rpc_requests_total",
             "Total count of HTTP RPC requests received"
         );
+    pub static ref HTTP_RPC_BATCH_REQUEST_COUNT: near_metrics::Result<IntCounter> =
+        near_metrics::try_create_int_counter(
+            "http_rpc_batch_requests_total",
+            "Total count of requests received inside JSON-RPC batches"
+        );
     pub static ref HTTP_STATUS_REQUEST_COUNT: near_metrics::Result<IntCounter> =
         near_metrics::try_create_int_counter(
             "http_status_requests_total",
             "Total count of HTTP Status requests received"
         );
+    pub static ref WEBSOCKET_CONNECTIONS: near_metrics::Result<IntGauge> =
+        near_metrics::try_create_int_gauge(
+            "websocket_connections",
+            "Number of currently open WebSocket connections"
+        );
+    pub static ref WEBSOCKET_DROPPED_EVENTS: near_metrics::Result<IntCounter> =
+        near_metrics::try_create_int_counter(
+            "websocket_dropped_event_batches_total",
+            "Total count of chain event batches dropped for slow WebSocket subscribers"
+        );
 }
@@ -14,6 +14,15 @@ pub fn start_all(validator: bool) -> (Addr<ViewClientActor>, String) {
 pub fn start_all_with_validity_period(
     validator: bool,
     validity_period: BlockIndex,
+) -> (Addr<ViewClientActor>, String) {
+    let addr = format!("127.0.0.1:{}", open_port());
+    start_all_with_rpc_config(validator, validity_period, RpcConfig::new(&addr))
+}
+
+pub fn start_all_with_rpc_config(
+    validator: bool,
+    validity_period: BlockIndex,
+    config: RpcConfig,
 ) -> (Addr<ViewClientActor>, String) {
     let (client_addr, view_client_addr) = setup_no_network_with_validity_period(
         vec!["test1", "test2"],
@@ -22,7 +31,7 @@ pub fn start_all_with_validity_period(
         validity_period,
     );
 
-    let addr = format!("127.0.0.1:{}", open_port());
-    start_http(RpcConfig::new(&addr), client_addr.clone(), view_client_addr.clone());
+    let addr = config.addr.clone();
+    start_http(config, client_addr.clone(), view_client_addr.clone());
     (view_client_addr, addr)
 }
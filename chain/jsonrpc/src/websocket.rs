@@ -0,0 +1,206 @@
+//! WebSocket endpoint for subscribing to chain events.
+//! Speaks the same JSON-RPC 2.0 messages as the HTTP endpoint, with `subscribe` / `unsubscribe`
+//! methods. Matching events are pushed to the connection as `subscription` notifications.
+
+use std::collections::HashMap;
+
+use actix::{Actor, ActorContext, Addr, AsyncContext, Handler, StreamHandler};
+use actix_web::{web, Error as HttpError, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use near_client::{ChainEvent, ChainEvents, ClientActor, SubscribeChainEvents};
+use near_primitives::types::{AccountId, ShardId};
+use near_primitives::utils::generate_random_string;
+
+use crate::message::{self, Message, Request, RpcError};
+use crate::{metrics, parse_params, JsonRpcHandler};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct WebSocketConfig {
+    /// Maximum number of active subscriptions on a single connection.
+    pub max_subscriptions_per_connection: usize,
+    /// Number of blocks worth of events queued for a connection before new ones are dropped.
+    pub max_pending_events: usize,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        WebSocketConfig { max_subscriptions_per_connection: 16, max_pending_events: 64 }
+    }
+}
+
+/// Kind of events the subscriber is interested in.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Subscription {
+    /// Every block that becomes the new head.
+    NewHeads,
+    /// Every block that becomes final.
+    FinalBlocks,
+    /// Chunks of the given shard, as they are included into blocks.
+    Chunks { shard_id: ShardId },
+    /// Outcomes of the transactions and receipts executed on the given account.
+    ExecutionOutcomes { receiver_id: AccountId },
+}
+
+impl Subscription {
+    /// Returns the notification payload if the event matches this subscription.
+    fn notification(&self, event: &ChainEvent) -> Option<Value> {
+        match (self, event) {
+            (Subscription::NewHeads, ChainEvent::NewHead(block))
+            | (Subscription::FinalBlocks, ChainEvent::FinalBlock(block)) => {
+                serde_json::to_value(block).ok()
+            }
+            (Subscription::Chunks { shard_id }, ChainEvent::NewChunk { block_hash, chunk })
+                if chunk.shard_id == *shard_id =>
+            {
+                Some(json!({ "block_hash": block_hash, "chunk": chunk }))
+            }
+            (
+                Subscription::ExecutionOutcomes { receiver_id },
+                ChainEvent::ExecutionOutcome { receiver_id: executed_on, outcome },
+            ) if receiver_id == executed_on => {
+                Some(json!({ "receiver_id": executed_on, "outcome": outcome }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Actor serving a single WebSocket connection.
+pub struct WebSocketSession {
+    client_addr: Addr<ClientActor>,
+    config: WebSocketConfig,
+    subscriptions: HashMap<String, Subscription>,
+    /// Whether this session is already registered for chain events with the client.
+    registered: bool,
+}
+
+impl WebSocketSession {
+    pub fn new(client_addr: Addr<ClientActor>, config: WebSocketConfig) -> Self {
+        WebSocketSession { client_addr, config, subscriptions: HashMap::new(), registered: false }
+    }
+
+    fn process_request(
+        &mut self,
+        request: Request,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) -> Result<Value, RpcError> {
+        match request.method.as_ref() {
+            "subscribe" => self.subscribe(request.params, ctx),
+            "unsubscribe" => self.unsubscribe(request.params),
+            _ => Err(RpcError::method_not_found(request.method)),
+        }
+    }
+
+    fn subscribe(
+        &mut self,
+        params: Option<Value>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) -> Result<Value, RpcError> {
+        let (subscription,) = parse_params::<(Subscription,)>(params)?;
+        if self.subscriptions.len() >= self.config.max_subscriptions_per_connection {
+            return Err(RpcError::server_error(Some(format!(
+                "Connection already has the maximum of {} subscriptions",
+                self.config.max_subscriptions_per_connection
+            ))));
+        }
+        // Only connections with subscriptions are fed by the client.
+        if !self.registered {
+            self.client_addr.do_send(SubscribeChainEvents { recipient: ctx.address().recipient() });
+            self.registered = true;
+        }
+        let id = generate_random_string(16);
+        self.subscriptions.insert(id.clone(), subscription);
+        Ok(Value::String(id))
+    }
+
+    fn unsubscribe(&mut self, params: Option<Value>) -> Result<Value, RpcError> {
+        let (id,) = parse_params::<(String,)>(params)?;
+        Ok(Value::Bool(self.subscriptions.remove(&id).is_some()))
+    }
+
+    fn send(ctx: &mut ws::WebsocketContext<Self>, message: Message) {
+        let text: String = message.into();
+        ctx.text(text);
+    }
+}
+
+impl Actor for WebSocketSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        near_metrics::inc_gauge(&metrics::WEBSOCKET_CONNECTIONS);
+        // Bounds the number of event batches waiting to be sent over a slow connection.
+        ctx.set_mailbox_capacity(self.config.max_pending_events);
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        near_metrics::dec_gauge(&metrics::WEBSOCKET_CONNECTIONS);
+    }
+}
+
+impl StreamHandler<ws::Message, ws::ProtocolError> for WebSocketSession {
+    fn handle(&mut self, msg: ws::Message, ctx: &mut Self::Context) {
+        match msg {
+            ws::Message::Ping(msg) => ctx.pong(&msg),
+            ws::Message::Text(text) => {
+                let response = match message::from_str(&text) {
+                    Ok(Message::Request(request)) => {
+                        let id = request.id.clone();
+                        Message::response(id, self.process_request(request, ctx))
+                    }
+                    Ok(_) => Message::error(RpcError::invalid_request()),
+                    Err(broken) => broken.reply(),
+                };
+                Self::send(ctx, response);
+            }
+            ws::Message::Binary(_) => {
+                Self::send(ctx, Message::error(RpcError::invalid_request()));
+            }
+            ws::Message::Close(_) => ctx.stop(),
+            ws::Message::Pong(_) | ws::Message::Nop => {}
+        }
+    }
+}
+
+impl Handler<ChainEvents> for WebSocketSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: ChainEvents, ctx: &mut Self::Context) {
+        if msg.dropped > 0 {
+            near_metrics::inc_counter_by(&metrics::WEBSOCKET_DROPPED_EVENTS, msg.dropped as i64);
+            Self::send(
+                ctx,
+                Message::notification(
+                    "subscription_lagged".to_string(),
+                    Some(json!({ "dropped": msg.dropped })),
+                ),
+            );
+        }
+        for event in msg.events.iter() {
+            for (id, subscription) in self.subscriptions.iter() {
+                if let Some(result) = subscription.notification(event) {
+                    Self::send(
+                        ctx,
+                        Message::notification(
+                            "subscription".to_string(),
+                            Some(json!({ "subscription": id, "result": result })),
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn websocket_handler(
+    req: HttpRequest,
+    stream: web::Payload,
+    handler: web::Data<JsonRpcHandler>,
+    config: web::Data<WebSocketConfig>,
+) -> Result<HttpResponse, HttpError> {
+    ws::start(WebSocketSession::new(handler.client_addr.clone(), *config.get_ref()), &req, stream)
+}
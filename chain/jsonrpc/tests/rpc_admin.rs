@@ -0,0 +1,88 @@
+use actix::System;
+use futures::future;
+use futures::future::Future;
+use tempdir::TempDir;
+
+use near_jsonrpc::client::new_client;
+use near_jsonrpc::test_utils::start_all_with_rpc_config;
+use near_jsonrpc::RpcConfig;
+use near_network::test_utils::open_port;
+use near_primitives::test_utils::init_test_logger;
+use near_store::{column_names, SnapshotManifest, StoreBackend};
+
+fn admin_rpc_config(snapshots_dir: String) -> RpcConfig {
+    RpcConfig {
+        admin_addr: Some(format!("127.0.0.1:{}", open_port())),
+        snapshots_dir: Some(snapshots_dir),
+        ..RpcConfig::new(&format!("127.0.0.1:{}", open_port()))
+    }
+}
+
+/// Takes a snapshot of the running node into the snapshots directory.
+#[test]
+fn test_create_snapshot() {
+    init_test_logger();
+    let tmp_dir = TempDir::new("rpc_snapshots").unwrap();
+    let snapshots_dir = tmp_dir.path().to_path_buf();
+
+    System::run(move || {
+        let config = admin_rpc_config(snapshots_dir.to_str().unwrap().to_string());
+        let admin_addr = config.admin_addr.clone().unwrap();
+        start_all_with_rpc_config(true, 100, config);
+
+        let mut client = new_client(&format!("http://{}", admin_addr));
+        actix::spawn(client.adm_create_snapshot("snapshot".to_string()).then(move |res| {
+            let manifest: SnapshotManifest = serde_json::from_value(res.unwrap()).unwrap();
+            assert_eq!(manifest.backend, StoreBackend::Lsm);
+            assert_eq!(manifest.columns, column_names());
+            assert_eq!(manifest.head_hash, manifest.final_hash);
+            assert_eq!(SnapshotManifest::read(&snapshots_dir.join("snapshot")).unwrap(), manifest);
+            System::current().stop();
+            future::ok(())
+        }));
+    })
+    .unwrap();
+}
+
+/// Snapshot names can't point outside of the snapshots directory.
+#[test]
+fn test_create_snapshot_invalid_name() {
+    init_test_logger();
+    let tmp_dir = TempDir::new("rpc_snapshots").unwrap();
+    let snapshots_dir = tmp_dir.path().to_str().unwrap().to_string();
+
+    System::run(move || {
+        let config = admin_rpc_config(snapshots_dir.clone());
+        let admin_addr = config.admin_addr.clone().unwrap();
+        start_all_with_rpc_config(true, 100, config);
+
+        let mut client = new_client(&format!("http://{}", admin_addr));
+        actix::spawn(client.adm_create_snapshot("../snapshot".to_string()).then(|res| {
+            assert!(res.is_err());
+            System::current().stop();
+            future::ok(())
+        }));
+    })
+    .unwrap();
+}
+
+/// Admin methods are not served on the public listener.
+#[test]
+fn test_admin_methods_not_public() {
+    init_test_logger();
+    let tmp_dir = TempDir::new("rpc_snapshots").unwrap();
+    let snapshots_dir = tmp_dir.path().to_str().unwrap().to_string();
+
+    System::run(move || {
+        let (_view_client_addr, addr) =
+            start_all_with_rpc_config(true, 100, admin_rpc_config(snapshots_dir.clone()));
+
+        let mut client = new_client(&format!("http://{}", addr));
+        actix::spawn(client.adm_create_snapshot("snapshot".to_string()).then(|res| {
+            assert_eq!(res.unwrap_err().code, -32_601);
+            System::current().stop();
+            future::ok(())
+        }));
+    })
+    .unwrap();
+}
@@ -3,8 +3,10 @@ use std::convert::TryFrom;
 use actix::{Actor, System};
 use futures::future;
 use futures::future::Future;
+use serde_json::json;
 
 use near_crypto::{KeyType, PublicKey, Signature};
+use near_jsonrpc::client::message::Message;
 use near_jsonrpc::client::new_client;
 use near_jsonrpc::test_utils::start_all;
 use near_jsonrpc_client::{BlockId, ChunkId};
@@ -12,7 +14,7 @@ use near_network::test_utils::WaitOrTimeout;
 use near_primitives::account::{AccessKey, AccessKeyPermission};
 use near_primitives::hash::CryptoHash;
 use near_primitives::test_utils::init_test_logger;
-use near_primitives::types::ShardId;
+use near_primitives::types::{BlockIdOrFinality, Finality, ShardId};
 use near_primitives::views::QueryResponseKind;
 
 /// Retrieve blocks via json rpc
@@ -151,6 +153,68 @@ fn test_query_account() {
     .unwrap();
 }
 
+/// Query account info at a given block and at the last final block.
+#[test]
+fn test_query_account_at_block() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = start_all(false);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        let queries = vec![
+            client.query_at(
+                "account/test".to_string(),
+                "".to_string(),
+                BlockIdOrFinality::BlockId(BlockId::Height(0)),
+            ),
+            client.query_at(
+                "account/test".to_string(),
+                "".to_string(),
+                BlockIdOrFinality::Finality(Finality::Final),
+            ),
+        ];
+        actix::spawn(future::join_all(queries).then(|responses| {
+            for query_response in responses.unwrap() {
+                assert_eq!(query_response.block_height, 0);
+                match query_response.kind {
+                    QueryResponseKind::ViewAccount(account) => assert_eq!(account.amount, 0),
+                    kind => panic!("queried account, but received something else: {:?}", kind),
+                }
+            }
+            System::current().stop();
+            future::result(Ok(()))
+        }));
+    })
+    .unwrap();
+}
+
+/// Querying state at an unknown block returns an error.
+#[test]
+fn test_query_account_at_unknown_block() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = start_all(false);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        actix::spawn(
+            client
+                .query_at(
+                    "account/test".to_string(),
+                    "".to_string(),
+                    BlockIdOrFinality::BlockId(BlockId::Height(1_000)),
+                )
+                .then(|query_response| {
+                    assert!(query_response.is_err());
+                    System::current().stop();
+                    future::result(Ok(()))
+                }),
+        );
+    })
+    .unwrap();
+}
+
 /// Connect to json rpc and query account info.
 #[test]
 fn test_query_access_keys() {
@@ -383,3 +447,157 @@ fn test_gas_price() {
     })
     .unwrap();
 }
+
+/// Search for contract events, none are emitted by the genesis.
+#[test]
+fn test_contract_events() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_, addr) = start_all(false);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        actix::spawn(client.contract_events(0, 10, Some("test1".to_string()), None).then(
+            move |res| {
+                assert_eq!(res.unwrap(), vec![]);
+                let mut client = new_client(&format!("http://{}", addr));
+                client.contract_events(10, 0, None, None).then(|res| {
+                    assert!(res.is_err());
+                    System::current().stop();
+                    future::ok(())
+                })
+            },
+        ));
+    })
+    .unwrap();
+}
+
+/// Search for state changes, the node is not subscribed to any.
+#[test]
+fn test_changes() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_, addr) = start_all(false);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        actix::spawn(client.changes(0, 10, None).then(move |res| {
+            assert_eq!(res.unwrap(), vec![]);
+            let mut client = new_client(&format!("http://{}", addr));
+            client.changes(0, 1000, None).then(|res| {
+                assert!(res.is_err());
+                System::current().stop();
+                future::ok(())
+            })
+        }));
+    })
+    .unwrap();
+}
+
+/// Send several requests in one batch and get responses in the same order.
+#[test]
+fn test_batch() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_, addr) = start_all(false);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        let requests = vec![
+            Message::request("block".to_string(), Some(json!([0]))),
+            Message::request("no_such_method".to_string(), Some(json!([]))),
+            Message::request("gas_price".to_string(), Some(json!([null]))),
+        ];
+        let ids: Vec<_> = requests.iter().map(|request| request.id()).collect();
+        actix::spawn(client.batch(requests).then(move |res| {
+            let responses: Vec<_> = res
+                .unwrap()
+                .into_iter()
+                .map(|message| match message {
+                    Message::Response(response) => response,
+                    message => panic!("Unexpected message {:?}", message),
+                })
+                .collect();
+            assert_eq!(responses.iter().map(|r| r.id.clone()).collect::<Vec<_>>(), ids);
+            assert_eq!(responses[0].result.as_ref().unwrap()["header"]["height"], 0);
+            assert_eq!(responses[1].result.as_ref().unwrap_err().code, -32_601);
+            assert!(responses[2].result.is_ok());
+            System::current().stop();
+            future::ok(())
+        }));
+    })
+    .unwrap();
+}
+
+/// Notifications are processed without responses, and a batch of only notifications gets an empty
+/// response.
+#[test]
+fn test_batch_notifications() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_, addr) = start_all(false);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        let requests = vec![
+            Message::notification("block".to_string(), Some(json!([0]))),
+            Message::request("gas_price".to_string(), Some(json!([null]))),
+            Message::notification("no_such_method".to_string(), Some(json!([]))),
+        ];
+        let id = requests[1].id();
+        actix::spawn(client.batch(requests).then(move |res| {
+            let responses = res.unwrap();
+            assert_eq!(responses.len(), 1);
+            match &responses[0] {
+                Message::Response(response) => assert_eq!(response.id, id),
+                message => panic!("Unexpected message {:?}", message),
+            }
+            let mut client = new_client(&format!("http://{}", addr));
+            let notifications = vec![Message::notification("status".to_string(), None)];
+            client.batch(notifications).then(|res| {
+                assert_eq!(res.unwrap(), vec![]);
+                System::current().stop();
+                future::ok(())
+            })
+        }));
+    })
+    .unwrap();
+}
+
+/// Every described method is dispatched by the node.
+#[test]
+fn test_discover() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_, addr) = start_all(false);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        actix::spawn(client.discover().then(move |res| {
+            let document = res.unwrap();
+            let requests: Vec<_> = document["methods"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|method| {
+                    let name = method["name"].as_str().unwrap().to_string();
+                    Message::request(name, Some(json!([])))
+                })
+                .collect();
+            assert!(requests.len() > 10);
+            let mut client = new_client(&format!("http://{}", addr));
+            client.batch(requests).then(|res| {
+                for message in res.unwrap() {
+                    if let Message::Response(response) = message {
+                        if let Err(err) = response.result {
+                            assert_ne!(err.code, -32_601, "{:?}", err);
+                        }
+                    }
+                }
+                System::current().stop();
+                future::ok(())
+            })
+        }));
+    })
+    .unwrap();
+}
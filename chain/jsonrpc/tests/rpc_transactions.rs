@@ -9,6 +9,7 @@ use near_client::GetBlock;
 use near_crypto::{InMemorySigner, KeyType};
 use near_jsonrpc::client::new_client;
 use near_jsonrpc::test_utils::{start_all, start_all_with_validity_period};
+use near_jsonrpc_client::BlockId;
 use near_network::test_utils::{wait_or_panic, WaitOrTimeout};
 use near_primitives::block::BlockHeader;
 use near_primitives::hash::hash;
@@ -116,6 +117,44 @@ fn test_send_tx_commit() {
     .unwrap();
 }
 
+/// Test simulating a transaction against the head state.
+#[test]
+fn test_simulate_tx() {
+    init_test_logger();
+
+    System::run(|| {
+        let (view_client, addr) = start_all(true);
+
+        let mut client = new_client(&format!("http://{}", addr));
+
+        actix::spawn(view_client.send(GetBlock::Best).then(move |res| {
+            let header: BlockHeader = res.unwrap().unwrap().header.into();
+            let signer = InMemorySigner::from_seed("test1", KeyType::ED25519, "test1");
+            let tx = SignedTransaction::send_money(
+                1,
+                "test1".to_string(),
+                "test2".to_string(),
+                &signer,
+                100,
+                header.hash,
+            );
+            let bytes = tx.try_to_vec().unwrap();
+            client
+                .simulate_tx(to_base64(&bytes))
+                .map_err(|why| {
+                    System::current().stop();
+                    panic!(why);
+                })
+                .map(move |result| {
+                    assert_eq!(result.outcomes[0].id, tx.get_hash());
+                    System::current().stop();
+                })
+        }));
+        wait_or_panic(10000);
+    })
+    .unwrap();
+}
+
 /// Test that expired transaction should be rejected
 #[test]
 fn test_expired_tx() {
@@ -205,3 +244,122 @@ fn test_replay_protection() {
     })
     .unwrap();
 }
+
+/// Test that a transaction sent without waiting can be looked up in the pool until it's included.
+#[test]
+fn test_pending_transactions() {
+    init_test_logger();
+
+    System::run(|| {
+        let (view_client, addr) = start_all(true);
+
+        let mut client = new_client(&format!("http://{}", addr.clone()));
+        let mut client1 = new_client(&format!("http://{}", addr.clone()));
+        let mut client2 = new_client(&format!("http://{}", addr.clone()));
+        let mut client3 = new_client(&format!("http://{}", addr));
+
+        actix::spawn(view_client.send(GetBlock::Best).then(move |res| {
+            let header: BlockHeader = res.unwrap().unwrap().header.into();
+            let signer = InMemorySigner::from_seed("test1", KeyType::ED25519, "test1");
+            let tx = SignedTransaction::send_money(
+                1,
+                "test1".to_string(),
+                "test2".to_string(),
+                &signer,
+                100,
+                header.hash,
+            );
+            let bytes = tx.try_to_vec().unwrap();
+            // The pool keeps the transaction until the block with its chunk is processed, which
+            // takes at least two blocks.
+            client
+                .broadcast_tx_async(to_base64(&bytes))
+                .and_then(move |_| {
+                    client1.pending_transactions(None, Some("test1".to_string()), None)
+                })
+                .and_then(move |pending| {
+                    let pending_tx = pending
+                        .iter()
+                        .find(|pending_tx| pending_tx.hash == tx.get_hash())
+                        .expect("the transaction is in the pool");
+                    assert_eq!(pending_tx.signer_id, "test1");
+                    assert_eq!(pending_tx.nonce, 1);
+                    client2.pending_transactions(None, None, Some(0))
+                })
+                .and_then(move |pending| {
+                    assert!(pending.is_empty());
+                    client3.tx_pool_stats()
+                })
+                .map_err(|err| panic!("{:?}", err))
+                .map(|stats| {
+                    for shard_stats in stats {
+                        assert!(shard_stats.num_groups <= shard_stats.num_transactions);
+                    }
+                    System::current().stop();
+                })
+        }));
+        wait_or_panic(10000);
+    })
+    .unwrap();
+}
+
+/// Test that the inclusion proof of a committed transaction verifies against the hash of the block.
+#[test]
+fn test_light_client_proof() {
+    init_test_logger();
+
+    System::run(|| {
+        let (view_client, addr) = start_all(true);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        let tx_hash = Arc::new(Mutex::new(None));
+        let tx_hash1 = tx_hash.clone();
+
+        actix::spawn(view_client.send(GetBlock::Best).then(move |res| {
+            let header: BlockHeader = res.unwrap().unwrap().header.into();
+            let signer = InMemorySigner::from_seed("test1", KeyType::ED25519, "test1");
+            let tx = SignedTransaction::send_money(
+                1,
+                "test1".to_string(),
+                "test2".to_string(),
+                &signer,
+                100,
+                header.hash,
+            );
+            let bytes = tx.try_to_vec().unwrap();
+            *tx_hash1.lock().unwrap() = Some(tx.get_hash());
+            client
+                .broadcast_tx_commit(to_base64(&bytes))
+                .map_err(|err| panic!("{:?}", err))
+                .map(|_| ())
+        }));
+        WaitOrTimeout::new(
+            Box::new(move |_| {
+                if let Some(tx_hash) = *tx_hash.lock().unwrap() {
+                    let mut client = new_client(&format!("http://{}", addr));
+                    let mut client1 = new_client(&format!("http://{}", addr));
+                    actix::spawn(
+                        client
+                            .light_client_proof((&tx_hash).into())
+                            .and_then(move |proof| {
+                                let height = proof.block_header_lite.inner_lite.height;
+                                client1.block(BlockId::Height(height)).map(|block| (proof, block))
+                            })
+                            // The outcome may not be included into a block yet.
+                            .map_err(|_| ())
+                            .map(move |(proof, block)| {
+                                assert_eq!(proof.outcome_proof.id, tx_hash);
+                                assert!(proof.verify(&block.header.hash));
+                                assert!(!proof.verify(&hash(&[1])));
+                                System::current().stop();
+                            }),
+                    );
+                }
+            }),
+            100,
+            10000,
+        )
+        .start();
+    })
+    .unwrap();
+}
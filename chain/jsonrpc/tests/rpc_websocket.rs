@@ -0,0 +1,117 @@
+use actix::System;
+use awc::ws::{Frame, Message as WsMessage};
+use futures::future;
+use futures::{Future, Sink, Stream};
+use serde_json::json;
+
+use near_jsonrpc::client::message::{from_slice, Message};
+use near_jsonrpc::test_utils::start_all;
+use near_primitives::test_utils::init_test_logger;
+
+fn text_message(frame: Option<Frame>) -> Message {
+    match frame {
+        Some(Frame::Text(Some(bytes))) => from_slice(&bytes).unwrap(),
+        frame => panic!("Unexpected frame {:?}", frame),
+    }
+}
+
+/// Subscribe to new heads over WebSocket and receive a notification once a block is produced.
+#[test]
+fn test_subscribe_new_heads() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = start_all(true);
+
+        let subscribe = json!({
+            "jsonrpc": "2.0",
+            "id": "dontcare",
+            "method": "subscribe",
+            "params": [{"type": "new_heads"}],
+        });
+        actix::spawn(
+            awc::Client::new()
+                .ws(format!("ws://{}/ws", addr))
+                .connect()
+                .map_err(|err| panic!("Failed to connect: {:?}", err))
+                .and_then(move |(_response, framed)| {
+                    framed
+                        .send(WsMessage::Text(subscribe.to_string()))
+                        .map_err(|err| panic!("Failed to send: {:?}", err))
+                })
+                .and_then(|framed| framed.into_future().map_err(|(err, _)| panic!("{:?}", err)))
+                .and_then(|(frame, framed)| {
+                    let subscription_id = match text_message(frame) {
+                        Message::Response(response) => response.result.unwrap(),
+                        message => panic!("Unexpected message {:?}", message),
+                    };
+                    framed.into_future().map_err(|(err, _)| panic!("{:?}", err)).map(
+                        move |(frame, _)| match text_message(frame) {
+                            Message::Notification(notification) => {
+                                assert_eq!(notification.method, "subscription");
+                                let params = notification.params.unwrap();
+                                assert_eq!(params["subscription"], subscription_id);
+                                assert!(params["result"]["header"]["height"].as_u64().unwrap() > 0);
+                            }
+                            message => panic!("Unexpected message {:?}", message),
+                        },
+                    )
+                })
+                .then(|_| {
+                    System::current().stop();
+                    future::ok(())
+                }),
+        );
+    })
+    .unwrap();
+}
+
+/// Subscriptions over the per-connection limit are rejected.
+#[test]
+fn test_subscription_limit() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = start_all(false);
+
+        let subscribe = json!({
+            "jsonrpc": "2.0",
+            "id": "dontcare",
+            "method": "subscribe",
+            "params": [{"type": "chunks", "shard_id": 0}],
+        })
+        .to_string();
+        let limit = near_jsonrpc::WebSocketConfig::default().max_subscriptions_per_connection;
+        actix::spawn(
+            awc::Client::new()
+                .ws(format!("ws://{}/ws", addr))
+                .connect()
+                .map_err(|err| panic!("Failed to connect: {:?}", err))
+                .and_then(move |(_response, framed)| {
+                    let requests = (0..=limit).map(move |_| WsMessage::Text(subscribe.clone()));
+                    framed
+                        .send_all(futures::stream::iter_ok(requests))
+                        .map_err(|err| panic!("Failed to send: {:?}", err))
+                })
+                .and_then(move |(framed, _)| {
+                    framed.take(limit as u64 + 1).collect().map_err(|err| panic!("{:?}", err))
+                })
+                .map(move |frames| {
+                    let results: Vec<_> = frames
+                        .into_iter()
+                        .map(|frame| match text_message(Some(frame)) {
+                            Message::Response(response) => response.result.is_ok(),
+                            message => panic!("Unexpected message {:?}", message),
+                        })
+                        .collect();
+                    assert!(results[..limit].iter().all(|ok| *ok));
+                    assert!(!results[limit]);
+                })
+                .then(|_| {
+                    System::current().stop();
+                    future::ok(())
+                }),
+        );
+    })
+    .unwrap();
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::AccountId;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The block is neither in the epoch of the head nor in the next one.
+    UnknownEpoch(CryptoHash),
+    /// The block is in the epoch of the trusted checkpoint, whose block producers are not known.
+    UnknownBlockProducers(CryptoHash),
+    /// The block doesn't carry the block producers of the next epoch.
+    MissingNextBlockProducers,
+    /// The block producers of the next epoch don't match `next_bp_hash` of the block.
+    InvalidNextBlockProducers,
+    /// The number of approvals doesn't match the number of block producers.
+    InvalidApprovalsLength(usize, usize),
+    /// None of the future blocks is the quorum pre-vote block.
+    QuorumPreVoteNotFound,
+    /// The approval doesn't refer to the blocks following the light client block.
+    InvalidApproval(AccountId),
+    /// The signature of the approval is not valid for the block producer.
+    InvalidSignature(AccountId),
+    /// The approvals don't carry more than 2/3 of the stake of the block producers.
+    NotEnoughStake { quorum: &'static str },
+    /// Error talking to the node.
+    Rpc(String),
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownEpoch(epoch_id) => write!(f, "Unknown epoch {}", epoch_id),
+            Error::UnknownBlockProducers(epoch_id) => {
+                write!(f, "Block producers of epoch {} are not known", epoch_id)
+            }
+            Error::MissingNextBlockProducers => write!(f, "Missing next block producers"),
+            Error::InvalidNextBlockProducers => {
+                write!(f, "Next block producers don't match next_bp_hash")
+            }
+            Error::InvalidApprovalsLength(approvals, block_producers) => {
+                write!(f, "{} approvals given for {} block producers", approvals, block_producers)
+            }
+            Error::QuorumPreVoteNotFound => write!(f, "Quorum pre-vote block not found"),
+            Error::InvalidApproval(account_id) => {
+                write!(f, "Approval of {} doesn't match the future blocks", account_id)
+            }
+            Error::InvalidSignature(account_id) => {
+                write!(f, "Invalid approval signature of {}", account_id)
+            }
+            Error::NotEnoughStake { quorum } => {
+                write!(f, "Not enough stake approved the {}", quorum)
+            }
+            Error::Rpc(err) => write!(f, "RPC error: {}", err),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Light client that follows the chain epoch by epoch through the `next_light_client_block` RPC.
+//!
+//! Starting from a trusted checkpoint, every light client block returned by the node is checked
+//! against the block producers of its epoch, which are in turn committed to by the previously
+//! verified block. Lite headers of verified blocks are kept to check state and outcome proofs.
+use std::collections::HashMap;
+
+use actix::System;
+
+use near_jsonrpc_client::JsonRpcClient;
+use near_primitives::hash::CryptoHash;
+use near_primitives::views::{BlockHeaderInnerLiteView, LightClientBlockView, ValidatorStakeView};
+
+pub use crate::error::Error;
+pub use crate::verifier::{compute_block_hash, compute_bp_hash, verify_light_client_block};
+
+mod error;
+mod verifier;
+
+pub struct LightClient {
+    /// Hash of the last verified block.
+    head_hash: CryptoHash,
+    /// Lite header of the last verified block.
+    head: BlockHeaderInnerLiteView,
+    /// Block producers of the epoch of the head, not known for the epoch of the checkpoint.
+    block_producers: Option<Vec<ValidatorStakeView>>,
+    /// Block producers of the epoch following the epoch of the head.
+    next_block_producers: Vec<ValidatorStakeView>,
+    /// Lite headers of all the verified blocks, including the checkpoint.
+    verified_blocks: HashMap<CryptoHash, BlockHeaderInnerLiteView>,
+}
+
+impl LightClient {
+    /// Creates light client from a light client block that is trusted without verification.
+    /// Blocks of the checkpoint's own epoch can't be verified, since only the block producers of
+    /// the next epoch are known.
+    pub fn new(checkpoint: LightClientBlockView) -> Result<Self, Error> {
+        let next_block_producers = checkpoint.next_bps.ok_or(Error::MissingNextBlockProducers)?;
+        if compute_bp_hash(&next_block_producers) != checkpoint.inner_lite.next_bp_hash {
+            return Err(Error::InvalidNextBlockProducers);
+        }
+        let head_hash = compute_block_hash(
+            &checkpoint.inner_lite,
+            checkpoint.inner_rest_hash,
+            checkpoint.prev_hash,
+        );
+        let mut verified_blocks = HashMap::new();
+        verified_blocks.insert(head_hash, checkpoint.inner_lite.clone());
+        Ok(LightClient {
+            head_hash,
+            head: checkpoint.inner_lite,
+            block_producers: None,
+            next_block_producers,
+            verified_blocks,
+        })
+    }
+
+    pub fn head_hash(&self) -> &CryptoHash {
+        &self.head_hash
+    }
+
+    pub fn head(&self) -> &BlockHeaderInnerLiteView {
+        &self.head
+    }
+
+    /// Lite header of a verified block.
+    pub fn verified_block(&self, block_hash: &CryptoHash) -> Option<&BlockHeaderInnerLiteView> {
+        self.verified_blocks.get(block_hash)
+    }
+
+    /// State root before applying the verified block with given hash.
+    pub fn prev_state_root(&self, block_hash: &CryptoHash) -> Option<&CryptoHash> {
+        self.verified_blocks.get(block_hash).map(|inner_lite| &inner_lite.prev_state_root)
+    }
+
+    /// Verifies the light client block and moves the head to it.
+    /// Returns `false` if the block is not higher than the head.
+    pub fn process_block(&mut self, block: LightClientBlockView) -> Result<bool, Error> {
+        if block.inner_lite.height <= self.head.height {
+            return Ok(false);
+        }
+
+        let block_producers = if block.inner_lite.epoch_id == self.head.next_epoch_id {
+            &self.next_block_producers
+        } else if block.inner_lite.epoch_id == self.head.epoch_id {
+            self.block_producers
+                .as_ref()
+                .ok_or_else(|| Error::UnknownBlockProducers(block.inner_lite.epoch_id))?
+        } else {
+            return Err(Error::UnknownEpoch(block.inner_lite.epoch_id));
+        };
+        let block_hash = verify_light_client_block(&block, block_producers)?;
+
+        let next_block_producers = block.next_bps.ok_or(Error::MissingNextBlockProducers)?;
+        if compute_bp_hash(&next_block_producers) != block.inner_lite.next_bp_hash {
+            return Err(Error::InvalidNextBlockProducers);
+        }
+
+        if block.inner_lite.epoch_id == self.head.next_epoch_id {
+            self.block_producers =
+                Some(std::mem::replace(&mut self.next_block_producers, next_block_producers));
+        } else {
+            self.next_block_producers = next_block_producers;
+        }
+        self.head_hash = block_hash;
+        self.head = block.inner_lite.clone();
+        self.verified_blocks.insert(block_hash, block.inner_lite);
+        Ok(true)
+    }
+
+    /// Fetches the next light client block from the node and processes it.
+    /// Returns whether the head has moved.
+    pub fn advance(&mut self, client: &mut JsonRpcClient) -> Result<bool, Error> {
+        let block = System::new("actix")
+            .block_on(client.next_light_client_block(self.head_hash.to_string()))
+            .map_err(|err| Error::Rpc(err.to_string()))?;
+        match block {
+            Some(block) => match self.process_block(block) {
+                // The node keeps returning blocks of the checkpoint's epoch until the chain
+                // moves to the next one.
+                Err(Error::UnknownBlockProducers(_)) => Ok(false),
+                res => res,
+            },
+            None => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Stateless checks of a `LightClientBlockView` against the block producers of its epoch.
+//! Implements the conditions of the light client spec (https://github.com/nearprotocol/NEPs/pull/25).
+use std::collections::HashSet;
+
+use borsh::BorshSerialize;
+
+use near_primitives::block::{Approval, BlockHeaderInnerLite};
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::merkle::combine_hash;
+use near_primitives::types::Balance;
+use near_primitives::views::{
+    BlockHeaderInnerLiteView, LightClientApprovalView, LightClientBlockView, ValidatorStakeView,
+};
+
+use crate::error::Error;
+
+/// Hash of the block with given lite header, hash of the rest of the header and previous hash.
+pub fn compute_block_hash(
+    inner_lite: &BlockHeaderInnerLiteView,
+    inner_rest_hash: CryptoHash,
+    prev_hash: CryptoHash,
+) -> CryptoHash {
+    let inner_lite: BlockHeaderInnerLite = inner_lite.clone().into();
+    combine_hash(combine_hash(inner_lite.hash(), inner_rest_hash), prev_hash)
+}
+
+/// Hash of the ordered block producers set, as committed to by `next_bp_hash` in block headers.
+/// Must be kept in sync with `Chain::compute_bp_hash_inner`.
+pub fn compute_bp_hash(block_producers: &[ValidatorStakeView]) -> CryptoHash {
+    let mut arr = vec![];
+    for bp in block_producers.iter() {
+        arr.append(&mut hash(bp.account_id.as_bytes()).into());
+        arr.append(&mut hash(&bp.public_key.try_to_vec().expect("Failed to serialize")).into());
+        arr.append(&mut hash(&bp.amount.try_to_vec().expect("Failed to serialize")).into());
+    }
+    hash(&arr)
+}
+
+/// Verifies that the light client block was finalized by the given block producers and returns
+/// its hash.
+///
+/// Both the quorum pre-vote and the quorum pre-commit approvals must come from more than 2/3 of
+/// the stake. `block_producers` must be ordered as the approvals, i.e. the way the node returns
+/// them in `next_bps` of the light client blocks of the previous epoch.
+pub fn verify_light_client_block(
+    block: &LightClientBlockView,
+    block_producers: &[ValidatorStakeView],
+) -> Result<CryptoHash, Error> {
+    if block.qv_approvals.len() != block_producers.len() {
+        return Err(Error::InvalidApprovalsLength(block.qv_approvals.len(), block_producers.len()));
+    }
+    if block.qc_approvals.len() != block_producers.len() {
+        return Err(Error::InvalidApprovalsLength(block.qc_approvals.len(), block_producers.len()));
+    }
+
+    let block_hash = compute_block_hash(&block.inner_lite, block.inner_rest_hash, block.prev_hash);
+
+    // Blocks following the light client block, before and starting from the quorum pre-vote block.
+    let mut qv_blocks = HashSet::new();
+    let mut qc_blocks = HashSet::new();
+    let mut prev_hash = block_hash;
+    let mut passed_qv = false;
+    for future_inner_hash in block.future_inner_hashes.iter() {
+        let cur_hash = combine_hash(*future_inner_hash, prev_hash);
+        if cur_hash == block.qv_hash {
+            passed_qv = true;
+        }
+        if passed_qv {
+            qc_blocks.insert(cur_hash);
+        } else {
+            qv_blocks.insert(cur_hash);
+        }
+        prev_hash = cur_hash;
+    }
+    if !passed_qv {
+        return Err(Error::QuorumPreVoteNotFound);
+    }
+
+    let mut total_stake: Balance = 0;
+    let mut qv_stake: Balance = 0;
+    let mut qc_stake: Balance = 0;
+    for (bp, (qv_approval, qc_approval)) in
+        block_producers.iter().zip(block.qv_approvals.iter().zip(block.qc_approvals.iter()))
+    {
+        total_stake += bp.amount;
+
+        if let Some(qv_approval) = qv_approval {
+            // The parent must be the block itself or one of its descendants before the quorum
+            // pre-vote block, and the reference must not be among those descendants.
+            if (!qv_blocks.contains(&qv_approval.parent_hash)
+                && qv_approval.parent_hash != block_hash)
+                || qv_blocks.contains(&qv_approval.reference_hash)
+            {
+                return Err(Error::InvalidApproval(bp.account_id.clone()));
+            }
+            verify_approval_signature(qv_approval, bp)?;
+            qv_stake += bp.amount;
+        }
+
+        if let Some(qc_approval) = qc_approval {
+            // The parent must be the quorum pre-vote block or one of its descendants, and the
+            // reference must be the block itself or in its ancestry.
+            if !qc_blocks.contains(&qc_approval.parent_hash)
+                || qc_blocks.contains(&qc_approval.reference_hash)
+                || qv_blocks.contains(&qc_approval.reference_hash)
+            {
+                return Err(Error::InvalidApproval(bp.account_id.clone()));
+            }
+            verify_approval_signature(qc_approval, bp)?;
+            qc_stake += bp.amount;
+        }
+    }
+
+    let threshold = total_stake * 2 / 3;
+    if qv_stake <= threshold {
+        return Err(Error::NotEnoughStake { quorum: "quorum pre-vote" });
+    }
+    if qc_stake <= threshold {
+        return Err(Error::NotEnoughStake { quorum: "quorum pre-commit" });
+    }
+
+    Ok(block_hash)
+}
+
+fn verify_approval_signature(
+    approval: &LightClientApprovalView,
+    block_producer: &ValidatorStakeView,
+) -> Result<(), Error> {
+    let data = Approval::get_data_for_sig(&approval.parent_hash, &approval.reference_hash);
+    if approval.signature.verify(&data, &block_producer.public_key) {
+        Ok(())
+    } else {
+        Err(Error::InvalidSignature(block_producer.account_id.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{InMemorySigner, KeyType, Signer};
+
+    use super::*;
+
+    fn approval(signer: &InMemorySigner, parent_hash: CryptoHash) -> LightClientApprovalView {
+        // All approvals refer to the light client block itself.
+        let reference_hash = light_client_block_hash();
+        LightClientApprovalView {
+            parent_hash,
+            reference_hash,
+            signature: signer.sign(&Approval::get_data_for_sig(&parent_hash, &reference_hash)),
+        }
+    }
+
+    fn inner_lite() -> BlockHeaderInnerLiteView {
+        BlockHeaderInnerLiteView {
+            height: 5,
+            epoch_id: hash(b"epoch"),
+            next_epoch_id: hash(b"next_epoch"),
+            prev_state_root: hash(b"state"),
+            outcome_root: hash(b"outcome"),
+            timestamp: 1,
+            next_bp_hash: CryptoHash::default(),
+        }
+    }
+
+    fn light_client_block_hash() -> CryptoHash {
+        compute_block_hash(&inner_lite(), hash(b"rest"), hash(b"prev"))
+    }
+
+    /// Light client block followed by three blocks, the second one being the quorum pre-vote.
+    fn setup(num_approvals: usize) -> (LightClientBlockView, Vec<ValidatorStakeView>) {
+        let signers = (0..3)
+            .map(|i| {
+                let account_id = format!("test{}", i);
+                InMemorySigner::from_seed(&account_id, KeyType::ED25519, &account_id)
+            })
+            .collect::<Vec<_>>();
+        let block_producers = signers
+            .iter()
+            .map(|signer| ValidatorStakeView {
+                account_id: signer.account_id.clone(),
+                public_key: signer.public_key.clone(),
+                amount: 100,
+            })
+            .collect::<Vec<_>>();
+
+        let future_inner_hashes = vec![hash(b"1"), hash(b"2"), hash(b"3")];
+        let h1 = combine_hash(future_inner_hashes[0], light_client_block_hash());
+        let h2 = combine_hash(future_inner_hashes[1], h1);
+        let block = LightClientBlockView {
+            inner_lite: inner_lite(),
+            inner_rest_hash: hash(b"rest"),
+            next_bps: None,
+            qv_hash: h2,
+            future_inner_hashes,
+            qv_approvals: signers
+                .iter()
+                .enumerate()
+                .map(
+                    |(i, signer)| if i < num_approvals { Some(approval(signer, h1)) } else { None },
+                )
+                .collect(),
+            qc_approvals: signers
+                .iter()
+                .enumerate()
+                .map(
+                    |(i, signer)| if i < num_approvals { Some(approval(signer, h2)) } else { None },
+                )
+                .collect(),
+            prev_hash: hash(b"prev"),
+        };
+        (block, block_producers)
+    }
+
+    #[test]
+    fn test_verify_light_client_block() {
+        let (block, block_producers) = setup(3);
+        assert_eq!(
+            verify_light_client_block(&block, &block_producers),
+            Ok(light_client_block_hash())
+        );
+    }
+
+    #[test]
+    fn test_verify_tampered_state_root() {
+        let (mut block, block_producers) = setup(3);
+        block.inner_lite.prev_state_root = hash(b"other state");
+        assert_eq!(
+            verify_light_client_block(&block, &block_producers),
+            Err(Error::QuorumPreVoteNotFound)
+        );
+    }
+
+    #[test]
+    fn test_verify_not_enough_stake() {
+        let (block, block_producers) = setup(2);
+        assert_eq!(
+            verify_light_client_block(&block, &block_producers),
+            Err(Error::NotEnoughStake { quorum: "quorum pre-vote" })
+        );
+    }
+
+    #[test]
+    fn test_verify_invalid_signature() {
+        let (mut block, block_producers) = setup(3);
+        let other = InMemorySigner::from_seed("other", KeyType::ED25519, "other");
+        block.qc_approvals[1] = Some(approval(&other, block.qv_hash));
+        assert_eq!(
+            verify_light_client_block(&block, &block_producers),
+            Err(Error::InvalidSignature("test1".to_string()))
+        );
+    }
+}
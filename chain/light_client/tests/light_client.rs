@@ -0,0 +1,68 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use actix::System;
+
+use near_jsonrpc_client::new_client;
+use near_light_client::LightClient;
+use near_primitives::test_utils::{heavy_test, init_integration_logger};
+use testlib::genesis_hash;
+use testlib::node::{create_nodes, Node, NodeConfig, ThreadNode};
+
+const EPOCH_LENGTH: u64 = 10;
+
+/// Starts 4 validators, bootstraps the light client from the first light client block after
+/// genesis and checks that it follows the chain over two epoch switches.
+#[test]
+fn test_light_client_follows_epochs() {
+    init_integration_logger();
+    heavy_test(|| {
+        let mut nodes = create_nodes(4, "light_client")
+            .into_iter()
+            .map(|cfg| match cfg {
+                NodeConfig::Thread(mut config) => {
+                    config.genesis_config.epoch_length = EPOCH_LENGTH;
+                    config.client_config.epoch_length = EPOCH_LENGTH;
+                    ThreadNode::new(config)
+                }
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        for node in nodes.iter_mut() {
+            node.start();
+        }
+        let genesis_hash = genesis_hash(&nodes[0].config.genesis_config);
+        let mut client = new_client(&format!("http://{}", nodes[0].config.rpc_config.addr));
+
+        // The node can't produce a light client block until some block is final.
+        let started = Instant::now();
+        let checkpoint = loop {
+            assert!(started.elapsed() < Duration::from_secs(60), "No light client block");
+            let res = System::new("actix")
+                .block_on(client.next_light_client_block(genesis_hash.to_string()));
+            if let Ok(Some(block)) = res {
+                break block;
+            }
+            thread::sleep(Duration::from_millis(100));
+        };
+        let checkpoint_epoch_id = checkpoint.inner_lite.epoch_id;
+        let checkpoint_next_epoch_id = checkpoint.inner_lite.next_epoch_id;
+        let mut light_client = LightClient::new(checkpoint).unwrap();
+
+        let started = Instant::now();
+        while light_client.head().epoch_id == checkpoint_epoch_id
+            || light_client.head().epoch_id == checkpoint_next_epoch_id
+        {
+            assert!(started.elapsed() < Duration::from_secs(120), "Light client is stuck");
+            if !light_client.advance(&mut client).unwrap() {
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+
+        let head_hash = *light_client.head_hash();
+        assert_eq!(
+            light_client.prev_state_root(&head_hash),
+            Some(&light_client.head().prev_state_root)
+        );
+    });
+}
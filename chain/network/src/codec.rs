@@ -1,27 +1,133 @@
 use std::io::{Error, ErrorKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use bytes::{BufMut, BytesMut};
 use tokio::codec::{Decoder, Encoder};
 
+use crate::metrics;
+use crate::noise::NoiseSession;
 use crate::types::PeerMessage;
 
+/// Maximum size of a decompressed frame, frames that claim to be larger are rejected.
+const MAX_DECOMPRESSED_LENGTH: usize = 512 * 1024 * 1024;
+
+/// First byte of the frame, before the message.
+const FRAME_RAW: u8 = 0;
+const FRAME_COMPRESSED: u8 = 1;
+
+/// Compression settings of a connection, shared between its codecs and the peer.
+/// Frames are only compressed after the peer announced in the handshake that it accepts them.
+#[derive(Clone)]
+pub struct Compression {
+    /// Minimum size of the messages to compress. Compression is disabled if None.
+    threshold: Option<usize>,
+    /// Whether the peer accepts compressed frames.
+    peer_accepts: Arc<AtomicBool>,
+}
+
+impl Compression {
+    pub fn new(threshold: Option<usize>) -> Self {
+        Compression { threshold, peer_accepts: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// Whether this node accepts compressed frames.
+    pub fn is_enabled(&self) -> bool {
+        self.threshold.is_some()
+    }
+
+    /// Called with the compression capability from the handshake of the peer.
+    pub fn set_peer_accepts(&self, peer_accepts: bool) {
+        self.peer_accepts.store(peer_accepts && self.is_enabled(), Ordering::SeqCst);
+    }
+
+    fn should_compress(&self, len: usize) -> bool {
+        self.peer_accepts.load(Ordering::SeqCst) && self.threshold.map_or(false, |t| len >= t)
+    }
+}
+
+/// Serialized message to send, with its type for metrics.
+pub struct OutboundMessage {
+    pub msg_type: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+impl OutboundMessage {
+    pub fn new(msg: &PeerMessage) -> Result<Self, Error> {
+        Ok(OutboundMessage { msg_type: msg.msg_type(), bytes: msg.try_to_vec()? })
+    }
+}
+
 pub struct Codec {
     max_length: u32,
+    /// Session to encrypt and decrypt the frames with. Frames are sent as is without it.
+    session: Option<NoiseSession>,
+    compression: Compression,
 }
 
 #[allow(clippy::new_without_default)]
 impl Codec {
     pub fn new() -> Self {
-        Codec { max_length: std::u32::MAX }
+        Codec { max_length: std::u32::MAX, session: None, compression: Compression::new(None) }
+    }
+
+    pub fn with_session(session: NoiseSession, compression: Compression) -> Self {
+        Codec { max_length: std::u32::MAX, session: Some(session), compression }
+    }
+
+    fn compress(&self, msg: OutboundMessage) -> Vec<u8> {
+        if self.compression.should_compress(msg.bytes.len()) {
+            if let Ok(compressed) = snap::Encoder::new().compress_vec(&msg.bytes) {
+                if compressed.len() < msg.bytes.len() {
+                    metrics::record_compression(msg.msg_type, msg.bytes.len(), compressed.len());
+                    let mut frame = Vec::with_capacity(compressed.len() + 1);
+                    frame.push(FRAME_COMPRESSED);
+                    frame.extend_from_slice(&compressed);
+                    return frame;
+                }
+            }
+        }
+        let mut frame = Vec::with_capacity(msg.bytes.len() + 1);
+        frame.push(FRAME_RAW);
+        frame.extend_from_slice(&msg.bytes);
+        frame
+    }
+
+    fn decompress(&self, frame: &[u8]) -> Result<Vec<u8>, Error> {
+        match frame.split_first() {
+            Some((&FRAME_RAW, bytes)) => Ok(bytes.to_vec()),
+            Some((&FRAME_COMPRESSED, bytes)) => {
+                if !self.compression.is_enabled() {
+                    return Err(Error::new(ErrorKind::InvalidData, "Compression is disabled"));
+                }
+                let len = snap::decompress_len(bytes)
+                    .map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+                if len > MAX_DECOMPRESSED_LENGTH {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "Decompressed frame is too long",
+                    ));
+                }
+                snap::Decoder::new()
+                    .decompress_vec(bytes)
+                    .map_err(|err| Error::new(ErrorKind::InvalidData, err))
+            }
+            _ => Err(Error::new(ErrorKind::InvalidData, "Invalid frame type")),
+        }
     }
 }
 
 impl Encoder for Codec {
-    type Item = Vec<u8>;
+    type Item = OutboundMessage;
     type Error = Error;
 
     fn encode(&mut self, item: Self::Item, buf: &mut BytesMut) -> Result<(), Error> {
+        let item = self.compress(item);
+        let item = match &self.session {
+            Some(session) => session.encrypt(&item)?,
+            None => item,
+        };
         if item.len() > self.max_length as usize {
             Err(Error::new(ErrorKind::InvalidInput, "Input is too long"))
         } else {
@@ -50,17 +156,17 @@ impl Decoder for Codec {
             // not enough bytes, keep waiting
             Ok(None)
         } else {
-            let res = Some(buf[4..4 + len as usize].to_vec());
+            let res = buf[4..4 + len as usize].to_vec();
             buf.advance(4 + len as usize);
-            Ok(res)
+            let res = match &self.session {
+                Some(session) => session.decrypt(&res)?,
+                None => res,
+            };
+            self.decompress(&res).map(Some)
         }
     }
 }
 
-pub fn peer_message_to_bytes(peer_message: PeerMessage) -> Result<Vec<u8>, std::io::Error> {
-    peer_message.try_to_vec()
-}
-
 pub fn bytes_to_peer_message(bytes: &[u8]) -> Result<PeerMessage, std::io::Error> {
     PeerMessage::try_from_slice(bytes)
 }
@@ -83,11 +189,57 @@ mod test {
     fn test_codec(msg: PeerMessage) {
         let mut codec = Codec::new();
         let mut buffer = BytesMut::new();
-        codec.encode(peer_message_to_bytes(msg.clone()).unwrap(), &mut buffer).unwrap();
+        codec.encode(OutboundMessage::new(&msg).unwrap(), &mut buffer).unwrap();
         let decoded = codec.decode(&mut buffer).unwrap().unwrap();
         assert_eq!(bytes_to_peer_message(&decoded).unwrap(), msg);
     }
 
+    fn compressed_codec(threshold: Option<usize>, peer_accepts: bool) -> Codec {
+        let compression = Compression::new(threshold);
+        compression.set_peer_accepts(peer_accepts);
+        Codec { max_length: std::u32::MAX, session: None, compression }
+    }
+
+    #[test]
+    fn test_compression() {
+        let msg = PeerMessage::PeersResponse(vec![PeerInfo::random(); 100]);
+        let bytes = msg.try_to_vec().unwrap();
+        let mut buffer = BytesMut::new();
+        compressed_codec(Some(0), true)
+            .encode(OutboundMessage::new(&msg).unwrap(), &mut buffer)
+            .unwrap();
+        assert_eq!(buffer[4], FRAME_COMPRESSED);
+        assert!(buffer.len() < bytes.len());
+        let decoded = compressed_codec(Some(0), false).decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(decoded, bytes);
+
+        // Small messages and messages to peers that don't accept compression are sent as is.
+        for codec in
+            &mut [compressed_codec(Some(bytes.len() + 1), true), compressed_codec(Some(0), false)]
+        {
+            let mut buffer = BytesMut::new();
+            codec.encode(OutboundMessage::new(&msg).unwrap(), &mut buffer).unwrap();
+            assert_eq!(buffer[4], FRAME_RAW);
+        }
+
+        // Compressed frames are rejected if compression is disabled.
+        let mut buffer = BytesMut::new();
+        compressed_codec(Some(0), true)
+            .encode(OutboundMessage::new(&msg).unwrap(), &mut buffer)
+            .unwrap();
+        assert!(Codec::new().decode(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn test_decompressed_length_limit() {
+        // Snappy header with the decompressed length of 1GiB, encoded as varint.
+        let frame = vec![FRAME_COMPRESSED, 0x80, 0x80, 0x80, 0x80, 0x04];
+        let mut buffer = BytesMut::new();
+        buffer.put_u32_le(frame.len() as u32);
+        buffer.put(frame);
+        assert!(compressed_codec(Some(0), true).decode(&mut buffer).is_err());
+    }
+
     #[test]
     fn test_peer_message_handshake() {
         let peer_info = PeerInfo::random();
@@ -102,6 +254,7 @@ mod test {
                 tracked_shards: vec![],
             },
             edge_info: EdgeInfo::default(),
+            compression: true,
         };
         let msg = PeerMessage::Handshake(fake_handshake);
         test_codec(msg);
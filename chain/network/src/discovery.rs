@@ -0,0 +1,482 @@
+//! Kademlia-style peer discovery.
+//!
+//! Peers are placed by the XOR distance between the hashes of their ids. Each node keeps signed
+//! peer records in one bucket per distance and answers `DhtFindNode` queries with the records
+//! closest to the requested id. The peer manager runs iterative lookups: it starts with the
+//! connected peers closest to the target and keeps querying the closest discovered peers until
+//! the closest ones have all been asked. Discovered peers are added to the `PeerStore`, from where
+//! it picks the peers to connect to.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use near_crypto::{SecretKey, Signature};
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::types::AccountId;
+
+use crate::types::{PeerId, PeerInfo};
+
+/// Maximum number of records per bucket, and number of records returned for a query.
+pub const BUCKET_SIZE: usize = 20;
+/// Number of peers queried in parallel during a lookup.
+pub const LOOKUP_PARALLELISM: usize = 3;
+/// One bucket for every bit of the key.
+const NUM_BUCKETS: usize = 256;
+/// Records with timestamps further in the future are rejected.
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(60);
+/// Number of peers that have to see us at the same ip before we advertise it.
+const MIN_IP_OBSERVATIONS: usize = 3;
+/// Number of peers whose observation of our ip is kept.
+const MAX_IP_OBSERVATIONS: usize = 64;
+/// Maximum number of rounds of queries of a lookup.
+const MAX_LOOKUP_ROUNDS: usize = 8;
+
+/// Address of a peer, signed by the peer itself.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PeerRecord {
+    pub peer_info: PeerInfo,
+    /// Time when the record was created.
+    pub timestamp: u64,
+    pub signature: Signature,
+}
+
+impl PeerRecord {
+    pub fn new(peer_info: PeerInfo, timestamp: u64, secret_key: &SecretKey) -> Self {
+        let signature = secret_key.sign(Self::build_hash(&peer_info, timestamp).as_ref());
+        PeerRecord { peer_info, timestamp, signature }
+    }
+
+    fn build_hash(peer_info: &PeerInfo, timestamp: u64) -> CryptoHash {
+        let mut data = peer_info.try_to_vec().unwrap();
+        data.extend_from_slice(&timestamp.to_le_bytes());
+        hash(&data)
+    }
+
+    /// Checks that the record has an address and is signed by the peer.
+    pub fn verify(&self) -> bool {
+        self.peer_info.addr.is_some()
+            && self.signature.verify(
+                Self::build_hash(&self.peer_info, self.timestamp).as_ref(),
+                &self.peer_info.id.public_key(),
+            )
+    }
+}
+
+/// Records sent in response to `DhtFindNode`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DhtNodes {
+    pub target: PeerId,
+    pub records: Vec<PeerRecord>,
+    /// Address of the requesting peer as seen by the responding peer.
+    pub observed_addr: Option<SocketAddr>,
+}
+
+type Key = [u8; 32];
+
+fn key(peer_id: &PeerId) -> Key {
+    let mut result = [0; 32];
+    result.copy_from_slice(hash(&Vec::<u8>::from(peer_id.clone())).as_ref());
+    result
+}
+
+fn distance(a: &Key, b: &Key) -> Key {
+    let mut result = [0; 32];
+    for (byte, (a, b)) in result.iter_mut().zip(a.iter().zip(b.iter())) {
+        *byte = a ^ b;
+    }
+    result
+}
+
+/// Index of the bucket for the given distance, larger for further peers. `None` for itself.
+fn bucket_index(distance: &Key) -> Option<usize> {
+    let mut leading_zeros = 0;
+    for byte in distance.iter() {
+        leading_zeros += byte.leading_zeros() as usize;
+        if *byte != 0 {
+            return Some(NUM_BUCKETS - 1 - leading_zeros);
+        }
+    }
+    None
+}
+
+/// Iterative lookup of the peers closest to a target.
+struct Lookup {
+    /// Peers that were asked for the target.
+    queried: HashSet<PeerId>,
+    rounds: usize,
+    started: u64,
+}
+
+/// Routing table of the discovery protocol.
+pub struct Discovery {
+    peer_id: PeerId,
+    key: Key,
+    secret_key: SecretKey,
+    account_id: Option<AccountId>,
+    /// Address to advertise. If the ip is unspecified, it's learnt from the connected peers.
+    addr: Option<SocketAddr>,
+    /// Ip at which each peer reported seeing us, from the least recent report.
+    observed_ips: VecDeque<(PeerId, IpAddr)>,
+    own_record: Option<PeerRecord>,
+    /// Records ordered from the least recently updated.
+    buckets: Vec<VecDeque<PeerRecord>>,
+    record_ttl: u64,
+    /// Lookups in progress by target.
+    lookups: HashMap<PeerId, Lookup>,
+}
+
+impl Discovery {
+    pub fn new(
+        peer_id: PeerId,
+        secret_key: SecretKey,
+        account_id: Option<AccountId>,
+        addr: Option<SocketAddr>,
+        record_ttl: Duration,
+    ) -> Self {
+        Discovery {
+            key: key(&peer_id),
+            peer_id,
+            secret_key,
+            account_id,
+            addr,
+            observed_ips: VecDeque::new(),
+            own_record: None,
+            buckets: (0..NUM_BUCKETS).map(|_| VecDeque::new()).collect(),
+            record_ttl: record_ttl.as_nanos() as u64,
+            lookups: HashMap::new(),
+        }
+    }
+
+    /// Records the ip at which a peer sees us. Only the latest report of every peer counts, and
+    /// only the reports of the last `MAX_IP_OBSERVATIONS` peers are kept.
+    pub fn observe_ip(&mut self, peer_id: &PeerId, ip: IpAddr) {
+        if ip.is_unspecified() {
+            return;
+        }
+        self.observed_ips.retain(|(other, _)| other != peer_id);
+        self.observed_ips.push_back((peer_id.clone(), ip));
+        if self.observed_ips.len() > MAX_IP_OBSERVATIONS {
+            self.observed_ips.pop_front();
+        }
+    }
+
+    /// The configured address, or the configured port at the ip seen by most peers, if at least
+    /// `MIN_IP_OBSERVATIONS` of them agree on it.
+    fn advertised_addr(&self) -> Option<SocketAddr> {
+        let addr = self.addr?;
+        if !addr.ip().is_unspecified() {
+            return Some(addr);
+        }
+        let mut counts = HashMap::new();
+        for (_, ip) in self.observed_ips.iter() {
+            *counts.entry(*ip).or_insert(0) += 1;
+        }
+        counts
+            .into_iter()
+            .filter(|(_, count)| *count >= MIN_IP_OBSERVATIONS)
+            .max_by_key(|(_, count)| *count)
+            .map(|(ip, _)| SocketAddr::new(ip, addr.port()))
+    }
+
+    /// Returns a record for this node, re-signed when the address changes or the record is about
+    /// to expire. `None` if we don't know an address to advertise yet.
+    pub fn own_record(&mut self, now: u64) -> Option<PeerRecord> {
+        let addr = self.advertised_addr()?;
+        let is_fresh = self.own_record.as_ref().map_or(false, |record| {
+            record.peer_info.addr == Some(addr) && record.timestamp + self.record_ttl / 2 > now
+        });
+        if !is_fresh {
+            let peer_info = PeerInfo {
+                id: self.peer_id.clone(),
+                addr: Some(addr),
+                account_id: self.account_id.clone(),
+            };
+            self.own_record = Some(PeerRecord::new(peer_info, now, &self.secret_key));
+        }
+        self.own_record.clone()
+    }
+
+    /// Timestamps of remote records are arbitrary, so the expiration time saturates.
+    fn is_expired(&self, record: &PeerRecord, now: u64) -> bool {
+        record.timestamp.saturating_add(self.record_ttl) < now
+    }
+
+    /// Adds a valid record to the table, replacing older records of the same peer.
+    /// Full buckets keep their old records unless they are expired, as long-lived peers are more
+    /// likely to stay online. Returns whether the record was added.
+    pub fn insert(&mut self, record: PeerRecord, now: u64) -> bool {
+        if record.timestamp > now.saturating_add(MAX_CLOCK_SKEW.as_nanos() as u64)
+            || self.is_expired(&record, now)
+            || !record.verify()
+        {
+            return false;
+        }
+        let index = match bucket_index(&distance(&self.key, &key(&record.peer_info.id))) {
+            Some(index) => index,
+            None => return false,
+        };
+        let record_ttl = self.record_ttl;
+        let bucket = &mut self.buckets[index];
+        if let Some(position) =
+            bucket.iter().position(|other| other.peer_info.id == record.peer_info.id)
+        {
+            if bucket[position].timestamp >= record.timestamp {
+                return false;
+            }
+            bucket.remove(position);
+        } else if bucket.len() >= BUCKET_SIZE {
+            match bucket.front() {
+                Some(oldest) if oldest.timestamp.saturating_add(record_ttl) < now => {
+                    bucket.pop_front();
+                }
+                _ => return false,
+            }
+        }
+        bucket.push_back(record);
+        true
+    }
+
+    /// Returns up to `count` records closest to the target.
+    pub fn closest(&self, target: &PeerId, count: usize) -> Vec<PeerRecord> {
+        let target = key(target);
+        let mut records = self
+            .buckets
+            .iter()
+            .flat_map(|bucket| bucket.iter())
+            .map(|record| (distance(&target, &key(&record.peer_info.id)), record))
+            .collect::<Vec<_>>();
+        records.sort_by(|(a, _), (b, _)| a.cmp(b));
+        records.into_iter().take(count).map(|(_, record)| record.clone()).collect()
+    }
+
+    /// Returns the given peers ordered by distance to the target.
+    pub fn sort_by_distance(&self, target: &PeerId, mut peers: Vec<PeerId>) -> Vec<PeerId> {
+        let target = key(target);
+        peers.sort_by_key(|peer_id| distance(&target, &key(peer_id)));
+        peers
+    }
+
+    /// Starts a lookup of the target, returns the peers to query first.
+    pub fn start_lookup(
+        &mut self,
+        target: PeerId,
+        active_peers: Vec<PeerId>,
+        now: u64,
+    ) -> Vec<PeerId> {
+        let peers = self
+            .sort_by_distance(&target, active_peers)
+            .into_iter()
+            .take(LOOKUP_PARALLELISM)
+            .collect::<Vec<_>>();
+        if !peers.is_empty() {
+            let queried = peers.iter().cloned().collect();
+            self.lookups.insert(target, Lookup { queried, rounds: 1, started: now });
+        }
+        peers
+    }
+
+    /// Returns whether the peer was asked for the target by a lookup in progress.
+    pub fn is_queried(&self, target: &PeerId, peer_id: &PeerId) -> bool {
+        self.lookups.get(target).map_or(false, |lookup| lookup.queried.contains(peer_id))
+    }
+
+    /// Targets of the lookups in progress that asked the given peer.
+    pub fn lookups_querying(&self, peer_id: &PeerId) -> Vec<PeerId> {
+        self.lookups
+            .iter()
+            .filter(|(_, lookup)| lookup.queried.contains(peer_id))
+            .map(|(target, _)| target.clone())
+            .collect()
+    }
+
+    /// Continues the lookup of the target after a response: returns the closest known records
+    /// that weren't asked yet, to query next. The lookup ends once the `BUCKET_SIZE` closest
+    /// records have all been asked, or after `MAX_LOOKUP_ROUNDS`.
+    pub fn continue_lookup(&mut self, target: &PeerId) -> Vec<PeerRecord> {
+        let closest = self.closest(target, BUCKET_SIZE);
+        let lookup = match self.lookups.get_mut(target) {
+            Some(lookup) => lookup,
+            None => return vec![],
+        };
+        let next = closest
+            .into_iter()
+            .filter(|record| !lookup.queried.contains(&record.peer_info.id))
+            .take(LOOKUP_PARALLELISM)
+            .collect::<Vec<_>>();
+        if next.is_empty() || lookup.rounds >= MAX_LOOKUP_ROUNDS {
+            self.lookups.remove(target);
+            return vec![];
+        }
+        lookup.rounds += 1;
+        lookup.queried.extend(next.iter().map(|record| record.peer_info.id.clone()));
+        next
+    }
+
+    /// Removes the expired records, and the lookups that didn't finish within the record ttl.
+    pub fn remove_expired(&mut self, now: u64) {
+        let record_ttl = self.record_ttl;
+        for bucket in self.buckets.iter_mut() {
+            bucket.retain(|record| record.timestamp.saturating_add(record_ttl) >= now);
+        }
+        self.lookups.retain(|_, lookup| lookup.started + record_ttl >= now);
+    }
+
+    pub fn peer_ids(&self) -> Vec<PeerId> {
+        self.buckets
+            .iter()
+            .flat_map(|bucket| bucket.iter())
+            .map(|record| record.peer_info.id.clone())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buckets.iter().map(|bucket| bucket.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::KeyType;
+
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(60 * 60);
+
+    fn record(seed: &str, port: u16, timestamp: u64) -> PeerRecord {
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, seed);
+        let peer_info = PeerInfo::new(
+            secret_key.public_key().into(),
+            format!("127.0.0.1:{}", port).parse().unwrap(),
+        );
+        PeerRecord::new(peer_info, timestamp, &secret_key)
+    }
+
+    fn discovery(seed: &str, addr: &str) -> Discovery {
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, seed);
+        Discovery::new(
+            secret_key.public_key().into(),
+            secret_key,
+            None,
+            Some(addr.parse().unwrap()),
+            TTL,
+        )
+    }
+
+    #[test]
+    fn test_record_signature() {
+        let record = record("test", 1000, 1);
+        assert!(record.verify());
+        let mut forged = record.clone();
+        forged.peer_info.addr = Some("127.0.0.1:1001".parse().unwrap());
+        assert!(!forged.verify());
+        let mut forged = record;
+        forged.timestamp = 2;
+        assert!(!forged.verify());
+    }
+
+    #[test]
+    fn test_insert() {
+        let now = TTL.as_nanos() as u64 * 2;
+        let mut discovery = discovery("me", "127.0.0.1:1000");
+        assert!(discovery.insert(record("a", 1001, now), now));
+        // Only newer records of the same peer replace the old one.
+        assert!(!discovery.insert(record("a", 1002, now), now));
+        assert!(discovery.insert(record("a", 1002, now + 1), now + 1));
+        assert_eq!(discovery.len(), 1);
+        assert_eq!(
+            discovery.closest(&record("a", 0, 0).peer_info.id, 1)[0].peer_info.addr,
+            Some("127.0.0.1:1002".parse().unwrap())
+        );
+        // Expired, future and own records are rejected.
+        assert!(!discovery.insert(record("b", 1003, 1), now));
+        assert!(!discovery.insert(record("b", 1003, now + TTL.as_nanos() as u64), now));
+        assert!(!discovery.insert(record("me", 1000, now), now));
+        assert!(!discovery.insert(record("b", 1003, std::u64::MAX), now));
+        assert!(!discovery.insert(record("b", 1003, now), std::u64::MAX));
+        assert_eq!(discovery.len(), 1);
+        discovery.remove_expired(now * 2);
+        assert!(discovery.is_empty());
+    }
+
+    #[test]
+    fn test_closest() {
+        let now = 1;
+        let mut discovery = discovery("me", "127.0.0.1:1000");
+        let records =
+            (0..100).map(|i| record(&format!("peer{}", i), 1000 + i, now)).collect::<Vec<_>>();
+        for record in records.iter() {
+            discovery.insert(record.clone(), now);
+        }
+        let target = records[42].peer_info.id.clone();
+        let closest = discovery.closest(&target, BUCKET_SIZE);
+        assert_eq!(closest.len(), BUCKET_SIZE);
+        assert_eq!(closest[0], records[42]);
+        let target_key = key(&target);
+        for pair in closest.windows(2) {
+            assert!(
+                distance(&target_key, &key(&pair[0].peer_info.id))
+                    <= distance(&target_key, &key(&pair[1].peer_info.id))
+            );
+        }
+    }
+
+    #[test]
+    fn test_own_record() {
+        let mut discovery = discovery("me", "0.0.0.0:1000");
+        assert_eq!(discovery.own_record(1), None);
+        let peers =
+            (0..4).map(|i| record(&format!("peer{}", i), 0, 0).peer_info.id).collect::<Vec<_>>();
+        discovery.observe_ip(&peers[0], "10.0.0.1".parse().unwrap());
+        discovery.observe_ip(&peers[1], "10.0.0.2".parse().unwrap());
+        // Repeated reports of the same peer count once.
+        discovery.observe_ip(&peers[1], "10.0.0.2".parse().unwrap());
+        discovery.observe_ip(&peers[2], "10.0.0.2".parse().unwrap());
+        assert_eq!(discovery.own_record(1), None);
+        discovery.observe_ip(&peers[3], "10.0.0.2".parse().unwrap());
+        let record = discovery.own_record(1).unwrap();
+        assert!(record.verify());
+        assert_eq!(record.peer_info.addr, Some("10.0.0.2:1000".parse().unwrap()));
+        // The record is reused until it's half expired.
+        assert_eq!(discovery.own_record(2), Some(record.clone()));
+        let later = TTL.as_nanos() as u64;
+        assert_eq!(discovery.own_record(later).unwrap().timestamp, later);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let now = 1;
+        let mut discovery = discovery("me", "127.0.0.1:1000");
+        let records =
+            (0..100).map(|i| record(&format!("peer{}", i), 1000 + i, now)).collect::<Vec<_>>();
+        for record in records.iter() {
+            discovery.insert(record.clone(), now);
+        }
+        let target = records[42].peer_info.id.clone();
+        let active = records[..5].iter().map(|record| record.peer_info.id.clone()).collect();
+        let first = discovery.start_lookup(target.clone(), active, now);
+        assert_eq!(first.len(), LOOKUP_PARALLELISM);
+        assert!(discovery.is_queried(&target, &first[0]));
+        assert_eq!(discovery.lookups_querying(&first[0]), vec![target.clone()]);
+        // Every round asks the closest records that weren't asked yet, until they all were.
+        let mut asked = HashSet::new();
+        loop {
+            let next = discovery.continue_lookup(&target);
+            if next.is_empty() {
+                break;
+            }
+            assert!(next.len() <= LOOKUP_PARALLELISM);
+            for record in next {
+                assert!(asked.insert(record.peer_info.id));
+            }
+        }
+        assert!(asked.contains(&target));
+        assert!(!discovery.is_queried(&target, &first[0]));
+        assert!(discovery.continue_lookup(&target).is_empty());
+    }
+}
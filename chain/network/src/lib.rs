@@ -8,11 +8,15 @@ pub use types::{
 };
 
 mod codec;
+pub mod discovery;
 mod metrics;
+mod noise;
 mod peer;
 mod peer_manager;
 pub mod peer_store;
 mod rate_counter;
+pub mod rate_limiter;
+pub mod recorder;
 pub mod routing;
 pub mod types;
 
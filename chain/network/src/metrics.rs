@@ -1,4 +1,7 @@
-use near_metrics::{try_create_int_counter, try_create_int_gauge, IntCounter, IntGauge};
+use near_metrics::{
+    try_create_int_counter, try_create_int_counter_vec, try_create_int_gauge, IntCounter,
+    IntCounterVec, IntGauge,
+};
 
 macro_rules! type_messages {
     ($name_counter:ident, $name_bytes:ident) => {
@@ -87,6 +90,38 @@ lazy_static! {
             "drop_message_unreachable_peer",
             "Total messages dropped because target peer is not reachable"
         );
+    pub static ref PEER_MESSAGE_RATE_LIMITED_TOTAL: near_metrics::Result<IntCounter> =
+        try_create_int_counter(
+            "peer_message_rate_limited_total",
+            "Total messages from peers dropped because of rate limits"
+        );
+
+    // Compression metrics, the ratio is compressed bytes over original bytes.
+    pub static ref PEER_MESSAGE_COMPRESSED_ORIGINAL_BYTES: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "peer_message_compressed_original_bytes",
+            "Size of the sent compressed messages before compression",
+            &["type"]
+        );
+    pub static ref PEER_MESSAGE_COMPRESSED_BYTES: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "peer_message_compressed_bytes",
+            "Size of the sent compressed messages after compression",
+            &["type"]
+        );
+}
+
+pub fn record_compression(msg_type: &str, original_size: usize, compressed_size: usize) {
+    near_metrics::inc_counter_vec_by(
+        &PEER_MESSAGE_COMPRESSED_ORIGINAL_BYTES,
+        &[msg_type],
+        original_size as i64,
+    );
+    near_metrics::inc_counter_vec_by(
+        &PEER_MESSAGE_COMPRESSED_BYTES,
+        &[msg_type],
+        compressed_size as i64,
+    );
 }
 
 type_messages!(HANDSHAKE_RECEIVED_TOTAL, HANDSHAKE_RECEIVED_BYTES);
@@ -129,3 +164,5 @@ type_messages!(ROUTED_PING_RECEIVED_TOTAL, ROUTED_PING_RECEIVED_BYTES);
 type_messages!(ROUTED_PONG_RECEIVED_TOTAL, ROUTED_PONG_RECEIVED_BYTES);
 type_messages!(DISCONNECT_RECEIVED_TOTAL, DISCONNECT_RECEIVED_BYTES);
 type_messages!(CHALLENGE_RECEIVED_TOTAL, CHALLENGE_RECEIVED_BYTES);
+type_messages!(DHT_FIND_NODE_RECEIVED_TOTAL, DHT_FIND_NODE_RECEIVED_BYTES);
+type_messages!(DHT_NODES_RECEIVED_TOTAL, DHT_NODES_RECEIVED_BYTES);
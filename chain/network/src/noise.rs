@@ -0,0 +1,293 @@
+//! Noise XX handshake that runs on every connection before the `Handshake` message, and the
+//! session used by `Codec` to encrypt the messages after it.
+//!
+//! Noise static keys are X25519 keys generated for each connection. Every side proves that its
+//! static key belongs to its `PeerId` by signing it with the node's secret key, and sends the
+//! signature in its handshake payload, so it's only readable by the other side of the session.
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use futures::Future;
+use snow::params::NoiseParams;
+use snow::{Builder, HandshakeState, TransportState};
+use tokio::io::{read_exact, write_all};
+use tokio::net::TcpStream;
+
+use near_crypto::{SecretKey, Signature};
+
+use crate::types::{PeerId, PeerType};
+
+/// Noise protocol name, see https://noiseprotocol.org/noise.html#protocol-names-and-modifiers.
+const NOISE_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+
+/// Maximum length of a single Noise message.
+const NOISE_MAX_MESSAGE_LEN: usize = 65535;
+
+/// Length of the authentication tag appended to every encrypted message.
+const NOISE_TAG_LEN: usize = 16;
+
+/// Maximum length of the plaintext that fits into a single Noise message.
+const NOISE_MAX_PLAINTEXT_LEN: usize = NOISE_MAX_MESSAGE_LEN - NOISE_TAG_LEN;
+
+/// Prefix of the signed data, so the signature can't be reused in another context.
+const NOISE_STATIC_KEY_PREFIX: &[u8] = b"near-network-noise-static-key:";
+
+type HandshakeFuture<T> = Box<dyn Future<Item = T, Error = Error>>;
+
+/// Proof that the static key of the session belongs to the peer.
+#[derive(BorshSerialize, BorshDeserialize)]
+struct NoiseIdentity {
+    peer_id: PeerId,
+    signature: Signature,
+}
+
+impl NoiseIdentity {
+    fn new(peer_id: PeerId, secret_key: &SecretKey, static_key: &[u8]) -> Self {
+        let signature = secret_key.sign(&signed_data(static_key));
+        NoiseIdentity { peer_id, signature }
+    }
+
+    fn verify(&self, static_key: &[u8]) -> bool {
+        self.signature.verify(&signed_data(static_key), &self.peer_id.public_key())
+    }
+}
+
+fn signed_data(static_key: &[u8]) -> Vec<u8> {
+    [NOISE_STATIC_KEY_PREFIX, static_key].concat()
+}
+
+fn noise_error(err: snow::Error) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Noise error: {}", err))
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Encrypted session with a peer. Clones share the same state, so the read and the write half of
+/// the connection can use their own codecs.
+#[derive(Clone)]
+pub struct NoiseSession {
+    transport: Arc<Mutex<TransportState>>,
+}
+
+impl NoiseSession {
+    /// Encrypts a message of any length. Messages longer than a single Noise message are split.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut transport = self.transport.lock().expect("Noise session lock poisoned");
+        let num_chunks = std::cmp::max(
+            1,
+            (plaintext.len() + NOISE_MAX_PLAINTEXT_LEN - 1) / NOISE_MAX_PLAINTEXT_LEN,
+        );
+        let mut ciphertext = vec![0u8; plaintext.len() + num_chunks * NOISE_TAG_LEN];
+        let mut len = 0;
+        for chunk in plaintext.chunks(NOISE_MAX_PLAINTEXT_LEN) {
+            len += transport.write_message(chunk, &mut ciphertext[len..]).map_err(noise_error)?;
+        }
+        if plaintext.is_empty() {
+            len += transport.write_message(&[], &mut ciphertext).map_err(noise_error)?;
+        }
+        ciphertext.truncate(len);
+        Ok(ciphertext)
+    }
+
+    /// Decrypts a message produced by `encrypt` on the other side.
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        if ciphertext.is_empty() {
+            return Err(invalid_data("Empty encrypted message"));
+        }
+        let mut transport = self.transport.lock().expect("Noise session lock poisoned");
+        let mut plaintext = vec![0u8; ciphertext.len()];
+        let mut len = 0;
+        for chunk in ciphertext.chunks(NOISE_MAX_MESSAGE_LEN) {
+            len += transport.read_message(chunk, &mut plaintext[len..]).map_err(noise_error)?;
+        }
+        plaintext.truncate(len);
+        Ok(plaintext)
+    }
+}
+
+fn write_handshake_message(
+    stream: TcpStream,
+    mut state: HandshakeState,
+    payload: &[u8],
+) -> HandshakeFuture<(TcpStream, HandshakeState)> {
+    let mut message = vec![0u8; 4 + NOISE_MAX_MESSAGE_LEN];
+    let len = match state.write_message(payload, &mut message[4..]) {
+        Ok(len) => len,
+        Err(err) => return Box::new(futures::future::err(noise_error(err))),
+    };
+    message[..4].copy_from_slice(&(len as u32).to_le_bytes());
+    message.truncate(4 + len);
+    Box::new(write_all(stream, message).map(move |(stream, _)| (stream, state)))
+}
+
+/// Reads the next handshake message, framed the same way as by `Codec`, and returns its payload.
+/// Reads exactly the bytes of the message, so nothing sent after the handshake gets lost.
+fn read_handshake_message(
+    stream: TcpStream,
+    mut state: HandshakeState,
+) -> HandshakeFuture<(TcpStream, HandshakeState, Vec<u8>)> {
+    Box::new(read_exact(stream, [0u8; 4]).and_then(
+        move |(stream, len_bytes)| -> HandshakeFuture<_> {
+            let len = u32::from_le_bytes(len_bytes) as usize;
+            if len > NOISE_MAX_MESSAGE_LEN {
+                return Box::new(futures::future::err(invalid_data(
+                    "Noise handshake message is too long",
+                )));
+            }
+            Box::new(read_exact(stream, vec![0u8; len]).and_then(move |(stream, message)| {
+                let mut payload = vec![0u8; len];
+                let payload_len =
+                    state.read_message(&message, &mut payload).map_err(noise_error)?;
+                payload.truncate(payload_len);
+                Ok((stream, state, payload))
+            }))
+        },
+    ))
+}
+
+/// Checks the identity in the handshake payload of the peer against its static key.
+fn verify_identity(
+    state: &HandshakeState,
+    payload: &[u8],
+    expected_peer_id: Option<&PeerId>,
+) -> Result<PeerId, Error> {
+    let identity = NoiseIdentity::try_from_slice(payload)?;
+    let static_key =
+        state.get_remote_static().ok_or_else(|| invalid_data("Missing Noise static key"))?;
+    if !identity.verify(static_key) {
+        return Err(invalid_data("Noise static key doesn't match the peer id"));
+    }
+    if expected_peer_id.map_or(false, |peer_id| peer_id != &identity.peer_id) {
+        return Err(invalid_data("Peer id doesn't match the expected one"));
+    }
+    Ok(identity.peer_id)
+}
+
+/// Runs the Noise XX handshake over the stream:
+///   -> e
+///   <- e, ee, s, es, identity of the responder
+///   -> s, se, identity of the initiator
+/// Outbound connections are initiated by this node, and the identity of the responder must match
+/// `expected_peer_id`. Returns the stream, the authenticated id of the peer and the session.
+pub fn handshake(
+    stream: TcpStream,
+    peer_type: PeerType,
+    peer_id: PeerId,
+    secret_key: SecretKey,
+    expected_peer_id: Option<PeerId>,
+) -> HandshakeFuture<(TcpStream, PeerId, NoiseSession)> {
+    let params: NoiseParams = NOISE_PARAMS.parse().expect("Valid Noise parameters");
+    let state = Builder::new(params.clone())
+        .generate_keypair()
+        .and_then(|keypair| {
+            let identity = NoiseIdentity::new(peer_id, &secret_key, &keypair.public);
+            let builder = Builder::new(params).local_private_key(&keypair.private);
+            let state = match peer_type {
+                PeerType::Outbound => builder.build_initiator()?,
+                PeerType::Inbound => builder.build_responder()?,
+            };
+            Ok((state, identity.try_to_vec().expect("Failed to serialize")))
+        })
+        .map_err(noise_error);
+    let (state, identity) = match state {
+        Ok(state) => state,
+        Err(err) => return Box::new(futures::future::err(err)),
+    };
+
+    let session = match peer_type {
+        PeerType::Outbound => Box::new(
+            write_handshake_message(stream, state, &[])
+                .and_then(|(stream, state)| read_handshake_message(stream, state))
+                .and_then(move |(stream, state, payload)| {
+                    let peer_id = verify_identity(&state, &payload, expected_peer_id.as_ref());
+                    futures::future::result(peer_id).and_then(move |peer_id| {
+                        write_handshake_message(stream, state, &identity)
+                            .map(move |(stream, state)| (stream, state, peer_id))
+                    })
+                }),
+        ) as HandshakeFuture<_>,
+        PeerType::Inbound => Box::new(
+            read_handshake_message(stream, state)
+                .and_then(move |(stream, state, _)| {
+                    write_handshake_message(stream, state, &identity)
+                })
+                .and_then(|(stream, state)| read_handshake_message(stream, state))
+                .and_then(move |(stream, state, payload)| {
+                    let peer_id = verify_identity(&state, &payload, expected_peer_id.as_ref())?;
+                    Ok((stream, state, peer_id))
+                }),
+        ),
+    };
+    Box::new(session.and_then(|(stream, state, peer_id)| {
+        let transport = state.into_transport_mode().map_err(noise_error)?;
+        Ok((stream, peer_id, NoiseSession { transport: Arc::new(Mutex::new(transport)) }))
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn session_pair() -> (NoiseSession, NoiseSession) {
+        let params: NoiseParams = NOISE_PARAMS.parse().unwrap();
+        let initiator_key = Builder::new(params.clone()).generate_keypair().unwrap();
+        let responder_key = Builder::new(params.clone()).generate_keypair().unwrap();
+        let mut initiator = Builder::new(params.clone())
+            .local_private_key(&initiator_key.private)
+            .build_initiator()
+            .unwrap();
+        let mut responder = Builder::new(params)
+            .local_private_key(&responder_key.private)
+            .build_responder()
+            .unwrap();
+        let mut message = vec![0u8; NOISE_MAX_MESSAGE_LEN];
+        let mut payload = vec![0u8; NOISE_MAX_MESSAGE_LEN];
+        let len = initiator.write_message(&[], &mut message).unwrap();
+        responder.read_message(&message[..len], &mut payload).unwrap();
+        let len = responder.write_message(&[], &mut message).unwrap();
+        initiator.read_message(&message[..len], &mut payload).unwrap();
+        let len = initiator.write_message(&[], &mut message).unwrap();
+        responder.read_message(&message[..len], &mut payload).unwrap();
+        let session = |state: HandshakeState| NoiseSession {
+            transport: Arc::new(Mutex::new(state.into_transport_mode().unwrap())),
+        };
+        (session(initiator), session(responder))
+    }
+
+    #[test]
+    fn test_session_roundtrip() {
+        let (initiator, responder) = session_pair();
+        for len in &[0, 1, NOISE_MAX_PLAINTEXT_LEN, NOISE_MAX_PLAINTEXT_LEN + 1, 200_000] {
+            let plaintext: Vec<u8> = (0..*len).map(|i| i as u8).collect();
+            let ciphertext = initiator.encrypt(&plaintext).unwrap();
+            assert_ne!(ciphertext, plaintext);
+            assert_eq!(responder.decrypt(&ciphertext).unwrap(), plaintext);
+            let ciphertext = responder.encrypt(&plaintext).unwrap();
+            assert_eq!(initiator.decrypt(&ciphertext).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_session_rejects_tampering() {
+        let (initiator, responder) = session_pair();
+        let mut ciphertext = initiator.encrypt(b"approval").unwrap();
+        ciphertext[0] ^= 1;
+        assert!(responder.decrypt(&ciphertext).is_err());
+    }
+
+    #[test]
+    fn test_identity() {
+        let secret_key = SecretKey::from_random(near_crypto::KeyType::ED25519);
+        let peer_id = PeerId::from(secret_key.public_key());
+        let identity = NoiseIdentity::new(peer_id, &secret_key, b"static key");
+        assert!(identity.verify(b"static key"));
+        assert!(!identity.verify(b"other static key"));
+
+        let other_key = SecretKey::from_random(near_crypto::KeyType::ED25519);
+        let forged = NoiseIdentity::new(other_key.public_key().into(), &secret_key, b"static key");
+        assert!(!forged.verify(b"static key"));
+    }
+}
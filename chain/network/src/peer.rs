@@ -1,16 +1,18 @@
 use std::cmp::max;
 use std::io;
 use std::net::SocketAddr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use actix::io::{FramedWrite, WriteHandler};
 use actix::{
     Actor, ActorContext, ActorFuture, Addr, AsyncContext, Context, ContextFutureSpawner, Handler,
     Recipient, Running, StreamHandler, WrapFuture,
 };
+use futures::Future;
 use log::{debug, error, info, warn};
 use tokio::io::WriteHalf;
 use tokio::net::TcpStream;
+use tokio::timer::Delay;
 
 use near_metrics;
 use near_primitives::block::GenesisId;
@@ -18,8 +20,12 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::unwrap_option_or_return;
 use near_primitives::utils::DisplayOption;
 
-use crate::codec::{bytes_to_peer_message, peer_message_to_bytes, Codec};
+use crate::codec::{bytes_to_peer_message, Codec, Compression, OutboundMessage};
+use crate::discovery::DhtNodes;
+use crate::peer_store::ReputationEvent;
 use crate::rate_counter::RateCounter;
+use crate::rate_limiter::{PeerRateLimiter, RateLimitAction, RateLimits};
+use crate::recorder::{Direction, MessageRecorder};
 use crate::routing::{Edge, EdgeInfo};
 use crate::types::{
     Ban, Consolidate, ConsolidateResponse, Handshake, HandshakeFailureReason,
@@ -83,6 +89,8 @@ pub struct Tracker {
     requested: CircularUniqueQueue,
     /// Received elements.
     received: CircularUniqueQueue,
+    /// Sent header requests that the peer hasn't answered yet.
+    pending_header_requests: usize,
 }
 
 impl Default for Tracker {
@@ -92,6 +100,7 @@ impl Default for Tracker {
             received_bytes: RateCounter::new(),
             requested: CircularUniqueQueue::new(MAX_TRACK_SIZE),
             received: CircularUniqueQueue::new(MAX_TRACK_SIZE),
+            pending_header_requests: 0,
         }
     }
 }
@@ -120,6 +129,19 @@ impl Tracker {
     fn push_request(&mut self, hash: CryptoHash) {
         self.requested.push(hash);
     }
+
+    fn push_header_request(&mut self) {
+        self.pending_header_requests += 1;
+    }
+
+    /// Returns whether the received headers answer a header request.
+    fn pop_header_request(&mut self) -> bool {
+        if self.pending_header_requests == 0 {
+            return false;
+        }
+        self.pending_header_requests -= 1;
+        true
+    }
 }
 
 pub struct Peer {
@@ -127,6 +149,8 @@ pub struct Peer {
     pub node_info: PeerInfo,
     /// Peer address from connection.
     pub peer_addr: SocketAddr,
+    /// Peer id authenticated by the Noise handshake.
+    remote_peer_id: PeerId,
     /// Peer id and info. Present if outbound or ready.
     pub peer_info: DisplayOption<PeerInfo>,
     /// Peer type.
@@ -135,6 +159,12 @@ pub struct Peer {
     pub peer_status: PeerStatus,
     /// Framed wrapper to send messages through the TCP connection.
     framed: FramedWrite<WriteHalf<TcpStream>, Codec>,
+    /// Compression settings shared with the codecs of the connection.
+    compression: Compression,
+    /// Limits on the messages received from the peer.
+    rate_limiter: PeerRateLimiter,
+    /// Records the messages exchanged with the peer, if enabled.
+    recorder: Option<MessageRecorder>,
     /// Handshake timeout.
     handshake_timeout: Duration,
     /// Peer manager recipient to break the dependency loop.
@@ -157,9 +187,13 @@ impl Peer {
     pub fn new(
         node_info: PeerInfo,
         peer_addr: SocketAddr,
+        remote_peer_id: PeerId,
         peer_info: Option<PeerInfo>,
         peer_type: PeerType,
         framed: FramedWrite<WriteHalf<TcpStream>, Codec>,
+        compression: Compression,
+        rate_limits: RateLimits,
+        recorder: Option<MessageRecorder>,
         handshake_timeout: Duration,
         peer_manager_addr: Addr<PeerManagerActor>,
         client_addr: Recipient<NetworkClientMessages>,
@@ -169,10 +203,14 @@ impl Peer {
         Peer {
             node_info,
             peer_addr,
+            remote_peer_id,
             peer_info: peer_info.into(),
             peer_type,
             peer_status: PeerStatus::Connecting,
             framed,
+            compression,
+            rate_limiter: PeerRateLimiter::new(rate_limits),
+            recorder,
             handshake_timeout,
             peer_manager_addr,
             client_addr,
@@ -197,12 +235,16 @@ impl Peer {
             PeerMessage::Block(b) if self.tracker.has_received(b.hash()) => return,
             PeerMessage::BlockHeaderAnnounce(h) if self.tracker.has_received(h.hash()) => return,
             PeerMessage::BlockRequest(h) => self.tracker.push_request(*h),
+            PeerMessage::BlockHeadersRequest(_) => self.tracker.push_header_request(),
             _ => (),
         };
-        match peer_message_to_bytes(msg) {
-            Ok(bytes) => {
-                self.tracker.increment_sent(bytes.len() as u64);
-                self.framed.write(bytes);
+        match OutboundMessage::new(&msg) {
+            Ok(msg) => {
+                if let Some(recorder) = &self.recorder {
+                    recorder.record(&self.remote_peer_id, Direction::Outbound, &msg.bytes);
+                }
+                self.tracker.increment_sent(msg.bytes.len() as u64);
+                self.framed.write(msg);
             }
             Err(err) => error!(target: "network", "Error converting message to bytes: {}", err),
         };
@@ -240,6 +282,7 @@ impl Peer {
                         act.node_info.addr_port(),
                         PeerChainInfo { genesis_id, height, weight_and_score, tracked_shards },
                         act.edge_info.as_ref().unwrap().clone(),
+                        act.compression.is_enabled(),
                     );
                     act.send_message(PeerMessage::Handshake(handshake));
                     actix::fut::ok(())
@@ -259,6 +302,11 @@ impl Peer {
         ctx.stop();
     }
 
+    fn update_reputation(&self, event: ReputationEvent) {
+        self.peer_manager_addr
+            .do_send(PeerRequest::UpdateReputation(self.remote_peer_id.clone(), event));
+    }
+
     fn peer_id(&self) -> Option<PeerId> {
         self.peer_info.as_ref().as_ref().map(|peer_info| peer_info.id.clone())
     }
@@ -272,40 +320,18 @@ impl Peer {
     }
 
     fn receive_view_client_message(&mut self, ctx: &mut Context<Peer>, msg: PeerMessage) {
-        let msg_hash;
-        let view_client_message = match msg {
-            PeerMessage::Routed(message) => {
-                msg_hash = message.hash();
-                match message.body {
-                    RoutedMessageBody::QueryRequest { path, data, id } => {
-                        NetworkViewClientMessages::Query { path, data, id }
-                    }
-                    RoutedMessageBody::QueryResponse { response, id } => {
-                        NetworkViewClientMessages::QueryResponse { response, id }
-                    }
-                    RoutedMessageBody::TxStatusRequest(account_id, tx_hash) => {
-                        NetworkViewClientMessages::TxStatus {
-                            tx_hash,
-                            signer_account_id: account_id,
-                        }
-                    }
-                    RoutedMessageBody::TxStatusResponse(tx_result) => {
-                        NetworkViewClientMessages::TxStatusResponse(tx_result)
-                    }
-                    RoutedMessageBody::ReceiptOutcomeRequest(receipt_id) => {
-                        NetworkViewClientMessages::ReceiptOutcomeRequest(receipt_id)
-                    }
-                    RoutedMessageBody::ReceiptOutComeResponse(response) => {
-                        NetworkViewClientMessages::ReceiptOutcomeResponse(response)
-                    }
-                    body => {
-                        error!(target: "network", "Peer receive_view_client_message received unexpected type: {:?}", body);
-                        return;
-                    }
-                }
+        let msg_type = msg.msg_type();
+        let msg_hash = match &msg {
+            PeerMessage::Routed(message) => message.hash(),
+            _ => {
+                error!(target: "network", "Peer receive_view_client_message received unexpected type: {}", msg_type);
+                return;
             }
-            peer_message => {
-                error!(target: "network", "Peer receive_view_client_message received unexpected type: {:?}", peer_message);
+        };
+        let view_client_message = match msg.into_view_client_message() {
+            Some(view_client_message) => view_client_message,
+            None => {
+                error!(target: "network", "Peer receive_view_client_message received unexpected type: {}", msg_type);
                 return;
             }
         };
@@ -347,9 +373,7 @@ impl Peer {
     fn receive_client_message(&mut self, ctx: &mut Context<Peer>, msg: PeerMessage) {
         near_metrics::inc_counter(&metrics::PEER_CLIENT_MESSAGE_RECEIVED_TOTAL);
         let peer_id = unwrap_option_or_return!(self.peer_id());
-
-        // Wrap peer message into what client expects.
-        let network_client_msg = match msg {
+        let was_requested = match &msg {
             PeerMessage::Block(block) => {
                 near_metrics::inc_counter(&metrics::PEER_BLOCK_RECEIVED_TOTAL);
                 let block_hash = block.hash();
@@ -360,80 +384,29 @@ impl Peer {
                     self.chain_info.weight_and_score,
                     block.header.inner_rest.weight_and_score(),
                 );
-                NetworkClientMessages::Block(block, peer_id, self.tracker.has_request(block_hash))
+                self.tracker.has_request(block_hash)
             }
+            PeerMessage::BlockHeaders(_) => self.tracker.pop_header_request(),
             PeerMessage::BlockHeaderAnnounce(header) => {
-                let block_hash = header.hash();
-                self.tracker.push_received(block_hash);
+                self.tracker.push_received(header.hash());
                 self.chain_info.height = max(self.chain_info.height, header.inner_lite.height);
                 self.chain_info.weight_and_score =
                     max(self.chain_info.weight_and_score, header.inner_rest.weight_and_score());
-                NetworkClientMessages::BlockHeader(header, peer_id)
+                false
             }
-            PeerMessage::Transaction(transaction) => {
+            PeerMessage::Transaction(_) => {
                 near_metrics::inc_counter(&metrics::PEER_TRANSACTION_RECEIVED_TOTAL);
-                NetworkClientMessages::Transaction(transaction)
-            }
-            PeerMessage::BlockRequest(hash) => NetworkClientMessages::BlockRequest(hash),
-            PeerMessage::BlockHeadersRequest(hashes) => {
-                NetworkClientMessages::BlockHeadersRequest(hashes)
+                false
             }
-            PeerMessage::BlockHeaders(headers) => {
-                NetworkClientMessages::BlockHeaders(headers, peer_id)
-            }
-            // All Routed messages received at this point are for us.
-            PeerMessage::Routed(routed_message) => {
-                let msg_hash = routed_message.hash();
+            _ => false,
+        };
 
-                match routed_message.body {
-                    RoutedMessageBody::BlockApproval(approval) => {
-                        NetworkClientMessages::BlockApproval(approval, peer_id)
-                    }
-                    RoutedMessageBody::ForwardTx(transaction) => {
-                        NetworkClientMessages::Transaction(transaction)
-                    }
-                    RoutedMessageBody::StateRequest(shard_id, hash, need_header, parts) => {
-                        NetworkClientMessages::StateRequest(
-                            shard_id,
-                            hash,
-                            need_header,
-                            parts,
-                            msg_hash,
-                        )
-                    }
-                    RoutedMessageBody::StateResponse(info) => {
-                        NetworkClientMessages::StateResponse(info)
-                    }
-                    RoutedMessageBody::PartialEncodedChunkRequest(request) => {
-                        NetworkClientMessages::PartialEncodedChunkRequest(request, msg_hash)
-                    }
-                    RoutedMessageBody::PartialEncodedChunk(partial_encoded_chunk) => {
-                        NetworkClientMessages::PartialEncodedChunk(partial_encoded_chunk)
-                    }
-                    RoutedMessageBody::Ping(_)
-                    | RoutedMessageBody::Pong(_)
-                    | RoutedMessageBody::TxStatusRequest(_, _)
-                    | RoutedMessageBody::TxStatusResponse(_)
-                    | RoutedMessageBody::QueryRequest { .. }
-                    | RoutedMessageBody::QueryResponse { .. }
-                    | RoutedMessageBody::ReceiptOutcomeRequest(_)
-                    | RoutedMessageBody::ReceiptOutComeResponse(_) => {
-                        error!(target: "network", "Peer receive_client_message received unexpected type");
-                        return;
-                    }
-                }
-            }
-            PeerMessage::Challenge(challenge) => NetworkClientMessages::Challenge(challenge),
-            PeerMessage::Handshake(_)
-            | PeerMessage::HandshakeFailure(_, _)
-            | PeerMessage::PeersRequest
-            | PeerMessage::PeersResponse(_)
-            | PeerMessage::Sync(_)
-            | PeerMessage::LastEdge(_)
-            | PeerMessage::Disconnect
-            | PeerMessage::RequestUpdateNonce(_)
-            | PeerMessage::ResponseUpdateNonce(_) => {
-                error!(target: "network", "Peer receive_client_message received unexpected type");
+        // Wrap peer message into what client expects.
+        let msg_type = msg.msg_type();
+        let network_client_msg = match msg.into_client_message(peer_id, was_requested) {
+            Some(network_client_msg) => network_client_msg,
+            None => {
+                error!(target: "network", "Peer receive_client_message received unexpected type: {}", msg_type);
                 return;
             }
         };
@@ -449,8 +422,15 @@ impl Peer {
                         // TODO: count as malicious behaviour?
                     }
                     Ok(NetworkClientResponses::Ban { ban_reason }) => {
+                        act.update_reputation(ReputationEvent::InvalidData);
                         act.ban_peer(ctx, ban_reason);
                     }
+                    // Only the data we asked this peer for improves its reputation, once the client
+                    // accepts it. Unsolicited and routed data may be duplicates or come from other
+                    // peers.
+                    Ok(NetworkClientResponses::NoResponse) if was_requested => {
+                        act.update_reputation(ReputationEvent::UsefulResponse)
+                    }
                     Ok(NetworkClientResponses::Block(block)) => {
                         act.send_message(PeerMessage::Block(block))
                     }
@@ -526,11 +506,34 @@ impl StreamHandler<Vec<u8>, io::Error> for Peer {
             Ok(peer_msg) => peer_msg,
             Err(err) => {
                 error!(target: "network", "Received invalid data {:?} from {}: {}", msg, self.peer_info, err);
+                self.update_reputation(ReputationEvent::InvalidData);
                 return;
             }
         };
 
         peer_msg.record(msg.len());
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&self.remote_peer_id, Direction::Inbound, &msg);
+        }
+
+        match self.rate_limiter.check(&peer_msg) {
+            RateLimitAction::Accept => {}
+            RateLimitAction::Drop => {
+                near_metrics::inc_counter(&metrics::PEER_MESSAGE_RATE_LIMITED_TOTAL);
+                debug!(target: "network", "Dropping {} from {}: rate limit exceeded", peer_msg, self.peer_info);
+                return;
+            }
+            RateLimitAction::Throttle(duration) => {
+                near_metrics::inc_counter(&metrics::PEER_MESSAGE_RATE_LIMITED_TOTAL);
+                info!(target: "network", "Throttling {} for {:?}: rate limit exceeded by {}", self.peer_info, duration, peer_msg);
+                ctx.wait(Delay::new(Instant::now() + duration).map_err(|_| ()).into_actor(self));
+                return;
+            }
+            RateLimitAction::Ban => {
+                self.ban_peer(ctx, ReasonForBan::Abusive);
+                return;
+            }
+        }
 
         match (self.peer_type, self.peer_status, peer_msg) {
             (_, PeerStatus::Connecting, PeerMessage::HandshakeFailure(peer_info, reason)) => {
@@ -571,6 +574,12 @@ impl StreamHandler<Vec<u8>, io::Error> for Peer {
                     // Connection will be closed by a handshake timeout
                 }
 
+                if handshake.peer_id != self.remote_peer_id {
+                    info!(target: "network", "Received handshake with peer id {} different from the authenticated {}. Disconnecting this peer.", handshake.peer_id, self.remote_peer_id);
+                    ctx.stop();
+                    return;
+                }
+
                 if handshake.peer_id == self.node_info.id {
                     warn!(target: "network", "Received info about itself. Disconnecting this peer.");
                     ctx.stop();
@@ -599,6 +608,8 @@ impl StreamHandler<Vec<u8>, io::Error> for Peer {
                     }
                 }
 
+                self.compression.set_peer_accepts(handshake.compression);
+
                 let peer_info = PeerInfo {
                     id: handshake.peer_id.clone(),
                     addr: handshake
@@ -695,6 +706,28 @@ impl StreamHandler<Vec<u8>, io::Error> for Peer {
                 debug!(target: "network", "Received peers from {}: {} peers.", self.peer_info, peers.len());
                 self.peer_manager_addr.do_send(PeersResponse { peers });
             }
+            (_, PeerStatus::Ready, PeerMessage::DhtFindNode(target)) => {
+                self.peer_manager_addr
+                    .send(PeerRequest::DhtFindNode(target.clone()))
+                    .into_actor(self)
+                    .then(move |res, act, _ctx| {
+                        if let Ok(PeerResponse::DhtRecords(records)) = res {
+                            act.send_message(PeerMessage::DhtNodes(DhtNodes {
+                                target,
+                                records,
+                                observed_addr: Some(act.peer_addr),
+                            }));
+                        }
+                        actix::fut::ok(())
+                    })
+                    .spawn(ctx);
+            }
+            (_, PeerStatus::Ready, PeerMessage::DhtNodes(nodes)) => {
+                debug!(target: "network", "Received {} peer records from {}", nodes.records.len(), self.peer_info);
+                if let Some(peer_id) = self.peer_id() {
+                    self.peer_manager_addr.do_send(PeerRequest::DhtNodes(peer_id, nodes));
+                }
+            }
             (_, PeerStatus::Ready, PeerMessage::RequestUpdateNonce(edge_info)) => self
                 .peer_manager_addr
                 .send(NetworkRequests::RequestUpdateNonce(self.peer_id().unwrap(), edge_info))
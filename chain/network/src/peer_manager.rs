@@ -1,7 +1,7 @@
 use std::cmp;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use actix::actors::resolver::{ConnectAddr, Resolver};
 use actix::io::FramedWrite;
@@ -18,16 +18,21 @@ use rand::{thread_rng, Rng};
 use tokio::codec::FramedRead;
 use tokio::io::AsyncRead;
 use tokio::net::{TcpListener, TcpStream};
+use tokio::timer::Timeout;
 
+use near_crypto::{KeyType, SecretKey};
 use near_primitives::hash::CryptoHash;
 use near_primitives::types::AccountId;
-use near_primitives::utils::from_timestamp;
+use near_primitives::utils::{from_timestamp, to_timestamp, DisplayOption};
 use near_store::Store;
 
-use crate::codec::Codec;
+use crate::codec::{Codec, Compression};
+use crate::discovery::{DhtNodes, Discovery, PeerRecord, BUCKET_SIZE};
 use crate::metrics;
+use crate::noise::{self, NoiseSession};
 use crate::peer::Peer;
-use crate::peer_store::PeerStore;
+use crate::peer_store::{PeerStore, ReputationEvent};
+use crate::recorder::MessageRecorder;
 use crate::routing::{Edge, EdgeInfo, EdgeType, ProcessEdgeResult, RoutingTable};
 use crate::types::{
     AccountOrPeerIdOrHash, AnnounceAccount, Ban, Consolidate, ConsolidateResponse, FullPeerInfo,
@@ -50,6 +55,10 @@ const WAIT_ON_TRY_UPDATE_NONCE: u64 = 6_000;
 /// If we see an edge between us and other peer, but this peer is not a current connection, wait this
 /// timeout and in case it didn't become an active peer, broadcast edge removal update.
 const WAIT_PEER_BEFORE_REMOVE: u64 = 6_000;
+/// How long to wait for a pong before lowering the reputation of the peer.
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+/// Peers with lower reputation are only used for sync if there are no other peers.
+const MIN_SYNC_PEER_REPUTATION: i64 = -100;
 
 macro_rules! unwrap_or_error(($obj: expr, $error: expr) => (match $obj {
     Ok(result) => result,
@@ -93,6 +102,12 @@ pub struct PeerManagerActor {
     monitor_peers_attempts: u64,
     /// Active peers we have sent new edge update, but we haven't received response so far.
     pending_update_nonce_request: HashMap<PeerId, u64>,
+    /// Pings we have sent by nonce, with the target and the time to measure latency.
+    pending_pings: HashMap<usize, (PeerId, Instant)>,
+    /// Kademlia-style table of signed peer records.
+    discovery: Discovery,
+    /// Records the messages exchanged with all peers, if enabled in the config.
+    recorder: Option<MessageRecorder>,
 }
 
 impl PeerManagerActor {
@@ -105,7 +120,22 @@ impl PeerManagerActor {
         let peer_store = PeerStore::new(store.clone(), &config.boot_nodes)?;
         debug!(target: "network", "Found known peers: {} (boot nodes={})", peer_store.len(), config.boot_nodes.len());
 
-        let me = config.public_key.clone().into();
+        let recorder = match &config.recording_path {
+            Some(path) => {
+                info!(target: "network", "Recording network messages to {}", path.display());
+                Some(MessageRecorder::new(path)?)
+            }
+            None => None,
+        };
+
+        let me: PeerId = config.public_key.clone().into();
+        let discovery = Discovery::new(
+            me.clone(),
+            config.secret_key.clone(),
+            config.account_id.clone(),
+            config.external_address.or(config.addr),
+            config.dht_record_ttl,
+        );
         Ok(PeerManagerActor {
             peer_id: config.public_key.clone().into(),
             config,
@@ -117,6 +147,9 @@ impl PeerManagerActor {
             routing_table: RoutingTable::new(me, store),
             monitor_peers_attempts: 0,
             pending_update_nonce_request: HashMap::new(),
+            pending_pings: HashMap::new(),
+            discovery,
+            recorder,
         })
     }
 
@@ -185,8 +218,15 @@ impl PeerManagerActor {
                 }),
             });
 
-            // Ask for peers list on connection.
+            // Ask for peers list on connection, and for the peers closest to us to fill the
+            // discovery table.
             let _ = addr.do_send(SendMessage { message: PeerMessage::PeersRequest });
+            let _ = addr
+                .do_send(SendMessage { message: PeerMessage::DhtFindNode(act.peer_id.clone()) });
+            // Continue the lookups that connected to the peer to ask it.
+            for target in act.discovery.lookups_querying(&target_peer_id) {
+                let _ = addr.do_send(SendMessage { message: PeerMessage::DhtFindNode(target) });
+            }
             if let Some(active_peer) = act.active_peers.get_mut(&target_peer_id) {
                 active_peer.last_time_peer_requested = Utc::now();
             }
@@ -239,10 +279,56 @@ impl PeerManagerActor {
     }
 
     /// Connects peer with given TcpStream and optional information if it's outbound.
+    /// Messages are only exchanged after the Noise handshake, which authenticates the id of the
+    /// peer and must match the expected id for outbound connections.
     fn connect_peer(
+        &mut self,
+        ctx: &mut Context<Self>,
+        stream: TcpStream,
+        peer_type: PeerType,
+        peer_info: Option<PeerInfo>,
+        edge_info: Option<EdgeInfo>,
+    ) {
+        let expected_peer_id = peer_info.as_ref().map(|peer_info| peer_info.id.clone());
+        let handshake = noise::handshake(
+            stream,
+            peer_type,
+            self.peer_id.clone(),
+            self.config.secret_key.clone(),
+            expected_peer_id,
+        );
+        Timeout::new(handshake, self.config.handshake_timeout)
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                match res {
+                    Ok((stream, remote_peer_id, session)) => act.create_peer(
+                        ctx.address(),
+                        stream,
+                        remote_peer_id,
+                        session,
+                        peer_type,
+                        peer_info,
+                        edge_info,
+                    ),
+                    Err(err) => {
+                        info!(target: "network", "Noise handshake with {} failed: {:?}", DisplayOption(peer_info.clone()), err);
+                        if let Some(peer_info) = peer_info {
+                            act.outgoing_peers.remove(&peer_info.id);
+                        }
+                    }
+                }
+                actix::fut::ok(())
+            })
+            .spawn(ctx);
+    }
+
+    /// Starts the actor of the peer with the encrypted stream.
+    fn create_peer(
         &mut self,
         recipient: Addr<Self>,
         stream: TcpStream,
+        remote_peer_id: PeerId,
+        session: NoiseSession,
         peer_type: PeerType,
         peer_info: Option<PeerInfo>,
         edge_info: Option<EdgeInfo>,
@@ -253,6 +339,9 @@ impl PeerManagerActor {
         let handshake_timeout = self.config.handshake_timeout;
         let client_addr = self.client_addr.clone();
         let view_client_addr = self.view_client_addr.clone();
+        let compression = Compression::new(self.config.compression_threshold);
+        let rate_limits = self.config.rate_limits.clone();
+        let recorder = self.recorder.clone();
         Peer::create(move |ctx| {
             let server_addr = server_addr.unwrap_or_else(|| stream.local_addr().unwrap());
             let remote_addr = stream.peer_addr().unwrap();
@@ -260,13 +349,20 @@ impl PeerManagerActor {
 
             // TODO: check if peer is banned or known based on IP address and port.
 
-            Peer::add_stream(FramedRead::new(read, Codec::new()), ctx);
+            Peer::add_stream(
+                FramedRead::new(read, Codec::with_session(session.clone(), compression.clone())),
+                ctx,
+            );
             Peer::new(
                 PeerInfo { id: peer_id, addr: Some(server_addr), account_id },
                 remote_addr,
+                remote_peer_id,
                 peer_info,
                 peer_type,
-                FramedWrite::new(write, Codec::new(), ctx),
+                FramedWrite::new(write, Codec::with_session(session, compression.clone()), ctx),
+                compression,
+                rate_limits,
+                recorder,
                 handshake_timeout,
                 recipient,
                 client_addr,
@@ -299,7 +395,8 @@ impl PeerManagerActor {
             None => return vec![],
         };
         // Find all peers whose height is within `most_weighted_peer_height_horizon` from max weight peer(s).
-        self.active_peers
+        let peers = self
+            .active_peers
             .values()
             .filter_map(|active_peer| {
                 if max_weight_and_score.beyond_threshold(
@@ -311,7 +408,20 @@ impl PeerManagerActor {
                     Some(active_peer.full_peer_info.clone())
                 }
             })
-            .collect::<Vec<_>>()
+            .collect::<Vec<_>>();
+        // Sync from peers with low reputation only if there are no others.
+        let reputable_peers = peers
+            .iter()
+            .filter(|peer| {
+                self.peer_store.reputation(&peer.peer_info.id) >= MIN_SYNC_PEER_REPUTATION
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if reputable_peers.is_empty() {
+            peers
+        } else {
+            reputable_peers
+        }
     }
 
     /// Returns bytes sent/received across all peers.
@@ -321,16 +431,10 @@ impl PeerManagerActor {
         (sent_bps, received_bps)
     }
 
-    /// Get a random peer we are not connected to from the known list.
+    /// Get a random peer we are not connected to from the known list, preferring peers with
+    /// higher reputation.
     fn sample_random_peer(&self, ignore_list: &HashSet<PeerId>) -> Option<PeerInfo> {
-        let unconnected_peers = self.peer_store.unconnected_peers(ignore_list);
-        let index = thread_rng().gen_range(0, std::cmp::max(unconnected_peers.len(), 1));
-
-        unconnected_peers
-            .iter()
-            .enumerate()
-            .filter_map(|(i, v)| if i == index { Some(v.clone()) } else { None })
-            .next()
+        self.peer_store.sample_unconnected_peer(ignore_list)
     }
 
     /// Query current peers for more peers.
@@ -352,6 +456,84 @@ impl PeerManagerActor {
             .spawn(ctx);
     }
 
+    /// Periodically looks up a random id in the discovery table, to learn about peers in all
+    /// parts of the network.
+    fn dht_lookup(&mut self, ctx: &mut Context<Self>) {
+        self.discovery.remove_expired(to_timestamp(Utc::now()));
+        let target: PeerId = SecretKey::from_random(KeyType::ED25519).public_key().into();
+        self.dht_find_node(ctx, target);
+
+        ctx.run_later(self.config.dht_lookup_period, move |act, ctx| {
+            act.dht_lookup(ctx);
+        });
+    }
+
+    /// Starts a lookup of the target by asking the active peers closest to it for the records
+    /// they know near the target. Peers found this way are added to the peer store, so we connect
+    /// to them once there is room.
+    fn dht_find_node(&mut self, ctx: &mut Context<Self>, target: PeerId) {
+        let active_peers = self.active_peers.keys().cloned().collect();
+        let peers =
+            self.discovery.start_lookup(target.clone(), active_peers, to_timestamp(Utc::now()));
+        for peer_id in peers {
+            self.send_dht_find_node(ctx, &peer_id, &target);
+        }
+    }
+
+    /// Asks the peer for the records near the target. Peers we are not connected to are asked
+    /// once connected, if there is room to connect to them.
+    fn send_dht_find_node(&mut self, ctx: &mut Context<Self>, peer_id: &PeerId, target: &PeerId) {
+        if let Some(active_peer) = self.active_peers.get(peer_id) {
+            active_peer
+                .addr
+                .do_send(SendMessage { message: PeerMessage::DhtFindNode(target.clone()) });
+        } else if !self.outgoing_peers.contains(peer_id) && self.is_outbound_bootstrap_needed() {
+            if let Some(peer_state) = self.peer_store.get(peer_id) {
+                if let KnownPeerStatus::Banned(_, _) = peer_state.status {
+                    return;
+                }
+                let peer_info = peer_state.peer_info.clone();
+                self.outgoing_peers.insert(peer_id.clone());
+                ctx.notify(OutboundTcpConnect { peer_info });
+            }
+        }
+    }
+
+    fn dht_records(&mut self, target: &PeerId) -> Vec<PeerRecord> {
+        let mut records = self.discovery.closest(target, BUCKET_SIZE);
+        if let Some(own_record) = self.discovery.own_record(to_timestamp(Utc::now())) {
+            records.push(own_record);
+        }
+        records
+    }
+
+    fn add_dht_nodes(&mut self, ctx: &mut Context<Self>, peer_id: PeerId, nodes: DhtNodes) {
+        if let Some(addr) = nodes.observed_addr {
+            self.discovery.observe_ip(&peer_id, addr.ip());
+        }
+        let now = to_timestamp(Utc::now());
+        let mut peers = vec![];
+        for record in nodes.records {
+            let peer_info = record.peer_info.clone();
+            if self.discovery.insert(record, now) {
+                peers.push(peer_info);
+            }
+        }
+        debug!(target: "network", "Discovered {} peers near {}", peers.len(), nodes.target);
+        if let Err(err) = self.peer_store.add_signed_peers(peers) {
+            error!(target: "network", "Failed to save discovered peers: {}", err);
+        }
+        if self.discovery.is_queried(&nodes.target, &peer_id) {
+            for record in self.discovery.continue_lookup(&nodes.target) {
+                self.send_dht_find_node(ctx, &record.peer_info.id, &nodes.target);
+            }
+        }
+    }
+
+    pub(crate) fn dht_peers(&self) -> Vec<PeerId> {
+        self.discovery.peer_ids()
+    }
+
     /// Add an edge update to the routing table and return if it is a new edge update.
     fn process_edge(&mut self, ctx: &mut Context<Self>, edge: Edge) -> bool {
         let ProcessEdgeResult { new_edge, schedule_computation } =
@@ -417,8 +599,14 @@ impl PeerManagerActor {
         });
     }
 
-    /// Periodically query peer actors for latest weight and traffic info.
+    /// Periodically query peer actors for latest weight and traffic info, ping them and save the
+    /// changed reputations.
     fn monitor_peer_stats(&mut self, ctx: &mut Context<Self>) {
+        self.expire_pending_pings();
+        self.ping_active_peers(ctx);
+        if let Err(err) = self.peer_store.flush_reputations() {
+            error!(target: "network", "Failed to save peer reputations: {}", err);
+        }
         for (peer_id, active_peer) in self.active_peers.iter() {
             let peer_id1 = peer_id.clone();
             active_peer
@@ -632,6 +820,7 @@ impl PeerManagerActor {
     // Ping pong useful functions.
 
     fn send_ping(&mut self, ctx: &mut Context<Self>, nonce: usize, target: PeerId) {
+        self.pending_pings.insert(nonce, (target.clone(), Instant::now()));
         let body =
             RoutedMessageBody::Ping(Ping { nonce: nonce as u64, source: self.peer_id.clone() });
         let msg = RawRoutedMessage { target: AccountOrPeerIdOrHash::PeerId(target), body };
@@ -651,9 +840,76 @@ impl PeerManagerActor {
     }
 
     fn handle_pong(&mut self, _ctx: &mut Context<Self>, pong: Pong) {
+        if let Some((peer_id, sent)) = self.pending_pings.remove(&(pong.nonce as usize)) {
+            if peer_id == pong.source {
+                self.update_reputation(&peer_id, ReputationEvent::Latency(sent.elapsed()));
+            }
+        }
         self.routing_table.add_pong(pong);
     }
 
+    /// Lowers the reputation of the peers that didn't respond to pings in time.
+    fn expire_pending_pings(&mut self) {
+        let expired = self
+            .pending_pings
+            .iter()
+            .filter(|(_, (_, sent))| sent.elapsed() > PING_TIMEOUT)
+            .map(|(nonce, _)| *nonce)
+            .collect::<Vec<_>>();
+        for nonce in expired {
+            if let Some((peer_id, _)) = self.pending_pings.remove(&nonce) {
+                self.update_reputation(&peer_id, ReputationEvent::Timeout);
+            }
+        }
+    }
+
+    fn update_reputation(&mut self, peer_id: &PeerId, event: ReputationEvent) {
+        let reputation = self.peer_store.update_reputation(peer_id, event);
+        trace!(target: "network", "Reputation of {} is {} after {:?}", peer_id, reputation, event);
+    }
+
+    /// Pings the active peers without a pending ping, to measure their latency.
+    fn ping_active_peers(&mut self, ctx: &mut Context<Self>) {
+        let pinged =
+            self.pending_pings.values().map(|(peer_id, _)| peer_id).collect::<HashSet<_>>();
+        let targets = self
+            .active_peers
+            .keys()
+            .filter(|peer_id| !pinged.contains(peer_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        for target in targets {
+            // Random nonces don't collide with the ones of the pings requested by `PingTo`.
+            self.send_ping(ctx, thread_rng().gen(), target);
+        }
+    }
+
+    /// Disconnects the active peer with the lowest reputation to make room for the given peer,
+    /// if its reputation is lower. Returns whether there is room for the peer.
+    fn evict_peer_for(&mut self, peer_id: &PeerId) -> bool {
+        if self.active_peers.len() < self.config.peer_max_count as usize {
+            return true;
+        }
+        let reputation = self.peer_store.reputation(peer_id);
+        let worst_peer = self
+            .active_peers
+            .iter()
+            .map(|(active_peer_id, active_peer)| {
+                (self.peer_store.reputation(active_peer_id), active_peer_id, active_peer)
+            })
+            .min_by_key(|(active_reputation, _, _)| *active_reputation);
+        match worst_peer {
+            Some((worst_reputation, worst_peer_id, worst_peer))
+                if worst_reputation < reputation =>
+            {
+                debug!(target: "network", "Evicting peer {} with reputation {} for {} with {}", worst_peer_id, worst_reputation, peer_id, reputation);
+                worst_peer.addr.do_send(PeerManagerRequest::UnregisterPeer);
+                true
+            }
+            _ => false,
+        }
+    }
+
     pub(crate) fn get_network_info(&mut self) -> NetworkInfo {
         let (sent_bytes_per_sec, received_bytes_per_sec) = self.get_total_bytes_per_sec();
         NetworkInfo {
@@ -668,6 +924,7 @@ impl PeerManagerActor {
             sent_bytes_per_sec,
             received_bytes_per_sec,
             known_producers: self.routing_table.get_accounts_keys(),
+            peer_reputations: self.peer_store.reputations(self.active_peers.keys()),
         }
     }
 
@@ -701,10 +958,16 @@ impl Actor for PeerManagerActor {
 
         // Start active peer stats querying.
         self.monitor_peer_stats(ctx);
+
+        // Start discovery lookups.
+        self.dht_lookup(ctx);
     }
 
     /// Try to gracefully disconnect from active peers.
     fn stopping(&mut self, _: &mut Self::Context) -> Running {
+        if let Err(err) = self.peer_store.flush_reputations() {
+            error!(target: "network", "Failed to save peer reputations: {}", err);
+        }
         let msg = SendMessage { message: PeerMessage::Disconnect };
 
         for (_, active_peer) in self.active_peers.iter() {
@@ -1024,7 +1287,7 @@ impl Handler<InboundTcpConnect> for PeerManagerActor {
     type Result = ();
 
     fn handle(&mut self, msg: InboundTcpConnect, ctx: &mut Self::Context) {
-        self.connect_peer(ctx.address(), msg.stream, PeerType::Inbound, None, None);
+        self.connect_peer(ctx, msg.stream, PeerType::Inbound, None, None);
     }
 }
 
@@ -1043,7 +1306,7 @@ impl Handler<OutboundTcpConnect> for PeerManagerActor {
                             let edge_info = act.propose_edge(msg.peer_info.id.clone(), None);
 
                             act.connect_peer(
-                                ctx.address(),
+                                ctx,
                                 stream,
                                 PeerType::Outbound,
                                 Some(msg.peer_info),
@@ -1104,6 +1367,12 @@ impl Handler<Consolidate> for PeerManagerActor {
             return ConsolidateResponse::InvalidNonce(last_edge.unwrap());
         }
 
+        // Only inbound connections can exceed the limit, outbound ones are made when there is room.
+        if msg.peer_type == PeerType::Inbound && !self.evict_peer_for(&msg.peer_info.id) {
+            debug!(target: "network", "Dropping handshake (Too many peers). {:?} {:?}", self.peer_id, msg.peer_info.id);
+            return ConsolidateResponse::Reject;
+        }
+
         let require_response = msg.this_edge_info.is_none();
 
         let edge_info = msg.this_edge_info.clone().unwrap_or_else(|| {
@@ -1220,6 +1489,15 @@ impl Handler<PeerRequest> for PeerManagerActor {
                 );
                 PeerResponse::NoResponse
             }
+            PeerRequest::UpdateReputation(peer_id, event) => {
+                self.update_reputation(&peer_id, event);
+                PeerResponse::NoResponse
+            }
+            PeerRequest::DhtFindNode(target) => PeerResponse::DhtRecords(self.dht_records(&target)),
+            PeerRequest::DhtNodes(peer_id, nodes) => {
+                self.add_dht_nodes(ctx, peer_id, nodes);
+                PeerResponse::NoResponse
+            }
         }
     }
 }
@@ -1,24 +1,60 @@
 use std::collections::{hash_map::Iter, HashMap, HashSet};
 use std::convert::TryInto;
 use std::sync::Arc;
+use std::time::Duration;
 
-use borsh::BorshSerialize;
+use borsh::{BorshDeserialize, BorshSerialize};
 use chrono::Utc;
 use log::debug;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 
 use near_primitives::utils::to_timestamp;
-use near_store::{ColPeers, Store};
+use near_store::{ColPeerReputations, ColPeers, Store};
 
 use crate::types::{
     FullPeerInfo, KnownPeerState, KnownPeerStatus, NetworkConfig, PeerId, PeerInfo, ReasonForBan,
 };
 
+/// Bounds of the reputation of a peer.
+pub const MIN_REPUTATION: i64 = -1000;
+pub const MAX_REPUTATION: i64 = 1000;
+
+/// Events that change the reputation of a peer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReputationEvent {
+    /// Valid block, block headers, chunk part or state part received from the peer.
+    UsefulResponse,
+    /// Round trip time of a ping to the peer.
+    Latency(Duration),
+    /// The peer didn't respond in time.
+    Timeout,
+    /// The peer sent data that failed validation.
+    InvalidData,
+}
+
+impl ReputationEvent {
+    fn delta(&self) -> i64 {
+        match self {
+            ReputationEvent::UsefulResponse => 1,
+            ReputationEvent::Latency(latency) if *latency < Duration::from_millis(100) => 2,
+            ReputationEvent::Latency(latency) if *latency < Duration::from_millis(500) => 1,
+            ReputationEvent::Latency(latency) if *latency < Duration::from_secs(2) => 0,
+            ReputationEvent::Latency(_) => -1,
+            ReputationEvent::Timeout => -10,
+            ReputationEvent::InvalidData => -100,
+        }
+    }
+}
+
 /// Known peers store, maintaining cache of known peers and connection to storage to save/load them.
 pub struct PeerStore {
     store: Arc<Store>,
     peer_states: HashMap<PeerId, KnownPeerState>,
+    /// Reputation of the peers, peers without one have 0.
+    reputations: HashMap<PeerId, i64>,
+    /// Peers whose reputation changed since the last `flush_reputations`.
+    changed_reputations: HashSet<PeerId>,
 }
 
 impl PeerStore {
@@ -43,7 +79,12 @@ impl PeerStore {
                 peer_states.insert(peer_info.id.clone(), KnownPeerState::new(peer_info.clone()));
             }
         }
-        Ok(PeerStore { store, peer_states })
+        let mut reputations = HashMap::default();
+        for (key, value) in store.iter(ColPeerReputations) {
+            let peer_id: PeerId = Vec::from(key).try_into()?;
+            reputations.insert(peer_id, i64::try_from_slice(&value)?);
+        }
+        Ok(PeerStore { store, peer_states, reputations, changed_reputations: HashSet::new() })
     }
 
     pub fn len(&self) -> usize {
@@ -111,6 +152,51 @@ impl PeerStore {
         }
     }
 
+    pub fn get(&self, peer_id: &PeerId) -> Option<&KnownPeerState> {
+        self.peer_states.get(peer_id)
+    }
+
+    pub fn reputation(&self, peer_id: &PeerId) -> i64 {
+        self.reputations.get(peer_id).cloned().unwrap_or(0)
+    }
+
+    /// Reputation of the given peers.
+    pub fn reputations<'a>(
+        &self,
+        peer_ids: impl Iterator<Item = &'a PeerId>,
+    ) -> HashMap<PeerId, i64> {
+        peer_ids.map(|peer_id| (peer_id.clone(), self.reputation(peer_id))).collect()
+    }
+
+    /// Updates the reputation of the peer in memory, returns the new reputation. It's saved by
+    /// the next `flush_reputations`.
+    pub fn update_reputation(&mut self, peer_id: &PeerId, event: ReputationEvent) -> i64 {
+        let delta = event.delta();
+        let reputation = self.reputations.entry(peer_id.clone()).or_insert(0);
+        if delta == 0 {
+            return *reputation;
+        }
+        *reputation = (*reputation + delta).max(MIN_REPUTATION).min(MAX_REPUTATION);
+        self.changed_reputations.insert(peer_id.clone());
+        *reputation
+    }
+
+    /// Saves the reputations changed since the last flush.
+    pub fn flush_reputations(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.changed_reputations.is_empty() {
+            return Ok(());
+        }
+        let mut store_update = self.store.store_update();
+        for peer_id in self.changed_reputations.iter() {
+            if let Some(reputation) = self.reputations.get(peer_id) {
+                store_update.set_ser(ColPeerReputations, &peer_id.try_to_vec()?, reputation)?;
+            }
+        }
+        store_update.commit()?;
+        self.changed_reputations.clear();
+        Ok(())
+    }
+
     fn find_peers<F>(&self, mut filter: F, count: u32) -> Vec<PeerInfo>
     where
         F: FnMut(&KnownPeerState) -> bool,
@@ -138,6 +224,16 @@ impl PeerStore {
         )
     }
 
+    /// Return a random unconnected peer, peers with higher reputation are more likely.
+    pub fn sample_unconnected_peer(&self, ignore_list: &HashSet<PeerId>) -> Option<PeerInfo> {
+        self.unconnected_peers(ignore_list)
+            .choose_weighted(&mut thread_rng(), |peer_info| {
+                self.reputation(&peer_info.id) - MIN_REPUTATION + 1
+            })
+            .ok()
+            .cloned()
+    }
+
     /// Return healthy known peers up to given amount.
     pub fn healthy_peers(&self, max_count: u32) -> Vec<PeerInfo> {
         // TODO: better healthy peer definition here.
@@ -175,7 +271,10 @@ impl PeerStore {
         let mut store_update = self.store.store_update();
         for peer_id in to_remove {
             self.peer_states.remove(&peer_id);
+            self.reputations.remove(&peer_id);
+            self.changed_reputations.remove(&peer_id);
             store_update.delete(ColPeers, &peer_id.try_to_vec()?);
+            store_update.delete(ColPeerReputations, &peer_id.try_to_vec()?);
         }
         store_update.commit().map_err(|err| err.into())
     }
@@ -187,6 +286,32 @@ impl PeerStore {
             }
         }
     }
+
+    /// Adds and saves peers from records signed by the peers themselves. Unlike gossiped peers,
+    /// their addresses replace the known ones, unless we are connected to the peer.
+    pub fn add_signed_peers(
+        &mut self,
+        peers: Vec<PeerInfo>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut store_update = self.store.store_update();
+        for peer_info in peers.into_iter() {
+            let peer_id = peer_info.id.clone();
+            if self
+                .peer_states
+                .get(&peer_id)
+                .map_or(false, |peer_state| peer_state.status == KnownPeerStatus::Connected)
+            {
+                continue;
+            }
+            let peer_state = self
+                .peer_states
+                .entry(peer_id.clone())
+                .or_insert_with(|| KnownPeerState::new(peer_info.clone()));
+            peer_state.peer_info = peer_info;
+            store_update.set_ser(ColPeers, &peer_id.try_to_vec()?, peer_state)?;
+        }
+        store_update.commit().map_err(|err| err.into())
+    }
 }
 
 #[cfg(test)]
@@ -225,4 +350,75 @@ mod test {
             assert_eq!(peer_store_new.healthy_peers(3).iter().count(), 1);
         }
     }
+
+    #[test]
+    fn reputation_store() {
+        let tmp_dir = tempdir::TempDir::new("_test_store_reputation").unwrap();
+        let peer_info = gen_peer_info();
+        let boot_nodes = vec![peer_info.clone()];
+        {
+            let store = create_store(tmp_dir.path().to_str().unwrap());
+            let mut peer_store = PeerStore::new(store, &boot_nodes).unwrap();
+            assert_eq!(peer_store.reputation(&peer_info.id), 0);
+            peer_store.update_reputation(&peer_info.id, ReputationEvent::UsefulResponse);
+            peer_store.update_reputation(
+                &peer_info.id,
+                ReputationEvent::Latency(Duration::from_millis(1)),
+            );
+            assert_eq!(peer_store.reputation(&peer_info.id), 3);
+            peer_store.update_reputation(&peer_info.id, ReputationEvent::Timeout);
+            assert_eq!(peer_store.reputation(&peer_info.id), -7);
+            peer_store.flush_reputations().unwrap();
+            // Not saved until the next flush.
+            peer_store.update_reputation(&peer_info.id, ReputationEvent::UsefulResponse);
+        }
+        {
+            let store_new = create_store(tmp_dir.path().to_str().unwrap());
+            let mut peer_store_new = PeerStore::new(store_new, &boot_nodes).unwrap();
+            assert_eq!(peer_store_new.reputation(&peer_info.id), -7);
+            for _ in 0..20 {
+                peer_store_new.update_reputation(&peer_info.id, ReputationEvent::InvalidData);
+            }
+            assert_eq!(peer_store_new.reputation(&peer_info.id), MIN_REPUTATION);
+        }
+    }
+
+    #[test]
+    fn signed_peers_store() {
+        let tmp_dir = tempdir::TempDir::new("_test_store_signed_peers").unwrap();
+        let mut peer_info = gen_peer_info();
+        {
+            let store = create_store(tmp_dir.path().to_str().unwrap());
+            let mut peer_store = PeerStore::new(store, &[]).unwrap();
+            peer_store.add_signed_peers(vec![peer_info.clone()]).unwrap();
+            peer_info.addr = Some("127.0.0.1:1000".parse().unwrap());
+            peer_store.add_signed_peers(vec![peer_info.clone()]).unwrap();
+        }
+        {
+            let store_new = create_store(tmp_dir.path().to_str().unwrap());
+            let peer_store_new = PeerStore::new(store_new, &[]).unwrap();
+            assert_eq!(peer_store_new.get(&peer_info.id).unwrap().peer_info, peer_info);
+        }
+    }
+
+    #[test]
+    fn sample_by_reputation() {
+        let tmp_dir = tempdir::TempDir::new("_test_store_sample").unwrap();
+        let good_peer = gen_peer_info();
+        let bad_peer = gen_peer_info();
+        let store = create_store(tmp_dir.path().to_str().unwrap());
+        let mut peer_store = PeerStore::new(store, &[good_peer.clone(), bad_peer.clone()]).unwrap();
+        for _ in 0..10 {
+            peer_store.update_reputation(&bad_peer.id, ReputationEvent::InvalidData);
+        }
+        let good_count = (0..100)
+            .filter(|_| peer_store.sample_unconnected_peer(&HashSet::new()).unwrap() == good_peer)
+            .count();
+        // The bad peer has weight 1 against 1001 of the good one.
+        assert!(good_count > 90);
+        assert_eq!(
+            peer_store.sample_unconnected_peer(&vec![good_peer.id.clone()].into_iter().collect()),
+            Some(bad_peer)
+        );
+    }
 }
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::rate_counter::RateCounter;
+use crate::types::{PeerMessage, RoutedMessageBody};
+
+/// Token bucket limit: `burst` messages at once, refilled at `per_second` messages per second.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn new(per_second: f64, burst: u32) -> Self {
+        RateLimit { per_second, burst }
+    }
+}
+
+/// Types of the messages that can be limited. Limits are keyed by them, so that a misspelled type
+/// in the config fails to load instead of silently not limiting anything.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitedMessage {
+    BlockHeadersRequest,
+    BlockRequest,
+    PeersRequest,
+    DhtFindNode,
+    Transaction,
+    ForwardTx,
+    TxStatusRequest,
+    QueryRequest,
+    ReceiptOutcomeRequest,
+    StateRequest,
+    PartialEncodedChunkRequest,
+    Ping,
+}
+
+impl RateLimitedMessage {
+    /// Returns the type of the message, if messages of this type can be limited.
+    pub fn from_peer_message(msg: &PeerMessage) -> Option<Self> {
+        match msg {
+            PeerMessage::BlockHeadersRequest(_) => Some(RateLimitedMessage::BlockHeadersRequest),
+            PeerMessage::BlockRequest(_) => Some(RateLimitedMessage::BlockRequest),
+            PeerMessage::PeersRequest => Some(RateLimitedMessage::PeersRequest),
+            PeerMessage::DhtFindNode(_) => Some(RateLimitedMessage::DhtFindNode),
+            PeerMessage::Transaction(_) => Some(RateLimitedMessage::Transaction),
+            PeerMessage::Routed(routed_message) => match routed_message.body {
+                RoutedMessageBody::ForwardTx(_) => Some(RateLimitedMessage::ForwardTx),
+                RoutedMessageBody::TxStatusRequest(_, _) => {
+                    Some(RateLimitedMessage::TxStatusRequest)
+                }
+                RoutedMessageBody::QueryRequest { .. } => Some(RateLimitedMessage::QueryRequest),
+                RoutedMessageBody::ReceiptOutcomeRequest(_) => {
+                    Some(RateLimitedMessage::ReceiptOutcomeRequest)
+                }
+                RoutedMessageBody::StateRequest(_, _, _, _) => {
+                    Some(RateLimitedMessage::StateRequest)
+                }
+                RoutedMessageBody::PartialEncodedChunkRequest(_) => {
+                    Some(RateLimitedMessage::PartialEncodedChunkRequest)
+                }
+                RoutedMessageBody::Ping(_) => Some(RateLimitedMessage::Ping),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Limits on the messages received from a single peer.
+/// Messages over the limit are dropped. If the peer keeps sending them, reading from it is paused
+/// for a while, and eventually the peer is banned.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RateLimits {
+    /// Limits per message type. Other types are not limited.
+    pub messages: HashMap<RateLimitedMessage, RateLimit>,
+    /// Number of dropped messages per minute after which reading from the peer is paused.
+    pub throttle_violations_per_min: u64,
+    /// How long to pause reading from the peer.
+    pub throttle_duration: Duration,
+    /// Number of dropped messages per minute after which the peer is banned.
+    pub ban_violations_per_min: u64,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        use RateLimitedMessage::*;
+        let messages = vec![
+            (BlockHeadersRequest, RateLimit::new(20.0, 100)),
+            (BlockRequest, RateLimit::new(200.0, 1000)),
+            (PeersRequest, RateLimit::new(1.0, 10)),
+            (DhtFindNode, RateLimit::new(5.0, 50)),
+            (Transaction, RateLimit::new(500.0, 2000)),
+            (ForwardTx, RateLimit::new(500.0, 2000)),
+            (TxStatusRequest, RateLimit::new(100.0, 500)),
+            (QueryRequest, RateLimit::new(100.0, 500)),
+            (ReceiptOutcomeRequest, RateLimit::new(100.0, 500)),
+            (StateRequest, RateLimit::new(20.0, 100)),
+            (PartialEncodedChunkRequest, RateLimit::new(200.0, 1000)),
+            (Ping, RateLimit::new(10.0, 50)),
+        ]
+        .into_iter()
+        .collect();
+        RateLimits {
+            messages,
+            throttle_violations_per_min: 100,
+            throttle_duration: Duration::from_secs(1),
+            ban_violations_per_min: 1000,
+        }
+    }
+}
+
+impl RateLimits {
+    /// Checks that every limit lets some messages through, since a zero limit would drop all the
+    /// messages of its type and get every peer sending them banned.
+    pub fn validate(&self) -> Result<(), String> {
+        for (msg_type, limit) in self.messages.iter() {
+            if !(limit.per_second > 0.0) || limit.burst == 0 {
+                return Err(format!(
+                    "Rate limit of {:?} must be positive, got {:?}",
+                    msg_type, limit
+                ));
+            }
+        }
+        if self.throttle_violations_per_min == 0 || self.ban_violations_per_min == 0 {
+            return Err("Rate limit violation thresholds must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// What to do with a received message.
+#[derive(Debug, PartialEq)]
+pub enum RateLimitAction {
+    Accept,
+    Drop,
+    /// Drop the message and stop reading from the peer for the given duration.
+    Throttle(Duration),
+    Ban,
+}
+
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last_update: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        TokenBucket { limit, tokens: f64::from(limit.burst), last_update: now }
+    }
+
+    fn try_acquire(&mut self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.last_update).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.limit.per_second).min(f64::from(self.limit.burst));
+        self.last_update = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Enforces `RateLimits` on the messages received from a single peer.
+pub struct PeerRateLimiter {
+    limits: RateLimits,
+    buckets: HashMap<RateLimitedMessage, TokenBucket>,
+    /// Messages dropped over the last minute.
+    violations: RateCounter,
+}
+
+impl PeerRateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        PeerRateLimiter { limits, buckets: HashMap::new(), violations: RateCounter::new() }
+    }
+
+    /// Records the received message and returns what to do with it.
+    pub fn check(&mut self, msg: &PeerMessage) -> RateLimitAction {
+        match RateLimitedMessage::from_peer_message(msg) {
+            Some(msg_type) => self.check_at(msg_type, Instant::now()),
+            None => RateLimitAction::Accept,
+        }
+    }
+
+    fn check_at(&mut self, msg_type: RateLimitedMessage, now: Instant) -> RateLimitAction {
+        let limit = match self.limits.messages.get(&msg_type) {
+            Some(limit) => *limit,
+            None => return RateLimitAction::Accept,
+        };
+        let bucket = self.buckets.entry(msg_type).or_insert_with(|| TokenBucket::new(limit, now));
+        if bucket.try_acquire(now) {
+            return RateLimitAction::Accept;
+        }
+        self.violations.increment(1);
+        let violations = self.violations.count_per_min();
+        if violations >= self.limits.ban_violations_per_min {
+            RateLimitAction::Ban
+        } else if violations >= self.limits.throttle_violations_per_min {
+            RateLimitAction::Throttle(self.limits.throttle_duration)
+        } else {
+            RateLimitAction::Drop
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RateLimitedMessage::*;
+
+    fn limiter() -> PeerRateLimiter {
+        let mut messages = HashMap::new();
+        messages.insert(BlockHeadersRequest, RateLimit::new(10.0, 5));
+        PeerRateLimiter::new(RateLimits {
+            messages,
+            throttle_violations_per_min: 3,
+            throttle_duration: Duration::from_secs(1),
+            ban_violations_per_min: 5,
+        })
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+        for _ in 0..5 {
+            assert_eq!(limiter.check_at(BlockHeadersRequest, now), RateLimitAction::Accept);
+        }
+        assert_eq!(limiter.check_at(BlockHeadersRequest, now), RateLimitAction::Drop);
+        // Other types are not limited.
+        for _ in 0..100 {
+            assert_eq!(limiter.check_at(BlockRequest, now), RateLimitAction::Accept);
+        }
+        // Tokens are refilled over time, but not above the burst.
+        let later = now + Duration::from_millis(200);
+        assert_eq!(limiter.check_at(BlockHeadersRequest, later), RateLimitAction::Accept);
+        assert_eq!(limiter.check_at(BlockHeadersRequest, later), RateLimitAction::Accept);
+        assert_eq!(limiter.check_at(BlockHeadersRequest, later), RateLimitAction::Drop);
+        let much_later = now + Duration::from_secs(100);
+        for _ in 0..5 {
+            assert_eq!(limiter.check_at(BlockHeadersRequest, much_later), RateLimitAction::Accept);
+        }
+    }
+
+    #[test]
+    fn test_graduated_response() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+        for _ in 0..5 {
+            limiter.check_at(BlockHeadersRequest, now);
+        }
+        assert_eq!(limiter.check_at(BlockHeadersRequest, now), RateLimitAction::Drop);
+        assert_eq!(limiter.check_at(BlockHeadersRequest, now), RateLimitAction::Drop);
+        assert_eq!(
+            limiter.check_at(BlockHeadersRequest, now),
+            RateLimitAction::Throttle(Duration::from_secs(1))
+        );
+        assert_eq!(
+            limiter.check_at(BlockHeadersRequest, now),
+            RateLimitAction::Throttle(Duration::from_secs(1))
+        );
+        assert_eq!(limiter.check_at(BlockHeadersRequest, now), RateLimitAction::Ban);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(RateLimits::default().validate(), Ok(()));
+        let mut limits = RateLimits::default();
+        limits.messages.insert(Ping, RateLimit::new(0.0, 10));
+        assert!(limits.validate().is_err());
+        limits.messages.insert(Ping, RateLimit::new(10.0, 0));
+        assert!(limits.validate().is_err());
+        let config = r#"{"messages": {"Blocks": {"per_second": 1.0, "burst": 1}},
+            "throttle_violations_per_min": 1, "throttle_duration": {"secs": 1, "nanos": 0},
+            "ban_violations_per_min": 1}"#;
+        assert!(serde_json::from_str::<RateLimits>(config).is_err());
+    }
+}
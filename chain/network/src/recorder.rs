@@ -0,0 +1,116 @@
+//! Recording of the messages exchanged with the peers, to reproduce network issues offline.
+//!
+//! Each record is a little-endian u32 length followed by a borsh encoded `RecordedMessage`.
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use chrono::Utc;
+use log::error;
+
+use near_primitives::utils::to_timestamp;
+
+use crate::types::{PeerId, PeerMessage};
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RecordedMessage {
+    pub timestamp: u64,
+    /// Peer the message was received from or sent to.
+    pub peer_id: PeerId,
+    pub direction: Direction,
+    pub message: PeerMessage,
+}
+
+/// Appends the messages exchanged with all peers to a file. Cloned handles write to the same file.
+#[derive(Clone)]
+pub struct MessageRecorder {
+    writer: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl MessageRecorder {
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(MessageRecorder { writer: Arc::new(Mutex::new(BufWriter::new(file))) })
+    }
+
+    /// Records a message given as its borsh encoding, as received from or sent to the peer.
+    /// Messages are flushed immediately, so the recording is complete if the node crashes.
+    pub fn record(&self, peer_id: &PeerId, direction: Direction, message: &[u8]) {
+        if let Err(err) = self.write(peer_id, direction, message) {
+            error!(target: "network", "Failed to record message: {}", err);
+        }
+    }
+
+    fn write(&self, peer_id: &PeerId, direction: Direction, message: &[u8]) -> io::Result<()> {
+        // Same encoding as `RecordedMessage`, without decoding and encoding the message again.
+        let mut data = to_timestamp(Utc::now()).try_to_vec()?;
+        data.extend(peer_id.try_to_vec()?);
+        data.extend(direction.try_to_vec()?);
+        data.extend_from_slice(message);
+
+        let mut writer = self.writer.lock().expect("Recorder lock poisoned");
+        writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        writer.write_all(&data)?;
+        writer.flush()
+    }
+}
+
+/// Reads all messages of a recording.
+pub fn read_recording(path: &Path) -> io::Result<Vec<RecordedMessage>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut result = vec![];
+    loop {
+        let mut len = [0u8; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(result),
+            Err(err) => return Err(err),
+        }
+        let mut data = vec![0; u32::from_le_bytes(len) as usize];
+        reader.read_exact(&mut data)?;
+        result.push(RecordedMessage::try_from_slice(&data)?);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use near_primitives::hash::hash;
+
+    use super::*;
+    use crate::types::PeerInfo;
+
+    #[test]
+    fn test_record_and_read() {
+        let tmp_dir = TempDir::new("_test_recorder").unwrap();
+        let path = tmp_dir.path().join("recording");
+        let peer_id = PeerId::random();
+        let messages = vec![
+            (Direction::Outbound, PeerMessage::BlockRequest(hash(&[1]))),
+            (Direction::Inbound, PeerMessage::PeersResponse(vec![PeerInfo::random()])),
+            (Direction::Inbound, PeerMessage::Disconnect),
+        ];
+        let recorder = MessageRecorder::new(&path).unwrap();
+        for (direction, message) in messages.iter() {
+            recorder.clone().record(&peer_id, *direction, &message.try_to_vec().unwrap());
+        }
+
+        let recording = read_recording(&path).unwrap();
+        assert_eq!(recording.len(), messages.len());
+        for (recorded, (direction, message)) in recording.iter().zip(messages.iter()) {
+            assert_eq!(recorded.peer_id, peer_id);
+            assert_eq!(recorded.direction, *direction);
+            assert_eq!(&recorded.message, message);
+        }
+        assert!(recording.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+    }
+}
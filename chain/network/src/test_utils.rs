@@ -1,18 +1,25 @@
 use std::collections::{HashMap, HashSet};
-use std::net::TcpListener;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
 use std::time::{Duration, Instant};
 
 use actix::{Actor, AsyncContext, Context, Handler, Message, System};
 use futures::future;
 use futures::future::Future;
+use futures::{Sink, Stream};
 use rand::{thread_rng, RngCore};
+use tokio::codec::Framed;
+use tokio::net::TcpStream;
 use tokio::timer::Delay;
 
 use near_crypto::{KeyType, SecretKey};
 use near_primitives::hash::hash;
 use near_primitives::types::EpochId;
 
-use crate::types::{NetworkConfig, NetworkInfo, PeerId, PeerInfo};
+use crate::codec::{bytes_to_peer_message, Codec, Compression, OutboundMessage};
+use crate::noise;
+use crate::rate_limiter::RateLimits;
+use crate::types::{NetworkConfig, NetworkInfo, PeerId, PeerInfo, PeerMessage, PeerType};
 use crate::PeerManagerActor;
 use near_chain::chain::WEIGHT_MULTIPLIER;
 use near_primitives::utils::index_to_bytes;
@@ -49,6 +56,12 @@ impl NetworkConfig {
             max_routes_to_store: 1,
             most_weighted_peer_horizon: 5 * WEIGHT_MULTIPLIER,
             push_info_period: Duration::from_millis(100),
+            compression_threshold: Some(1024),
+            rate_limits: RateLimits::default(),
+            external_address: None,
+            dht_lookup_period: Duration::from_millis(500),
+            dht_record_ttl: Duration::from_secs(60 * 60),
+            recording_path: None,
         }
     }
 }
@@ -198,3 +211,51 @@ impl Handler<GetInfo> for PeerManagerActor {
         self.get_network_info()
     }
 }
+
+/// Returns the peers in the discovery table.
+pub struct GetDhtPeers {}
+
+impl Message for GetDhtPeers {
+    type Result = Vec<PeerId>;
+}
+
+impl Handler<GetDhtPeers> for PeerManagerActor {
+    type Result = Vec<PeerId>;
+
+    fn handle(&mut self, _msg: GetDhtPeers, _ctx: &mut Context<Self>) -> Self::Result {
+        self.dht_peers()
+    }
+}
+
+/// Connects to the node at `addr` over TCP as the peer with the given secret key, runs the Noise
+/// handshake with it and sends `msg`. Resolves into the messages the node sent back until it closed
+/// the connection.
+pub fn send_to_peer(
+    addr: SocketAddr,
+    secret_key: SecretKey,
+    expected_peer_id: PeerId,
+    msg: PeerMessage,
+) -> Box<dyn Future<Item = Vec<PeerMessage>, Error = io::Error>> {
+    let peer_id = PeerId::from(secret_key.public_key());
+    Box::new(
+        TcpStream::connect(&addr)
+            .and_then(move |stream| {
+                noise::handshake(
+                    stream,
+                    PeerType::Outbound,
+                    peer_id,
+                    secret_key,
+                    Some(expected_peer_id),
+                )
+            })
+            .and_then(move |(stream, _, session)| {
+                let codec = Codec::with_session(session, Compression::new(None));
+                let msg = OutboundMessage::new(&msg);
+                future::result(msg).and_then(|msg| Framed::new(stream, codec).send(msg))
+            })
+            .and_then(|framed| framed.collect())
+            .and_then(|frames| {
+                frames.iter().map(|frame| bytes_to_peer_message(frame)).collect::<Result<_, _>>()
+            }),
+    )
+}
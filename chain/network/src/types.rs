@@ -4,6 +4,7 @@ use std::convert::{Into, TryFrom};
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::Duration;
 
@@ -28,13 +29,18 @@ use near_primitives::types::{AccountId, BlockIndex, EpochId, ShardId};
 use near_primitives::utils::{from_timestamp, to_timestamp};
 use near_primitives::views::{FinalExecutionOutcomeView, QueryResponse};
 
+use crate::discovery::{DhtNodes, PeerRecord};
 use crate::metrics;
 use crate::peer::Peer;
+use crate::peer_store::ReputationEvent;
+use crate::rate_limiter::RateLimits;
 use crate::routing::{Edge, EdgeInfo, RoutingTableInfo};
 use std::sync::RwLock;
 
-/// Current latest version of the protocol
-pub const PROTOCOL_VERSION: u32 = 4;
+/// Current latest version of the protocol.
+/// Version 5 encrypts and authenticates all the connections with Noise.
+/// Version 6 adds the compression capability to `Handshake`.
+pub const PROTOCOL_VERSION: u32 = 6;
 
 /// Peer id is the public key.
 #[derive(BorshSerialize, BorshDeserialize, Clone, Eq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -212,6 +218,8 @@ pub struct Handshake {
     pub chain_info: PeerChainInfo,
     /// Info for new edge.
     pub edge_info: EdgeInfo,
+    /// Whether the sender accepts compressed frames. Since protocol version 6.
+    pub compression: bool,
 }
 
 impl Handshake {
@@ -220,8 +228,16 @@ impl Handshake {
         listen_port: Option<u16>,
         chain_info: PeerChainInfo,
         edge_info: EdgeInfo,
+        compression: bool,
     ) -> Self {
-        Handshake { version: PROTOCOL_VERSION, peer_id, listen_port, chain_info, edge_info }
+        Handshake {
+            version: PROTOCOL_VERSION,
+            peer_id,
+            listen_port,
+            chain_info,
+            edge_info,
+            compression,
+        }
     }
 }
 
@@ -480,55 +496,60 @@ pub enum PeerMessage {
     Disconnect,
 
     Challenge(Challenge),
+
+    /// Asks for the peer records closest to the given id.
+    DhtFindNode(PeerId),
+    DhtNodes(DhtNodes),
 }
 
-impl fmt::Display for PeerMessage {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl PeerMessage {
+    /// Name of the message type, for logs and metrics.
+    pub fn msg_type(&self) -> &'static str {
         match self {
-            PeerMessage::Handshake(_) => f.write_str("Handshake"),
-            PeerMessage::HandshakeFailure(_, _) => f.write_str("HandshakeFailure"),
-            PeerMessage::Sync(_) => f.write_str("Sync"),
-            PeerMessage::RequestUpdateNonce(_) => f.write_str("RequestUpdateNonce"),
-            PeerMessage::ResponseUpdateNonce(_) => f.write_str("ResponseUpdateNonce"),
-            PeerMessage::LastEdge(_) => f.write_str("LastEdge"),
-            PeerMessage::PeersRequest => f.write_str("PeersRequest"),
-            PeerMessage::PeersResponse(_) => f.write_str("PeersResponse"),
-            PeerMessage::BlockHeadersRequest(_) => f.write_str("BlockHeaderRequest"),
-            PeerMessage::BlockHeaders(_) => f.write_str("BlockHeaders"),
-            PeerMessage::BlockRequest(_) => f.write_str("BlockRequest"),
-            PeerMessage::Block(_) => f.write_str("Block"),
-            PeerMessage::BlockHeaderAnnounce(_) => f.write_str("BlockHeaderAnnounce"),
-            PeerMessage::Transaction(_) => f.write_str("Transaction"),
+            PeerMessage::Handshake(_) => "Handshake",
+            PeerMessage::HandshakeFailure(_, _) => "HandshakeFailure",
+            PeerMessage::Sync(_) => "Sync",
+            PeerMessage::RequestUpdateNonce(_) => "RequestUpdateNonce",
+            PeerMessage::ResponseUpdateNonce(_) => "ResponseUpdateNonce",
+            PeerMessage::LastEdge(_) => "LastEdge",
+            PeerMessage::PeersRequest => "PeersRequest",
+            PeerMessage::PeersResponse(_) => "PeersResponse",
+            PeerMessage::BlockHeadersRequest(_) => "BlockHeaderRequest",
+            PeerMessage::BlockHeaders(_) => "BlockHeaders",
+            PeerMessage::BlockRequest(_) => "BlockRequest",
+            PeerMessage::Block(_) => "Block",
+            PeerMessage::BlockHeaderAnnounce(_) => "BlockHeaderAnnounce",
+            PeerMessage::Transaction(_) => "Transaction",
             PeerMessage::Routed(routed_message) => match routed_message.body {
-                RoutedMessageBody::BlockApproval(_) => f.write_str("BlockApproval"),
-                RoutedMessageBody::ForwardTx(_) => f.write_str("ForwardTx"),
-                RoutedMessageBody::TxStatusRequest(_, _) => f.write_str("Transaction status query"),
-                RoutedMessageBody::TxStatusResponse(_) => {
-                    f.write_str("Transaction status response")
-                }
-                RoutedMessageBody::QueryRequest { .. } => f.write_str("Query request"),
-                RoutedMessageBody::QueryResponse { .. } => f.write_str("Query response"),
-                RoutedMessageBody::StateRequest(_, _, _, _) => f.write_str("StateResponse"),
-                RoutedMessageBody::StateResponse(_) => f.write_str("StateResponse"),
-                RoutedMessageBody::ReceiptOutcomeRequest(_) => {
-                    f.write_str("Receipt outcome request")
-                }
-                RoutedMessageBody::ReceiptOutComeResponse(_) => {
-                    f.write_str("Receipt outcome response")
-                }
-                RoutedMessageBody::PartialEncodedChunkRequest(_) => {
-                    f.write_str("PartialEncodedChunkRequest")
-                }
-                RoutedMessageBody::PartialEncodedChunk(_) => f.write_str("PartialEncodedChunk"),
-                RoutedMessageBody::Ping(_) => f.write_str("Ping"),
-                RoutedMessageBody::Pong(_) => f.write_str("Pong"),
+                RoutedMessageBody::BlockApproval(_) => "BlockApproval",
+                RoutedMessageBody::ForwardTx(_) => "ForwardTx",
+                RoutedMessageBody::TxStatusRequest(_, _) => "Transaction status query",
+                RoutedMessageBody::TxStatusResponse(_) => "Transaction status response",
+                RoutedMessageBody::QueryRequest { .. } => "Query request",
+                RoutedMessageBody::QueryResponse { .. } => "Query response",
+                RoutedMessageBody::StateRequest(_, _, _, _) => "StateRequest",
+                RoutedMessageBody::StateResponse(_) => "StateResponse",
+                RoutedMessageBody::ReceiptOutcomeRequest(_) => "Receipt outcome request",
+                RoutedMessageBody::ReceiptOutComeResponse(_) => "Receipt outcome response",
+                RoutedMessageBody::PartialEncodedChunkRequest(_) => "PartialEncodedChunkRequest",
+                RoutedMessageBody::PartialEncodedChunk(_) => "PartialEncodedChunk",
+                RoutedMessageBody::Ping(_) => "Ping",
+                RoutedMessageBody::Pong(_) => "Pong",
             },
-            PeerMessage::Disconnect => f.write_str("Disconnect"),
-            PeerMessage::Challenge(_) => f.write_str("Challenge"),
+            PeerMessage::Disconnect => "Disconnect",
+            PeerMessage::Challenge(_) => "Challenge",
+            PeerMessage::DhtFindNode(_) => "DhtFindNode",
+            PeerMessage::DhtNodes(_) => "DhtNodes",
         }
     }
 }
 
+impl fmt::Display for PeerMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.msg_type())
+    }
+}
+
 impl PeerMessage {
     pub fn record(&self, size: usize) {
         match self {
@@ -711,6 +732,14 @@ impl PeerMessage {
                 near_metrics::inc_counter(&metrics::CHALLENGE_RECEIVED_TOTAL);
                 near_metrics::inc_counter_by(&metrics::CHALLENGE_RECEIVED_BYTES, size as i64);
             }
+            PeerMessage::DhtFindNode(_) => {
+                near_metrics::inc_counter(&metrics::DHT_FIND_NODE_RECEIVED_TOTAL);
+                near_metrics::inc_counter_by(&metrics::DHT_FIND_NODE_RECEIVED_BYTES, size as i64);
+            }
+            PeerMessage::DhtNodes(_) => {
+                near_metrics::inc_counter(&metrics::DHT_NODES_RECEIVED_TOTAL);
+                near_metrics::inc_counter_by(&metrics::DHT_NODES_RECEIVED_BYTES, size as i64);
+            }
         }
     }
 
@@ -750,6 +779,113 @@ impl PeerMessage {
             _ => false,
         }
     }
+
+    /// Wraps a message received from the given peer into what the client expects.
+    /// `was_requested` tells whether a received block was requested from this peer.
+    pub fn into_client_message(
+        self,
+        peer_id: PeerId,
+        was_requested: bool,
+    ) -> Option<NetworkClientMessages> {
+        Some(match self {
+            PeerMessage::Block(block) => {
+                NetworkClientMessages::Block(block, peer_id, was_requested)
+            }
+            PeerMessage::BlockHeaderAnnounce(header) => {
+                NetworkClientMessages::BlockHeader(header, peer_id)
+            }
+            PeerMessage::Transaction(transaction) => {
+                NetworkClientMessages::Transaction(transaction)
+            }
+            PeerMessage::BlockRequest(hash) => NetworkClientMessages::BlockRequest(hash),
+            PeerMessage::BlockHeadersRequest(hashes) => {
+                NetworkClientMessages::BlockHeadersRequest(hashes)
+            }
+            PeerMessage::BlockHeaders(headers) => {
+                NetworkClientMessages::BlockHeaders(headers, peer_id)
+            }
+            // All Routed messages received at this point are for us.
+            PeerMessage::Routed(routed_message) => {
+                let msg_hash = routed_message.hash();
+
+                match routed_message.body {
+                    RoutedMessageBody::BlockApproval(approval) => {
+                        NetworkClientMessages::BlockApproval(approval, peer_id)
+                    }
+                    RoutedMessageBody::ForwardTx(transaction) => {
+                        NetworkClientMessages::Transaction(transaction)
+                    }
+                    RoutedMessageBody::StateRequest(shard_id, hash, need_header, parts) => {
+                        NetworkClientMessages::StateRequest(
+                            shard_id,
+                            hash,
+                            need_header,
+                            parts,
+                            msg_hash,
+                        )
+                    }
+                    RoutedMessageBody::StateResponse(info) => {
+                        NetworkClientMessages::StateResponse(info)
+                    }
+                    RoutedMessageBody::PartialEncodedChunkRequest(request) => {
+                        NetworkClientMessages::PartialEncodedChunkRequest(request, msg_hash)
+                    }
+                    RoutedMessageBody::PartialEncodedChunk(partial_encoded_chunk) => {
+                        NetworkClientMessages::PartialEncodedChunk(partial_encoded_chunk)
+                    }
+                    RoutedMessageBody::Ping(_)
+                    | RoutedMessageBody::Pong(_)
+                    | RoutedMessageBody::TxStatusRequest(_, _)
+                    | RoutedMessageBody::TxStatusResponse(_)
+                    | RoutedMessageBody::QueryRequest { .. }
+                    | RoutedMessageBody::QueryResponse { .. }
+                    | RoutedMessageBody::ReceiptOutcomeRequest(_)
+                    | RoutedMessageBody::ReceiptOutComeResponse(_) => return None,
+                }
+            }
+            PeerMessage::Challenge(challenge) => NetworkClientMessages::Challenge(challenge),
+            PeerMessage::Handshake(_)
+            | PeerMessage::HandshakeFailure(_, _)
+            | PeerMessage::PeersRequest
+            | PeerMessage::PeersResponse(_)
+            | PeerMessage::Sync(_)
+            | PeerMessage::LastEdge(_)
+            | PeerMessage::Disconnect
+            | PeerMessage::RequestUpdateNonce(_)
+            | PeerMessage::ResponseUpdateNonce(_)
+            | PeerMessage::DhtFindNode(_)
+            | PeerMessage::DhtNodes(_) => return None,
+        })
+    }
+
+    /// Wraps a received message into what the view client expects.
+    pub fn into_view_client_message(self) -> Option<NetworkViewClientMessages> {
+        let routed_message = match self {
+            PeerMessage::Routed(routed_message) => routed_message,
+            _ => return None,
+        };
+        Some(match routed_message.body {
+            RoutedMessageBody::QueryRequest { path, data, id } => {
+                NetworkViewClientMessages::Query { path, data, id }
+            }
+            RoutedMessageBody::QueryResponse { response, id } => {
+                NetworkViewClientMessages::QueryResponse { response, id }
+            }
+            RoutedMessageBody::TxStatusRequest(account_id, tx_hash) => {
+                NetworkViewClientMessages::TxStatus { tx_hash, signer_account_id: account_id }
+            }
+            RoutedMessageBody::TxStatusResponse(tx_result) => {
+                NetworkViewClientMessages::TxStatusResponse(tx_result)
+            }
+            RoutedMessageBody::ReceiptOutcomeRequest(receipt_id) => {
+                NetworkViewClientMessages::ReceiptOutcomeRequest(receipt_id)
+            }
+            RoutedMessageBody::ReceiptOutComeResponse(response) => {
+                NetworkViewClientMessages::ReceiptOutcomeResponse(response)
+            }
+            _ => return None,
+        })
+    }
 }
 
 /// Configuration for the peer-to-peer manager.
@@ -782,6 +918,19 @@ pub struct NetworkConfig {
     pub most_weighted_peer_horizon: u128,
     /// Period between pushing network info to client
     pub push_info_period: Duration,
+    /// Minimum size of the messages to compress, if the peer accepts compressed messages.
+    /// Compressed messages are neither sent nor accepted if None.
+    pub compression_threshold: Option<usize>,
+    /// Limits on the messages received from each peer.
+    pub rate_limits: RateLimits,
+    /// Address advertised to the discovery protocol. If None, the listening address is used.
+    pub external_address: Option<SocketAddr>,
+    /// Period between discovery lookups.
+    pub dht_lookup_period: Duration,
+    /// Time after which signed peer records are discarded.
+    pub dht_record_ttl: Duration,
+    /// File to record all the messages exchanged with the peers to. Not recorded if None.
+    pub recording_path: Option<PathBuf>,
 }
 
 /// Status of the known peers.
@@ -895,6 +1044,10 @@ pub struct PeerList {
 pub enum PeerRequest {
     UpdateEdge((PeerId, u64)),
     RouteBack(RoutedMessageBody, CryptoHash),
+    UpdateReputation(PeerId, ReputationEvent),
+    DhtFindNode(PeerId),
+    /// Records received from the given peer.
+    DhtNodes(PeerId, DhtNodes),
 }
 
 impl Message for PeerRequest {
@@ -905,6 +1058,7 @@ impl Message for PeerRequest {
 pub enum PeerResponse {
     NoResponse,
     UpdatedEdge(EdgeInfo),
+    DhtRecords(Vec<PeerRecord>),
 }
 
 /// Requesting peers from peer manager to communicate to a peer.
@@ -1070,6 +1224,8 @@ pub struct NetworkInfo {
     pub received_bytes_per_sec: u64,
     /// Accounts of known block and chunk producers from routing table.
     pub known_producers: Vec<AccountId>,
+    /// Reputation of the active peers.
+    pub peer_reputations: HashMap<PeerId, i64>,
 }
 
 impl<A, M> MessageResponse<A, M> for NetworkInfo
@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use actix::actors::mocker::Mocker;
+use actix::Actor;
+use actix::System;
+use futures::future;
+use futures::future::Future;
+
+use near_client::{ClientActor, ViewClientActor};
+use near_network::test_utils::{convert_boot_nodes, open_port, GetDhtPeers, WaitOrTimeout};
+use near_network::types::NetworkViewClientResponses;
+use near_network::{
+    NetworkClientMessages, NetworkClientResponses, NetworkConfig, PeerManagerActor,
+};
+use near_primitives::block::WeightAndScore;
+use near_primitives::test_utils::init_test_logger;
+use near_store::test_utils::create_test_store;
+
+type ClientMock = Mocker<ClientActor>;
+type ViewClientMock = Mocker<ViewClientActor>;
+
+fn make_peer_manager(
+    seed: &str,
+    port: u16,
+    boot_nodes: Vec<(&str, u16)>,
+    peer_max_count: u32,
+) -> PeerManagerActor {
+    let store = create_test_store();
+    let mut config = NetworkConfig::from_seed(seed, port);
+    config.boot_nodes = convert_boot_nodes(boot_nodes);
+    config.peer_max_count = peer_max_count;
+    let client_addr = ClientMock::mock(Box::new(move |msg, _ctx| {
+        let msg = msg.downcast_ref::<NetworkClientMessages>().unwrap();
+        match msg {
+            NetworkClientMessages::GetChainInfo => {
+                Box::new(Some(NetworkClientResponses::ChainInfo {
+                    genesis_id: Default::default(),
+                    height: 1,
+                    weight_and_score: WeightAndScore::from_ints(1, 0),
+                    tracked_shards: vec![],
+                }))
+            }
+            _ => Box::new(Some(NetworkClientResponses::NoResponse)),
+        }
+    }))
+    .start();
+    let view_client_addr = ViewClientMock::mock(Box::new(move |_msg, _ctx| {
+        Box::new(Some(NetworkViewClientResponses::NoResponse))
+    }))
+    .start();
+    PeerManagerActor::new(store, config, client_addr.recipient(), view_client_addr.recipient())
+        .unwrap()
+}
+
+/// Peers connected in a line, with fewer connections allowed than there are peers, learn the
+/// signed records of all the other peers.
+#[test]
+fn discover_all_peers() {
+    init_test_logger();
+
+    System::run(|| {
+        let num_peers = 10;
+        let ports = (0..num_peers).map(|_| open_port()).collect::<Vec<_>>();
+        let seeds = (0..num_peers).map(|i| format!("test{}", i)).collect::<Vec<_>>();
+        let mut peers = vec![];
+        for i in 0..num_peers {
+            let boot_nodes =
+                if i == 0 { vec![] } else { vec![(seeds[i - 1].as_str(), ports[i - 1])] };
+            peers.push(make_peer_manager(&seeds[i], ports[i], boot_nodes, 3).start());
+        }
+        let flags = Arc::new(AtomicUsize::new(0));
+        WaitOrTimeout::new(
+            Box::new(move |_| {
+                for i in 0..num_peers {
+                    let flags1 = flags.clone();
+                    actix::spawn(peers[i].send(GetDhtPeers {}).then(move |res| {
+                        let dht_peers = res.unwrap();
+                        if dht_peers.len() == num_peers - 1 {
+                            flags1.fetch_or(1 << i, Ordering::Relaxed);
+                        }
+                        future::result(Ok(()))
+                    }));
+                }
+                if flags.load(Ordering::Relaxed) == (1 << num_peers) - 1 {
+                    System::current().stop();
+                }
+            }),
+            100,
+            30000,
+        )
+        .start();
+    })
+    .unwrap();
+}
@@ -1,15 +1,23 @@
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use actix::actors::mocker::Mocker;
 use actix::Actor;
 use actix::System;
 use futures::future;
 use futures::future::Future;
+use tokio::timer::Delay;
 
 use near_client::{ClientActor, ViewClientActor};
-use near_network::test_utils::{convert_boot_nodes, open_port, GetInfo, WaitOrTimeout};
-use near_network::types::{NetworkViewClientResponses, StopSignal};
+use near_crypto::{KeyType, SecretKey};
+use near_network::routing::EdgeInfo;
+use near_network::test_utils::{
+    convert_boot_nodes, open_port, send_to_peer, wait_or_panic, GetInfo, WaitOrTimeout,
+};
+use near_network::types::{
+    Handshake, NetworkViewClientResponses, PeerChainInfo, PeerId, PeerMessage, StopSignal,
+};
 use near_network::{
     NetworkClientMessages, NetworkClientResponses, NetworkConfig, PeerManagerActor,
 };
@@ -79,6 +87,51 @@ fn peer_handshake() {
     .unwrap();
 }
 
+/// A peer that authenticates with Noise as one peer and claims to be another one in its handshake
+/// is disconnected without an answer.
+#[test]
+fn peer_handshake_id_mismatch() {
+    init_test_logger();
+
+    System::run(|| {
+        let port = open_port();
+        let pm = make_peer_manager("test1", port, vec![], 10).start();
+        let node_peer_id =
+            PeerId::from(SecretKey::from_seed(KeyType::ED25519, "test1").public_key());
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, "test2");
+        let other_key = SecretKey::from_seed(KeyType::ED25519, "test3");
+        let claimed_peer_id = PeerId::from(other_key.public_key());
+        let handshake = Handshake::new(
+            claimed_peer_id.clone(),
+            None,
+            PeerChainInfo {
+                genesis_id: Default::default(),
+                height: 1,
+                weight_and_score: WeightAndScore::from_ints(1, 0),
+                tracked_shards: vec![],
+            },
+            EdgeInfo::new(node_peer_id.clone(), claimed_peer_id, 1, &other_key),
+            false,
+        );
+        let addr = format!("127.0.0.1:{}", port).parse().unwrap();
+        // Gives the peer manager time to start listening.
+        actix::spawn(Delay::new(Instant::now() + Duration::from_millis(100)).then(move |_| {
+            send_to_peer(addr, secret_key, node_peer_id, PeerMessage::Handshake(handshake)).then(
+                move |res| {
+                    assert_eq!(res.unwrap(), vec![]);
+                    pm.send(GetInfo {}).then(|res| {
+                        assert_eq!(res.unwrap().num_active_peers, 0);
+                        System::current().stop();
+                        future::result(Ok(()))
+                    })
+                },
+            )
+        }));
+        wait_or_panic(5000);
+    })
+    .unwrap();
+}
+
 #[test]
 fn peers_connect_all() {
     init_test_logger();
@@ -1,13 +1,18 @@
-use std::collections::{HashMap, HashSet, VecDeque};
-
-use crate::types::{AccountPK, PoolIterator, TransactionGroup};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::types::{
+    AccountPK, InsertTransactionResult, PoolConfig, PoolIterator, TransactionGroup,
+    TransactionOrdering,
+};
 use near_primitives::hash::CryptoHash;
 use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::AccountId;
 
 pub mod types;
 
 /// Transaction pool: keeps track of transactions that were not yet accepted into the block chain.
-#[derive(Default)]
 pub struct TransactionPool {
     /// Transactions are grouped by a pair of (account ID, signer public key).
     /// NOTE: It's more efficient on average to keep transactions unsorted and with potentially
@@ -15,21 +20,150 @@ pub struct TransactionPool {
     pub transactions: HashMap<AccountPK, Vec<SignedTransaction>>,
     /// Set of all hashes to quickly check if the given transaction is in the pool.
     pub unique_transactions: HashSet<CryptoHash>,
+    /// Time when each transaction was first added to the pool. Transactions pulled by the pool
+    /// iterator keep their time, so that reintroducing them doesn't reset it. Times of transactions
+    /// that were not reintroduced are pruned along with expired transactions.
+    received: HashMap<CryptoHash, Instant>,
+    /// Number of transactions in the pool signed by each account with any of its keys.
+    signer_counts: HashMap<AccountId, usize>,
+    /// Groups by the priority of their transaction with the highest nonce, which is the one
+    /// evicted first when the pool is full.
+    eviction_index: BTreeMap<u128, HashSet<AccountPK>>,
+    /// Priority under which every group is in `eviction_index`.
+    eviction_priorities: HashMap<AccountPK, u128>,
+    /// Number of transactions evicted because the pool was full or because they expired.
+    pub num_evicted: u64,
+    config: PoolConfig,
+    ordering: Box<dyn TransactionOrdering>,
+}
+
+impl Default for TransactionPool {
+    fn default() -> Self {
+        Self::new(PoolConfig::default())
+    }
 }
 
 impl TransactionPool {
+    pub fn new(config: PoolConfig) -> Self {
+        let ordering = config.ordering.strategy();
+        Self::with_ordering(config, ordering)
+    }
+
+    /// Creates a pool with a custom ordering strategy instead of the one selected in the config.
+    pub fn with_ordering(config: PoolConfig, ordering: Box<dyn TransactionOrdering>) -> Self {
+        Self {
+            transactions: HashMap::new(),
+            unique_transactions: HashSet::new(),
+            received: HashMap::new(),
+            signer_counts: HashMap::new(),
+            eviction_index: BTreeMap::new(),
+            eviction_priorities: HashMap::new(),
+            num_evicted: 0,
+            config,
+            ordering,
+        }
+    }
+
     /// Insert a signed transaction into the pool that passed validation.
-    pub fn insert_transaction(&mut self, signed_transaction: SignedTransaction) {
+    /// If the pool is full, the transaction replaces the lowest priority transaction that would be
+    /// pulled last from its group, as long as the new transaction has a higher priority.
+    pub fn insert_transaction(
+        &mut self,
+        signed_transaction: SignedTransaction,
+    ) -> InsertTransactionResult {
         if self.unique_transactions.contains(&signed_transaction.get_hash()) {
-            return;
+            return InsertTransactionResult::Duplicate;
         }
-        self.unique_transactions.insert(signed_transaction.get_hash());
         let signer_id = signed_transaction.transaction.signer_id.clone();
+        if self.num_signer_transactions(&signer_id) >= self.config.max_transactions_per_signer {
+            return InsertTransactionResult::SignerQuotaExceeded;
+        }
+        if self.len() >= self.config.capacity {
+            let priority = self.ordering.priority(&signed_transaction);
+            match self.lowest_priority_transaction() {
+                Some((key, hash, lowest_priority)) if lowest_priority < priority => {
+                    self.evict_transaction(&key, &hash)
+                }
+                _ => return InsertTransactionResult::NoSpaceLeft,
+            }
+        }
+        self.unique_transactions.insert(signed_transaction.get_hash());
+        self.received.entry(signed_transaction.get_hash()).or_insert_with(Instant::now);
         let signer_public_key = signed_transaction.transaction.public_key.clone();
-        self.transactions
-            .entry((signer_id, signer_public_key))
-            .or_insert_with(Vec::new)
-            .push(signed_transaction);
+        *self.signer_counts.entry(signer_id.clone()).or_insert(0) += 1;
+        let key = (signer_id, signer_public_key);
+        self.transactions.entry(key.clone()).or_insert_with(Vec::new).push(signed_transaction);
+        self.update_eviction_index(&key);
+        InsertTransactionResult::Success
+    }
+
+    /// Number of transactions in the pool signed by the given account with any of its keys.
+    fn num_signer_transactions(&self, signer_id: &AccountId) -> usize {
+        self.signer_counts.get(signer_id).cloned().unwrap_or(0)
+    }
+
+    fn remove_signer_transactions(&mut self, signer_id: &AccountId, num_transactions: usize) {
+        let mut remove_entry = false;
+        if let Some(count) = self.signer_counts.get_mut(signer_id) {
+            *count = count.saturating_sub(num_transactions);
+            remove_entry = *count == 0;
+        }
+        if remove_entry {
+            self.signer_counts.remove(signer_id);
+        }
+    }
+
+    /// Moves the group in `eviction_index` under the priority of its current transaction with the
+    /// highest nonce, or removes it if the group is gone.
+    fn update_eviction_index(&mut self, key: &AccountPK) {
+        if let Some(priority) = self.eviction_priorities.remove(key) {
+            let mut remove_entry = false;
+            if let Some(keys) = self.eviction_index.get_mut(&priority) {
+                keys.remove(key);
+                remove_entry = keys.is_empty();
+            }
+            if remove_entry {
+                self.eviction_index.remove(&priority);
+            }
+        }
+        let ordering = &self.ordering;
+        let priority = self
+            .transactions
+            .get(key)
+            .and_then(|transactions| transactions.iter().max_by_key(|tx| tx.transaction.nonce))
+            .map(|tx| ordering.priority(tx));
+        if let Some(priority) = priority {
+            self.eviction_index.entry(priority).or_insert_with(HashSet::new).insert(key.clone());
+            self.eviction_priorities.insert(key.clone(), priority);
+        }
+    }
+
+    /// Among the transactions with the highest nonce of every group, finds the one with the lowest
+    /// priority. Evicting it keeps the remaining transactions of the group valid.
+    fn lowest_priority_transaction(&self) -> Option<(AccountPK, CryptoHash, u128)> {
+        let (priority, keys) = self.eviction_index.iter().next()?;
+        let key = keys.iter().next()?;
+        let tx = self.transactions.get(key)?.iter().max_by_key(|tx| tx.transaction.nonce)?;
+        Some((key.clone(), tx.get_hash(), *priority))
+    }
+
+    fn evict_transaction(&mut self, key: &AccountPK, hash: &CryptoHash) {
+        let mut remove_entry = false;
+        let mut num_removed = 0;
+        if let Some(v) = self.transactions.get_mut(key) {
+            let len = v.len();
+            v.retain(|tx| tx.get_hash() != *hash);
+            num_removed = len - v.len();
+            remove_entry = v.is_empty();
+        }
+        if remove_entry {
+            self.transactions.remove(key);
+        }
+        self.remove_signer_transactions(&key.0, num_removed);
+        self.update_eviction_index(key);
+        self.unique_transactions.remove(hash);
+        self.received.remove(hash);
+        self.num_evicted += 1;
     }
 
     /// Returns a pool iterator wrapper that implements an iterator like trait to iterate over
@@ -56,17 +190,63 @@ impl TransactionPool {
         for (key, hashes) in grouped_transactions {
             let key = (key.0.clone(), key.1.clone());
             let mut remove_entry = false;
+            let mut num_removed = 0;
             if let Some(v) = self.transactions.get_mut(&key) {
+                let len = v.len();
                 v.retain(|tx| !hashes.contains(&tx.get_hash()));
+                num_removed = len - v.len();
                 remove_entry = v.is_empty();
             }
             if remove_entry {
                 self.transactions.remove(&key);
             }
+            self.remove_signer_transactions(&key.0, num_removed);
+            self.update_eviction_index(&key);
             for hash in hashes {
                 self.unique_transactions.remove(&hash);
+                self.received.remove(&hash);
+            }
+        }
+    }
+
+    /// Evicts transactions that can no longer be included into the chain, e.g. because their
+    /// `block_hash` is past the transaction validity period. `is_expired` is called once for every
+    /// distinct `block_hash` in the pool. Returns the number of evicted transactions.
+    pub fn remove_expired_transactions<F>(&mut self, mut is_expired: F) -> usize
+    where
+        F: FnMut(&CryptoHash) -> bool,
+    {
+        let mut expired_block_hashes = HashMap::new();
+        let mut evicted = vec![];
+        let mut changed_groups = vec![];
+        for (key, transactions) in self.transactions.iter_mut() {
+            let len = transactions.len();
+            transactions.retain(|tx| {
+                let block_hash = tx.transaction.block_hash;
+                let expired = *expired_block_hashes
+                    .entry(block_hash)
+                    .or_insert_with(|| is_expired(&block_hash));
+                if expired {
+                    evicted.push(tx.get_hash());
+                }
+                !expired
+            });
+            if transactions.len() < len {
+                changed_groups.push((key.clone(), len - transactions.len()));
             }
         }
+        self.transactions.retain(|_, transactions| !transactions.is_empty());
+        for (key, num_removed) in changed_groups {
+            self.remove_signer_transactions(&key.0, num_removed);
+            self.update_eviction_index(&key);
+        }
+        for hash in evicted.iter() {
+            self.unique_transactions.remove(hash);
+        }
+        let unique_transactions = &self.unique_transactions;
+        self.received.retain(|hash, _| unique_transactions.contains(hash));
+        self.num_evicted += evicted.len() as u64;
+        evicted.len()
     }
 
     /// Reintroduce transactions back during the chain reorg
@@ -76,6 +256,32 @@ impl TransactionPool {
         }
     }
 
+    /// Returns transactions in the pool with the time since they were added to it, optionally only
+    /// the ones signed by the given account.
+    pub fn pending_transactions(
+        &self,
+        signer_id: Option<&AccountId>,
+    ) -> Vec<(&SignedTransaction, Duration)> {
+        let now = Instant::now();
+        self.transactions
+            .iter()
+            .filter(|((account_id, _), _)| signer_id.map_or(true, |id| id == account_id))
+            .flat_map(|(_, transactions)| transactions.iter())
+            .map(|tx| {
+                let age = self
+                    .received
+                    .get(&tx.get_hash())
+                    .map_or(Duration::from_secs(0), |received| now.duration_since(*received));
+                (tx, age)
+            })
+            .collect()
+    }
+
+    /// Number of groups of transactions with the same signer account and public key.
+    pub fn num_groups(&self) -> usize {
+        self.transactions.len()
+    }
+
     pub fn len(&self) -> usize {
         self.unique_transactions.len()
     }
@@ -92,76 +298,88 @@ pub struct PoolIteratorWrapper<'a> {
     /// Mutable reference to the pool, to avoid exposing it while the iterator exists.
     pool: &'a mut TransactionPool,
 
-    /// Queue of transaction groups. Each group there is sorted by nonce.
-    sorted_groups: VecDeque<TransactionGroup>,
+    /// Transaction groups taken from the pool. Each group there is sorted by nonce.
+    sorted_groups: Vec<TransactionGroup>,
+    /// Indices of the non-empty groups in `sorted_groups` that are queued to be returned, by the
+    /// priority of their next transaction and then by the time they were queued.
+    queue: BinaryHeap<(u128, Reverse<u64>, usize)>,
+    /// Number of times a group was queued, to keep round robin among equal priorities.
+    num_queued: u64,
+    /// Index of the group returned by the last call to `next`, to queue it again.
+    last_returned: Option<usize>,
 }
 
 impl<'a> PoolIteratorWrapper<'a> {
     pub fn new(pool: &'a mut TransactionPool) -> Self {
-        Self { pool, sorted_groups: Default::default() }
+        Self {
+            pool,
+            sorted_groups: vec![],
+            queue: BinaryHeap::new(),
+            num_queued: 0,
+            last_returned: None,
+        }
+    }
+
+    /// Queues the group behind all the queued groups with the same priority, unless it's empty.
+    fn queue_group(&mut self, index: usize) {
+        if let Some(next_transaction) = self.sorted_groups[index].transactions.last() {
+            let priority = self.pool.ordering.priority(next_transaction);
+            self.queue.push((priority, Reverse(self.num_queued), index));
+            self.num_queued += 1;
+        }
     }
 }
 
 /// The iterator works with the following algorithm:
-/// On next(), the iterator tries to get a transaction group from the pool, sorts transactions in
-/// it, and add it to the back of the sorted groups queue.
-///
-/// If the pool is empty, the iterator gets the group from the front of the sorted groups queue.
-///
-/// If this group is empty (no transactions left inside), then the iterator discards it and
-/// updates `unique_transactions` in the pool. Then gets the next one.
+/// On next(), the iterator takes all transaction groups from the pool, sorts transactions in
+/// them, and queues them by the priority of their next transaction according to the pool's
+/// ordering. The group returned by the previous call is queued again behind the groups with the
+/// same priority, unless no transactions are left inside it.
 ///
-/// Once a non-empty group is found, this group is pushed to the back of the sorted groups queue
-/// and the iterator returns a mutable reference to this group.
+/// Then the iterator pops the group with the highest priority that was queued first and returns
+/// a mutable reference to it. With equal priorities this is round robin scheduling. Every call
+/// only touches the returned group, apart from the first one that takes the groups from the pool.
 ///
-/// If the sorted groups queue is empty, the iterator returns None.
+/// If the queue is empty, the iterator returns None.
 ///
 /// When the iterator is dropped, `unique_transactions` in the pool is updated for every group.
-/// And all non-empty group from the sorted groups queue are inserted back into the pool.
+/// And all non-empty group are inserted back into the pool.
 impl<'a> PoolIterator for PoolIteratorWrapper<'a> {
     fn next(&mut self) -> Option<&mut TransactionGroup> {
-        let key = self.pool.transactions.keys().next().cloned();
-        match key {
-            Some(key) => {
-                let mut transactions =
-                    self.pool.transactions.remove(&key.clone()).expect("just checked existence");
-                transactions.sort_by_key(|st| std::cmp::Reverse(st.transaction.nonce));
-                self.sorted_groups.push_back(TransactionGroup {
-                    key,
-                    transactions,
-                    removed_transaction_hashes: vec![],
-                });
-                Some(self.sorted_groups.back_mut().expect("just pushed"))
-            }
-            None => {
-                while let Some(sorted_group) = self.sorted_groups.pop_front() {
-                    if sorted_group.transactions.is_empty() {
-                        for hash in sorted_group.removed_transaction_hashes {
-                            self.pool.unique_transactions.remove(&hash);
-                        }
-                    } else {
-                        self.sorted_groups.push_back(sorted_group);
-                        return Some(self.sorted_groups.back_mut().expect("just pushed"));
-                    }
-                }
-                None
-            }
+        let drained: Vec<_> = self.pool.transactions.drain().collect();
+        for (key, mut transactions) in drained {
+            transactions.sort_by_key(|st| Reverse(st.transaction.nonce));
+            self.sorted_groups.push(TransactionGroup {
+                key,
+                transactions,
+                removed_transaction_hashes: vec![],
+            });
+            self.queue_group(self.sorted_groups.len() - 1);
         }
+        if let Some(index) = self.last_returned.take() {
+            self.queue_group(index);
+        }
+        let (_, _, index) = self.queue.pop()?;
+        self.last_returned = Some(index);
+        Some(&mut self.sorted_groups[index])
     }
 }
 
-/// When a pool iterator is dropped, all remaining non empty transaction groups from the sorted
-/// groups queue are inserted back into the pool. And removed transactions hashes from groups are
-/// removed from the pool's unique_transactions.
+/// When a pool iterator is dropped, all remaining non empty transaction groups are inserted back
+/// into the pool. And removed transactions hashes from groups are removed from the pool's
+/// unique_transactions.
 impl<'a> Drop for PoolIteratorWrapper<'a> {
     fn drop(&mut self) {
         for group in self.sorted_groups.drain(..) {
-            for hash in group.removed_transaction_hashes {
-                self.pool.unique_transactions.remove(&hash);
+            for hash in group.removed_transaction_hashes.iter() {
+                self.pool.unique_transactions.remove(hash);
             }
+            self.pool
+                .remove_signer_transactions(&group.key.0, group.removed_transaction_hashes.len());
             if !group.transactions.is_empty() {
-                self.pool.transactions.insert(group.key, group.transactions);
+                self.pool.transactions.insert(group.key.clone(), group.transactions);
             }
+            self.pool.update_eviction_index(&group.key);
         }
     }
 }
@@ -176,8 +394,29 @@ mod tests {
 
     use near_crypto::{InMemorySigner, KeyType};
 
-    use near_primitives::hash::CryptoHash;
-    use near_primitives::types::Balance;
+    use near_primitives::hash::{hash, CryptoHash};
+    use near_primitives::transaction::{Action, FunctionCallAction};
+    use near_primitives::types::{Balance, Gas};
+
+    use crate::types::DEFAULT_MAX_TRANSACTIONS_PER_SIGNER;
+
+    /// Ranks transactions by the gas they attach to function calls. Attached gas is refunded, so
+    /// it's no fee to rank by on a node, but it makes the priority of a test transaction explicit.
+    struct AttachedGasOrdering;
+
+    impl TransactionOrdering for AttachedGasOrdering {
+        fn priority(&self, transaction: &SignedTransaction) -> u128 {
+            transaction
+                .transaction
+                .actions
+                .iter()
+                .map(|action| match action {
+                    Action::FunctionCall(function_call) => function_call.gas as u128,
+                    _ => 0,
+                })
+                .sum()
+        }
+    }
 
     fn generate_transactions(
         signer_id: &str,
@@ -201,6 +440,23 @@ mod tests {
             .collect()
     }
 
+    fn function_call(signer_id: &str, nonce: u64, gas: Gas) -> SignedTransaction {
+        let signer = InMemorySigner::from_seed(signer_id, KeyType::ED25519, signer_id);
+        SignedTransaction::from_actions(
+            nonce,
+            signer_id.to_string(),
+            "bob.near".to_string(),
+            &signer,
+            vec![Action::FunctionCall(FunctionCallAction {
+                method_name: "run".to_string(),
+                args: vec![],
+                gas,
+                deposit: 0,
+            })],
+            CryptoHash::default(),
+        )
+    }
+
     fn process_txs_to_nonces(
         mut transactions: Vec<SignedTransaction>,
         expected_weight: u32,
@@ -379,4 +635,172 @@ mod tests {
         let txs = prepare_transactions(&mut pool, 10);
         assert_eq!(txs.len(), 10);
     }
+
+    #[test]
+    fn test_signer_quota() {
+        let config = PoolConfig { max_transactions_per_signer: 5, ..PoolConfig::default() };
+        let mut pool = TransactionPool::new(config);
+        let mut transactions = generate_transactions("alice.near", "alice.near", 1, 3);
+        transactions.extend(generate_transactions("alice.near", "bob.near", 1, 3));
+        let results: Vec<_> =
+            transactions.iter().map(|tx| pool.insert_transaction(tx.clone())).collect();
+        assert_eq!(results[..5], vec![InsertTransactionResult::Success; 5][..]);
+        assert_eq!(results[5], InsertTransactionResult::SignerQuotaExceeded);
+        assert_eq!(
+            pool.insert_transaction(transactions[0].clone()),
+            InsertTransactionResult::Duplicate
+        );
+        for tx in generate_transactions("carol.near", "carol.near", 1, 3) {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        assert_eq!(pool.len(), 8);
+    }
+
+    /// Transactions pulled from the pool no longer count towards the quota of their signer.
+    #[test]
+    fn test_signer_quota_after_pull() {
+        let config = PoolConfig { max_transactions_per_signer: 3, ..PoolConfig::default() };
+        let mut pool = TransactionPool::new(config);
+        for tx in generate_transactions("alice.near", "alice.near", 1, 3) {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        let transactions = generate_transactions("alice.near", "alice.near", 4, 5);
+        assert_eq!(
+            pool.insert_transaction(transactions[0].clone()),
+            InsertTransactionResult::SignerQuotaExceeded
+        );
+        assert_eq!(prepare_transactions(&mut pool, 2).len(), 2);
+        for tx in transactions {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        assert_eq!(pool.len(), 3);
+    }
+
+    /// A full pool only accepts transactions of a higher priority, in place of the lowest priority
+    /// transaction with the highest nonce in its group.
+    #[test]
+    fn test_capacity_eviction() {
+        let config = PoolConfig {
+            capacity: 3,
+            max_transactions_per_signer: DEFAULT_MAX_TRANSACTIONS_PER_SIGNER,
+            ..PoolConfig::default()
+        };
+        let mut pool = TransactionPool::with_ordering(config, Box::new(AttachedGasOrdering));
+        for tx in vec![
+            function_call("alice.near", 1, 100),
+            function_call("alice.near", 2, 300),
+            function_call("bob.near", 1, 200),
+        ] {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        assert_eq!(
+            pool.insert_transaction(function_call("carol.near", 1, 100)),
+            InsertTransactionResult::NoSpaceLeft
+        );
+        assert_eq!(
+            pool.insert_transaction(function_call("carol.near", 1, 250)),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(pool.len(), 3);
+        assert_eq!(pool.num_evicted, 1);
+        let mut txs: Vec<_> = prepare_transactions(&mut pool, 10)
+            .into_iter()
+            .map(|tx| (tx.transaction.signer_id, tx.transaction.nonce))
+            .collect();
+        txs.sort();
+        assert_eq!(
+            txs,
+            vec![
+                ("alice.near".to_string(), 1),
+                ("alice.near".to_string(), 2),
+                ("carol.near".to_string(), 1)
+            ]
+        );
+    }
+
+    /// Groups are pulled by the priority of their next transaction, while each group is still
+    /// pulled in nonce order.
+    #[test]
+    fn test_custom_ordering() {
+        let mut pool =
+            TransactionPool::with_ordering(PoolConfig::default(), Box::new(AttachedGasOrdering));
+        let mut transactions = vec![
+            function_call("alice.near", 1, 10),
+            function_call("alice.near", 2, 1000),
+            function_call("bob.near", 1, 100),
+            function_call("bob.near", 2, 20),
+        ];
+        transactions.shuffle(&mut thread_rng());
+        for tx in transactions {
+            pool.insert_transaction(tx);
+        }
+        let txs: Vec<_> = prepare_transactions(&mut pool, 10)
+            .into_iter()
+            .map(|tx| (tx.transaction.signer_id, tx.transaction.nonce))
+            .collect();
+        assert_eq!(
+            txs,
+            vec![
+                ("bob.near".to_string(), 1),
+                ("bob.near".to_string(), 2),
+                ("alice.near".to_string(), 1),
+                ("alice.near".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remove_expired_transactions() {
+        let signer = InMemorySigner::from_seed("alice.near", KeyType::ED25519, "alice.near");
+        let old_block_hash = hash(&[1]);
+        let mut pool = TransactionPool::default();
+        for nonce in 1..=6 {
+            let block_hash = if nonce % 2 == 0 { old_block_hash } else { CryptoHash::default() };
+            pool.insert_transaction(SignedTransaction::send_money(
+                nonce,
+                "alice.near".to_string(),
+                "bob.near".to_string(),
+                &signer,
+                nonce as Balance,
+                block_hash,
+            ));
+        }
+        let mut num_checks = 0;
+        let num_expired = pool.remove_expired_transactions(|block_hash| {
+            num_checks += 1;
+            *block_hash == old_block_hash
+        });
+        assert_eq!(num_expired, 3);
+        assert_eq!(num_checks, 2);
+        assert_eq!(pool.len(), 3);
+        assert_eq!(pool.num_evicted, 3);
+        let nonces: Vec<u64> =
+            prepare_transactions(&mut pool, 10).iter().map(|tx| tx.transaction.nonce).collect();
+        assert_eq!(nonces, vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn test_pending_transactions() {
+        let mut pool = TransactionPool::default();
+        let mut transactions = generate_transactions("alice.near", "alice.near", 1, 3);
+        transactions.extend(generate_transactions("alice.near", "bob.near", 1, 2));
+        transactions.extend(generate_transactions("bob.near", "bob.near", 1, 4));
+        for tx in transactions {
+            pool.insert_transaction(tx);
+        }
+        assert_eq!(pool.num_groups(), 3);
+        assert_eq!(pool.pending_transactions(None).len(), 9);
+        let alice = pool.pending_transactions(Some(&"alice.near".to_string()));
+        assert_eq!(alice.len(), 5);
+        assert!(alice.iter().all(|(tx, _)| tx.transaction.signer_id == "alice.near"));
+
+        std::thread::sleep(Duration::from_millis(10));
+        let pulled = prepare_transactions(&mut pool, 9);
+        pool.reintroduce_transactions(pulled);
+        assert_eq!(pool.len(), 9);
+        assert!(pool
+            .pending_transactions(None)
+            .iter()
+            .all(|(_, age)| *age >= Duration::from_millis(10)));
+    }
 }
@@ -1,3 +1,5 @@
+use serde_derive::{Deserialize, Serialize};
+
 use near_crypto::PublicKey;
 use near_primitives::hash::CryptoHash;
 use near_primitives::transaction::SignedTransaction;
@@ -5,7 +7,7 @@ use near_primitives::types::AccountId;
 
 /// Trait acts like an iterator. It iterates over transactions groups by returning mutable
 /// references to them. Each transaction group implements a draining iterator to pull transactions.
-/// The order of the transaction groups is round robin scheduling.
+/// The order of the transaction groups is defined by the `TransactionOrdering` of the pool.
 /// When this iterator is dropped the remaining transactions are returned back to the pool.
 pub trait PoolIterator {
     fn next(&mut self) -> Option<&mut TransactionGroup>;
@@ -37,3 +39,80 @@ impl TransactionGroup {
         }
     }
 }
+
+/// Strategy that decides which transaction group is pulled from the pool next, and which
+/// transaction is evicted when the pool is full.
+/// The group whose next transaction (the one with the smallest nonce) has the highest priority goes
+/// first, so transactions within a group always stay in nonce order. Groups with equal priority
+/// are pulled in round robin.
+pub trait TransactionOrdering: Send + Sync {
+    fn priority(&self, transaction: &SignedTransaction) -> u128;
+}
+
+/// Gives every transaction the same priority, which results in round robin over the groups.
+pub struct RoundRobinOrdering;
+
+impl TransactionOrdering for RoundRobinOrdering {
+    fn priority(&self, _transaction: &SignedTransaction) -> u128 {
+        0
+    }
+}
+
+/// Built-in ordering strategies that can be selected in the config.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum PoolOrdering {
+    #[serde(rename = "round_robin")]
+    RoundRobin,
+}
+
+impl PoolOrdering {
+    pub fn strategy(&self) -> Box<dyn TransactionOrdering> {
+        match self {
+            PoolOrdering::RoundRobin => Box::new(RoundRobinOrdering),
+        }
+    }
+}
+
+impl Default for PoolOrdering {
+    fn default() -> Self {
+        PoolOrdering::RoundRobin
+    }
+}
+
+pub const DEFAULT_POOL_CAPACITY: usize = 100_000;
+pub const DEFAULT_MAX_TRANSACTIONS_PER_SIGNER: usize = 1_000;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct PoolConfig {
+    /// Maximum number of transactions kept in the pool.
+    pub capacity: usize,
+    /// Maximum number of transactions kept in the pool for a single signer account, across all of
+    /// its access keys.
+    pub max_transactions_per_signer: usize,
+    /// Order in which transaction groups are pulled from the pool.
+    pub ordering: PoolOrdering,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            capacity: DEFAULT_POOL_CAPACITY,
+            max_transactions_per_signer: DEFAULT_MAX_TRANSACTIONS_PER_SIGNER,
+            ordering: PoolOrdering::default(),
+        }
+    }
+}
+
+/// Outcome of inserting a transaction into the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertTransactionResult {
+    /// The transaction was added to the pool.
+    Success,
+    /// The transaction is already in the pool.
+    Duplicate,
+    /// The signer already has `max_transactions_per_signer` transactions in the pool.
+    SignerQuotaExceeded,
+    /// The pool is full and has no transaction of lower priority to evict.
+    NoSpaceLeft,
+}
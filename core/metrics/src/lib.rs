@@ -55,7 +55,9 @@
 //! }
 //! ```
 
-pub use prometheus::{Encoder, Histogram, IntCounter, IntGauge, Result, TextEncoder};
+pub use prometheus::{
+    Encoder, Histogram, IntCounter, IntCounterVec, IntGauge, Result, TextEncoder,
+};
 use prometheus::{HistogramOpts, HistogramTimer, Opts};
 
 use log::error;
@@ -74,6 +76,19 @@ pub fn try_create_int_counter(name: &str, help: &str) -> Result<IntCounter> {
     Ok(counter)
 }
 
+/// Attempts to crate an `IntCounterVec`, returning `Err` if the registry does not accept the counter
+/// (potentially due to naming conflict).
+pub fn try_create_int_counter_vec(
+    name: &str,
+    help: &str,
+    labels: &[&str],
+) -> Result<IntCounterVec> {
+    let opts = Opts::new(name, help);
+    let counter = IntCounterVec::new(opts, labels)?;
+    prometheus::register(Box::new(counter.clone()))?;
+    Ok(counter)
+}
+
 /// Attempts to crate an `IntGauge`, returning `Err` if the registry does not accept the counter
 /// (potentially due to naming conflict).
 pub fn try_create_int_gauge(name: &str, help: &str) -> Result<IntGauge> {
@@ -132,6 +147,14 @@ pub fn inc_counter_by(counter: &Result<IntCounter>, value: i64) {
     }
 }
 
+pub fn inc_counter_vec_by(counter: &Result<IntCounterVec>, labels: &[&str], value: i64) {
+    if let Ok(counter) = counter {
+        counter.with_label_values(labels).inc_by(value);
+    } else {
+        error!(target: "metrics", "Failed to fetch counter");
+    }
+}
+
 pub fn set_gauge(gauge: &Result<IntGauge>, value: i64) {
     if let Ok(gauge) = gauge {
         gauge.set(value);
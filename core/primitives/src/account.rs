@@ -1,5 +1,9 @@
+use std::collections::HashSet;
+
 use borsh::{BorshDeserialize, BorshSerialize};
 
+use near_crypto::PublicKey;
+
 use crate::hash::CryptoHash;
 use crate::types::{AccountId, Balance, BlockIndex, Nonce, StorageUsage};
 
@@ -55,6 +59,9 @@ pub enum AccessKeyPermission {
     /// Grants full access to the account.
     /// NOTE: It's used to replace account-level public keys.
     FullAccess,
+
+    /// Grants full access to the account, once the transaction is signed by enough keys.
+    Multisig(MultisigPermission),
 }
 
 /// Grants limited permission to make transactions with FunctionCallActions
@@ -80,6 +87,48 @@ pub struct FunctionCallPermission {
     pub method_names: Vec<String>,
 }
 
+/// Maximum number of keys of a multisig access key.
+pub const MAX_MULTISIG_KEYS: usize = 32;
+
+/// Grants full access to transactions signed by a set of keys with the total weight reaching the
+/// threshold. The public key of the access key itself only counts if it's listed in `keys`.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Hash, Clone, Debug)]
+pub struct MultisigPermission {
+    /// Keys that can approve transactions on behalf of this access key.
+    pub keys: Vec<WeightedKey>,
+
+    /// Minimum total weight of the keys that signed the transaction.
+    pub threshold: u64,
+}
+
+impl MultisigPermission {
+    /// Total weight of the keys of the set that are among the given signing keys.
+    pub fn signed_weight(&self, public_keys: &[&PublicKey]) -> u64 {
+        self.keys
+            .iter()
+            .filter(|key| public_keys.contains(&&key.public_key))
+            .fold(0u64, |weight, key| weight.saturating_add(key.weight))
+    }
+
+    /// Whether the keys are unique and there are at most `MAX_MULTISIG_KEYS` of them, and the
+    /// threshold is positive and reachable by their total weight.
+    pub fn is_valid(&self) -> bool {
+        let unique_keys = self.keys.iter().map(|key| &key.public_key).collect::<HashSet<_>>();
+        let total_weight = self.keys.iter().map(|key| key.weight as u128).sum::<u128>();
+        !self.keys.is_empty()
+            && self.keys.len() <= MAX_MULTISIG_KEYS
+            && unique_keys.len() == self.keys.len()
+            && self.threshold > 0
+            && self.threshold as u128 <= total_weight
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Hash, Clone, Debug)]
+pub struct WeightedKey {
+    pub public_key: PublicKey,
+    pub weight: u64,
+}
+
 #[cfg(test)]
 mod tests {
     use borsh::BorshSerialize;
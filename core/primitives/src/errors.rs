@@ -1,3 +1,4 @@
+use crate::account::MAX_MULTISIG_KEYS;
 use crate::types::{AccountId, Balance, Nonce};
 use borsh::{BorshDeserialize, BorshSerialize};
 use near_crypto::PublicKey;
@@ -49,6 +50,8 @@ pub enum InvalidAccessKeyError {
     MethodNameMismatch(String),
     ActionError,
     NotEnoughAllowance(AccountId, PublicKey, Balance, Balance),
+    NotEnoughSignatureWeight(AccountId, PublicKey, u64, u64),
+    InvalidExtraSignatures(AccountId, PublicKey),
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
@@ -59,6 +62,7 @@ pub enum ActionError {
     ActorNoPermission(AccountId, AccountId, String),
     DeleteKeyDoesNotExist(AccountId),
     AddKeyAlreadyExists(PublicKey),
+    AddKeyInvalidMultisig(PublicKey),
     DeleteAccountStaking(AccountId),
     DeleteAccountHasRent(AccountId, Balance),
     RentUnpaid(AccountId, Balance),
@@ -143,6 +147,22 @@ impl Display for InvalidAccessKeyError {
                     account_id, public_key, allowance, cost
                 )
             }
+            InvalidAccessKeyError::NotEnoughSignatureWeight(
+                account_id,
+                public_key,
+                weight,
+                threshold,
+            ) => write!(
+                f,
+                "Access Key {:?}:{} requires signatures of weight {}, but only {} was signed",
+                account_id, public_key, threshold, weight
+            ),
+            InvalidAccessKeyError::InvalidExtraSignatures(account_id, public_key) => write!(
+                f,
+                "Access Key {:?}:{} only accepts extra signatures by distinct keys of its multisig \
+                 key set",
+                account_id, public_key
+            ),
         }
     }
 }
@@ -308,6 +328,12 @@ impl Display for ActionError {
                 "The public key {:?} is already used for an existing access key",
                 public_key
             ),
+            ActionError::AddKeyInvalidMultisig(public_key) => write!(
+                f,
+                "The multisig access key {:?} needs up to {} distinct keys and a positive \
+                 threshold reachable by their weights",
+                public_key, MAX_MULTISIG_KEYS
+            ),
             ActionError::DeleteAccountStaking(account_id) => {
                 write!(f, "Account {:?} is staking and can not be deleted", account_id)
             }
@@ -81,8 +81,8 @@ pub fn merklize<T: BorshSerialize>(arr: &[T]) -> (MerkleHash, Vec<MerklePath>) {
     (hashes[0], paths)
 }
 
-/// Verify merkle path for given item and corresponding path.
-pub fn verify_path<T: BorshSerialize>(root: MerkleHash, path: &MerklePath, item: &T) -> bool {
+/// Compute the root from given item and the merkle path from it.
+pub fn compute_root_from_path<T: BorshSerialize>(path: &MerklePath, item: &T) -> MerkleHash {
     let mut hash = hash(&item.try_to_vec().expect("Failed to serialize"));
     for item in path {
         match item.direction {
@@ -94,7 +94,12 @@ pub fn verify_path<T: BorshSerialize>(root: MerkleHash, path: &MerklePath, item:
             }
         }
     }
-    hash == root
+    hash
+}
+
+/// Verify merkle path for given item and corresponding path.
+pub fn verify_path<T: BorshSerialize>(root: MerkleHash, path: &MerklePath, item: &T) -> bool {
+    compute_root_from_path(path, item) == root
 }
 
 #[cfg(test)]
@@ -189,6 +189,28 @@ pub mod vec_base_format {
     }
 }
 
+pub mod base64_format {
+    use serde::de;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{from_base64, to_base64};
+
+    pub fn serialize<S>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&to_base64(data))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        from_base64(&s).map_err(|err| de::Error::custom(err.to_string()))
+    }
+}
+
 pub mod option_base64_format {
     use serde::de;
     use serde::{Deserialize, Deserializer, Serializer};
@@ -241,6 +263,36 @@ pub mod base_bytes_format {
     }
 }
 
+pub mod option_base_bytes_format {
+    use serde::de;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{from_base, to_base};
+
+    pub fn serialize<S>(data: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if let Some(ref bytes) = data {
+            serializer.serialize_str(&to_base(bytes))
+        } else {
+            serializer.serialize_none()
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: Option<String> = Option::deserialize(deserializer)?;
+        if let Some(s) = s {
+            Ok(Some(from_base(&s).map_err(|err| de::Error::custom(err.to_string()))?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
 pub mod u128_dec_format {
     use serde::de;
     use serde::{Deserialize, Deserializer, Serializer};
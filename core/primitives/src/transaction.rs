@@ -1,5 +1,6 @@
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::io::{Error, ErrorKind, Read, Write};
 
 use borsh::{BorshDeserialize, BorshSerialize};
 
@@ -142,30 +143,61 @@ pub struct DeleteAccountAction {
     pub beneficiary_id: AccountId,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Eq, Debug, Clone)]
-#[borsh_init(init)]
+/// Written in place of the key type of the signature of a transaction that carries extra
+/// signatures, which follow the signature. Transactions without them keep the original encoding.
+const EXTRA_SIGNATURES_TAG: u8 = 0xff;
+
+#[derive(Eq, Debug, Clone)]
 pub struct SignedTransaction {
     pub transaction: Transaction,
     pub signature: Signature,
-    #[borsh_skip]
+    /// Signatures of the transaction hash by keys other than `transaction.public_key`, used to
+    /// reach the threshold of multisig access keys.
+    pub extra_signatures: Vec<(PublicKey, Signature)>,
     hash: CryptoHash,
+    signing_hash: CryptoHash,
 }
 
 impl SignedTransaction {
     pub fn new(signature: Signature, transaction: Transaction) -> Self {
-        let mut signed_tx = Self { signature, transaction, hash: CryptoHash::default() };
+        let mut signed_tx = Self {
+            signature,
+            transaction,
+            extra_signatures: vec![],
+            hash: CryptoHash::default(),
+            signing_hash: CryptoHash::default(),
+        };
         signed_tx.init();
         signed_tx
     }
 
+    /// The hash of a transaction with extra signatures covers all of its signatures, so that they
+    /// can't be stripped or replaced without changing it.
     pub fn init(&mut self) {
-        self.hash = self.transaction.get_hash();
+        self.signing_hash = self.transaction.get_hash();
+        self.hash = if self.extra_signatures.is_empty() {
+            self.signing_hash
+        } else {
+            hash(&self.try_to_vec().expect("Failed to serialize"))
+        };
     }
 
     pub fn get_hash(&self) -> CryptoHash {
         self.hash
     }
 
+    /// Hash of the transaction that all of its signatures sign.
+    pub fn get_signing_hash(&self) -> CryptoHash {
+        self.signing_hash
+    }
+
+    /// Adds the signature of the transaction hash by another signer.
+    pub fn add_signature(&mut self, signer: &dyn Signer) {
+        let signature = signer.sign(self.signing_hash.as_ref());
+        self.extra_signatures.push((signer.public_key(), signature));
+        self.init();
+    }
+
     pub fn from_actions(
         nonce: Nonce,
         signer_id: AccountId,
@@ -186,6 +218,45 @@ impl SignedTransaction {
     }
 }
 
+impl BorshSerialize for SignedTransaction {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.transaction.serialize(writer)?;
+        if self.extra_signatures.is_empty() {
+            self.signature.serialize(writer)
+        } else {
+            EXTRA_SIGNATURES_TAG.serialize(writer)?;
+            self.signature.serialize(writer)?;
+            self.extra_signatures.serialize(writer)
+        }
+    }
+}
+
+impl BorshDeserialize for SignedTransaction {
+    fn deserialize<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let transaction = Transaction::deserialize(reader)?;
+        let tag = u8::deserialize(reader)?;
+        let (signature, extra_signatures) = if tag == EXTRA_SIGNATURES_TAG {
+            let signature = Signature::deserialize(reader)?;
+            let extra_signatures = Vec::<(PublicKey, Signature)>::deserialize(reader)?;
+            if extra_signatures.is_empty() {
+                return Err(Error::new(ErrorKind::InvalidData, "Empty extra signatures"));
+            }
+            (signature, extra_signatures)
+        } else {
+            (Signature::deserialize(&mut (&[tag][..]).chain(reader))?, vec![])
+        };
+        let mut signed_tx = Self {
+            transaction,
+            signature,
+            extra_signatures,
+            hash: CryptoHash::default(),
+            signing_hash: CryptoHash::default(),
+        };
+        signed_tx.init();
+        Ok(signed_tx)
+    }
+}
+
 impl Hash for SignedTransaction {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.hash.hash(state)
@@ -194,7 +265,9 @@ impl Hash for SignedTransaction {
 
 impl PartialEq for SignedTransaction {
     fn eq(&self, other: &SignedTransaction) -> bool {
-        self.hash == other.hash && self.signature == other.signature
+        self.hash == other.hash
+            && self.signature == other.signature
+            && self.extra_signatures == other.extra_signatures
     }
 }
 
@@ -251,6 +324,20 @@ pub struct ExecutionOutcome {
     pub receipt_ids: Vec<CryptoHash>,
     /// The amount of the gas burnt by the given transaction or receipt.
     pub gas_burnt: Gas,
+    /// Events emitted by the contract, if the execution succeeded.
+    pub events: Vec<ContractEvent>,
+}
+
+/// Event emitted by the contract through `emit_event`. Unlike logs, events are typed, so that
+/// indexers can find them by the name and the topics without parsing free-form strings.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Clone, Debug)]
+pub struct ContractEvent {
+    /// The contract that emitted the event.
+    pub account_id: AccountId,
+    pub name: String,
+    /// Indexed topics, 32 bytes each.
+    pub topics: Vec<[u8; 32]>,
+    pub data: Vec<u8>,
 }
 
 impl ExecutionOutcome {
@@ -267,6 +354,11 @@ impl ExecutionOutcome {
         for log in self.logs.iter() {
             result.push(hash(log.as_bytes()));
         }
+        // Outcomes without events hash the same as before events were introduced, so the
+        // outcome roots of the existing chunks don't change.
+        for event in self.events.iter() {
+            result.push(hash(&event.try_to_vec().expect("Failed to serialize")));
+        }
         result
     }
 }
@@ -278,6 +370,7 @@ impl fmt::Debug for ExecutionOutcome {
             .field("logs", &format_args!("{}", logging::pretty_vec(&self.logs)))
             .field("receipt_ids", &format_args!("{}", logging::pretty_vec(&self.receipt_ids)))
             .field("burnt_gas", &self.gas_burnt)
+            .field("events", &format_args!("{}", logging::pretty_vec(&self.events)))
             .finish()
     }
 }
@@ -318,7 +411,7 @@ pub fn verify_transaction_signature(
     transaction: &SignedTransaction,
     public_keys: &[PublicKey],
 ) -> bool {
-    let hash = transaction.get_hash();
+    let hash = transaction.get_signing_hash();
     let hash = hash.as_ref();
     public_keys.iter().any(|key| transaction.signature.verify(&hash, &key))
 }
@@ -408,6 +501,39 @@ mod tests {
         );
     }
 
+    /// Transactions without extra signatures keep the original encoding, while the hash of the
+    /// ones with extra signatures changes with their signatures.
+    #[test]
+    fn test_serialize_extra_signatures() {
+        let signers: Vec<_> = (0..3)
+            .map(|i| InMemorySigner::from_seed("test", KeyType::ED25519, &i.to_string()))
+            .collect();
+        let tx = SignedTransaction::send_money(
+            1,
+            "test".to_string(),
+            "other".to_string(),
+            &signers[0],
+            100,
+            CryptoHash::default(),
+        );
+        let mut bytes = tx.transaction.try_to_vec().unwrap();
+        bytes.extend(tx.signature.try_to_vec().unwrap());
+        assert_eq!(tx.try_to_vec().unwrap(), bytes);
+
+        let mut multisig_tx = tx.clone();
+        multisig_tx.add_signature(&signers[1]);
+        let decoded_tx =
+            SignedTransaction::try_from_slice(&multisig_tx.try_to_vec().unwrap()).unwrap();
+        assert_eq!(decoded_tx, multisig_tx);
+        assert_eq!(decoded_tx.get_hash(), multisig_tx.get_hash());
+        assert_eq!(decoded_tx.get_signing_hash(), tx.get_hash());
+        assert_ne!(multisig_tx.get_hash(), tx.get_hash());
+
+        let mut other_tx = tx.clone();
+        other_tx.add_signature(&signers[2]);
+        assert_ne!(other_tx.get_hash(), multisig_tx.get_hash());
+    }
+
     #[test]
     fn test_outcome_to_hashes() {
         let outcome = ExecutionOutcome {
@@ -415,8 +541,17 @@ mod tests {
             logs: vec!["123".to_string(), "321".to_string()],
             receipt_ids: vec![],
             gas_burnt: 123,
+            events: vec![ContractEvent {
+                account_id: "alice.near".to_string(),
+                name: "transfer".to_string(),
+                topics: vec![[0; 32]],
+                data: vec![1, 2, 3],
+            }],
         };
         let hashes = outcome.to_hashes();
-        assert_eq!(hashes.len(), 3);
+        assert_eq!(hashes.len(), 4);
+        assert_eq!(hashes[3], hash(&outcome.events[0].try_to_vec().unwrap()));
+        let outcome_without_events = ExecutionOutcome { events: vec![], ..outcome };
+        assert_eq!(outcome_without_events.to_hashes(), hashes[..3].to_vec());
     }
 }
@@ -133,3 +133,35 @@ pub struct Version {
     pub version: String,
     pub build: String,
 }
+
+/// Block identifier: either its height or its hash.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum BlockId {
+    Height(BlockIndex),
+    Hash(CryptoHash),
+}
+
+/// How final the block to read from must be.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Finality {
+    /// Current head of the chain, which can still be reverted.
+    Optimistic,
+    /// Last block finalized by the head.
+    Final,
+}
+
+/// Block to read data from, either a given block or the latest one with the given finality.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum BlockIdOrFinality {
+    BlockId(BlockId),
+    Finality(Finality),
+}
+
+impl BlockIdOrFinality {
+    pub fn latest() -> Self {
+        BlockIdOrFinality::Finality(Finality::Optimistic)
+    }
+}
@@ -7,22 +7,26 @@ use chrono::{DateTime, Utc};
 use borsh::{BorshDeserialize, BorshSerialize};
 use near_crypto::{PublicKey, Signature};
 
-use crate::account::{AccessKey, AccessKeyPermission, Account, FunctionCallPermission};
+use crate::account::{
+    AccessKey, AccessKeyPermission, Account, FunctionCallPermission, MultisigPermission,
+    WeightedKey,
+};
 use crate::block::{Approval, Block, BlockHeader, BlockHeaderInnerLite, BlockHeaderInnerRest};
 use crate::challenge::{Challenge, ChallengesResult};
 use crate::errors::{ActionError, ExecutionError, InvalidAccessKeyError, InvalidTxError};
-use crate::hash::CryptoHash;
+use crate::hash::{CryptoHash, Digest};
 use crate::logging;
-use crate::merkle::MerklePath;
+use crate::merkle::{combine_hash, compute_root_from_path, verify_path, MerklePath};
 use crate::receipt::{ActionReceipt, DataReceipt, DataReceiver, Receipt, ReceiptEnum};
 use crate::serialize::{
-    from_base64, option_base64_format, option_u128_dec_format, to_base64, u128_dec_format,
+    base64_format, from_base64, option_base64_format, option_base_bytes_format,
+    option_u128_dec_format, to_base64, u128_dec_format,
 };
 use crate::sharding::{ChunkHash, ShardChunk, ShardChunkHeader, ShardChunkHeaderInner};
 use crate::transaction::{
-    Action, AddKeyAction, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
-    DeployContractAction, ExecutionOutcome, ExecutionOutcomeWithIdAndProof, ExecutionStatus,
-    FunctionCallAction, SignedTransaction, StakeAction, TransferAction,
+    Action, AddKeyAction, ContractEvent, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
+    DeployContractAction, ExecutionOutcome, ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof,
+    ExecutionStatus, FunctionCallAction, SignedTransaction, StakeAction, TransferAction,
 };
 use crate::types::{
     AccountId, Balance, BlockIndex, EpochId, Gas, Nonce, ShardId, StateRoot, StorageUsage,
@@ -74,6 +78,28 @@ pub enum AccessKeyPermissionView {
         method_names: Vec<String>,
     },
     FullAccess,
+    Multisig {
+        keys: Vec<WeightedKeyView>,
+        threshold: u64,
+    },
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct WeightedKeyView {
+    pub public_key: PublicKey,
+    pub weight: u64,
+}
+
+impl From<WeightedKey> for WeightedKeyView {
+    fn from(key: WeightedKey) -> Self {
+        WeightedKeyView { public_key: key.public_key, weight: key.weight }
+    }
+}
+
+impl From<WeightedKeyView> for WeightedKey {
+    fn from(view: WeightedKeyView) -> Self {
+        WeightedKey { public_key: view.public_key, weight: view.weight }
+    }
 }
 
 impl From<AccessKeyPermission> for AccessKeyPermissionView {
@@ -85,6 +111,10 @@ impl From<AccessKeyPermission> for AccessKeyPermissionView {
                 method_names: func_call.method_names,
             },
             AccessKeyPermission::FullAccess => AccessKeyPermissionView::FullAccess,
+            AccessKeyPermission::Multisig(multisig) => AccessKeyPermissionView::Multisig {
+                keys: multisig.keys.into_iter().map(|key| key.into()).collect(),
+                threshold: multisig.threshold,
+            },
         }
     }
 }
@@ -100,6 +130,12 @@ impl From<AccessKeyPermissionView> for AccessKeyPermission {
                 })
             }
             AccessKeyPermissionView::FullAccess => AccessKeyPermission::FullAccess,
+            AccessKeyPermissionView::Multisig { keys, threshold } => {
+                AccessKeyPermission::Multisig(MultisigPermission {
+                    keys: keys.into_iter().map(|key| key.into()).collect(),
+                    threshold,
+                })
+            }
         }
     }
 }
@@ -125,6 +161,9 @@ impl From<AccessKeyView> for AccessKey {
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct ViewStateResult {
     pub values: HashMap<Vec<u8>, Vec<u8>>,
+    /// Start key of the next page, if the values were limited and there are more keys left.
+    #[serde(default, with = "option_base_bytes_format", skip_serializing_if = "Option::is_none")]
+    pub next_key: Option<Vec<u8>>,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -168,11 +207,25 @@ pub enum QueryResponseKind {
     Validators(EpochValidatorInfo),
 }
 
+/// Trie nodes read while answering a query, enough to check the answer against the state root
+/// with `near_store::verify_state_proof`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct StateProofView {
+    /// State root of the chunk the query was answered from.
+    pub state_root: StateRoot,
+    pub nodes: Vec<Vec<u8>>,
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct QueryResponse {
     #[serde(flatten)]
     pub kind: QueryResponseKind,
     pub block_height: BlockIndex,
+    /// Present only if the proof was requested. Queries with proofs are never routed to peers,
+    /// so it's not sent over the network.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[borsh_skip]
+    pub proof: Option<StateProofView>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -381,7 +434,7 @@ impl From<BlockHeaderView> for BlockHeader {
     }
 }
 
-#[derive(Serialize, Debug, Clone, BorshDeserialize, BorshSerialize)]
+#[derive(Serialize, Deserialize, Debug, Clone, BorshDeserialize, BorshSerialize)]
 pub struct BlockHeaderInnerLiteView {
     pub height: BlockIndex,
     pub epoch_id: CryptoHash,
@@ -392,6 +445,34 @@ pub struct BlockHeaderInnerLiteView {
     pub next_bp_hash: CryptoHash,
 }
 
+impl From<BlockHeaderInnerLite> for BlockHeaderInnerLiteView {
+    fn from(inner_lite: BlockHeaderInnerLite) -> Self {
+        BlockHeaderInnerLiteView {
+            height: inner_lite.height,
+            epoch_id: inner_lite.epoch_id.0,
+            next_epoch_id: inner_lite.next_epoch_id.0,
+            prev_state_root: inner_lite.prev_state_root,
+            outcome_root: inner_lite.outcome_root,
+            timestamp: inner_lite.timestamp,
+            next_bp_hash: inner_lite.next_bp_hash,
+        }
+    }
+}
+
+impl From<BlockHeaderInnerLiteView> for BlockHeaderInnerLite {
+    fn from(view: BlockHeaderInnerLiteView) -> Self {
+        BlockHeaderInnerLite {
+            height: view.height,
+            epoch_id: EpochId(view.epoch_id),
+            next_epoch_id: EpochId(view.next_epoch_id),
+            prev_state_root: view.prev_state_root,
+            outcome_root: view.outcome_root,
+            timestamp: view.timestamp,
+            next_bp_hash: view.next_bp_hash,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ChunkHeaderView {
     pub chunk_hash: CryptoHash,
@@ -613,6 +694,7 @@ pub struct SignedTransactionView {
     receiver_id: AccountId,
     actions: Vec<ActionView>,
     signature: Signature,
+    extra_signatures: Vec<(PublicKey, Signature)>,
     hash: CryptoHash,
 }
 
@@ -631,6 +713,7 @@ impl From<SignedTransaction> for SignedTransactionView {
                 .map(|action| action.into())
                 .collect(),
             signature: signed_tx.signature,
+            extra_signatures: signed_tx.extra_signatures,
             hash,
         }
     }
@@ -698,6 +781,9 @@ impl From<ExecutionError> for ExecutionErrorView {
                     ActionError::AddKeyAlreadyExists(_) => {
                         "ActionError::AddKeyAlreadyExists".to_string()
                     }
+                    ActionError::AddKeyInvalidMultisig(_) => {
+                        "ActionError::AddKeyInvalidMultisig".to_string()
+                    }
                     ActionError::DeleteAccountStaking(_) => {
                         "ActionError::DeleteAccountStaking".to_string()
                     }
@@ -734,6 +820,12 @@ impl From<ExecutionError> for ExecutionErrorView {
                         InvalidAccessKeyError::NotEnoughAllowance(_, _, _, _) => {
                             "InvalidTxError::InvalidAccessKey::NotEnoughAllowance".to_string()
                         }
+                        InvalidAccessKeyError::NotEnoughSignatureWeight(_, _, _, _) => {
+                            "InvalidTxError::InvalidAccessKey::NotEnoughSignatureWeight".to_string()
+                        }
+                        InvalidAccessKeyError::InvalidExtraSignatures(_, _) => {
+                            "InvalidTxError::InvalidAccessKey::InvalidExtraSignatures".to_string()
+                        }
                     },
                     InvalidTxError::InvalidNonce(_, _) => {
                         "InvalidTxError::InvalidNonce".to_string()
@@ -821,6 +913,9 @@ pub struct ExecutionOutcomeView {
     pub receipt_ids: Vec<CryptoHash>,
     /// The amount of the gas burnt by the given transaction or receipt.
     pub gas_burnt: Gas,
+    /// Events emitted by the contract.
+    #[serde(default)]
+    pub events: Vec<ContractEventView>,
 }
 
 impl From<ExecutionOutcome> for ExecutionOutcomeView {
@@ -830,10 +925,42 @@ impl From<ExecutionOutcome> for ExecutionOutcomeView {
             logs: outcome.logs,
             receipt_ids: outcome.receipt_ids,
             gas_burnt: outcome.gas_burnt,
+            events: outcome.events.into_iter().map(Into::into).collect(),
         }
     }
 }
 
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ContractEventView {
+    pub account_id: AccountId,
+    pub name: String,
+    pub topics: Vec<CryptoHash>,
+    /// Base64 encoded data of the event.
+    pub data: String,
+}
+
+impl From<ContractEvent> for ContractEventView {
+    fn from(event: ContractEvent) -> Self {
+        Self {
+            account_id: event.account_id,
+            name: event.name,
+            topics: event.topics.into_iter().map(|topic| CryptoHash(Digest(topic))).collect(),
+            data: to_base64(&event.data),
+        }
+    }
+}
+
+/// Event together with the outcome that emitted it, as returned by event filters.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ContractEventWithOutcomeView {
+    pub block_height: BlockIndex,
+    pub block_hash: CryptoHash,
+    /// Id of the receipt which execution emitted the event.
+    pub outcome_id: CryptoHash,
+    #[serde(flatten)]
+    pub event: ContractEventView,
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct ExecutionOutcomeWithIdView {
     pub id: CryptoHash,
@@ -872,6 +999,19 @@ impl fmt::Debug for FinalExecutionOutcomeView {
     }
 }
 
+/// Outcome of a transaction executed on top of the head state without being included in a block.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SimulationResultView {
+    /// The execution outcomes of the transaction and of its receipts, in the order of execution.
+    pub outcomes: Vec<ExecutionOutcomeWithIdView>,
+    /// The total amount of gas burnt by the transaction and its receipts.
+    pub gas_burnt: Gas,
+    /// Logs of all the outcomes.
+    pub logs: Vec<String>,
+    /// Receipts to accounts in other shards, which were not executed.
+    pub unprocessed_receipts: Vec<ReceiptView>,
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct ValidatorStakeView {
     pub account_id: AccountId,
@@ -1031,7 +1171,7 @@ pub struct LightClientApprovalView {
     pub signature: Signature,
 }
 
-#[derive(Serialize, Debug, Clone, BorshDeserialize, BorshSerialize)]
+#[derive(Serialize, Deserialize, Debug, Clone, BorshDeserialize, BorshSerialize)]
 pub struct LightClientBlockView {
     pub inner_lite: BlockHeaderInnerLiteView,
     pub inner_rest_hash: CryptoHash,
@@ -1043,8 +1183,115 @@ pub struct LightClientBlockView {
     pub prev_hash: CryptoHash,
 }
 
+/// Block header with everything but `inner_lite` replaced by its hash, enough to recompute the
+/// block hash.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LightClientBlockLiteView {
+    pub prev_hash: CryptoHash,
+    pub inner_rest_hash: CryptoHash,
+    pub inner_lite: BlockHeaderInnerLiteView,
+}
+
+impl From<BlockHeader> for LightClientBlockLiteView {
+    fn from(header: BlockHeader) -> Self {
+        Self {
+            prev_hash: header.prev_hash,
+            inner_rest_hash: header.inner_rest.hash(),
+            inner_lite: header.inner_lite.into(),
+        }
+    }
+}
+
+impl LightClientBlockLiteView {
+    pub fn hash(&self) -> CryptoHash {
+        let inner_lite: BlockHeaderInnerLite = self.inner_lite.clone().into();
+        combine_hash(combine_hash(inner_lite.hash(), self.inner_rest_hash), self.prev_hash)
+    }
+}
+
+/// Proof that the outcome of a transaction or receipt is included into a block.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LightClientProofView {
+    /// The outcome with the path from it to the outcome root of its chunk.
+    pub outcome_proof: ExecutionOutcomeWithIdView,
+    /// Borsh serialized `ExecutionOutcomeWithId`, since the view of the execution error is not
+    /// enough to recompute the outcome hashes.
+    #[serde(with = "base64_format")]
+    pub raw_outcome: Vec<u8>,
+    /// Path from the outcome root of the chunk to the outcome root of the block.
+    pub outcome_root_proof: MerklePath,
+    /// Header of the first block that includes the outcome root of the chunk.
+    pub block_header_lite: LightClientBlockLiteView,
+}
+
+impl LightClientProofView {
+    /// Checks that the outcome is included into the block with the given hash, which the caller
+    /// must have verified, e.g. with a light client.
+    pub fn verify(&self, block_hash: &CryptoHash) -> bool {
+        let outcome_with_id = match ExecutionOutcomeWithId::try_from_slice(&self.raw_outcome) {
+            Ok(outcome_with_id) => outcome_with_id,
+            Err(_) => return false,
+        };
+        if outcome_with_id.id != self.outcome_proof.id
+            || ExecutionOutcomeView::from(outcome_with_id.outcome.clone())
+                != self.outcome_proof.outcome
+        {
+            return false;
+        }
+        let chunk_outcome_root =
+            compute_root_from_path(&self.outcome_proof.proof, &outcome_with_id.to_hashes());
+        verify_path(
+            self.block_header_lite.inner_lite.outcome_root,
+            &self.outcome_root_proof,
+            &chunk_outcome_root,
+        ) && &self.block_header_lite.hash() == block_hash
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GasPriceView {
     #[serde(with = "u128_dec_format")]
     pub gas_price: Balance,
 }
+
+/// Transaction waiting in the pool of the node to be included into a chunk.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PendingTransactionView {
+    pub hash: CryptoHash,
+    pub shard_id: ShardId,
+    pub signer_id: AccountId,
+    pub public_key: PublicKey,
+    pub nonce: Nonce,
+    pub receiver_id: AccountId,
+    /// Milliseconds since the transaction was added to the pool.
+    pub age_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TransactionPoolStatsView {
+    pub shard_id: ShardId,
+    pub num_transactions: u64,
+    /// Number of groups of transactions with the same signer account and public key.
+    pub num_groups: u64,
+    /// Number of transactions evicted because the pool was full or because they expired.
+    pub num_evicted: u64,
+}
+
+/// Change of a state key under one of the prefixes the node is subscribed to.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StateChangeView {
+    #[serde(with = "base64_format")]
+    pub key: Vec<u8>,
+    /// New value of the key, or null if the key was deleted.
+    #[serde(with = "option_base64_format")]
+    pub value: Option<Vec<u8>>,
+}
+
+/// Changes of the subscribed state prefixes of a shard in a block.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StateChangesView {
+    pub block_height: BlockIndex,
+    pub block_hash: CryptoHash,
+    pub shard_id: ShardId,
+    pub changes: Vec<StateChangeView>,
+}
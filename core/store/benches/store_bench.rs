@@ -0,0 +1,82 @@
+#[macro_use]
+extern crate bencher;
+
+use bencher::Bencher;
+use rand::random;
+use tempdir::TempDir;
+
+use near_store::{ColState, Database, LsmDB, LsmOptions, RocksDB};
+
+const NUM_KEYS: usize = 10_000;
+
+fn rand_bytes(len: usize) -> Vec<u8> {
+    (0..len).map(|_| random::<u8>()).collect()
+}
+
+fn write_keys(db: &dyn Database, keys: &[Vec<u8>]) {
+    for chunk in keys.chunks(100) {
+        let mut transaction = db.transaction();
+        for key in chunk {
+            transaction.put(ColState, key, &rand_bytes(100));
+        }
+        db.write(transaction).unwrap();
+    }
+}
+
+fn bench_write(bench: &mut Bencher, db: &dyn Database) {
+    let keys: Vec<_> = (0..NUM_KEYS).map(|_| rand_bytes(32)).collect();
+    bench.iter(|| write_keys(db, &keys));
+}
+
+fn bench_read(bench: &mut Bencher, db: &dyn Database) {
+    let keys: Vec<_> = (0..NUM_KEYS).map(|_| rand_bytes(32)).collect();
+    write_keys(db, &keys);
+    bench.iter(|| {
+        for key in keys.iter() {
+            db.get(ColState, key).unwrap();
+        }
+    });
+}
+
+fn bench_iter(bench: &mut Bencher, db: &dyn Database) {
+    let keys: Vec<_> = (0..NUM_KEYS).map(|_| rand_bytes(32)).collect();
+    write_keys(db, &keys);
+    bench.iter(|| db.iter(ColState).count());
+}
+
+fn lsm_options() -> LsmOptions {
+    LsmOptions { memtable_size: 256 * 1024, ..LsmOptions::default() }
+}
+
+fn rocksdb_write(bench: &mut Bencher) {
+    let tmp_dir = TempDir::new("rocksdb_write").unwrap();
+    bench_write(bench, &RocksDB::new(tmp_dir.path()).unwrap());
+}
+
+fn rocksdb_read(bench: &mut Bencher) {
+    let tmp_dir = TempDir::new("rocksdb_read").unwrap();
+    bench_read(bench, &RocksDB::new(tmp_dir.path()).unwrap());
+}
+
+fn rocksdb_iter(bench: &mut Bencher) {
+    let tmp_dir = TempDir::new("rocksdb_iter").unwrap();
+    bench_iter(bench, &RocksDB::new(tmp_dir.path()).unwrap());
+}
+
+fn lsm_write(bench: &mut Bencher) {
+    let tmp_dir = TempDir::new("lsm_write").unwrap();
+    bench_write(bench, &LsmDB::new(tmp_dir.path(), lsm_options()).unwrap());
+}
+
+fn lsm_read(bench: &mut Bencher) {
+    let tmp_dir = TempDir::new("lsm_read").unwrap();
+    bench_read(bench, &LsmDB::new(tmp_dir.path(), lsm_options()).unwrap());
+}
+
+fn lsm_iter(bench: &mut Bencher) {
+    let tmp_dir = TempDir::new("lsm_iter").unwrap();
+    bench_iter(bench, &LsmDB::new(tmp_dir.path(), lsm_options()).unwrap());
+}
+
+benchmark_group!(benches, rocksdb_write, rocksdb_read, rocksdb_iter, lsm_write, lsm_read, lsm_iter);
+benchmark_main!(benches);
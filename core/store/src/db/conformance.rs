@@ -0,0 +1,231 @@
+//! Checks that a `Database` implementation behaves the way `Store` expects, so storage engines
+//! can be swapped without changing the rest of the node. Backends implemented outside of this
+//! crate can run the same checks from their tests, e.g. `check_database(|| MyDB::new())`.
+
+use std::path::Path;
+
+use crate::db::{DBCol, Database, ALL_COLUMNS};
+
+fn get(db: &dyn Database, col: DBCol, key: &[u8]) -> Option<Vec<u8>> {
+    db.get(col, key).unwrap_or_else(|err| panic!("Failed to read {}: {}", col, err))
+}
+
+fn write(db: &dyn Database, ops: &[(DBCol, &str, Option<&str>)]) {
+    let mut transaction = db.transaction();
+    for (col, key, value) in ops {
+        match value {
+            Some(value) => transaction.put(*col, key, value),
+            None => transaction.delete(*col, key),
+        }
+    }
+    db.write(transaction).expect("Failed to write a transaction");
+}
+
+fn entries(db: &dyn Database, col: DBCol) -> Vec<(Vec<u8>, Vec<u8>)> {
+    db.iter(col).map(|(key, value)| (key.to_vec(), value.to_vec())).collect()
+}
+
+/// Runs all the checks that don't need reopening the database, each on a new database.
+pub fn check_database<D: Database, F: FnMut() -> D>(mut new_db: F) {
+    check_empty(&new_db());
+    check_columns(&new_db());
+    check_transaction_order(&new_db());
+    check_delete_missing(&new_db());
+    check_iteration_order(&new_db());
+    check_iterator_snapshot(&new_db());
+    check_edge_sizes(&new_db());
+    check_many_keys(&new_db());
+}
+
+/// A new database has no data in any column.
+pub fn check_empty(db: &dyn Database) {
+    for col in ALL_COLUMNS.iter() {
+        assert_eq!(get(db, *col, b"key"), None, "{} is not empty", col);
+        assert_eq!(db.iter(*col).count(), 0, "{} is not empty", col);
+    }
+}
+
+/// Every column keeps its own data: the same key in different columns has different values.
+pub fn check_columns(db: &dyn Database) {
+    let mut transaction = db.transaction();
+    for (i, col) in ALL_COLUMNS.iter().enumerate() {
+        transaction.put(*col, b"key", &[i as u8]);
+    }
+    db.write(transaction).unwrap();
+    for (i, col) in ALL_COLUMNS.iter().enumerate() {
+        assert_eq!(get(db, *col, b"key"), Some(vec![i as u8]), "{}", col);
+        assert_eq!(entries(db, *col), vec![(b"key".to_vec(), vec![i as u8])], "{}", col);
+    }
+
+    let mut transaction = db.transaction();
+    for col in ALL_COLUMNS.iter().step_by(2) {
+        transaction.delete(*col, b"key");
+    }
+    db.write(transaction).unwrap();
+    for (i, col) in ALL_COLUMNS.iter().enumerate() {
+        let expected = if i % 2 == 0 { None } else { Some(vec![i as u8]) };
+        assert_eq!(get(db, *col, b"key"), expected, "{}", col);
+        assert_eq!(db.iter(*col).count(), i % 2, "{}", col);
+    }
+}
+
+/// Operations of a transaction are applied in order, the last one on a key wins.
+pub fn check_transaction_order(db: &dyn Database) {
+    let col = DBCol::ColBlock;
+    write(
+        db,
+        &[
+            (col, "a", Some("1")),
+            (col, "a", Some("2")),
+            (col, "b", Some("1")),
+            (col, "b", None),
+            (col, "c", None),
+            (col, "c", Some("3")),
+        ],
+    );
+    assert_eq!(get(db, col, b"a"), Some(b"2".to_vec()));
+    assert_eq!(get(db, col, b"b"), None);
+    assert_eq!(get(db, col, b"c"), Some(b"3".to_vec()));
+
+    // Overwrites and deletions across transactions.
+    write(db, &[(col, "a", None), (col, "b", Some("4")), (col, "c", Some("5"))]);
+    assert_eq!(get(db, col, b"a"), None);
+    assert_eq!(get(db, col, b"b"), Some(b"4".to_vec()));
+    assert_eq!(get(db, col, b"c"), Some(b"5".to_vec()));
+
+    // An empty transaction changes nothing.
+    db.write(db.transaction()).unwrap();
+    assert_eq!(entries(db, col).len(), 2);
+}
+
+/// Deleting a missing key is not an error and does not affect other keys.
+pub fn check_delete_missing(db: &dyn Database) {
+    let col = DBCol::ColState;
+    write(db, &[(col, "key", Some("value")), (col, "missing", None)]);
+    write(db, &[(col, "missing", None)]);
+    assert_eq!(get(db, col, b"missing"), None);
+    assert_eq!(entries(db, col), vec![(b"key".to_vec(), b"value".to_vec())]);
+}
+
+/// Iteration returns live entries of the column exactly once, sorted by the key bytes.
+pub fn check_iteration_order(db: &dyn Database) {
+    let col = DBCol::ColBlockHeader;
+    let keys = ["b", "", "\u{ff}", "a", "ab", "\x00", "aa", "a\x00", "\x7f"];
+    let ops: Vec<_> = keys.iter().map(|key| (col, *key, Some(*key))).collect();
+    write(db, &ops);
+    write(db, &[(col, "aa", None), (DBCol::ColBlockIndex, "c", Some("c"))]);
+    let mut expected: Vec<_> = keys
+        .iter()
+        .filter(|key| **key != "aa")
+        .map(|key| (key.as_bytes().to_vec(), key.as_bytes().to_vec()))
+        .collect();
+    expected.sort();
+    assert_eq!(entries(db, col), expected);
+}
+
+/// An iterator sees the column as of the moment it was created.
+pub fn check_iterator_snapshot(db: &dyn Database) {
+    let col = DBCol::ColChunks;
+    write(db, &[(col, "a", Some("1")), (col, "b", Some("2"))]);
+    let iter = db.iter(col);
+    write(db, &[(col, "a", None), (col, "c", Some("3"))]);
+    let seen: Vec<_> = iter.map(|(key, value)| (key.to_vec(), value.to_vec())).collect();
+    assert_eq!(seen, vec![(b"a".to_vec(), b"1".to_vec()), (b"b".to_vec(), b"2".to_vec())]);
+}
+
+/// Empty keys, empty values and large values are stored as is.
+pub fn check_edge_sizes(db: &dyn Database) {
+    let col = DBCol::ColStateParts;
+    let large = vec![7u8; 4 * 1024 * 1024];
+    let mut transaction = db.transaction();
+    transaction.put(col, b"", b"empty key");
+    transaction.put(col, b"empty", b"");
+    transaction.put(col, b"large", &large);
+    db.write(transaction).unwrap();
+    assert_eq!(get(db, col, b""), Some(b"empty key".to_vec()));
+    assert_eq!(get(db, col, b"empty"), Some(vec![]));
+    assert_eq!(get(db, col, b"large"), Some(large));
+}
+
+const NUM_KEYS: u32 = 10_000;
+
+/// Contents of `ColState` after `check_many_keys`: every third key is deleted and the next one
+/// is overwritten, except for the last hundred keys.
+fn many_keys_entries() -> Vec<(Vec<u8>, Vec<u8>)> {
+    let changed = NUM_KEYS / 300 * 300;
+    (0..NUM_KEYS)
+        .filter_map(|i| match i % 3 {
+            0 if i < changed => None,
+            1 if i < changed => Some((i, (i - 1) * 2)),
+            _ => Some((i, i)),
+        })
+        .map(|(key, value)| (key.to_be_bytes().to_vec(), value.to_le_bytes().to_vec()))
+        .collect()
+}
+
+/// Many keys written over many transactions, with overwrites and deletions. Large enough to make
+/// backends with in-memory buffers write to disk, given small buffer sizes.
+pub fn check_many_keys(db: &dyn Database) {
+    let col = DBCol::ColState;
+    for chunk in 0..NUM_KEYS / 100 {
+        let mut transaction = db.transaction();
+        for i in chunk * 100..(chunk + 1) * 100 {
+            transaction.put(col, &i.to_be_bytes(), &i.to_le_bytes());
+        }
+        db.write(transaction).unwrap();
+    }
+    for chunk in 0..NUM_KEYS / 300 {
+        let mut transaction = db.transaction();
+        for i in (chunk * 300..(chunk + 1) * 300).step_by(3) {
+            transaction.delete(col, &i.to_be_bytes());
+            transaction.put(col, &(i + 1).to_be_bytes(), &(i * 2).to_le_bytes());
+        }
+        db.write(transaction).unwrap();
+    }
+    let expected = many_keys_entries();
+    for (key, value) in expected.iter() {
+        assert_eq!(get(db, col, key).as_ref(), Some(value));
+    }
+    assert_eq!(get(db, col, &0u32.to_be_bytes()), None);
+    assert_eq!(entries(db, col), expected);
+}
+
+/// Data written before the database is closed is there after it is opened again.
+pub fn check_persistence<D: Database, F: Fn(&Path) -> D>(open: F, path: &Path) {
+    {
+        let db = open(path);
+        write(
+            &db,
+            &[
+                (DBCol::ColBlock, "a", Some("1")),
+                (DBCol::ColBlock, "", Some("2")),
+                (DBCol::ColBlockIndex, "a", Some("3")),
+            ],
+        );
+        write(&db, &[(DBCol::ColBlock, "", None)]);
+        check_many_keys(&db);
+    }
+    let db = open(path);
+    assert_eq!(get(&db, DBCol::ColBlock, b"a"), Some(b"1".to_vec()));
+    assert_eq!(get(&db, DBCol::ColBlock, b"b"), None);
+    assert_eq!(get(&db, DBCol::ColBlockIndex, b"a"), Some(b"3".to_vec()));
+    assert_eq!(entries(&db, DBCol::ColBlock), vec![(b"a".to_vec(), b"1".to_vec())]);
+    assert_eq!(entries(&db, DBCol::ColState), many_keys_entries());
+}
+
+/// A checkpoint has the data written before it was taken and nothing written after.
+pub fn check_checkpoint(db: &dyn Database, path: &Path) {
+    write(db, &[(DBCol::ColBlock, "a", Some("1")), (DBCol::ColBlock, "b", Some("2"))]);
+    check_many_keys(db);
+    write(db, &[(DBCol::ColBlock, "b", None), (DBCol::ColBlockIndex, "c", Some("3"))]);
+    let checkpoint = db.checkpoint(path).expect("Failed to create a checkpoint");
+    write(db, &[(DBCol::ColBlock, "a", Some("4")), (DBCol::ColBlockIndex, "d", Some("5"))]);
+
+    assert_eq!(entries(&*checkpoint, DBCol::ColBlock), vec![(b"a".to_vec(), b"1".to_vec())]);
+    assert_eq!(entries(&*checkpoint, DBCol::ColBlockIndex), vec![(b"c".to_vec(), b"3".to_vec())]);
+    assert_eq!(entries(&*checkpoint, DBCol::ColState), many_keys_entries());
+    // The checkpoint is independent of the original database.
+    write(&*checkpoint, &[(DBCol::ColBlock, "e", Some("6"))]);
+    assert_eq!(get(db, DBCol::ColBlock, b"e"), None);
+    assert!(db.checkpoint(path).is_err(), "Checkpoint overwrote an existing directory");
+}
@@ -0,0 +1,639 @@
+//! Embedded log-structured merge tree storage, an alternative to RocksDB.
+//!
+//! Writes are appended to a write-ahead log and applied to a sorted in-memory table. Once the
+//! in-memory table grows over `LsmOptions::memtable_size` it is flushed into an immutable sorted
+//! table file. When there are more than `LsmOptions::max_tables` tables, a background thread
+//! merges `LsmOptions::compaction_width` adjacent tables of the smallest total size into one,
+//! dropping overwritten values, and deletions once the oldest table is merged. The merged table
+//! replaces its sources atomically, so reads and writes go on while tables are merged.
+//!
+//! Keys of all the columns share one key space: the key is prefixed with the column number.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::iter::{self, Peekable};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use log::{error, info};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::db::{DBCol, DBError, DBOp, DBTransaction, Database};
+
+const WAL_FILENAME: &str = "LSM_WAL";
+const MANIFEST_FILENAME: &str = "LSM_MANIFEST";
+const TABLE_EXTENSION: &str = "sst";
+const TABLE_MAGIC: u64 = 0x6e65_6172_6c73_6d31;
+/// Every `INDEX_INTERVAL`-th entry of a table is kept in the in-memory index of the table.
+const INDEX_INTERVAL: usize = 16;
+
+/// Key with the column prefix, and the value or `None` for a deletion.
+type Entry = (Vec<u8>, Option<Vec<u8>>);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct LsmOptions {
+    /// Approximate size in bytes of keys and values kept in memory before flushing them to disk.
+    pub memtable_size: usize,
+    /// Number of tables on disk after which tables are merged in the background.
+    pub max_tables: usize,
+    /// Number of tables merged into one at a time. Bounds the work of a single merge.
+    pub compaction_width: usize,
+    /// Whether to fsync the write-ahead log on every write.
+    pub sync_writes: bool,
+}
+
+impl Default for LsmOptions {
+    fn default() -> Self {
+        LsmOptions {
+            memtable_size: 64 * 1024 * 1024,
+            max_tables: 8,
+            compaction_width: 4,
+            sync_writes: false,
+        }
+    }
+}
+
+fn column_key(col: DBCol, key: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(key.len() + 1);
+    result.push(col as u8);
+    result.extend_from_slice(key);
+    result
+}
+
+/// FNV-1a, used to detect torn writes at the end of the write-ahead log.
+fn checksum(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+fn write_entry<W: Write>(writer: &mut W, key: &[u8], value: &Option<Vec<u8>>) -> io::Result<()> {
+    writer.write_u32::<LittleEndian>(key.len() as u32)?;
+    writer.write_all(key)?;
+    match value {
+        Some(value) => {
+            writer.write_u8(1)?;
+            writer.write_u32::<LittleEndian>(value.len() as u32)?;
+            writer.write_all(value)
+        }
+        None => writer.write_u8(0),
+    }
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = reader.read_u32::<LittleEndian>()? as usize;
+    let mut result = vec![0; len];
+    reader.read_exact(&mut result)?;
+    Ok(result)
+}
+
+fn read_entry<R: Read>(reader: &mut R) -> io::Result<Entry> {
+    let key = read_bytes(reader)?;
+    let value = match reader.read_u8()? {
+        0 => None,
+        1 => Some(read_bytes(reader)?),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid entry tag")),
+    };
+    Ok((key, value))
+}
+
+/// Immutable sorted file of entries, followed by a sparse index and a footer:
+/// `entries | index_len: u32 | (key, offset: u64) * index_len | index_offset: u64 | magic: u64`.
+struct Table {
+    path: PathBuf,
+    file: Mutex<File>,
+    /// Key and offset of every `INDEX_INTERVAL`-th entry.
+    index: Vec<(Vec<u8>, u64)>,
+    /// Offset of the end of the entries.
+    data_end: u64,
+}
+
+impl Table {
+    /// Writes sorted entries into a new table at `path`. The table is complete once it is renamed
+    /// to `path`, so a crash never leaves a partially written table behind.
+    fn write<I: Iterator<Item = Entry>>(path: &Path, entries: I) -> io::Result<Table> {
+        let tmp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        let mut index = vec![];
+        let mut offset = 0u64;
+        for (i, (key, value)) in entries.enumerate() {
+            let mut buffer = vec![];
+            write_entry(&mut buffer, &key, &value)?;
+            writer.write_all(&buffer)?;
+            if i % INDEX_INTERVAL == 0 {
+                index.push((key, offset));
+            }
+            offset += buffer.len() as u64;
+        }
+        writer.write_u32::<LittleEndian>(index.len() as u32)?;
+        for (key, entry_offset) in index.iter() {
+            writer.write_u32::<LittleEndian>(key.len() as u32)?;
+            writer.write_all(key)?;
+            writer.write_u64::<LittleEndian>(*entry_offset)?;
+        }
+        writer.write_u64::<LittleEndian>(offset)?;
+        writer.write_u64::<LittleEndian>(TABLE_MAGIC)?;
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Table::open(path)
+    }
+
+    fn open(path: &Path) -> io::Result<Table> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::End(-16))?;
+        let data_end = file.read_u64::<LittleEndian>()?;
+        if file.read_u64::<LittleEndian>()? != TABLE_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a table", path.display()),
+            ));
+        }
+        file.seek(SeekFrom::Start(data_end))?;
+        let mut reader = BufReader::new(&mut file);
+        let index_len = reader.read_u32::<LittleEndian>()? as usize;
+        let mut index = Vec::with_capacity(index_len);
+        for _ in 0..index_len {
+            let key = read_bytes(&mut reader)?;
+            index.push((key, reader.read_u64::<LittleEndian>()?));
+        }
+        Ok(Table { path: path.to_path_buf(), file: Mutex::new(file), index, data_end })
+    }
+
+    /// Offset of the block of entries that may contain the given key, and the end of the block.
+    fn block_of(&self, key: &[u8]) -> Option<(u64, u64)> {
+        let position = match self.index.binary_search_by(|(index_key, _)| index_key[..].cmp(key)) {
+            Ok(position) => position,
+            Err(0) => return None,
+            Err(position) => position - 1,
+        };
+        let end = self.index.get(position + 1).map_or(self.data_end, |(_, offset)| *offset);
+        Some((self.index[position].1, end))
+    }
+
+    /// Returns `Some(None)` if the key is deleted in this table, `None` if it is not present.
+    fn get(&self, key: &[u8]) -> io::Result<Option<Option<Vec<u8>>>> {
+        let (start, end) = match self.block_of(key) {
+            Some(block) => block,
+            None => return Ok(None),
+        };
+        let mut block = vec![0; (end - start) as usize];
+        {
+            let mut file = self.file.lock().expect("Table file lock poisoned");
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(&mut block)?;
+        }
+        let mut cursor = Cursor::new(block);
+        while cursor.position() < end - start {
+            let (entry_key, value) = read_entry(&mut cursor)?;
+            if entry_key[..] == *key {
+                return Ok(Some(value));
+            }
+            if entry_key[..] > *key {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Iterates over the entries with keys greater or equal to `from`.
+    /// Reads through its own file handle, so it stays valid after the table is compacted away.
+    fn iter_from(&self, from: &[u8]) -> io::Result<TableIterator> {
+        let start = self.block_of(from).map_or(0, |(start, _)| start);
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(start))?;
+        Ok(TableIterator {
+            reader: BufReader::new(file),
+            position: start,
+            data_end: self.data_end,
+            from: from.to_vec(),
+        })
+    }
+}
+
+struct TableIterator {
+    reader: BufReader<File>,
+    position: u64,
+    data_end: u64,
+    from: Vec<u8>,
+}
+
+impl Iterator for TableIterator {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Entry> {
+        while self.position < self.data_end {
+            let mut counting = CountingReader { inner: &mut self.reader, count: 0 };
+            let entry = read_entry(&mut counting);
+            self.position += counting.count;
+            match entry {
+                Ok((key, value)) if key >= self.from => return Some((key, value)),
+                Ok(_) => {}
+                Err(err) => {
+                    error!(target: "store", "Failed to read a table: {}", err);
+                    self.position = self.data_end;
+                }
+            }
+        }
+        None
+    }
+}
+
+struct CountingReader<'a, R> {
+    inner: &'a mut R,
+    count: u64,
+}
+
+impl<'a, R: Read> Read for CountingReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+        Ok(read)
+    }
+}
+
+/// Merges sorted sources, newest first, into the latest entry for every key.
+struct MergeIterator<I: Iterator<Item = Entry>> {
+    sources: Vec<Peekable<I>>,
+}
+
+impl<I: Iterator<Item = Entry>> Iterator for MergeIterator<I> {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Entry> {
+        let key = self.sources.iter_mut().filter_map(|source| source.peek()).map(|e| &e.0).min()?;
+        let key = key.clone();
+        let mut result = None;
+        for source in self.sources.iter_mut() {
+            if source.peek().map_or(false, |(source_key, _)| *source_key == key) {
+                let entry = source.next();
+                if result.is_none() {
+                    result = entry;
+                }
+            }
+        }
+        result
+    }
+}
+
+struct LsmState {
+    /// Latest changes that are not flushed into a table yet.
+    memtable: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    memtable_size: usize,
+    /// Tables on disk, the newest first.
+    tables: Vec<Arc<Table>>,
+    next_table_id: u64,
+    wal: File,
+}
+
+pub struct LsmDB {
+    path: PathBuf,
+    options: LsmOptions,
+    state: Arc<RwLock<LsmState>>,
+    /// Wakes up the compaction thread. Dropped to stop it.
+    compaction_requests: Mutex<Option<Sender<()>>>,
+    compaction_thread: Option<JoinHandle<()>>,
+}
+
+impl LsmDB {
+    pub fn new<P: AsRef<Path>>(path: P, options: LsmOptions) -> Result<Self, DBError> {
+        let path = path.as_ref().to_path_buf();
+        fs::create_dir_all(&path)?;
+        if path.join("CURRENT").exists() {
+            return Err(DBError::Io(format!(
+                "{} contains a RocksDB database, it can't be opened as LSM storage",
+                path.display()
+            )));
+        }
+        let table_ids = Self::read_manifest(&path)?;
+        let next_table_id = table_ids.iter().max().map_or(0, |id| id + 1);
+        let tables = table_ids
+            .iter()
+            .map(|id| Table::open(&Self::table_path(&path, *id)).map(Arc::new))
+            .collect::<io::Result<Vec<_>>>()?;
+        Self::remove_unused_tables(&path, &table_ids)?;
+        let (memtable, memtable_size) = Self::replay_wal(&path.join(WAL_FILENAME))?;
+        let wal = OpenOptions::new().create(true).append(true).open(path.join(WAL_FILENAME))?;
+        let state = LsmState { memtable, memtable_size, tables, next_table_id, wal };
+        let state = Arc::new(RwLock::new(state));
+        let (sender, receiver) = mpsc::channel();
+        let compaction_thread = {
+            let (path, state) = (path.clone(), state.clone());
+            thread::Builder::new()
+                .name("lsm-compaction".to_string())
+                .spawn(move || Self::run_compaction(&path, &options, &state, receiver))?
+        };
+        // Tables may be over the limit after a crash in the middle of a compaction.
+        sender.send(()).expect("Compaction thread is running");
+        Ok(LsmDB {
+            path,
+            options,
+            state,
+            compaction_requests: Mutex::new(Some(sender)),
+            compaction_thread: Some(compaction_thread),
+        })
+    }
+
+    /// Whether there is an LSM database at the given path.
+    pub fn exists<P: AsRef<Path>>(path: P) -> bool {
+        path.as_ref().join(MANIFEST_FILENAME).exists() || path.as_ref().join(WAL_FILENAME).exists()
+    }
+
+    fn table_path(path: &Path, id: u64) -> PathBuf {
+        path.join(format!("{:08}.{}", id, TABLE_EXTENSION))
+    }
+
+    /// Reads ids of the live tables, the newest first.
+    fn read_manifest(path: &Path) -> io::Result<Vec<u64>> {
+        let content = match fs::read_to_string(path.join(MANIFEST_FILENAME)) {
+            Ok(content) => content,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+        content
+            .lines()
+            .map(|line| {
+                line.parse().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "Corrupted LSM manifest")
+                })
+            })
+            .collect()
+    }
+
+    fn write_manifest(path: &Path, tables: &[Arc<Table>]) -> io::Result<()> {
+        let tmp_path = path.join(format!("{}.tmp", MANIFEST_FILENAME));
+        let mut file = File::create(&tmp_path)?;
+        for table in tables {
+            let id = table.path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+            writeln!(file, "{}", id)?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp_path, path.join(MANIFEST_FILENAME))
+    }
+
+    /// Removes tables left over by a flush or compaction interrupted by a crash.
+    fn remove_unused_tables(path: &Path, table_ids: &[u64]) -> io::Result<()> {
+        for dir_entry in fs::read_dir(path)? {
+            let file_path = dir_entry?.path();
+            let unused = match file_path.extension().and_then(|extension| extension.to_str()) {
+                Some("tmp") => true,
+                Some(TABLE_EXTENSION) => {
+                    let id = file_path.file_stem().and_then(|stem| stem.to_str()?.parse().ok());
+                    id.map_or(true, |id: u64| !table_ids.contains(&id))
+                }
+                _ => false,
+            };
+            if unused {
+                fs::remove_file(&file_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Restores the memtable from the write-ahead log. Stops at the first incomplete batch and
+    /// truncates the log there, as that batch was never acknowledged to the writer.
+    fn replay_wal(wal_path: &Path) -> io::Result<(BTreeMap<Vec<u8>, Option<Vec<u8>>>, usize)> {
+        let mut memtable = BTreeMap::new();
+        let mut memtable_size = 0;
+        let data = match fs::read(wal_path) {
+            Ok(data) => data,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok((memtable, 0)),
+            Err(err) => return Err(err),
+        };
+        let mut cursor = Cursor::new(&data[..]);
+        let mut valid_len = 0;
+        while let Ok(batch_len) = cursor.read_u32::<LittleEndian>() {
+            let batch_start = cursor.position() as usize;
+            let batch_end = batch_start + batch_len as usize;
+            if batch_end + 8 > data.len() {
+                break;
+            }
+            let batch = &data[batch_start..batch_end];
+            cursor.set_position(batch_end as u64);
+            if cursor.read_u64::<LittleEndian>()? != checksum(batch) {
+                break;
+            }
+            let mut batch_cursor = Cursor::new(batch);
+            while (batch_cursor.position() as usize) < batch.len() {
+                let (key, value) = read_entry(&mut batch_cursor)?;
+                memtable_size += key.len() + value.as_ref().map_or(0, |value| value.len());
+                memtable.insert(key, value);
+            }
+            valid_len = cursor.position();
+        }
+        if valid_len < data.len() as u64 {
+            info!(target: "store", "Discarding {} bytes of incomplete writes", data.len() as u64 - valid_len);
+            OpenOptions::new().write(true).open(wal_path)?.set_len(valid_len)?;
+        }
+        Ok((memtable, memtable_size))
+    }
+
+    /// Writes the memtable into a new table and starts a new write-ahead log.
+    fn flush(&self, state: &mut LsmState) -> io::Result<()> {
+        if state.memtable.is_empty() {
+            return Ok(());
+        }
+        let table_path = Self::table_path(&self.path, state.next_table_id);
+        let entries = state.memtable.iter().map(|(key, value)| (key.clone(), value.clone()));
+        let table = Table::write(&table_path, entries)?;
+        state.next_table_id += 1;
+        state.tables.insert(0, Arc::new(table));
+        Self::write_manifest(&self.path, &state.tables)?;
+        state.wal.set_len(0)?;
+        state.memtable.clear();
+        state.memtable_size = 0;
+        if state.tables.len() > self.options.max_tables {
+            if let Some(sender) = self.compaction_requests.lock().expect("Lock poisoned").as_ref() {
+                let _ = sender.send(());
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges tables while there are too many of them, every time a compaction is requested.
+    /// Stops once the database is dropped.
+    fn run_compaction(
+        path: &Path,
+        options: &LsmOptions,
+        state: &RwLock<LsmState>,
+        requests: Receiver<()>,
+    ) {
+        while requests.recv().is_ok() {
+            while let Some(sources) = Self::pick_compaction(options, state) {
+                if let Err(err) = Self::compact(path, state, sources) {
+                    error!(target: "store", "Failed to compact tables: {}", err);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Picks adjacent tables of the smallest total size to merge, if there are too many tables.
+    /// Tables are adjacent in age, so that the merged table takes their place in the order.
+    fn pick_compaction(options: &LsmOptions, state: &RwLock<LsmState>) -> Option<Vec<Arc<Table>>> {
+        let state = state.read().expect("LSM state lock poisoned");
+        let tables = &state.tables;
+        if tables.len() <= options.max_tables {
+            return None;
+        }
+        let width = options.compaction_width.max(2).min(tables.len());
+        let start = (0..=tables.len() - width)
+            .min_by_key(|start| {
+                tables[*start..*start + width].iter().map(|table| table.data_end).sum::<u64>()
+            })
+            .expect("There are more tables than the compaction width");
+        Some(tables[start..start + width].to_vec())
+    }
+
+    /// Merges the given adjacent tables into a new one, without holding the state lock, and then
+    /// replaces them with it. Deletions are dropped only when no older table remains.
+    fn compact(path: &Path, state: &RwLock<LsmState>, sources: Vec<Arc<Table>>) -> io::Result<()> {
+        let (table_id, drop_deletions) = {
+            let mut state = state.write().expect("LSM state lock poisoned");
+            state.next_table_id += 1;
+            let oldest = state.tables.last().expect("Compacted tables are in the state");
+            (state.next_table_id - 1, Arc::ptr_eq(oldest, sources.last().unwrap()))
+        };
+        let iterators = sources
+            .iter()
+            .map(|table| table.iter_from(&[]).map(Iterator::peekable))
+            .collect::<io::Result<Vec<_>>>()?;
+        let merged = MergeIterator { sources: iterators }
+            .filter(|(_, value)| !drop_deletions || value.is_some());
+        let table = Table::write(&Self::table_path(path, table_id), merged)?;
+        {
+            let mut state = state.write().expect("LSM state lock poisoned");
+            // Flushes only add newer tables in front, so the sources are still adjacent.
+            let start = state
+                .tables
+                .iter()
+                .position(|table| Arc::ptr_eq(table, &sources[0]))
+                .expect("Compacted tables are in the state");
+            state.tables.splice(start..start + sources.len(), iter::once(Arc::new(table)));
+            Self::write_manifest(path, &state.tables)?;
+        }
+        for table in sources {
+            if let Err(err) = fs::remove_file(&table.path) {
+                error!(target: "store", "Failed to remove {}: {}", table.path.display(), err);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Database for LsmDB {
+    fn get(&self, col: DBCol, key: &[u8]) -> Result<Option<Vec<u8>>, DBError> {
+        let key = column_key(col, key);
+        let state = self.state.read().expect("LSM state lock poisoned");
+        if let Some(value) = state.memtable.get(&key) {
+            return Ok(value.clone());
+        }
+        for table in state.tables.iter() {
+            if let Some(value) = table.get(&key)? {
+                return Ok(value);
+            }
+        }
+        Ok(None)
+    }
+
+    fn iter<'a>(&'a self, col: DBCol) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        let from = vec![col as u8];
+        let state = self.state.read().expect("LSM state lock poisoned");
+        let memtable: Vec<Entry> = state
+            .memtable
+            .range(from.clone()..)
+            .take_while(|(key, _)| key[0] == col as u8)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let mut sources: Vec<Box<dyn Iterator<Item = Entry>>> =
+            vec![Box::new(memtable.into_iter())];
+        for table in state.tables.iter() {
+            match table.iter_from(&from) {
+                Ok(iterator) => sources.push(Box::new(iterator)),
+                Err(err) => {
+                    error!(target: "store", "Failed to read {}: {}", table.path.display(), err)
+                }
+            }
+        }
+        let sources = sources.into_iter().map(Iterator::peekable).collect();
+        Box::new(
+            MergeIterator { sources }.take_while(move |(key, _)| key[0] == col as u8).filter_map(
+                |(key, value)| {
+                    value.map(|value| {
+                        (key[1..].to_vec().into_boxed_slice(), value.into_boxed_slice())
+                    })
+                },
+            ),
+        )
+    }
+
+    fn write(&self, transaction: DBTransaction) -> Result<(), DBError> {
+        let mut batch = vec![];
+        let mut entries = Vec::with_capacity(transaction.ops.len());
+        for op in transaction.ops {
+            let (key, value) = match op {
+                DBOp::Insert { col, key, value } => (column_key(col, &key), Some(value)),
+                DBOp::Delete { col, key } => (column_key(col, &key), None),
+            };
+            write_entry(&mut batch, &key, &value)?;
+            entries.push((key, value));
+        }
+        let mut record = Vec::with_capacity(batch.len() + 12);
+        record.write_u32::<LittleEndian>(batch.len() as u32)?;
+        record.extend_from_slice(&batch);
+        record.write_u64::<LittleEndian>(checksum(&batch))?;
+
+        let mut state = self.state.write().expect("LSM state lock poisoned");
+        state.wal.write_all(&record)?;
+        if self.options.sync_writes {
+            state.wal.sync_data()?;
+        }
+        for (key, value) in entries {
+            state.memtable_size += key.len() + value.as_ref().map_or(0, |value| value.len());
+            state.memtable.insert(key, value);
+        }
+        if state.memtable_size >= self.options.memtable_size {
+            self.flush(&mut state)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the memtable and links the tables into the checkpoint, as they are never modified.
+    fn checkpoint(&self, path: &Path) -> Result<Arc<dyn Database>, DBError> {
+        {
+            let mut state = self.state.write().expect("LSM state lock poisoned");
+            self.flush(&mut state)?;
+            fs::create_dir_all(path.parent().unwrap_or(path))?;
+            fs::create_dir(path)?;
+            for table in state.tables.iter() {
+                let target = path.join(table.path.file_name().expect("Table path has a file name"));
+                if fs::hard_link(&table.path, &target).is_err() {
+                    fs::copy(&table.path, &target)?;
+                }
+            }
+            Self::write_manifest(path, &state.tables)?;
+        }
+        Ok(Arc::new(LsmDB::new(path, self.options)?))
+    }
+}
+
+impl Drop for LsmDB {
+    fn drop(&mut self) {
+        // Lets the compaction thread finish the current merge and stop.
+        self.compaction_requests.lock().expect("Lock poisoned").take();
+        if let Some(compaction_thread) = self.compaction_thread.take() {
+            if compaction_thread.join().is_err() {
+                error!(target: "store", "Compaction thread panicked");
+            }
+        }
+        if let Ok(mut state) = self.state.write() {
+            if let Err(err) = state.wal.sync_all() {
+                error!(target: "store", "Failed to sync the write-ahead log: {}", err);
+            }
+        }
+    }
+}
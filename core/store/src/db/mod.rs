@@ -1,30 +1,53 @@
+use rocksdb::checkpoint::Checkpoint;
 use rocksdb::{
     BlockBasedOptions, ColumnFamily, ColumnFamilyDescriptor, IteratorMode, Options, ReadOptions,
     WriteBatch, DB,
 };
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::io;
-use std::sync::RwLock;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+pub use lsm::{LsmDB, LsmOptions};
+
+pub mod conformance;
+mod lsm;
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct DBError(rocksdb::Error);
+pub enum DBError {
+    RocksDB(rocksdb::Error),
+    /// Errors of the storage backends working with files directly.
+    Io(String),
+}
 
 impl std::error::Error for DBError {
     fn description(&self) -> &str {
-        &self.0.description()
+        match self {
+            DBError::RocksDB(err) => err.description(),
+            DBError::Io(err) => err,
+        }
     }
 }
 
 impl std::fmt::Display for DBError {
     fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        self.0.fmt(formatter)
+        match self {
+            DBError::RocksDB(err) => err.fmt(formatter),
+            DBError::Io(err) => err.fmt(formatter),
+        }
     }
 }
 
 impl From<rocksdb::Error> for DBError {
     fn from(err: rocksdb::Error) -> Self {
-        DBError(err)
+        DBError::RocksDB(err)
+    }
+}
+
+impl From<io::Error> for DBError {
+    fn from(err: io::Error) -> Self {
+        DBError::Io(err.to_string())
     }
 }
 
@@ -79,6 +102,20 @@ pub enum DBCol {
     ColComponentEdges = 32,
     // Biggest nonce used.
     LastComponentNonce = 33,
+    /// Ids of execution outcomes computed in a block per shard, with the account they executed on.
+    ColOutcomesByBlockHash = 34,
+    /// `TrieChanges` applied to the state of a shard in a block, kept for garbage collection.
+    ColTrieChanges = 35,
+    /// Hashes of all the blocks with bodies stored at a height, including forks.
+    ColBlockHashesByHeight = 36,
+    /// Serialized compiled contracts by code hash and VM config, see `near_vm_runner::CompiledContractCache`.
+    ColCachedContractCode = 37,
+    /// Reputation score of known peers, see `near_network::peer_store::PeerStore`.
+    ColPeerReputations = 38,
+    /// Partial chunks still being assembled by `near_chunks::ShardsManager`, kept across restarts.
+    ColPendingPartialChunks = 39,
+    /// Changes of the subscribed state prefixes of a shard in a block, see `StateChanges`.
+    ColStateChanges = 40,
 }
 
 impl std::fmt::Display for DBCol {
@@ -118,12 +155,64 @@ impl std::fmt::Display for DBCol {
             Self::ColPeerComponent => "peer components",
             Self::ColComponentEdges => "component edges",
             Self::LastComponentNonce => "last component nonce",
+            Self::ColOutcomesByBlockHash => "outcomes by block hash",
+            Self::ColTrieChanges => "trie changes",
+            Self::ColBlockHashesByHeight => "block hashes by height",
+            Self::ColCachedContractCode => "cached contract code",
+            Self::ColPeerReputations => "peer reputations",
+            Self::ColPendingPartialChunks => "pending partial chunks",
+            Self::ColStateChanges => "state changes",
         };
         write!(formatter, "{}", desc)
     }
 }
 
-const NUM_COLS: usize = 34;
+pub const NUM_COLS: usize = 41;
+
+/// All the columns, in the order of their numbers.
+pub const ALL_COLUMNS: [DBCol; NUM_COLS] = [
+    DBCol::ColBlockMisc,
+    DBCol::ColBlock,
+    DBCol::ColBlockHeader,
+    DBCol::ColBlockIndex,
+    DBCol::ColState,
+    DBCol::ColChunkExtra,
+    DBCol::ColTransactionResult,
+    DBCol::ColOutgoingReceipts,
+    DBCol::ColIncomingReceipts,
+    DBCol::ColPeers,
+    DBCol::ColEpochInfo,
+    DBCol::ColBlockInfo,
+    DBCol::ColChunks,
+    DBCol::ColPartialChunks,
+    DBCol::ColBlocksToCatchup,
+    DBCol::ColStateDlInfos,
+    DBCol::ColChallengedBlocks,
+    DBCol::ColStateHeaders,
+    DBCol::ColInvalidChunks,
+    DBCol::ColBlockExtra,
+    DBCol::ColBlockPerHeight,
+    DBCol::ColLastApprovalPerAccount,
+    DBCol::ColMyLastApprovalsPerChain,
+    DBCol::ColStateParts,
+    DBCol::ColEpochStart,
+    DBCol::ColAccountAnnouncements,
+    DBCol::ColNextBlockHashes,
+    DBCol::ColEpochLightClientBlocks,
+    DBCol::ColReceiptIdToShardId,
+    DBCol::ColNextBlockWithNewChunk,
+    DBCol::ColLastBlockWithNewChunk,
+    DBCol::ColPeerComponent,
+    DBCol::ColComponentEdges,
+    DBCol::LastComponentNonce,
+    DBCol::ColOutcomesByBlockHash,
+    DBCol::ColTrieChanges,
+    DBCol::ColBlockHashesByHeight,
+    DBCol::ColCachedContractCode,
+    DBCol::ColPeerReputations,
+    DBCol::ColPendingPartialChunks,
+    DBCol::ColStateChanges,
+];
 
 pub struct DBTransaction {
     pub ops: Vec<DBOp>,
@@ -160,9 +249,12 @@ unsafe impl Send for RocksDB {}
 unsafe impl Sync for RocksDB {}
 
 pub struct TestDB {
-    db: RwLock<Vec<HashMap<Vec<u8>, Vec<u8>>>>,
+    db: RwLock<Vec<BTreeMap<Vec<u8>, Vec<u8>>>>,
 }
 
+/// Storage backend of the `Store`. Implementations must pass the checks in `conformance`:
+/// a transaction is applied atomically with the operations in order, and the iterator of a column
+/// returns its entries sorted by key, as of the moment it was created.
 pub trait Database: Sync + Send {
     fn transaction(&self) -> DBTransaction {
         DBTransaction { ops: Vec::new() }
@@ -170,6 +262,11 @@ pub trait Database: Sync + Send {
     fn get(&self, col: DBCol, key: &[u8]) -> Result<Option<Vec<u8>>, DBError>;
     fn iter<'a>(&'a self, column: DBCol) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>;
     fn write(&self, batch: DBTransaction) -> Result<(), DBError>;
+    /// Creates a consistent copy of all the columns at `path`, which must not exist, while the
+    /// database keeps serving reads and writes. Returns the copy opened as a database.
+    fn checkpoint(&self, _path: &Path) -> Result<Arc<dyn Database>, DBError> {
+        Err(DBError::Io("Checkpoints are not supported by this storage backend".to_string()))
+    }
 }
 
 impl Database for RocksDB {
@@ -202,6 +299,14 @@ impl Database for RocksDB {
         }
         Ok(self.db.write(batch)?)
     }
+
+    fn checkpoint(&self, path: &Path) -> Result<Arc<dyn Database>, DBError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Checkpoint::new(&self.db)?.create_checkpoint(path)?;
+        Ok(Arc::new(RocksDB::new(path)?))
+    }
 }
 
 impl Database for TestDB {
@@ -227,6 +332,22 @@ impl Database for TestDB {
         }
         Ok(())
     }
+
+    /// Test databases are checkpointed into an `LsmDB`, as they are not persisted themselves.
+    fn checkpoint(&self, path: &Path) -> Result<Arc<dyn Database>, DBError> {
+        if path.exists() {
+            return Err(DBError::Io(format!("{} already exists", path.display())));
+        }
+        let checkpoint = LsmDB::new(path, LsmOptions::default())?;
+        let mut transaction = checkpoint.transaction();
+        for (col, column) in self.db.read().unwrap().iter().enumerate() {
+            for (key, value) in column.iter() {
+                transaction.put(ALL_COLUMNS[col], key, value);
+            }
+        }
+        checkpoint.write(transaction)?;
+        Ok(Arc::new(checkpoint))
+    }
 }
 
 fn rocksdb_read_options() -> ReadOptions {
@@ -294,7 +415,136 @@ impl RocksDB {
 
 impl TestDB {
     pub fn new() -> Self {
-        let db: Vec<_> = (0..NUM_COLS).map(|_| HashMap::new()).collect();
+        let db: Vec<_> = (0..NUM_COLS).map(|_| BTreeMap::new()).collect();
         Self { db: RwLock::new(db) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::db::conformance::{check_checkpoint, check_database, check_persistence};
+    use crate::db::{DBCol, Database, LsmDB, LsmOptions, RocksDB, TestDB};
+
+    /// Small enough to flush and compact tables many times during the checks.
+    fn small_lsm_options() -> LsmOptions {
+        LsmOptions {
+            memtable_size: 16 * 1024,
+            max_tables: 3,
+            compaction_width: 2,
+            sync_writes: false,
+        }
+    }
+
+    #[test]
+    fn test_db_conformance() {
+        check_database(TestDB::new);
+        let tmp_dir = TempDir::new("test_db_checkpoint").unwrap();
+        check_checkpoint(&TestDB::new(), &tmp_dir.path().join("checkpoint"));
+    }
+
+    #[test]
+    fn rocksdb_conformance() {
+        let tmp_dir = TempDir::new("rocksdb_conformance").unwrap();
+        let mut i = 0;
+        check_database(|| {
+            i += 1;
+            RocksDB::new(tmp_dir.path().join(i.to_string())).unwrap()
+        });
+        check_persistence(|path| RocksDB::new(path).unwrap(), &tmp_dir.path().join("persistence"));
+        check_checkpoint(
+            &RocksDB::new(tmp_dir.path().join("original")).unwrap(),
+            &tmp_dir.path().join("checkpoint"),
+        );
+    }
+
+    #[test]
+    fn lsm_conformance() {
+        let tmp_dir = TempDir::new("lsm_conformance").unwrap();
+        for options in vec![LsmOptions::default(), small_lsm_options()] {
+            let mut i = 0;
+            check_database(|| {
+                i += 1;
+                LsmDB::new(tmp_dir.path().join(format!("{}-{}", options.memtable_size, i)), options)
+                    .unwrap()
+            });
+        }
+        check_persistence(
+            |path| LsmDB::new(path, small_lsm_options()).unwrap(),
+            &tmp_dir.path().join("persistence"),
+        );
+        check_checkpoint(
+            &LsmDB::new(tmp_dir.path().join("original"), small_lsm_options()).unwrap(),
+            &tmp_dir.path().join("checkpoint"),
+        );
+    }
+
+    /// Tables are merged in the background down to the limit, keeping the latest values and
+    /// deletions.
+    #[test]
+    fn lsm_compacts_in_background() {
+        let tmp_dir = TempDir::new("lsm_compaction").unwrap();
+        let options = small_lsm_options();
+        let db = LsmDB::new(tmp_dir.path(), options).unwrap();
+        for round in 0..20u32 {
+            let mut transaction = db.transaction();
+            for i in 0..1000u32 {
+                if i % 7 == round % 7 {
+                    transaction.delete(DBCol::ColState, &i.to_le_bytes());
+                } else {
+                    transaction.put(DBCol::ColState, &i.to_le_bytes(), &round.to_le_bytes());
+                }
+            }
+            db.write(transaction).unwrap();
+        }
+        let num_tables = || {
+            std::fs::read_dir(tmp_dir.path())
+                .unwrap()
+                .filter(|entry| {
+                    entry.as_ref().unwrap().path().extension().unwrap_or_default() == "sst"
+                })
+                .count()
+        };
+        let start = std::time::Instant::now();
+        while num_tables() > options.max_tables {
+            assert!(start.elapsed() < std::time::Duration::from_secs(30));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let check = |db: &LsmDB| {
+            for i in 0..1000u32 {
+                let expected =
+                    if i % 7 == 19 % 7 { None } else { Some(19u32.to_le_bytes().to_vec()) };
+                assert_eq!(db.get(DBCol::ColState, &i.to_le_bytes()).unwrap(), expected);
+            }
+        };
+        check(&db);
+        drop(db);
+        check(&LsmDB::new(tmp_dir.path(), options).unwrap());
+    }
+
+    #[test]
+    fn lsm_discards_incomplete_write() {
+        let tmp_dir = TempDir::new("lsm_incomplete_write").unwrap();
+        {
+            let db = LsmDB::new(tmp_dir.path(), LsmOptions::default()).unwrap();
+            let mut transaction = db.transaction();
+            transaction.put(DBCol::ColBlock, b"a", b"1");
+            db.write(transaction).unwrap();
+        }
+        let wal_path = tmp_dir.path().join("LSM_WAL");
+        let mut wal = std::fs::read(&wal_path).unwrap();
+        // Beginning of a batch that was never completed.
+        wal.extend_from_slice(&[100, 0, 0, 0, 1, 2]);
+        std::fs::write(&wal_path, &wal).unwrap();
+
+        let db = LsmDB::new(tmp_dir.path(), LsmOptions::default()).unwrap();
+        assert_eq!(db.get(DBCol::ColBlock, b"a").unwrap(), Some(b"1".to_vec()));
+        let mut transaction = db.transaction();
+        transaction.put(DBCol::ColBlock, b"b", b"2");
+        db.write(transaction).unwrap();
+        drop(db);
+        let db = LsmDB::new(tmp_dir.path(), LsmOptions::default()).unwrap();
+        assert_eq!(db.get(DBCol::ColBlock, b"b").unwrap(), Some(b"2".to_vec()));
+    }
+}
@@ -7,6 +7,7 @@ use std::{fmt, io};
 use borsh::{BorshDeserialize, BorshSerialize};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use cached::{Cached, SizedCache};
+use serde_derive::{Deserialize, Serialize};
 
 pub use db::DBCol::{self, *};
 use near_crypto::PublicKey;
@@ -22,14 +23,23 @@ use near_primitives::utils::{
     key_for_received_data, prefix_for_access_key, prefix_for_data,
 };
 
-use crate::db::{DBOp, DBTransaction, Database, RocksDB};
+pub use crate::db::{
+    conformance, DBError, DBOp, DBTransaction, Database, LsmDB, LsmOptions, RocksDB, TestDB,
+    ALL_COLUMNS, NUM_COLS,
+};
+pub use crate::migrations::{get_store_version, migrate_store, DbVersion, DB_VERSION};
+pub use crate::snapshot::{
+    column_names, detect_backend, restore_snapshot, SnapshotManifest, SNAPSHOT_MANIFEST_FILENAME,
+};
 pub use crate::trie::{
-    iterator::TrieIterator, update::PrefixKeyValueChanges, update::TrieUpdate,
-    update::TrieUpdateIterator, update::TrieUpdateValuePtr, PartialStorage, Trie, TrieChanges,
-    WrappedTrieChanges,
+    iterator::TrieIterator, proof::verify_state_proof, proof::verify_state_proof_for_prefix,
+    update::PrefixKeyValueChanges, update::TrieUpdate, update::TrieUpdateIterator,
+    update::TrieUpdateValuePtr, PartialStorage, Trie, TrieChanges, WrappedTrieChanges,
 };
 
 mod db;
+mod migrations;
+mod snapshot;
 pub mod test_utils;
 mod trie;
 
@@ -76,6 +86,13 @@ impl Store {
         self.storage.iter(column)
     }
 
+    /// Creates a consistent copy of all the columns at `path` while the store is in use.
+    /// `path` must not exist. Returns the copy opened as a store.
+    pub fn checkpoint(&self, path: &Path) -> Result<Arc<Store>, io::Error> {
+        let storage = self.storage.checkpoint(path).map_err(Into::<io::Error>::into)?;
+        Ok(Arc::new(Store::new(storage)))
+    }
+
     pub fn save_to_file(&self, column: DBCol, filename: &Path) -> Result<(), std::io::Error> {
         let mut file = File::create(filename)?;
         for (key, value) in self.storage.iter(column) {
@@ -145,6 +162,20 @@ impl StoreUpdate {
         self.transaction.delete(column, key);
     }
 
+    /// Value the update is going to write for the key: `Some(None)` if the key is going to be
+    /// deleted and `None` if the update doesn't touch the key.
+    pub fn get_pending(&self, column: DBCol, key: &[u8]) -> Option<Option<&[u8]>> {
+        self.transaction.ops.iter().rev().find_map(|op| match op {
+            DBOp::Insert { col, key: op_key, value } if *col == column && op_key[..] == key[..] => {
+                Some(Some(&value[..]))
+            }
+            DBOp::Delete { col, key: op_key } if *col == column && op_key[..] == key[..] => {
+                Some(None)
+            }
+            _ => None,
+        })
+    }
+
     /// Merge another store update into this one.
     pub fn merge(&mut self, other: StoreUpdate) {
         if self.trie.is_none() {
@@ -203,9 +234,52 @@ pub fn read_with_cache<'a, T: BorshDeserialize + 'a>(
     Ok(None)
 }
 
+/// Storage engine holding the node's data.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum StoreBackend {
+    #[serde(rename = "rocksdb")]
+    RocksDB,
+    /// Embedded log-structured merge tree, see `LsmDB`.
+    #[serde(rename = "lsm")]
+    Lsm,
+}
+
+impl Default for StoreBackend {
+    fn default() -> Self {
+        StoreBackend::RocksDB
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct StoreConfig {
+    pub backend: StoreBackend,
+    /// Options of the `lsm` backend.
+    pub lsm: LsmOptions,
+}
+
 pub fn create_store(path: &str) -> Arc<Store> {
-    let db = Arc::new(RocksDB::new(path).expect("Failed to open the database"));
-    Arc::new(Store::new(db))
+    create_store_with_config(path, &StoreConfig::default())
+}
+
+/// Opens the database at the given path with the configured backend and migrates its data to the
+/// current `DB_VERSION`. The backend can't be changed for an existing database, as backends don't
+/// read each other's files.
+pub fn create_store_with_config(path: &str, config: &StoreConfig) -> Arc<Store> {
+    let db: Arc<dyn Database> = match config.backend {
+        StoreBackend::RocksDB => {
+            if LsmDB::exists(path) {
+                panic!("Database at {} was created by the lsm backend", path);
+            }
+            Arc::new(RocksDB::new(path).expect("Failed to open the database"))
+        }
+        StoreBackend::Lsm => {
+            Arc::new(LsmDB::new(path, config.lsm).expect("Failed to open the database"))
+        }
+    };
+    let store = Arc::new(Store::new(db));
+    migrate_store(&store).expect("Failed to migrate the database");
+    store
 }
 
 /// Reads an object from Trie.
@@ -0,0 +1,125 @@
+use std::io;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use near_primitives::hash::CryptoHash;
+use near_primitives::merkle::MerklePath;
+use near_primitives::transaction::{
+    ExecutionOutcome, ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof, ExecutionStatus,
+    LogEntry,
+};
+use near_primitives::types::Gas;
+
+use crate::{ColBlockMisc, ColTransactionResult, Store};
+
+/// Version of the layout of the data in the store. Bump it on changes of the encoding of the
+/// stored data, together with a migration from the previous version.
+pub type DbVersion = u32;
+
+/// Version 1 added events to the execution outcomes.
+pub const DB_VERSION: DbVersion = 1;
+
+const VERSION_KEY: &[u8; 7] = b"VERSION";
+
+/// Number of values re-encoded by a migration in one store update.
+const MIGRATION_BATCH_SIZE: usize = 10_000;
+
+/// Stores without the version key were created before the versioning, at version 0.
+pub fn get_store_version(store: &Store) -> Result<DbVersion, io::Error> {
+    Ok(store.get_ser(ColBlockMisc, VERSION_KEY)?.unwrap_or(0))
+}
+
+/// Migrates the data in the store to `DB_VERSION`, one version at a time.
+pub fn migrate_store(store: &Store) -> Result<(), io::Error> {
+    let version = get_store_version(store)?;
+    if version > DB_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Store version {} is newer than the supported {}", version, DB_VERSION),
+        ));
+    }
+    if version < 1 {
+        migrate_0_to_1(store)?;
+    }
+    if version < DB_VERSION {
+        let mut store_update = store.store_update();
+        store_update.set_ser(ColBlockMisc, VERSION_KEY, &DB_VERSION)?;
+        store_update.commit()?;
+    }
+    Ok(())
+}
+
+#[derive(BorshDeserialize)]
+struct ExecutionOutcomeWithIdAndProofV0 {
+    id: CryptoHash,
+    status: ExecutionStatus,
+    logs: Vec<LogEntry>,
+    receipt_ids: Vec<CryptoHash>,
+    gas_burnt: Gas,
+    proof: MerklePath,
+    block_hash: CryptoHash,
+}
+
+/// Re-encodes the execution outcomes, which got the events field.
+fn migrate_0_to_1(store: &Store) -> Result<(), io::Error> {
+    let mut store_update = store.store_update();
+    for (i, (key, value)) in store.iter(ColTransactionResult).enumerate() {
+        if i > 0 && i % MIGRATION_BATCH_SIZE == 0 {
+            std::mem::replace(&mut store_update, store.store_update()).commit()?;
+        }
+        let outcome = ExecutionOutcomeWithIdAndProofV0::try_from_slice(&value)?;
+        let outcome = ExecutionOutcomeWithIdAndProof {
+            outcome_with_id: ExecutionOutcomeWithId {
+                id: outcome.id,
+                outcome: ExecutionOutcome {
+                    status: outcome.status,
+                    logs: outcome.logs,
+                    receipt_ids: outcome.receipt_ids,
+                    gas_burnt: outcome.gas_burnt,
+                    events: vec![],
+                },
+            },
+            proof: outcome.proof,
+            block_hash: outcome.block_hash,
+        };
+        store_update.set(ColTransactionResult, &key, &outcome.try_to_vec()?);
+    }
+    store_update.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_store;
+
+    #[test]
+    fn test_migrate_outcomes_0_to_1() {
+        let store = create_test_store();
+        let outcome = ExecutionOutcomeWithIdAndProof {
+            outcome_with_id: ExecutionOutcomeWithId {
+                id: CryptoHash::default(),
+                outcome: ExecutionOutcome {
+                    status: ExecutionStatus::SuccessValue(vec![1]),
+                    logs: vec!["log".to_string()],
+                    receipt_ids: vec![CryptoHash::default()],
+                    gas_burnt: 10,
+                    events: vec![],
+                },
+            },
+            proof: vec![],
+            block_hash: CryptoHash::default(),
+        };
+        let mut encoded = outcome.try_to_vec().unwrap();
+        // Version 0 outcomes are encoded without the events, which are a `u32` length here.
+        let events_offset = 32 + outcome.outcome_with_id.outcome.try_to_vec().unwrap().len() - 4;
+        encoded.drain(events_offset..events_offset + 4);
+        let mut store_update = store.store_update();
+        store_update.set(ColTransactionResult, &[1], &encoded);
+        store_update.commit().unwrap();
+
+        assert_eq!(get_store_version(&store).unwrap(), 0);
+        migrate_store(&store).unwrap();
+        assert_eq!(get_store_version(&store).unwrap(), DB_VERSION);
+        assert_eq!(store.get_ser(ColTransactionResult, &[1]).unwrap(), Some(outcome));
+    }
+}
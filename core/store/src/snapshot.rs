@@ -0,0 +1,164 @@
+//! Snapshots of the whole database, taken from a running node and restored on another machine.
+//! A snapshot is a database checkpoint with a manifest describing what it contains.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_derive::{Deserialize, Serialize};
+
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockIndex;
+
+use crate::db::ALL_COLUMNS;
+use crate::{LsmDB, StoreBackend};
+
+pub const SNAPSHOT_MANIFEST_FILENAME: &str = "snapshot_manifest.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SnapshotManifest {
+    pub chain_id: String,
+    /// Backend that can open the snapshot.
+    pub backend: StoreBackend,
+    /// Head of the snapshot. Snapshots are cut at the last final block, so it's the same as the
+    /// final block.
+    pub head_height: BlockIndex,
+    pub head_hash: CryptoHash,
+    /// Last final block of the node when the snapshot was taken.
+    pub final_height: BlockIndex,
+    pub final_hash: CryptoHash,
+    /// Names of the columns, in the order of their numbers.
+    pub columns: Vec<String>,
+}
+
+/// Names of the columns of this version of the store, in the order of their numbers.
+pub fn column_names() -> Vec<String> {
+    ALL_COLUMNS.iter().map(|col| format!("{:?}", col)).collect()
+}
+
+/// Backend that created the database at the given path.
+pub fn detect_backend(path: &Path) -> StoreBackend {
+    if LsmDB::exists(path) {
+        StoreBackend::Lsm
+    } else {
+        StoreBackend::RocksDB
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl SnapshotManifest {
+    pub fn read(snapshot_path: &Path) -> io::Result<Self> {
+        let content = fs::read_to_string(snapshot_path.join(SNAPSHOT_MANIFEST_FILENAME))?;
+        serde_json::from_str(&content).map_err(|err| invalid_data(err.to_string()))
+    }
+
+    pub fn write(&self, snapshot_path: &Path) -> io::Result<()> {
+        let content =
+            serde_json::to_string_pretty(self).map_err(|err| invalid_data(err.to_string()))?;
+        fs::write(snapshot_path.join(SNAPSHOT_MANIFEST_FILENAME), content)
+    }
+
+    /// Checks that this version of the store can open the snapshot: columns are only ever
+    /// appended, so the snapshot columns must be a prefix of the current ones.
+    pub fn check_columns(&self) -> io::Result<()> {
+        let columns = column_names();
+        if self.columns.len() > columns.len() {
+            return Err(invalid_data(format!(
+                "Snapshot has {} columns, this node knows only {}",
+                self.columns.len(),
+                columns.len()
+            )));
+        }
+        match self.columns.iter().zip(columns.iter()).position(|(a, b)| a != b) {
+            Some(i) => Err(invalid_data(format!(
+                "Column {} of the snapshot is {}, expected {}",
+                i, self.columns[i], columns[i]
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Copies the snapshot into `store_path`, which must not exist or be empty, after checking that it
+/// is compatible with this version of the store.
+pub fn restore_snapshot(snapshot_path: &Path, store_path: &Path) -> io::Result<SnapshotManifest> {
+    let manifest = SnapshotManifest::read(snapshot_path)?;
+    manifest.check_columns()?;
+    if store_path.exists() && fs::read_dir(store_path)?.next().is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} is not empty", store_path.display()),
+        ));
+    }
+    fs::create_dir_all(store_path)?;
+    for entry in fs::read_dir(snapshot_path)? {
+        let entry = entry?;
+        if entry.file_name() == SNAPSHOT_MANIFEST_FILENAME || !entry.file_type()?.is_file() {
+            continue;
+        }
+        fs::copy(entry.path(), store_path.join(entry.file_name()))?;
+    }
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::test_utils::create_test_store;
+    use crate::{create_store_with_config, ColBlock, StoreConfig};
+
+    #[test]
+    fn restore_checkpoint() {
+        let tmp_dir = TempDir::new("restore_snapshot").unwrap();
+        let store = create_test_store();
+        let mut store_update = store.store_update();
+        store_update.set(ColBlock, b"key", b"value");
+        store_update.commit().unwrap();
+
+        let snapshot_path = tmp_dir.path().join("snapshot");
+        store.checkpoint(&snapshot_path).unwrap();
+        let manifest = SnapshotManifest {
+            chain_id: "test".to_string(),
+            backend: detect_backend(&snapshot_path),
+            head_height: 0,
+            head_hash: CryptoHash::default(),
+            final_height: 0,
+            final_hash: CryptoHash::default(),
+            columns: column_names(),
+        };
+        manifest.write(&snapshot_path).unwrap();
+
+        let store_path = tmp_dir.path().join("data");
+        assert_eq!(restore_snapshot(&snapshot_path, &store_path).unwrap(), manifest);
+        assert!(restore_snapshot(&snapshot_path, &store_path).is_err());
+        let config = StoreConfig { backend: manifest.backend, ..Default::default() };
+        let restored = create_store_with_config(store_path.to_str().unwrap(), &config);
+        assert_eq!(restored.get(ColBlock, b"key").unwrap(), Some(b"value".to_vec()));
+    }
+
+    #[test]
+    fn check_snapshot_columns() {
+        let mut manifest = SnapshotManifest {
+            chain_id: "test".to_string(),
+            backend: StoreBackend::RocksDB,
+            head_height: 0,
+            head_hash: CryptoHash::default(),
+            final_height: 0,
+            final_hash: CryptoHash::default(),
+            columns: column_names(),
+        };
+        assert!(manifest.check_columns().is_ok());
+        manifest.columns.pop();
+        assert!(manifest.check_columns().is_ok());
+        manifest.columns.swap(0, 1);
+        assert!(manifest.check_columns().is_err());
+        manifest.columns = column_names();
+        manifest.columns.push("ColFromTheFuture".to_string());
+        assert!(manifest.check_columns().is_err());
+    }
+}
@@ -6,12 +6,13 @@ use std::io::{Cursor, ErrorKind, Read, Write};
 use std::sync::{Arc, Mutex};
 
 use crate::db::{DBOp, DBTransaction};
+use borsh::{BorshDeserialize, BorshSerialize};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use cached::Cached;
 
 use near_primitives::challenge::PartialState;
 use near_primitives::hash::{hash, CryptoHash};
-use near_primitives::types::{StateRoot, StateRootNode};
+use near_primitives::types::{ShardId, StateRoot, StateRootNode};
 
 use crate::trie::insert_delete::NodesStorage;
 use crate::trie::iterator::TrieIterator;
@@ -25,6 +26,7 @@ use crate::{ColState, StorageError, Store, StoreUpdate};
 mod insert_delete;
 pub mod iterator;
 mod nibble_slice;
+pub mod proof;
 mod state_parts;
 mod trie_storage;
 pub mod update;
@@ -443,6 +445,7 @@ pub struct Trie {
 /// Having old_root and values in deletions allows to apply TrieChanges in reverse
 ///
 /// StoreUpdate are the changes from current state refcount to refcount + delta.
+#[derive(BorshSerialize, BorshDeserialize, Clone, PartialEq, Debug)]
 pub struct TrieChanges {
     #[allow(dead_code)]
     old_root: StateRoot,
@@ -455,6 +458,24 @@ impl TrieChanges {
     pub fn empty(old_root: StateRoot) -> Self {
         TrieChanges { old_root, new_root: old_root, insertions: vec![], deletions: vec![] }
     }
+    /// Refcount of the node, taking into account the changes already pending in the update.
+    fn retrieve_rc(
+        trie: &Trie,
+        store_update: &StoreUpdate,
+        key: &CryptoHash,
+    ) -> Result<u32, Box<dyn std::error::Error>> {
+        match store_update.get_pending(ColState, key.as_ref()) {
+            Some(Some(bytes)) => Ok(RcTrieNode::decode_raw(bytes)?.1),
+            Some(None) => Ok(0),
+            None => Ok(trie
+                .storage
+                .as_caching_storage()
+                .expect("Must be caching storage")
+                .retrieve_rc(&key)
+                .unwrap_or_default()),
+        }
+    }
+
     pub fn insertions_into(
         &self,
         trie: Arc<Trie>,
@@ -462,32 +483,29 @@ impl TrieChanges {
     ) -> Result<(), Box<dyn std::error::Error>> {
         store_update.trie = Some(trie.clone());
         for (key, value, rc) in self.insertions.iter() {
-            let storage_rc = trie
-                .storage
-                .as_caching_storage()
-                .expect("Must be caching storage")
-                .retrieve_rc(&key)
-                .unwrap_or_default();
+            let storage_rc = Self::retrieve_rc(&trie, store_update, key)?;
             let bytes = RcTrieNode::encode(&value, storage_rc + rc)?;
             store_update.set(ColState, key.as_ref(), &bytes);
         }
         Ok(())
     }
 
-    pub fn deletions_into(
-        &self,
+    /// Decrements refcounts of the given nodes, deleting the ones no longer referenced. Fails with
+    /// `StorageInconsistentState` if a node is released more times than it's referenced.
+    fn decrement_refcounts_into(
+        changes: &[(CryptoHash, Vec<u8>, u32)],
         trie: Arc<Trie>,
         store_update: &mut StoreUpdate,
     ) -> Result<(), Box<dyn std::error::Error>> {
         store_update.trie = Some(trie.clone());
-        for (key, value, rc) in self.deletions.iter() {
-            let storage_rc = trie
-                .storage
-                .as_caching_storage()
-                .expect("Must be caching storage")
-                .retrieve_rc(&key)
-                .unwrap_or_default();
-            assert!(*rc <= storage_rc);
+        for (key, value, rc) in changes.iter() {
+            let storage_rc = Self::retrieve_rc(&trie, store_update, key)?;
+            if *rc > storage_rc {
+                return Err(Box::new(StorageError::StorageInconsistentState(format!(
+                    "Releasing {} references of trie node {}, which has {}",
+                    rc, key, storage_rc
+                ))));
+            }
             if *rc < storage_rc {
                 let bytes = RcTrieNode::encode(&value, storage_rc - rc)?;
                 store_update.set(ColState, key.as_ref(), &bytes);
@@ -498,6 +516,23 @@ impl TrieChanges {
         Ok(())
     }
 
+    pub fn deletions_into(
+        &self,
+        trie: Arc<Trie>,
+        store_update: &mut StoreUpdate,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Self::decrement_refcounts_into(&self.deletions, trie, store_update)
+    }
+
+    /// Applies insertions as deletions, to discard the state of a fork.
+    pub fn revert_insertions_into(
+        &self,
+        trie: Arc<Trie>,
+        store_update: &mut StoreUpdate,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Self::decrement_refcounts_into(&self.insertions, trie, store_update)
+    }
+
     pub fn into(
         self,
         trie: Arc<Trie>,
@@ -520,11 +555,30 @@ impl TrieChanges {
 pub struct WrappedTrieChanges {
     trie: Arc<Trie>,
     trie_changes: TrieChanges,
+    shard_id: ShardId,
+    block_hash: CryptoHash,
 }
 
 impl WrappedTrieChanges {
-    pub fn new(trie: Arc<Trie>, trie_changes: TrieChanges) -> Self {
-        WrappedTrieChanges { trie, trie_changes }
+    pub fn new(
+        trie: Arc<Trie>,
+        trie_changes: TrieChanges,
+        shard_id: ShardId,
+        block_hash: CryptoHash,
+    ) -> Self {
+        WrappedTrieChanges { trie, trie_changes, shard_id, block_hash }
+    }
+
+    pub fn shard_id(&self) -> ShardId {
+        self.shard_id
+    }
+
+    pub fn block_hash(&self) -> &CryptoHash {
+        &self.block_hash
+    }
+
+    pub fn trie_changes(&self) -> &TrieChanges {
+        &self.trie_changes
     }
 
     pub fn insertions_into(
@@ -540,6 +594,13 @@ impl WrappedTrieChanges {
     ) -> Result<(), Box<dyn std::error::Error>> {
         self.trie_changes.deletions_into(self.trie.clone(), store_update)
     }
+
+    pub fn revert_insertions_into(
+        &self,
+        store_update: &mut StoreUpdate,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.trie_changes.revert_insertions_into(self.trie.clone(), store_update)
+    }
 }
 
 impl Trie {
@@ -869,7 +930,11 @@ mod tests {
 
     type TrieChanges = Vec<(Vec<u8>, Option<Vec<u8>>)>;
 
-    fn test_populate_trie(trie: Arc<Trie>, root: &CryptoHash, changes: TrieChanges) -> CryptoHash {
+    pub(crate) fn test_populate_trie(
+        trie: Arc<Trie>,
+        root: &CryptoHash,
+        changes: TrieChanges,
+    ) -> CryptoHash {
         let mut other_changes = changes.clone();
         let (store_update, root) =
             trie.update(root, other_changes.drain(..)).unwrap().into(trie.clone()).unwrap();
@@ -1211,6 +1276,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_refcounts_within_one_update() {
+        let trie = create_trie();
+        let changes = vec![
+            (b"doge".to_vec(), Some(b"coin".to_vec())),
+            (b"docu".to_vec(), Some(b"value".to_vec())),
+        ];
+        // Two shards ending up with the same state share all the nodes.
+        let trie_changes = trie.update(&Trie::empty_root(), changes.into_iter()).unwrap();
+        let store = trie.storage.as_caching_storage().unwrap().store.clone();
+        let mut store_update = store.store_update();
+        trie_changes.insertions_into(trie.clone(), &mut store_update).unwrap();
+        trie_changes.insertions_into(trie.clone(), &mut store_update).unwrap();
+        store_update.commit().unwrap();
+
+        let mut store_update = store.store_update();
+        trie_changes.revert_insertions_into(trie.clone(), &mut store_update).unwrap();
+        store_update.commit().unwrap();
+        assert!(store.iter(ColState).peekable().peek().is_some(), "Storage must not be empty");
+
+        let mut store_update = store.store_update();
+        trie_changes.revert_insertions_into(trie.clone(), &mut store_update).unwrap();
+        trie_changes.insertions_into(trie.clone(), &mut store_update).unwrap();
+        trie_changes.revert_insertions_into(trie.clone(), &mut store_update).unwrap();
+        store_update.commit().unwrap();
+        assert!(store.iter(ColState).peekable().peek().is_none(), "Storage must be empty");
+    }
+
     #[test]
     fn test_trie_restart() {
         let store = create_test_store();
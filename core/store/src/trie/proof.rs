@@ -0,0 +1,129 @@
+//! Verification of state proofs: trie nodes read by a node while answering a query, which let a
+//! client that only knows the state root check the answer without trusting the node.
+
+use std::sync::Arc;
+
+use near_primitives::challenge::PartialState;
+use near_primitives::types::StateRoot;
+
+use crate::{PartialStorage, StorageError, Trie, TrieUpdate};
+
+fn proof_trie(nodes: &[Vec<u8>]) -> Trie {
+    Trie::from_recorded_storage(PartialStorage { nodes: PartialState(nodes.to_vec()) })
+}
+
+/// Returns the value of `key` in the state with the given root, looked up in the proof nodes.
+/// Nodes are addressed by their hashes, so a proof can't lie about the value, it can only miss
+/// some of the nodes.
+///
+/// # Errors
+/// StorageError::TrieNodeMissing if the proof doesn't have all the nodes on the path to the key.
+pub fn verify_state_proof(
+    state_root: &StateRoot,
+    nodes: &[Vec<u8>],
+    key: &[u8],
+) -> Result<Option<Vec<u8>>, StorageError> {
+    proof_trie(nodes).get(state_root, key)
+}
+
+/// Returns all the keys and values with the given prefix in the state with the given root, in
+/// the order of the keys. The trie is walked the same way `TrieUpdate::iter` walks it, so a proof
+/// recorded while iterating over the prefix has all the nodes needed, and no key can be omitted.
+///
+/// # Errors
+/// StorageError::TrieNodeMissing if the proof doesn't cover the whole prefix.
+pub fn verify_state_proof_for_prefix(
+    state_root: &StateRoot,
+    nodes: &[Vec<u8>],
+    prefix: &[u8],
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, StorageError> {
+    let state_update = TrieUpdate::new(Arc::new(proof_trie(nodes)), *state_root);
+    let mut values = vec![];
+    for key in state_update.iter(prefix)? {
+        let key = key?;
+        let value = state_update.get(&key)?.ok_or(StorageError::TrieNodeMissing)?;
+        values.push((key, value));
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::test_utils::create_test_store;
+    use crate::trie::tests::test_populate_trie;
+
+    fn populate() -> (Arc<Trie>, StateRoot) {
+        let store = create_test_store();
+        let trie = Arc::new(Trie::new(store));
+        let changes = vec![
+            (b"doge".to_vec(), Some(b"coin".to_vec())),
+            (b"docu".to_vec(), Some(b"value".to_vec())),
+            (b"do".to_vec(), Some(b"verb".to_vec())),
+            (b"horse".to_vec(), Some(b"stallion".to_vec())),
+            (b"dog".to_vec(), Some(b"puppy".to_vec())),
+            (b"h".to_vec(), Some(b"value".to_vec())),
+        ];
+        let root = test_populate_trie(trie.clone(), &Trie::empty_root(), changes);
+        (trie, root)
+    }
+
+    #[test]
+    fn verify_key_proof() {
+        let (trie, root) = populate();
+        let recording = trie.recording_reads();
+        assert_eq!(recording.get(&root, b"dog").unwrap(), Some(b"puppy".to_vec()));
+        assert_eq!(recording.get(&root, b"dogs").unwrap(), None);
+        let nodes = recording.recorded_storage().unwrap().nodes.0;
+
+        assert_eq!(verify_state_proof(&root, &nodes, b"dog"), Ok(Some(b"puppy".to_vec())));
+        assert_eq!(verify_state_proof(&root, &nodes, b"dogs"), Ok(None));
+        assert_eq!(verify_state_proof(&root, &nodes, b"horse"), Err(StorageError::TrieNodeMissing));
+        // A proof for another root proves nothing.
+        let other_root =
+            test_populate_trie(trie.clone(), &root, vec![(b"dog".to_vec(), Some(b"cat".to_vec()))]);
+        assert_eq!(
+            verify_state_proof(&other_root, &nodes, b"dog"),
+            Err(StorageError::TrieNodeMissing)
+        );
+        // Nor does a proof with a node removed.
+        for i in 0..nodes.len() {
+            let mut incomplete = nodes.clone();
+            incomplete.remove(i);
+            assert!(verify_state_proof(&root, &incomplete, b"dog").is_err());
+        }
+    }
+
+    #[test]
+    fn verify_prefix_proof() {
+        let (trie, root) = populate();
+        let recording = Arc::new(trie.recording_reads());
+        let state_update = TrieUpdate::new(recording.clone(), root);
+        let mut keys = vec![];
+        state_update.for_keys_with_prefix(b"do", |key| keys.push(key.to_vec())).unwrap();
+        for key in keys.iter() {
+            state_update.get(key).unwrap();
+        }
+        let nodes = recording.recorded_storage().unwrap().nodes.0;
+
+        let expected = vec![
+            (b"do".to_vec(), b"verb".to_vec()),
+            (b"docu".to_vec(), b"value".to_vec()),
+            (b"dog".to_vec(), b"puppy".to_vec()),
+            (b"doge".to_vec(), b"coin".to_vec()),
+        ];
+        assert_eq!(verify_state_proof_for_prefix(&root, &nodes, b"do"), Ok(expected));
+        assert_eq!(
+            verify_state_proof_for_prefix(&root, &nodes, b"dog"),
+            Ok(vec![(b"dog".to_vec(), b"puppy".to_vec()), (b"doge".to_vec(), b"coin".to_vec())])
+        );
+        assert!(verify_state_proof_for_prefix(&root, &nodes, b"").is_err());
+        for i in 0..nodes.len() {
+            let mut incomplete = nodes.clone();
+            incomplete.remove(i);
+            assert!(verify_state_proof_for_prefix(&root, &incomplete, b"do").is_err());
+        }
+    }
+}
@@ -85,6 +85,7 @@ pub fn csv_to_json_configs(home: &Path, chain_id: String, tracked_shards: Vec<Sh
         chunk_producer_kickout_threshold: CHUNK_PRODUCER_KICKOUT_THRESHOLD,
         min_gas_price: MIN_GAS_PRICE,
         fishermen_threshold: FISHERMEN_THRESHOLD,
+        erasure_coding_per_shard: vec![],
     };
 
     // Write all configs to files.
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::convert::TryInto;
 use std::fs::File;
 use std::io::{Read, Write};
@@ -17,15 +18,21 @@ use near_client::BlockProducer;
 use near_client::ClientConfig;
 use near_crypto::{InMemorySigner, KeyFile, KeyType, PublicKey, Signer};
 use near_jsonrpc::RpcConfig;
+use near_network::rate_limiter::RateLimits;
 use near_network::test_utils::open_port;
 use near_network::types::PROTOCOL_VERSION;
 use near_network::NetworkConfig;
+use near_pool::types::PoolConfig;
 use near_primitives::account::AccessKey;
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::serialize::{to_base64, u128_dec_format};
 use near_primitives::types::{AccountId, Balance, BlockIndex, Gas, ShardId, ValidatorId};
-use near_primitives::utils::{generate_random_string, get_num_block_producers_per_shard};
+use near_primitives::utils::{
+    col, generate_random_string, get_num_block_producers_per_shard, key_for_account,
+    prefix_for_access_key, prefix_for_data,
+};
 use near_primitives::views::AccountView;
+use near_store::StoreConfig;
 use near_telemetry::TelemetryConfig;
 use node_runtime::config::RuntimeConfig;
 use node_runtime::StateRecord;
@@ -93,6 +100,10 @@ pub const FAST_EPOCH_LENGTH: u64 = 60;
 pub const TTL_ACCOUNT_ID_ROUTER: u64 = 60 * 60;
 /// Maximum amount of routes to store for each account id.
 pub const MAX_ROUTES_TO_STORE: usize = 5;
+/// Period between peer discovery lookups in seconds.
+pub const DHT_LOOKUP_PERIOD: u64 = 60;
+/// Time to keep signed peer records of the discovery protocol in seconds.
+pub const DHT_RECORD_TTL: u64 = 60 * 60;
 /// Expected number of blocks per year
 pub const NUM_BLOCKS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
 
@@ -124,12 +135,18 @@ pub const MAX_INFLATION_RATE: u8 = 5;
 /// Number of blocks for which a given transaction is valid
 pub const TRANSACTION_VALIDITY_PERIOD: u64 = 100;
 
+/// Number of epochs of history kept by nodes that are not archival.
+pub const GC_NUM_EPOCHS_TO_KEEP: u64 = 5;
+
 /// Number of seats for block producers
 pub const NUM_BLOCK_PRODUCERS: ValidatorId = 50;
 
 /// How much height horizon to give to consider peer up to date.
 pub const MOST_WEIGHTED_PEER_HORIZON: u128 = 5 * WEIGHT_MULTIPLIER;
 
+/// Minimum size of network messages to compress, in bytes.
+pub const COMPRESSION_THRESHOLD: usize = 4 * 1024;
+
 pub const CONFIG_FILENAME: &str = "config.json";
 pub const GENESIS_CONFIG_FILENAME: &str = "genesis.json";
 pub const NODE_KEY_FILE: &str = "node_key.json";
@@ -142,7 +159,7 @@ pub struct Network {
     /// Address to listen for incoming connections.
     pub addr: String,
     /// Address to advertise to peers for them to connect.
-    /// If empty, will use the same port as the addr, and will introspect on the listener.
+    /// If empty, will use the same port as the addr, and the ip at which the peers see this node.
     pub external_address: String,
     /// Comma separated list of nodes to connect to.
     pub boot_nodes: String,
@@ -156,6 +173,15 @@ pub struct Network {
     pub skip_sync_wait: bool,
     /// Ban window for peers who misbehave.
     pub ban_window: Duration,
+    /// Minimum size of the messages to compress. Compression is disabled if null.
+    #[serde(default = "default_compression_threshold")]
+    pub compression_threshold: Option<usize>,
+    /// Limits on the messages received from each peer.
+    #[serde(default)]
+    pub rate_limits: RateLimits,
+    /// File to record all the messages exchanged with the peers to, for debugging.
+    #[serde(default)]
+    pub recording_path: Option<PathBuf>,
 }
 
 impl Default for Network {
@@ -169,10 +195,17 @@ impl Default for Network {
             reconnect_delay: Duration::from_secs(60),
             skip_sync_wait: false,
             ban_window: Duration::from_secs(3 * 60 * 60),
+            compression_threshold: default_compression_threshold(),
+            rate_limits: RateLimits::default(),
+            recording_path: None,
         }
     }
 }
 
+fn default_compression_threshold() -> Option<usize> {
+    Some(COMPRESSION_THRESHOLD)
+}
+
 /// Serde default only supports functions without parameters.
 fn default_reduce_wait_for_missing_block() -> Duration {
     Duration::from_millis(REDUCE_DELAY_FOR_MISSING_BLOCKS)
@@ -226,6 +259,34 @@ impl Default for Consensus {
     }
 }
 
+/// Part of the state which changes are stored for every applied chunk and served by the `changes`
+/// RPC. Only the shards tracked by the node are covered.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StateChangesSubscription {
+    /// Account record of the account, e.g. its balance, together with its contract data.
+    /// Being a prefix, it also covers the accounts which id starts with the given one.
+    Account(AccountId),
+    /// Contract data of the account.
+    Data(AccountId),
+    /// Access keys of the account.
+    AccessKeys(AccountId),
+    /// Records and contract data of all accounts.
+    AllAccounts,
+}
+
+impl StateChangesSubscription {
+    /// Prefix of the state keys covered by the subscription.
+    pub fn prefix(&self) -> Vec<u8> {
+        match self {
+            StateChangesSubscription::Account(account_id) => key_for_account(account_id),
+            StateChangesSubscription::Data(account_id) => prefix_for_data(account_id),
+            StateChangesSubscription::AccessKeys(account_id) => prefix_for_access_key(account_id),
+            StateChangesSubscription::AllAccounts => col::ACCOUNT.to_vec(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(default)]
 pub struct Config {
@@ -238,6 +299,14 @@ pub struct Config {
     pub consensus: Consensus,
     pub tracked_accounts: Vec<AccountId>,
     pub tracked_shards: Vec<ShardId>,
+    /// Keep all the history instead of garbage collecting old blocks and state.
+    pub archive: bool,
+    /// Number of epochs of blocks and state to keep when not archiving.
+    pub gc_num_epochs_to_keep: u64,
+    pub store: StoreConfig,
+    pub tx_pool: PoolConfig,
+    /// Parts of the state which changes are stored, see `StateChangesSubscription`.
+    pub state_changes_subscriptions: Vec<StateChangesSubscription>,
 }
 
 impl Default for Config {
@@ -252,6 +321,11 @@ impl Default for Config {
             consensus: Consensus::default(),
             tracked_accounts: vec![],
             tracked_shards: vec![],
+            archive: false,
+            gc_num_epochs_to_keep: GC_NUM_EPOCHS_TO_KEEP,
+            store: StoreConfig::default(),
+            tx_pool: PoolConfig::default(),
+            state_changes_subscriptions: vec![],
         }
     }
 }
@@ -275,7 +349,11 @@ impl Config {
 
 impl From<&str> for Config {
     fn from(content: &str) -> Self {
-        serde_json::from_str(content).expect("Failed to deserialize config")
+        let config: Config = serde_json::from_str(content).expect("Failed to deserialize config");
+        if let Err(err) = config.network.rate_limits.validate() {
+            panic!("Invalid network rate limits: {}", err);
+        }
+        config
     }
 }
 
@@ -286,6 +364,7 @@ pub struct NearConfig {
     pub network_config: NetworkConfig,
     pub rpc_config: RpcConfig,
     pub telemetry_config: TelemetryConfig,
+    pub store_config: StoreConfig,
     pub block_producer: Option<BlockProducer>,
     pub genesis_config: GenesisConfig,
 }
@@ -336,6 +415,9 @@ impl NearConfig {
                 chunk_request_retry_period: Duration::from_millis(CHUNK_REQUEST_RETRY_PERIOD),
                 tracked_accounts: config.tracked_accounts,
                 tracked_shards: config.tracked_shards,
+                archive: config.archive,
+                gc_num_epochs_to_keep: config.gc_num_epochs_to_keep,
+                tx_pool: config.tx_pool,
             },
             network_config: NetworkConfig {
                 public_key: network_key_pair.public_key,
@@ -368,8 +450,19 @@ impl NearConfig {
                 max_routes_to_store: MAX_ROUTES_TO_STORE,
                 most_weighted_peer_horizon: MOST_WEIGHTED_PEER_HORIZON,
                 push_info_period: Duration::from_millis(100),
+                compression_threshold: config.network.compression_threshold,
+                rate_limits: config.network.rate_limits,
+                external_address: if config.network.external_address.is_empty() {
+                    None
+                } else {
+                    Some(config.network.external_address.parse().unwrap())
+                },
+                dht_lookup_period: Duration::from_secs(DHT_LOOKUP_PERIOD),
+                dht_record_ttl: Duration::from_secs(DHT_RECORD_TTL),
+                recording_path: config.network.recording_path,
             },
             telemetry_config: config.telemetry,
+            store_config: config.store,
             rpc_config: config.rpc,
             genesis_config: genesis_config.clone(),
             block_producer,
@@ -378,6 +471,15 @@ impl NearConfig {
 }
 
 impl NearConfig {
+    /// Prefixes of the state keys which changes are stored.
+    pub fn subscribed_prefixes(&self) -> HashSet<Vec<u8>> {
+        self.config
+            .state_changes_subscriptions
+            .iter()
+            .map(|subscription| subscription.prefix())
+            .collect()
+    }
+
     /// Test tool to save configs back to the folder.
     /// Useful for dynamic creating testnet configs and then saving them in different folders.
     pub fn save_to_dir(&self, dir: &Path) {
@@ -406,6 +508,15 @@ pub struct AccountInfo {
     pub amount: Balance,
 }
 
+/// Reed-Solomon parameters used to encode the chunks of a shard.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ErasureCodingConfig {
+    /// Number of parts each chunk is encoded into. Zero means one part per block producer.
+    pub num_total_parts: usize,
+    /// Percentage of the parts enough to reconstruct the chunk (this is a number between 1 and 100).
+    pub data_parts_percentage: u8,
+}
+
 /// Runtime configuration, defining genesis block.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct GenesisConfig {
@@ -459,6 +570,10 @@ pub struct GenesisConfig {
     /// Fishermen stake threshold.
     #[serde(with = "u128_dec_format")]
     pub fishermen_threshold: Balance,
+    /// Erasure coding parameters for each shard. If empty, they are derived from the number of
+    /// block producers.
+    #[serde(default)]
+    pub erasure_coding_per_shard: Vec<ErasureCodingConfig>,
 }
 
 pub fn get_initial_supply(records: &[StateRecord]) -> Balance {
@@ -566,6 +681,7 @@ impl GenesisConfig {
             chunk_producer_kickout_threshold: CHUNK_PRODUCER_KICKOUT_THRESHOLD,
             fishermen_threshold: FISHERMEN_THRESHOLD,
             min_gas_price: MIN_GAS_PRICE,
+            erasure_coding_per_shard: vec![],
         }
     }
 
@@ -616,6 +732,18 @@ impl From<&str> for GenesisConfig {
                 config.protocol_version, PROTOCOL_VERSION
             ));
         }
+        if !config.erasure_coding_per_shard.is_empty()
+            && config.erasure_coding_per_shard.len() != config.block_producers_per_shard.len()
+        {
+            panic!("Erasure coding parameters must be given for every shard or for none");
+        }
+        if config
+            .erasure_coding_per_shard
+            .iter()
+            .any(|params| params.data_parts_percentage == 0 || params.data_parts_percentage > 100)
+        {
+            panic!("Erasure coding data parts percentage must be between 1 and 100");
+        }
         let total_supply = get_initial_supply(&config.records);
         config.total_supply = total_supply;
         config
@@ -778,6 +906,7 @@ pub fn init_configs(
                 chunk_producer_kickout_threshold: CHUNK_PRODUCER_KICKOUT_THRESHOLD,
                 fishermen_threshold: FISHERMEN_THRESHOLD,
                 min_gas_price: MIN_GAS_PRICE,
+                erasure_coding_per_shard: vec![],
             };
             genesis_config.write_to_file(&dir.join(config.genesis_file));
             info!(target: "near", "Generated node key, validator key, genesis file in {}", dir.to_str().unwrap());
@@ -8,14 +8,14 @@ use log::info;
 use near_client::{ClientActor, ViewClientActor};
 use near_jsonrpc::start_http;
 use near_network::{NetworkRecipient, PeerManagerActor};
-use near_store::create_store;
+use near_store::{create_store_with_config, SnapshotManifest};
 use near_telemetry::TelemetryActor;
 
 pub use crate::config::{
     init_configs, load_config, load_test_config, GenesisConfig, NearConfig, NEAR_BASE,
 };
 pub use crate::runtime::NightshadeRuntime;
-use near_chain::ChainGenesis;
+use near_chain::{ChainGenesis, ChainStore};
 
 pub mod config;
 mod runtime;
@@ -52,14 +52,17 @@ pub fn start_with_config(
     home_dir: &Path,
     config: NearConfig,
 ) -> (Addr<ClientActor>, Addr<ViewClientActor>) {
-    let store = create_store(&get_store_path(home_dir));
-    let runtime = Arc::new(NightshadeRuntime::new(
-        home_dir,
-        store.clone(),
-        config.genesis_config.clone(),
-        config.client_config.tracked_accounts.clone(),
-        config.client_config.tracked_shards.clone(),
-    ));
+    let store = create_store_with_config(&get_store_path(home_dir), &config.store_config);
+    let runtime = Arc::new(
+        NightshadeRuntime::new(
+            home_dir,
+            store.clone(),
+            config.genesis_config.clone(),
+            config.client_config.tracked_accounts.clone(),
+            config.client_config.tracked_shards.clone(),
+        )
+        .with_subscriptions(config.subscribed_prefixes()),
+    );
 
     let telemetry = TelemetryActor::new(config.telemetry_config.clone()).start();
     let chain_genesis = ChainGenesis::new(
@@ -111,3 +114,38 @@ pub fn start_with_config(
 
     (client_actor, view_client)
 }
+
+/// Takes a snapshot of the database of a stopped node into `destination`. Snapshots of a running
+/// node are taken with the `adm_create_snapshot` RPC, as only one process can open the database.
+pub fn create_snapshot(
+    home_dir: &Path,
+    config: &NearConfig,
+    destination: &Path,
+) -> Result<SnapshotManifest, near_chain::Error> {
+    let store = create_store_with_config(&get_store_path(home_dir), &config.store_config);
+    ChainStore::new(store).create_snapshot(&config.genesis_config.chain_id, destination)
+}
+
+/// Restores the database of a node without data from a snapshot taken on the same chain, so the
+/// node starts from the snapshot head instead of syncing from genesis.
+pub fn restore_snapshot(
+    home_dir: &Path,
+    config: &NearConfig,
+    source: &Path,
+) -> Result<SnapshotManifest, String> {
+    let manifest = SnapshotManifest::read(source).map_err(|err| err.to_string())?;
+    if manifest.chain_id != config.genesis_config.chain_id {
+        return Err(format!(
+            "Snapshot is of chain {}, while the node is configured for {}",
+            manifest.chain_id, config.genesis_config.chain_id
+        ));
+    }
+    if manifest.backend != config.store_config.backend {
+        return Err(format!(
+            "Snapshot is of {:?} storage, while the node is configured to use {:?}",
+            manifest.backend, config.store_config.backend
+        ));
+    }
+    near_store::restore_snapshot(source, Path::new(&get_store_path(home_dir)))
+        .map_err(|err| err.to_string())
+}
@@ -9,7 +9,10 @@ use log::{info, LevelFilter};
 
 use git_version::git_version;
 use near::config::init_testnet_configs;
-use near::{get_default_home, get_store_path, init_configs, load_config, start_with_config};
+use near::{
+    create_snapshot, get_default_home, get_store_path, init_configs, load_config, restore_snapshot,
+    start_with_config,
+};
 use near_primitives::types::Version;
 
 fn init_logging(verbose: Option<&str>) {
@@ -78,6 +81,13 @@ fn main() {
             .arg(Arg::with_name("rpc-addr").long("rpc-addr").help("Customize RPC listening address (useful for running multiple nodes on the same machine)").takes_value(true))
             .arg(Arg::with_name("telemetry-url").long("telemetry-url").help("Customize telemetry url").takes_value(true))
         )
+        .subcommand(SubCommand::with_name("snapshot").about("Creates or restores a snapshot of the database")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("create").about("Takes a snapshot of the database of a stopped node (use `adm_create_snapshot` RPC for a running node)")
+                .arg(Arg::with_name("destination").long("destination").required(true).takes_value(true).help("Directory to create the snapshot in, must not exist")))
+            .subcommand(SubCommand::with_name("restore").about("Restores the database from a snapshot, the node must have no data")
+                .arg(Arg::with_name("source").long("source").required(true).takes_value(true).help("Directory of the snapshot")))
+        )
         .subcommand(SubCommand::with_name("unsafe_reset_data").about("(unsafe) Remove all the data, effectively resetting node to genesis state (keeps genesis and config)"))
         .subcommand(SubCommand::with_name("unsafe_reset_all").about("(unsafe) Remove all the config, keys, data and effectively removing all information about the network"))
         .get_matches();
@@ -159,6 +169,23 @@ fn main() {
             start_with_config(home_dir, near_config);
             system.run().unwrap();
         }
+        ("snapshot", Some(args)) => {
+            let near_config = load_config(home_dir);
+            let manifest = match args.subcommand() {
+                ("create", Some(args)) => {
+                    let destination = Path::new(args.value_of("destination").unwrap());
+                    create_snapshot(home_dir, &near_config, destination)
+                        .expect("Failed to create a snapshot")
+                }
+                ("restore", Some(args)) => {
+                    let source = Path::new(args.value_of("source").unwrap());
+                    restore_snapshot(home_dir, &near_config, source)
+                        .expect("Failed to restore the snapshot")
+                }
+                (_, _) => unreachable!(),
+            };
+            info!(target: "near", "Snapshot of {} at head #{} {}, final #{} {}", manifest.chain_id, manifest.head_height, manifest.head_hash, manifest.final_height, manifest.final_hash);
+        }
         ("unsafe_reset_data", Some(_args)) => {
             let store_path = get_store_path(home_dir);
             info!(target: "near", "Removing all data from {}", store_path);
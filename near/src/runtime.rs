@@ -1,5 +1,5 @@
-use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::cmp::{max, min, Ordering};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
 use std::fs::File;
 use std::io::Read;
@@ -8,9 +8,9 @@ use std::sync::{Arc, RwLock};
 
 use borsh::ser::BorshSerialize;
 use borsh::BorshDeserialize;
-use log::debug;
+use log::{debug, warn};
 
-use near_chain::types::ApplyTransactionResult;
+use near_chain::types::{ApplyTransactionResult, StateChanges};
 use near_chain::{BlockHeader, Error, ErrorKind, RuntimeAdapter};
 use near_crypto::{PublicKey, Signature};
 use near_epoch_manager::{BlockInfo, EpochConfig, EpochError, EpochManager, RewardCalculator};
@@ -21,23 +21,24 @@ use near_primitives::challenge::{ChallengesResult, SlashedValidator};
 use near_primitives::errors::{InvalidTxError, RuntimeError};
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::receipt::Receipt;
-use near_primitives::serialize::from_base64;
+use near_primitives::serialize::{from_base, from_base64};
 use near_primitives::sharding::ShardChunkHeader;
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{
     AccountId, Balance, BlockIndex, EpochId, Gas, MerkleHash, ShardId, StateRoot, StateRootNode,
     ValidatorStake,
 };
-use near_primitives::utils::{prefix_for_access_key, ACCOUNT_DATA_SEPARATOR};
+use near_primitives::utils::{is_valid_account_id, prefix_for_access_key, ACCOUNT_DATA_SEPARATOR};
 use near_primitives::views::{
-    AccessKeyInfoView, CallResult, EpochValidatorInfo, QueryError, QueryResponse,
-    QueryResponseKind, ViewStateResult,
+    AccessKeyInfoView, CallResult, EpochValidatorInfo, ExecutionOutcomeWithIdView, QueryError,
+    QueryResponse, QueryResponseKind, SimulationResultView, StateProofView, ViewStateResult,
 };
 use near_store::{
-    get_access_key_raw, ColState, PartialStorage, Store, StoreUpdate, Trie, TrieUpdate,
-    WrappedTrieChanges,
+    get_access_key, get_access_key_raw, get_account, ColState, PartialStorage, Store, StoreUpdate,
+    Trie, TrieUpdate, WrappedTrieChanges,
 };
 use node_runtime::adapter::ViewRuntimeAdapter;
+use node_runtime::cache::StoreCompiledContractCache;
 use node_runtime::state_viewer::TrieViewer;
 use node_runtime::{ApplyState, Runtime, StateRecord, ValidatorAccountsUpdate};
 
@@ -57,6 +58,7 @@ pub struct NightshadeRuntime {
     store: Arc<Store>,
     pub trie: Arc<Trie>,
     trie_viewer: TrieViewer,
+    compiled_contract_cache: Arc<StoreCompiledContractCache>,
     pub runtime: Runtime,
     epoch_manager: Arc<RwLock<EpochManager>>,
     shard_tracker: ShardTracker,
@@ -74,7 +76,8 @@ impl NightshadeRuntime {
     ) -> Self {
         let trie = Arc::new(Trie::new(store.clone()));
         let runtime = Runtime::new(genesis_config.runtime_config.clone());
-        let trie_viewer = TrieViewer::new();
+        let compiled_contract_cache = Arc::new(StoreCompiledContractCache::new(store.clone()));
+        let trie_viewer = TrieViewer::new_with_cache(compiled_contract_cache.clone());
         let num_shards = genesis_config.block_producers_per_shard.len() as ShardId;
         let initial_epoch_config = EpochConfig {
             epoch_length: genesis_config.epoch_length,
@@ -129,12 +132,20 @@ impl NightshadeRuntime {
             trie,
             runtime,
             trie_viewer,
+            compiled_contract_cache,
             epoch_manager,
             shard_tracker,
             subscriptions: HashSet::new(),
         }
     }
 
+    /// Subscribes to changes of the state keys with the given prefixes. The changes are returned
+    /// in `ApplyTransactionResult::state_changes` of every applied chunk.
+    pub fn with_subscriptions(mut self, subscriptions: HashSet<Vec<u8>>) -> Self {
+        self.subscriptions = subscriptions;
+        self
+    }
+
     fn genesis_state_from_dump(&self) -> (StoreUpdate, Vec<StateRoot>) {
         let store_update = self.store.store_update();
         let mut state_file = self.home_dir.clone();
@@ -206,6 +217,7 @@ impl NightshadeRuntime {
         block_index: BlockIndex,
         block_timestamp: u64,
         prev_block_hash: &CryptoHash,
+        block_hash: &CryptoHash,
         receipts: &[Receipt],
         transactions: &[SignedTransaction],
         last_validator_proposals: &[ValidatorStake],
@@ -285,6 +297,7 @@ impl NightshadeRuntime {
             gas_price,
             block_timestamp,
             gas_limit: Some(gas_limit),
+            cache: Some(self.compiled_contract_cache.clone()),
         };
 
         let apply_result = self
@@ -307,6 +320,9 @@ impl NightshadeRuntime {
                 }
                 RuntimeError::StorageError(_) => ErrorKind::StorageError,
             })?;
+        if let Err(err) = self.compiled_contract_cache.flush() {
+            warn!(target: "runtime", "Failed to store compiled contracts: {}", err);
+        }
 
         // Sort the receipts into appropriate outgoing shards.
         let mut receipt_result = HashMap::default();
@@ -318,9 +334,22 @@ impl NightshadeRuntime {
         }
         let total_gas_burnt =
             apply_result.outcomes.iter().map(|tx_result| tx_result.outcome.gas_burnt).sum();
+        // Subscribed prefixes may overlap, so the same key can be reported under several of them.
+        let state_changes: StateChanges = apply_result
+            .key_value_changes
+            .into_iter()
+            .flat_map(|(_prefix, changes)| changes.into_iter())
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .collect();
 
         let result = ApplyTransactionResult {
-            trie_changes: WrappedTrieChanges::new(self.trie.clone(), apply_result.trie_changes),
+            trie_changes: WrappedTrieChanges::new(
+                self.trie.clone(),
+                apply_result.trie_changes,
+                shard_id,
+                *block_hash,
+            ),
             new_root: apply_result.state_root,
             outcomes: apply_result.outcomes,
             receipt_result,
@@ -331,10 +360,58 @@ impl NightshadeRuntime {
             total_balance_burnt: apply_result.stats.total_balance_burnt
                 + apply_result.stats.total_balance_slashed,
             proof: trie.recorded_storage(),
+            state_changes,
         };
 
         Ok(result)
     }
+
+    /// Answers a query that only reads the state, recording the trie nodes it reads as the proof.
+    /// Function calls can read anything, so they have no proofs.
+    fn query_with_proof(
+        &self,
+        state_root: &StateRoot,
+        block_height: BlockIndex,
+        path_parts: Vec<&str>,
+        data: &[u8],
+    ) -> Result<QueryResponse, Box<dyn std::error::Error>> {
+        let trie = Arc::new(self.trie.recording_reads());
+        let state_update = TrieUpdate::new(trie.clone(), *state_root);
+        let account_id = AccountId::from(path_parts[1]);
+        if !is_valid_account_id(&account_id) {
+            return Err(format!("Account ID '{}' is not valid", account_id).into());
+        }
+        // Absent keys are answered with an error together with the proof of their absence.
+        let not_found =
+            |error: String| QueryResponseKind::Error(QueryError { error, logs: vec![] });
+        let kind = match path_parts[0] {
+            "account" => match get_account(&state_update, &account_id)? {
+                Some(account) => QueryResponseKind::ViewAccount(account.into()),
+                None => not_found(format!("account {} does not exist while viewing", account_id)),
+            },
+            "contract" if path_parts.len() == 2 => QueryResponseKind::ViewState(
+                self.trie_viewer.view_state(&state_update, &account_id, data)?,
+            ),
+            "access_key" if path_parts.len() == 3 => {
+                let public_key = PublicKey::try_from(path_parts[2])?;
+                match get_access_key(&state_update, &account_id, &public_key)? {
+                    Some(access_key) => QueryResponseKind::AccessKey(access_key.into()),
+                    None => {
+                        not_found(format!("access key {} does not exist while viewing", public_key))
+                    }
+                }
+            }
+            _ => {
+                return Err(format!("Query {} can't be answered with a proof", path_parts[0]).into())
+            }
+        };
+        let nodes = trie.recorded_storage().expect("Trie records reads").nodes.0;
+        Ok(QueryResponse {
+            kind,
+            block_height,
+            proof: Some(StateProofView { state_root: *state_root, nodes }),
+        })
+    }
 }
 
 pub fn state_record_to_shard_id(state_record: &StateRecord, num_shards: ShardId) -> ShardId {
@@ -362,6 +439,10 @@ pub fn state_record_to_shard_id(state_record: &StateRecord, num_shards: ShardId)
 }
 
 impl RuntimeAdapter for NightshadeRuntime {
+    fn get_trie(&self) -> Arc<Trie> {
+        self.trie.clone()
+    }
+
     fn genesis_state(&self) -> (StoreUpdate, Vec<StateRoot>) {
         let has_records = !self.genesis_config.records.is_empty();
         let has_dump = {
@@ -578,7 +659,15 @@ impl RuntimeAdapter for NightshadeRuntime {
         self.genesis_config.block_producers_per_shard.len() as ShardId
     }
 
-    fn num_total_parts(&self, parent_hash: &CryptoHash) -> usize {
+    fn num_total_parts(&self, parent_hash: &CryptoHash, shard_id: ShardId) -> usize {
+        let configured = self
+            .genesis_config
+            .erasure_coding_per_shard
+            .get(shard_id as usize)
+            .map_or(0, |params| params.num_total_parts);
+        if configured > 0 {
+            return max(configured, 2);
+        }
         let mut epoch_manager = self.epoch_manager.write().expect(POISONED_LOCK_ERR);
         let epoch_id = epoch_manager.get_epoch_id_from_prev_block(parent_hash).unwrap();
         if let Ok(block_producers) = epoch_manager.get_all_block_producers(&epoch_id, &parent_hash)
@@ -594,8 +683,13 @@ impl RuntimeAdapter for NightshadeRuntime {
         }
     }
 
-    fn num_data_parts(&self, parent_hash: &CryptoHash) -> usize {
-        let total_parts = self.num_total_parts(parent_hash);
+    fn num_data_parts(&self, parent_hash: &CryptoHash, shard_id: ShardId) -> usize {
+        let total_parts = self.num_total_parts(parent_hash, shard_id);
+        if let Some(params) = self.genesis_config.erasure_coding_per_shard.get(shard_id as usize) {
+            // At least one part has to be left for parity.
+            let data_parts = total_parts * params.data_parts_percentage as usize / 100;
+            return min(max(data_parts, 1), total_parts - 1);
+        }
         if total_parts <= 3 {
             1
         } else {
@@ -687,6 +781,7 @@ impl RuntimeAdapter for NightshadeRuntime {
             block_timestamp,
             // NOTE: verify transaction doesn't use gas limit
             gas_limit: None,
+            cache: Some(self.compiled_contract_cache.clone()),
         };
 
         match self.runtime.verify_and_charge_transaction(
@@ -722,6 +817,7 @@ impl RuntimeAdapter for NightshadeRuntime {
             gas_price,
             block_timestamp,
             gas_limit: Some(gas_limit),
+            cache: Some(self.compiled_contract_cache.clone()),
         };
 
         // Total amount of gas burnt for converting transactions towards receipts.
@@ -814,7 +910,7 @@ impl RuntimeAdapter for NightshadeRuntime {
         block_index: BlockIndex,
         block_timestamp: u64,
         prev_block_hash: &CryptoHash,
-        _block_hash: &CryptoHash,
+        block_hash: &CryptoHash,
         receipts: &[Receipt],
         transactions: &[SignedTransaction],
         last_validator_proposals: &[ValidatorStake],
@@ -835,6 +931,7 @@ impl RuntimeAdapter for NightshadeRuntime {
             block_index,
             block_timestamp,
             prev_block_hash,
+            block_hash,
             receipts,
             transactions,
             last_validator_proposals,
@@ -860,7 +957,7 @@ impl RuntimeAdapter for NightshadeRuntime {
         block_index: BlockIndex,
         block_timestamp: u64,
         prev_block_hash: &CryptoHash,
-        _block_hash: &CryptoHash,
+        block_hash: &CryptoHash,
         receipts: &[Receipt],
         transactions: &[SignedTransaction],
         last_validator_proposals: &[ValidatorStake],
@@ -876,6 +973,7 @@ impl RuntimeAdapter for NightshadeRuntime {
             block_index,
             block_timestamp,
             prev_block_hash,
+            block_hash,
             receipts,
             transactions,
             last_validator_proposals,
@@ -893,15 +991,20 @@ impl RuntimeAdapter for NightshadeRuntime {
         _block_hash: &CryptoHash,
         path_parts: Vec<&str>,
         data: &[u8],
+        include_proof: bool,
     ) -> Result<QueryResponse, Box<dyn std::error::Error>> {
         if path_parts.is_empty() {
             return Err("Path must contain at least single token".into());
         }
+        if include_proof {
+            return self.query_with_proof(state_root, block_height, path_parts, data);
+        }
         match path_parts[0] {
             "account" => match self.view_account(*state_root, &AccountId::from(path_parts[1])) {
                 Ok(r) => Ok(QueryResponse {
                     kind: QueryResponseKind::ViewAccount(r.into()),
                     block_height,
+                    proof: None,
                 }),
                 Err(e) => Err(e),
             },
@@ -919,18 +1022,33 @@ impl RuntimeAdapter for NightshadeRuntime {
                     Ok(result) => Ok(QueryResponse {
                         kind: QueryResponseKind::CallResult(CallResult { result, logs }),
                         block_height,
+                        proof: None,
                     }),
                     Err(err) => Ok(QueryResponse {
                         kind: QueryResponseKind::Error(QueryError { error: err.to_string(), logs }),
                         block_height,
+                        proof: None,
                     }),
                 }
             }
             "contract" => {
-                match self.view_state(*state_root, &AccountId::from(path_parts[1]), data) {
+                let account_id = AccountId::from(path_parts[1]);
+                // `contract/<account_id>/<limit>/<start_key>` pages through the state.
+                let result = if path_parts.len() == 2 {
+                    self.view_state(*state_root, &account_id, data)
+                } else {
+                    let limit = path_parts[2].parse::<usize>()?;
+                    let start_key = match path_parts.get(3) {
+                        Some(start_key) => from_base(start_key)?,
+                        None => data.to_vec(),
+                    };
+                    self.view_state_page(*state_root, &account_id, data, &start_key, limit)
+                };
+                match result {
                     Ok(result) => Ok(QueryResponse {
                         kind: QueryResponseKind::ViewState(result),
                         block_height,
+                        proof: None,
                     }),
                     Err(err) => Ok(QueryResponse {
                         kind: QueryResponseKind::Error(QueryError {
@@ -938,6 +1056,7 @@ impl RuntimeAdapter for NightshadeRuntime {
                             logs: vec![],
                         }),
                         block_height,
+                        proof: None,
                     }),
                 }
             }
@@ -954,6 +1073,7 @@ impl RuntimeAdapter for NightshadeRuntime {
                                     .collect(),
                             ),
                             block_height,
+                            proof: None,
                         }
                     })
                 } else {
@@ -965,6 +1085,7 @@ impl RuntimeAdapter for NightshadeRuntime {
                     .map(|access_key| QueryResponse {
                         kind: QueryResponseKind::AccessKey(access_key.into()),
                         block_height,
+                        proof: None,
                     })
                 };
                 match result {
@@ -975,6 +1096,7 @@ impl RuntimeAdapter for NightshadeRuntime {
                             logs: vec![],
                         }),
                         block_height,
+                        proof: None,
                     }),
                 }
             }
@@ -982,6 +1104,60 @@ impl RuntimeAdapter for NightshadeRuntime {
         }
     }
 
+    fn simulate_transaction(
+        &self,
+        shard_id: ShardId,
+        state_root: &StateRoot,
+        block_index: BlockIndex,
+        block_timestamp: u64,
+        gas_price: Balance,
+        transaction: &SignedTransaction,
+    ) -> Result<SimulationResultView, Box<dyn std::error::Error>> {
+        let apply_state = ApplyState {
+            block_index,
+            epoch_length: self.genesis_config.epoch_length,
+            gas_price,
+            block_timestamp,
+            gas_limit: None,
+            // Compiling contracts doesn't change the state, but the simulation shouldn't write
+            // to the store at all.
+            cache: None,
+        };
+        let num_shards = self.num_shards();
+        let result = self
+            .runtime
+            .simulate_transaction(
+                self.trie.clone(),
+                *state_root,
+                &apply_state,
+                transaction,
+                &|account_id| account_id_to_shard_id(account_id, num_shards) == shard_id,
+            )
+            .map_err(|err| match err {
+                RuntimeError::InvalidTxError(err) => err.to_string(),
+                err => format!("{:?}", err),
+            })?;
+        let logs = result
+            .outcomes
+            .iter()
+            .flat_map(|outcome_with_id| outcome_with_id.outcome.logs.iter().cloned())
+            .collect();
+        Ok(SimulationResultView {
+            outcomes: result
+                .outcomes
+                .into_iter()
+                .map(|outcome_with_id| ExecutionOutcomeWithIdView {
+                    id: outcome_with_id.id,
+                    outcome: outcome_with_id.outcome.into(),
+                    proof: vec![],
+                })
+                .collect(),
+            gas_burnt: result.gas_burnt,
+            logs,
+            unprocessed_receipts: result.unprocessed_receipts.into_iter().map(Into::into).collect(),
+        })
+    }
+
     fn get_validator_info(&self, block_hash: &CryptoHash) -> Result<EpochValidatorInfo, Error> {
         let mut epoch_manager = self.epoch_manager.write().expect(POISONED_LOCK_ERR);
         epoch_manager.get_validator_info(block_hash).map_err(|e| e.into())
@@ -1149,17 +1325,31 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
         let state_update = TrieUpdate::new(self.trie.clone(), state_root);
         self.trie_viewer.view_state(&state_update, account_id, prefix)
     }
+
+    fn view_state_page(
+        &self,
+        state_root: MerkleHash,
+        account_id: &AccountId,
+        prefix: &[u8],
+        start_key: &[u8],
+        limit: usize,
+    ) -> Result<ViewStateResult, Box<dyn std::error::Error>> {
+        let state_update = TrieUpdate::new(self.trie.clone(), state_root);
+        self.trie_viewer.view_state_page(&state_update, account_id, prefix, start_key, limit)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::collections::{BTreeSet, HashMap, HashSet};
 
+    use borsh::BorshDeserialize;
     use tempdir::TempDir;
 
     use near_chain::{ReceiptResult, RuntimeAdapter, Tip};
     use near_client::BlockProducer;
     use near_crypto::{InMemorySigner, KeyType, Signer};
+    use near_primitives::account::Account;
     use near_primitives::block::WeightAndScore;
     use near_primitives::challenge::{ChallengesResult, SlashedValidator};
     use near_primitives::hash::{hash, CryptoHash};
@@ -1172,14 +1362,18 @@ mod test {
         AccountId, Balance, BlockIndex, EpochId, Gas, Nonce, ShardId, StateRoot, ValidatorId,
         ValidatorStake,
     };
-    use near_primitives::utils::key_for_account;
-    use near_primitives::views::{AccountView, CurrentEpochValidatorInfo, EpochValidatorInfo};
-    use near_store::create_store;
+    use near_primitives::utils::{col, key_for_account};
+    use near_primitives::views::{
+        AccountView, CurrentEpochValidatorInfo, EpochValidatorInfo, QueryResponseKind,
+    };
+    use near_store::{create_store, verify_state_proof};
     use node_runtime::adapter::ViewRuntimeAdapter;
     use node_runtime::config::RuntimeConfig;
     use node_runtime::ApplyState;
 
-    use crate::config::{FISHERMEN_THRESHOLD, TESTING_INIT_BALANCE, TESTING_INIT_STAKE};
+    use crate::config::{
+        ErasureCodingConfig, FISHERMEN_THRESHOLD, TESTING_INIT_BALANCE, TESTING_INIT_STAKE,
+    };
     use crate::runtime::POISONED_LOCK_ERR;
     use crate::{get_store_path, GenesisConfig, NightshadeRuntime};
 
@@ -2277,6 +2471,7 @@ mod test {
             gas_price: 10,
             block_timestamp: 100,
             gas_limit: None,
+            cache: None,
         };
         let mut prefixes = HashSet::new();
         prefixes.insert(prefix);
@@ -2296,6 +2491,54 @@ mod test {
         assert!(!apply_result.key_value_changes.is_empty());
     }
 
+    /// Changes under overlapping subscribed prefixes are reported once per key, sorted by key.
+    #[test]
+    fn test_state_changes_of_subscriptions() {
+        let num_nodes = 2;
+        let validators = (0..num_nodes).map(|i| format!("test{}", i + 1)).collect::<Vec<_>>();
+        let mut env = TestEnv::new(
+            "test_state_changes_of_subscriptions",
+            vec![validators.clone()],
+            2,
+            vec![],
+            vec![],
+            true,
+        );
+        env.runtime.subscriptions.insert(col::ACCOUNT.to_vec());
+        env.runtime.subscriptions.insert(key_for_account(&validators[1]));
+        let signer = InMemorySigner::from_seed(&validators[0], KeyType::ED25519, &validators[0]);
+        let transaction = SignedTransaction::send_money(
+            1,
+            validators[0].clone(),
+            validators[1].clone(),
+            &signer,
+            10,
+            CryptoHash::default(),
+        );
+        let apply_result = env
+            .runtime
+            .apply_transactions(
+                0,
+                &env.state_roots[0],
+                1,
+                0,
+                &env.head.last_block_hash,
+                &hash(&[1]),
+                &[],
+                &[transaction],
+                &[],
+                env.runtime.genesis_config.min_gas_price,
+                u64::max_value(),
+                &vec![],
+            )
+            .unwrap();
+        let keys: Vec<_> = apply_result.state_changes.iter().map(|(key, _)| key.clone()).collect();
+        assert!(keys.contains(&key_for_account(&validators[0])));
+        assert!(keys.contains(&key_for_account(&validators[1])));
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(keys.iter().all(|key| key.starts_with(col::ACCOUNT)));
+    }
+
     /// Run 4 validators. Two of them first change their stake to below validator threshold but above
     /// fishermen threshold. Make sure their balance is correct. Then one fisherman increases their
     /// stake to become a validator again while the other one decreases to below fishermen threshold.
@@ -2370,4 +2613,76 @@ mod test {
         let response = env.runtime.get_validator_info(&env.head.last_block_hash).unwrap();
         assert!(response.current_fishermen.is_empty());
     }
+
+    #[test]
+    fn test_query_with_proof() {
+        let validators = vec!["test1".to_string()];
+        let env = TestEnv::new("test_query_with_proof", vec![validators], 2, vec![], vec![], true);
+        let state_root = env.state_roots[0];
+        let response = env
+            .runtime
+            .query(&state_root, 0, 0, &CryptoHash::default(), vec!["account", "test1"], &[], true)
+            .unwrap();
+        let proof = response.proof.unwrap();
+        assert_eq!(proof.state_root, state_root);
+        let value =
+            verify_state_proof(&state_root, &proof.nodes, &key_for_account(&"test1".to_string()))
+                .unwrap()
+                .unwrap();
+        let account: AccountView = Account::try_from_slice(&value).unwrap().into();
+        match response.kind {
+            QueryResponseKind::ViewAccount(view) => assert_eq!(view, account),
+            kind => panic!("queried account, but received something else: {:?}", kind),
+        }
+        assert_eq!(account, env.view_account("test1"));
+
+        // Absence of an account is proven as well.
+        let response = env
+            .runtime
+            .query(&state_root, 0, 0, &CryptoHash::default(), vec!["account", "absent"], &[], true)
+            .unwrap();
+        match response.kind {
+            QueryResponseKind::Error(_) => {}
+            kind => panic!("queried absent account, but received something else: {:?}", kind),
+        }
+        let proof = response.proof.unwrap();
+        assert_eq!(
+            verify_state_proof(&state_root, &proof.nodes, &key_for_account(&"absent".to_string())),
+            Ok(None)
+        );
+
+        // Function calls can read any state, so they have no proofs.
+        assert!(env
+            .runtime
+            .query(&state_root, 0, 0, &CryptoHash::default(), vec!["call", "test1", "f"], &[], true)
+            .is_err());
+    }
+
+    #[test]
+    fn test_erasure_coding_per_shard() {
+        init_test_logger();
+        let validators = (0..4).map(|i| format!("test{}", i + 1)).collect::<Vec<_>>();
+        let mut env = TestEnv::new(
+            "test_erasure_coding_per_shard",
+            vec![validators.clone(), validators],
+            4,
+            vec![],
+            vec![],
+            false,
+        );
+        let genesis_hash = env.head.last_block_hash;
+        // Without configuration, there is one part per block producer.
+        assert_eq!(env.runtime.num_total_parts(&genesis_hash, 1), 4);
+        assert_eq!(env.runtime.num_data_parts(&genesis_hash, 1), 1);
+
+        env.runtime.genesis_config.erasure_coding_per_shard = vec![
+            ErasureCodingConfig { num_total_parts: 0, data_parts_percentage: 50 },
+            ErasureCodingConfig { num_total_parts: 10, data_parts_percentage: 100 },
+        ];
+        assert_eq!(env.runtime.num_total_parts(&genesis_hash, 0), 4);
+        assert_eq!(env.runtime.num_data_parts(&genesis_hash, 0), 2);
+        assert_eq!(env.runtime.num_total_parts(&genesis_hash, 1), 10);
+        // At least one parity part is kept.
+        assert_eq!(env.runtime.num_data_parts(&genesis_hash, 1), 9);
+    }
 }
@@ -9,6 +9,9 @@ class Signature:
 class SignedTransaction:
     pass
 
+class ExtraSignature:
+    pass
+
 class Transaction:
     pass
 
@@ -27,6 +30,12 @@ class FunctionCallPermission:
 class FullAccessPermission:
     pass
 
+class MultisigPermission:
+    pass
+
+class WeightedKey:
+    pass
+
 class Action:
     pass
 
@@ -62,6 +71,10 @@ tx_schema = dict([[Signature, { 'kind': 'struct', 'fields': [
             ['transaction', Transaction],
             ['signature', Signature]
         ] }],
+[ExtraSignature, { 'kind': 'struct', 'fields': [
+            ['publicKey', PublicKey],
+            ['signature', Signature]
+        ] }],
 [Transaction, { 'kind': 'struct', 'fields': [
             ['signerId', 'string'],
             ['publicKey', PublicKey],
@@ -81,6 +94,7 @@ tx_schema = dict([[Signature, { 'kind': 'struct', 'fields': [
 [AccessKeyPermission, { 'kind': 'enum', 'field': 'enum', 'values': [
             ['functionCall', FunctionCallPermission],
             ['fullAccess', FullAccessPermission],
+            ['multisig', MultisigPermission],
         ] }],
 [FunctionCallPermission, { 'kind': 'struct', 'fields': [
             ['allowance', { 'kind': 'option', type: 'u128' }],
@@ -88,6 +102,14 @@ tx_schema = dict([[Signature, { 'kind': 'struct', 'fields': [
             ['methodNames', ['string']],
         ] }],
 [FullAccessPermission, { 'kind': 'struct', 'fields': [] }],
+[MultisigPermission, { 'kind': 'struct', 'fields': [
+            ['keys', [WeightedKey]],
+            ['threshold', 'u64'],
+        ] }],
+[WeightedKey, { 'kind': 'struct', 'fields': [
+            ['publicKey', PublicKey],
+            ['weight', 'u64'],
+        ] }],
 [Action, { 'kind': 'enum', 'field': 'enum', 'values': [
             ['createAccount', CreateAccount],
             ['deployContract', DeployContract],
@@ -151,6 +173,44 @@ def sign_and_serialize_transaction(receiverId, nonce, actions, blockHash, accoun
 
     return BinarySerializer(tx_schema).serialize(signedTx)
 
+# Extra signatures don't fit the schema: the signature of such a transaction is preceded by a 0xff
+# tag and followed by the list of (public key, signature) pairs of the other signers.
+def sign_and_serialize_multisig_transaction(receiverId, nonce, actions, blockHash, accountId, keys):
+    tx = Transaction()
+    tx.signerId = accountId
+    tx.publicKey = PublicKey()
+    tx.publicKey.keyType = 0
+    tx.publicKey.data = keys[0][0]
+    tx.nonce = nonce
+    tx.receiverId = receiverId
+    tx.actions = actions
+    tx.blockHash = blockHash
+
+    msg = BinarySerializer(tx_schema).serialize(tx)
+    hash_ = hashlib.sha256(msg).digest()
+
+    def sign(sk):
+        signature = Signature()
+        signature.keyType = 0
+        signature.data = SigningKey(sk).sign(hash_)
+        return signature
+
+    extraSignatures = []
+    for pk, sk in keys[1:]:
+        extraSignature = ExtraSignature()
+        extraSignature.publicKey = PublicKey()
+        extraSignature.publicKey.keyType = 0
+        extraSignature.publicKey.data = pk
+        extraSignature.signature = sign(sk)
+        extraSignatures.append(extraSignature)
+
+    serializer = BinarySerializer(tx_schema)
+    serializer.serialize_struct(tx)
+    serializer.serialize_num(0xff, 1)
+    serializer.serialize_struct(sign(keys[0][1]))
+    serializer.serialize_field(extraSignatures, [ExtraSignature])
+    return bytes(serializer.array)
+
 def create_payment_action(amount):
     transfer = Transfer()
     transfer.deposit = amount
@@ -170,6 +230,32 @@ def create_staking_action(amount, pk):
     action.stake = stake
     return action
 
+def create_add_multisig_key_action(pk, keys, threshold):
+    permission = MultisigPermission()
+    permission.keys = []
+    for key_pk, weight in keys:
+        weightedKey = WeightedKey()
+        weightedKey.publicKey = PublicKey()
+        weightedKey.publicKey.keyType = 0
+        weightedKey.publicKey.data = key_pk
+        weightedKey.weight = weight
+        permission.keys.append(weightedKey)
+    permission.threshold = threshold
+    accessKey = AccessKey()
+    accessKey.nonce = 0
+    accessKey.permission = AccessKeyPermission()
+    accessKey.permission.enum = 'multisig'
+    accessKey.permission.multisig = permission
+    addKey = AddKey()
+    addKey.publicKey = PublicKey()
+    addKey.publicKey.keyType = 0
+    addKey.publicKey.data = pk
+    addKey.accessKey = accessKey
+    action = Action()
+    action.enum = 'addKey'
+    action.addKey = addKey
+    return action
+
 def sign_payment_tx(key, to, amount, nonce, blockHash):
     action = create_payment_action(amount)
     return sign_and_serialize_transaction(to, nonce, [action], blockHash, key.account_id, key.decoded_pk(), key.decoded_sk())
@@ -178,3 +264,10 @@ def sign_staking_tx(signer_key, validator_key, amount, nonce, blockHash):
     action = create_staking_action(amount, validator_key.decoded_pk())
     return sign_and_serialize_transaction(signer_key.account_id, nonce, [action], blockHash, signer_key.account_id, signer_key.decoded_pk(), signer_key.decoded_sk())
 
+def sign_add_multisig_key_tx(signer_key, multisig_keys, threshold, nonce, blockHash):
+    action = create_add_multisig_key_action(multisig_keys[0].decoded_pk(), [(key.decoded_pk(), 1) for key in multisig_keys], threshold)
+    return sign_and_serialize_transaction(signer_key.account_id, nonce, [action], blockHash, signer_key.account_id, signer_key.decoded_pk(), signer_key.decoded_sk())
+
+def sign_multisig_payment_tx(multisig_keys, to, amount, nonce, blockHash):
+    action = create_payment_action(amount)
+    return sign_and_serialize_multisig_transaction(to, nonce, [action], blockHash, multisig_keys[0].account_id, [(key.decoded_pk(), key.decoded_sk()) for key in multisig_keys])
@@ -0,0 +1,50 @@
+# Adds a multisig access key that requires three signatures to the account of the first node,
+# then sends a transfer signed by only two of the keys, which must be rejected, and a transfer
+# signed by all three of them, which must go through.
+
+import sys, time, base58, ed25519
+
+sys.path.append('lib')
+
+
+from cluster import start_cluster, Key
+from transaction import sign_add_multisig_key_tx, sign_multisig_payment_tx
+
+nodes = start_cluster(2, 0, 1, None, [["min_gas_price", 0], ["epoch_length", 10], ["block_producer_kickout_threshold", 70]], {})
+
+time.sleep(3)
+
+def new_key(account_id):
+    sk, pk = ed25519.create_keypair()
+    return Key(account_id, base58.b58encode(pk.to_bytes()).decode('ascii'), base58.b58encode(sk.to_bytes()).decode('ascii'))
+
+def latest_block_hash():
+    hash_ = nodes[1].get_status()['sync_info']['latest_block_hash']
+    return base58.b58decode(hash_.encode('utf8'))
+
+multisig_keys = [new_key('test0') for _ in range(3)]
+
+tx = sign_add_multisig_key_tx(nodes[0].signer_key, multisig_keys, 3, 1, latest_block_hash())
+res = nodes[1].send_tx_and_wait(tx, timeout=20)
+assert 'result' in res, res
+assert 'SuccessValue' in res['result']['status'], res
+
+old_balances = [int(nodes[1].get_account("test%s" % x)['result']['amount']) for x in [0, 1]]
+print("BALANCES BEFORE", old_balances)
+
+tx = sign_multisig_payment_tx(multisig_keys[:2], 'test1', 100, 1, latest_block_hash())
+res = nodes[1].send_tx_and_wait(tx, timeout=20)
+assert 'error' in res, res
+print(res['error'])
+
+tx = sign_multisig_payment_tx(multisig_keys, 'test1', 100, 1, latest_block_hash())
+res = nodes[1].send_tx_and_wait(tx, timeout=20)
+assert 'result' in res, res
+assert 'SuccessValue' in res['result']['status'], res
+
+new_balances = [int(nodes[1].get_account("test%s" % x)['result']['amount']) for x in [0, 1]]
+print("BALANCES AFTER", new_balances)
+
+old_balances[0] -= 100
+old_balances[1] += 100
+assert old_balances == new_balances, "%s != %s" % (old_balances, new_balances)
@@ -50,6 +50,8 @@ pub struct RuntimeFeesConfig {
     pub action_creation_config: ActionCreationConfig,
     /// Describes fees for storage rent
     pub storage_usage_config: StorageUsageConfig,
+    /// Cost of verifying each signature of a transaction beyond the first one.
+    pub extra_signature_verification_cost: Gas,
 
     /// Fraction of the burnt gas to reward to the contract account for execution.
     pub burnt_gas_reward: Fraction,
@@ -105,6 +107,10 @@ pub struct AccessKeyCreationConfig {
     pub function_call_cost: Fee,
     /// Cost per byte of method_names of creating a restricted access-key.
     pub function_call_cost_per_byte: Fee,
+    /// Base cost of creating a multisig access-key.
+    pub multisig_cost: Fee,
+    /// Cost per weighted key of creating a multisig access-key.
+    pub multisig_cost_per_key: Fee,
 }
 
 /// Describes cost of storage per block
@@ -178,6 +184,13 @@ impl Default for RuntimeFeesConfig {
                         send_not_sir: 37538150,
                         execution: 37538150,
                     },
+                    multisig_cost: Fee { send_sir: 0, send_not_sir: 0, execution: 0 },
+                    // A weighted key is 41 bytes serialized, priced as function call key bytes.
+                    multisig_cost_per_key: Fee {
+                        send_sir: 1539064150,
+                        send_not_sir: 1539064150,
+                        execution: 1539064150,
+                    },
                 },
                 delete_key_cost: Fee { send_sir: 0, send_not_sir: 0, execution: 0 },
                 delete_account_cost: Fee {
@@ -193,6 +206,7 @@ impl Default for RuntimeFeesConfig {
                 value_cost_per_byte: 1,
                 code_cost_per_byte: 1,
             },
+            extra_signature_verification_cost: 60000000000,
             burnt_gas_reward: Fraction { numerator: 3, denominator: 10 },
         }
     }
@@ -219,6 +233,8 @@ impl RuntimeFeesConfig {
                     full_access_cost: free.clone(),
                     function_call_cost: free.clone(),
                     function_call_cost_per_byte: free.clone(),
+                    multisig_cost: free.clone(),
+                    multisig_cost_per_key: free.clone(),
                 },
                 delete_key_cost: free.clone(),
                 delete_account_cost: free.clone(),
@@ -230,6 +246,7 @@ impl RuntimeFeesConfig {
                 value_cost_per_byte: 0,
                 code_cost_per_byte: 0,
             },
+            extra_signature_verification_cost: 0,
             burnt_gas_reward: Fraction { numerator: 0, denominator: 1 },
         }
     }
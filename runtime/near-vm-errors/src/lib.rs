@@ -85,6 +85,9 @@ pub enum HostError {
     InvalidMethodName,
     InvalidPublicKey,
     ProhibitedInView(String),
+    InvalidEventTopics,
+    NumberOfEventsExceeded(u64),
+    TotalEventLengthExceeded(u64),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -189,6 +192,9 @@ impl std::fmt::Display for HostError {
             InvalidMethodName => write!(f, "VM Logic returned an invalid method name"),
             InvalidPublicKey => write!(f, "VM Logic provided an invalid public key"),
             ProhibitedInView(method_name) => write!(f, "{} is not allowed in view calls", method_name),
+            InvalidEventTopics => write!(f, "Event topics must be 32 bytes each and not exceed the limit"),
+            NumberOfEventsExceeded(limit) => write!(f, "Exceeded the limit of {} events per call", limit),
+            TotalEventLengthExceeded(limit) => write!(f, "Exceeded the limit of {} bytes of events per call", limit),
         }
     }
 }
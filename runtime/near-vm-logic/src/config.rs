@@ -37,6 +37,13 @@ pub struct VMConfig {
     pub max_number_logs: u64,
     /// Maximum length of a single log, in bytes.
     pub max_log_len: u64,
+    /// Maximum number of topics of a single event.
+    pub max_number_event_topics: u64,
+    /// Maximum number of events emitted by a single function call.
+    pub max_number_events: u64,
+    /// Maximum total length of the names, topics and data of the events emitted by a single
+    /// function call, in bytes.
+    pub max_total_event_length: u64,
 }
 
 impl Default for VMConfig {
@@ -57,6 +64,9 @@ impl Default for VMConfig {
             max_number_registers: 100,
             max_number_logs: 100,
             max_log_len: 500,
+            max_number_event_topics: 4,
+            max_number_events: 100,
+            max_total_event_length: 16 * 1024,
         }
     }
 }
@@ -84,6 +94,9 @@ impl VMConfig {
             max_number_registers: 100,
             max_number_logs: 100,
             max_log_len: 500,
+            max_number_event_topics: 4,
+            max_number_events: 100,
+            max_total_event_length: 16 * 1024,
         }
     }
 }
@@ -133,6 +146,11 @@ pub struct ExtCostsConfig {
     /// Cost for logging per byte
     pub log_byte: Gas,
 
+    /// Cost for emitting an event.
+    pub event_base: Gas,
+    /// Cost for emitting an event per byte of its name, topics and data.
+    pub event_byte: Gas,
+
     // ###############
     // # Storage API #
     // ###############
@@ -217,6 +235,8 @@ impl Default for ExtCostsConfig {
             sha256_byte: 5536829,
             log_base: 0,
             log_byte: 0,
+            event_base: 0,
+            event_byte: 0,
             storage_write_base: 21058769282,
             storage_write_key_byte: 23447086,
             storage_write_value_byte: 9437547,
@@ -265,6 +285,8 @@ impl ExtCostsConfig {
             sha256_byte: 0,
             log_base: 0,
             log_byte: 0,
+            event_base: 0,
+            event_byte: 0,
             storage_write_base: 0,
             storage_write_key_byte: 0,
             storage_write_value_byte: 0,
@@ -314,6 +336,8 @@ pub enum ExtCosts {
     sha256_byte,
     log_base,
     log_byte,
+    event_base,
+    event_byte,
     storage_write_base,
     storage_write_key_byte,
     storage_write_value_byte,
@@ -361,6 +385,8 @@ impl ExtCosts {
             sha256_byte => config.sha256_byte,
             log_base => config.log_base,
             log_byte => config.log_byte,
+            event_base => config.event_base,
+            event_byte => config.event_byte,
             storage_write_base => config.storage_write_base,
             storage_write_key_byte => config.storage_write_key_byte,
             storage_write_value_byte => config.storage_write_value_byte,
@@ -13,7 +13,7 @@ pub use context::VMContext;
 pub use dependencies::{External, MemoryLike, ValuePtr};
 pub use logic::{VMLogic, VMOutcome};
 pub use near_vm_errors::{HostError, HostErrorOrStorageError};
-pub use types::ReturnData;
+pub use types::{ContractEvent, ReturnData};
 
 #[cfg(feature = "costs_counting")]
 pub use gas_counter::EXT_COSTS_COUNTER;
@@ -4,8 +4,8 @@ use crate::context::VMContext;
 use crate::dependencies::{External, MemoryLike};
 use crate::gas_counter::GasCounter;
 use crate::types::{
-    AccountId, Balance, Gas, IteratorIndex, PromiseIndex, PromiseResult, ReceiptIndex, ReturnData,
-    StorageUsage,
+    AccountId, Balance, ContractEvent, Gas, IteratorIndex, PromiseIndex, PromiseResult,
+    ReceiptIndex, ReturnData, StorageUsage,
 };
 use crate::{ExtCosts, HostError, HostErrorOrStorageError, ValuePtr};
 use byteorder::ByteOrder;
@@ -45,6 +45,10 @@ pub struct VMLogic<'a> {
     return_data: ReturnData,
     /// Logs written by the runtime.
     logs: Vec<String>,
+    /// Events emitted by the contract.
+    events: Vec<ContractEvent>,
+    /// Total length of the names, topics and data of the emitted events.
+    total_event_length: u64,
     /// Registers can be used by the guest to store blobs of data without moving them across
     /// host-guest boundary.
     registers: HashMap<u64, Vec<u8>>,
@@ -127,6 +131,8 @@ impl<'a> VMLogic<'a> {
             gas_counter,
             return_data: ReturnData::None,
             logs: vec![],
+            events: vec![],
+            total_event_length: 0,
             registers: HashMap::new(),
             valid_iterators: HashSet::new(),
             invalid_iterators: HashSet::new(),
@@ -1608,6 +1614,74 @@ impl<'a> VMLogic<'a> {
         Ok(())
     }
 
+    /// Emits an event with the UTF-8 encoded name, the indexed topics and the data. Topics are
+    /// concatenated, 32 bytes each. If `len == u64::MAX` for the topics or the data then they
+    /// are read from the register with id `ptr`. The name is read like the message of `log_utf8`.
+    ///
+    /// # Errors
+    ///
+    /// * If the name, topics or data extend outside the memory of the guest with
+    ///   `MemoryAccessViolation`;
+    /// * If a register is used that does not exist returns `InvalidRegisterId`;
+    /// * If the name is not UTF-8 or longer than `max_log_len` returns `BadUtf8`;
+    /// * If the length of topics is not a multiple of 32 or there are more than
+    ///   `max_number_event_topics` of them returns `InvalidEventTopics`;
+    /// * If the call already emitted `max_number_events` events returns `NumberOfEventsExceeded`;
+    /// * If the total length of the events of the call exceeds `max_total_event_length` returns
+    ///   `TotalEventLengthExceeded`;
+    /// * If called as view function returns `ProhibitedInView`.
+    ///
+    /// # Cost
+    ///
+    /// `base + event_base + event_byte * (name bytes + topics bytes + data bytes) + utf8
+    /// decoding cost + cost of reading the topics and the data from memory or registers`
+    pub fn emit_event(
+        &mut self,
+        name_len: u64,
+        name_ptr: u64,
+        topics_len: u64,
+        topics_ptr: u64,
+        data_len: u64,
+        data_ptr: u64,
+    ) -> Result<()> {
+        self.gas_counter.pay_base(base)?;
+        if self.context.is_view {
+            return Err(HostError::ProhibitedInView("emit_event".to_string()).into());
+        }
+        if self.events.len() as u64 >= self.config.max_number_events {
+            return Err(HostError::NumberOfEventsExceeded(self.config.max_number_events).into());
+        }
+        let name = self.get_utf8_string(name_len, name_ptr)?;
+        let topics_buf = self.get_vec_from_memory_or_register(topics_ptr, topics_len)?;
+        if topics_buf.len() % 32 != 0
+            || (topics_buf.len() / 32) as u64 > self.config.max_number_event_topics
+        {
+            return Err(HostError::InvalidEventTopics.into());
+        }
+        let data = self.get_vec_from_memory_or_register(data_ptr, data_len)?;
+        let event_length = (name.as_bytes().len() + topics_buf.len() + data.len()) as u64;
+        self.gas_counter.pay_base(event_base)?;
+        self.gas_counter.pay_per_byte(event_byte, event_length)?;
+        let total_event_length = self.total_event_length.saturating_add(event_length);
+        if total_event_length > self.config.max_total_event_length {
+            return Err(
+                HostError::TotalEventLengthExceeded(self.config.max_total_event_length).into()
+            );
+        }
+        self.total_event_length = total_event_length;
+        let topics = topics_buf
+            .chunks(32)
+            .map(|chunk| {
+                let mut topic = [0u8; 32];
+                topic.copy_from_slice(chunk);
+                topic
+            })
+            .collect();
+        let account_id = self.context.current_account_id.clone();
+        self.events.push(ContractEvent { account_id, name, topics, data });
+        Ok(())
+    }
+
     /// Special import kept for compatibility with AssemblyScript contracts. Not called by smart
     /// contracts directly, but instead called by the code generated by AssemblyScript.
     ///
@@ -1983,6 +2057,7 @@ impl<'a> VMLogic<'a> {
             burnt_gas: self.gas_counter.burnt_gas(),
             used_gas: self.gas_counter.used_gas(),
             logs: self.logs,
+            events: self.events,
         }
     }
 }
@@ -1995,4 +2070,5 @@ pub struct VMOutcome {
     pub burnt_gas: Gas,
     pub used_gas: Gas,
     pub logs: Vec<String>,
+    pub events: Vec<ContractEvent>,
 }
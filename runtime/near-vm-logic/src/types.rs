@@ -1,3 +1,4 @@
+use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 
 pub type AccountId = String;
@@ -33,3 +34,16 @@ pub enum PromiseResult {
     Successful(Vec<u8>),
     Failed,
 }
+
+/// Event emitted by the contract through `emit_event`. Unlike logs, events are typed, so that
+/// indexers can find them by the name and the topics without parsing free-form strings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct ContractEvent {
+    /// The contract that emitted the event.
+    pub account_id: AccountId,
+    pub name: String,
+    /// Indexed topics, 32 bytes each.
+    pub topics: Vec<[u8; 32]>,
+    #[serde(with = "crate::serde_with::bytes_as_str")]
+    pub data: Vec<u8>,
+}
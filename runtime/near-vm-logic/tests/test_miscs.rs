@@ -351,3 +351,89 @@ fn test_hash256_register() {
         ExtCosts::sha256_byte: len,
     });
 }
+
+#[test]
+fn test_emit_event() {
+    let mut logic_builder = VMLogicBuilder::default();
+    let mut logic = logic_builder.build(get_context(vec![], false));
+    let name = b"transfer";
+    let mut topics = vec![1u8; 32];
+    topics.extend_from_slice(&[2u8; 32]);
+    let data = b"payload";
+    logic
+        .emit_event(
+            name.len() as _,
+            name.as_ptr() as _,
+            topics.len() as _,
+            topics.as_ptr() as _,
+            data.len() as _,
+            data.as_ptr() as _,
+        )
+        .expect("Valid event");
+    let outcome = logic.outcome();
+    assert_eq!(outcome.events.len(), 1);
+    assert_eq!(outcome.events[0].account_id, "alice.near");
+    assert_eq!(outcome.events[0].name, "transfer");
+    assert_eq!(outcome.events[0].topics, vec![[1u8; 32], [2u8; 32]]);
+    assert_eq!(outcome.events[0].data, data.to_vec());
+    let len = (name.len() + topics.len() + data.len()) as u64;
+    assert_costs(map! {
+        ExtCosts::base: 1,
+        ExtCosts::event_base: 1,
+        ExtCosts::event_byte: len,
+        ExtCosts::read_memory_base: 3,
+        ExtCosts::read_memory_byte: len,
+        ExtCosts::utf8_decoding_base: 1,
+        ExtCosts::utf8_decoding_byte: name.len() as u64,
+    });
+}
+
+#[test]
+fn test_emit_event_invalid_topics() {
+    let mut logic_builder = VMLogicBuilder::default();
+    let mut logic = logic_builder.build(get_context(vec![], false));
+    let name = b"transfer";
+    let data = b"payload";
+    let emit = |logic: &mut near_vm_logic::VMLogic, topics: &[u8]| {
+        logic.emit_event(
+            name.len() as _,
+            name.as_ptr() as _,
+            topics.len() as _,
+            topics.as_ptr() as _,
+            data.len() as _,
+            data.as_ptr() as _,
+        )
+    };
+    assert_eq!(emit(&mut logic, &[0u8; 31]), Err(HostError::InvalidEventTopics.into()));
+    assert_eq!(emit(&mut logic, &[0u8; 32 * 5]), Err(HostError::InvalidEventTopics.into()));
+    assert_eq!(logic.outcome().events.len(), 0);
+
+    let mut logic = logic_builder.build(get_context(vec![], true));
+    assert_eq!(
+        emit(&mut logic, &[0u8; 32]),
+        Err(HostError::ProhibitedInView("emit_event".to_string()).into())
+    );
+}
+
+#[test]
+fn test_emit_event_limits() {
+    let mut logic_builder = VMLogicBuilder::default();
+    logic_builder.config.max_number_events = 2;
+    logic_builder.config.max_total_event_length = 20;
+    let mut logic = logic_builder.build(get_context(vec![], false));
+    let name = b"transfer";
+    let emit = |logic: &mut near_vm_logic::VMLogic, data: &[u8]| {
+        logic.emit_event(
+            name.len() as _,
+            name.as_ptr() as _,
+            0,
+            0,
+            data.len() as _,
+            data.as_ptr() as _,
+        )
+    };
+    assert_eq!(emit(&mut logic, &[0u8; 13]), Err(HostError::TotalEventLengthExceeded(20).into()));
+    emit(&mut logic, &[0u8; 2]).expect("Valid event");
+    emit(&mut logic, &[]).expect("Valid event");
+    assert_eq!(emit(&mut logic, &[]), Err(HostError::NumberOfEventsExceeded(2).into()));
+}
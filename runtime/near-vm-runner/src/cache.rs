@@ -1,5 +1,8 @@
 use cached::{cached_key, SizedCache};
 use wasmer_runtime;
+use wasmer_runtime::{compiler_for_backend, Backend};
+use wasmer_runtime_core::cache::Artifact;
+use wasmer_runtime_core::load_cache_with;
 
 use crate::errors::IntoVMError;
 use crate::prepare;
@@ -8,18 +11,160 @@ use near_vm_logic::VMConfig;
 
 /// Cache size in number of cached modules to hold.
 const CACHE_SIZE: usize = 1024;
-// TODO: store a larger on-disk cache
+
+/// Version of the records in `CompiledContractCache`. Bump when the serialized artifacts of the
+/// same code and config change, e.g. on a wasmer update, so the old records get recompiled.
+const CACHE_RECORD_VERSION: u32 = 1;
+
+/// Length of the record header: version and checksum of the artifact.
+const CACHE_RECORD_HEADER_LEN: usize = 12;
+
+/// Persistent storage of compiled contracts, which outlives the in-memory cache of modules.
+/// Records are only written after the contract is compiled, and are checked when read, so the
+/// storage doesn't have to be consistent with anything else.
+pub trait CompiledContractCache: Send + Sync {
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), std::io::Error>;
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, std::io::Error>;
+}
+
+fn cache_key(code_hash: &[u8], config: &VMConfig) -> Vec<u8> {
+    let mut key = code_hash.to_vec();
+    key.extend_from_slice(&config.non_crypto_hash().to_le_bytes());
+    key
+}
+
+/// FNV-1a, enough to detect corrupted records.
+fn checksum(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn serialize_module(module: &wasmer_runtime::Module) -> Option<Vec<u8>> {
+    let artifact = module.cache().ok()?.serialize().ok()?;
+    let mut record = Vec::with_capacity(CACHE_RECORD_HEADER_LEN + artifact.len());
+    record.extend_from_slice(&CACHE_RECORD_VERSION.to_le_bytes());
+    record.extend_from_slice(&checksum(&artifact).to_le_bytes());
+    record.extend_from_slice(&artifact);
+    Some(record)
+}
+
+/// Returns None if the record has another version or is corrupted.
+fn deserialize_module(record: &[u8]) -> Option<wasmer_runtime::Module> {
+    if record.len() < CACHE_RECORD_HEADER_LEN {
+        return None;
+    }
+    let mut version = [0u8; 4];
+    version.copy_from_slice(&record[..4]);
+    let mut expected_checksum = [0u8; 8];
+    expected_checksum.copy_from_slice(&record[4..CACHE_RECORD_HEADER_LEN]);
+    let artifact = &record[CACHE_RECORD_HEADER_LEN..];
+    if u32::from_le_bytes(version) != CACHE_RECORD_VERSION
+        || u64::from_le_bytes(expected_checksum) != checksum(artifact)
+    {
+        return None;
+    }
+    let artifact = Artifact::deserialize(artifact).ok()?;
+    let compiler = compiler_for_backend(Backend::Singlepass)?;
+    // Safe, because the artifact was serialized by the same version of the compiler, as checked
+    // by the record version, and was not modified since, as checked by the checksum.
+    unsafe { load_cache_with(artifact, compiler.as_ref()).ok() }
+}
+
+fn compile_module(code: &[u8], config: &VMConfig) -> Result<wasmer_runtime::Module, VMError> {
+    let prepared_code = prepare::prepare_contract(code, config)?;
+    wasmer_runtime::compile(&prepared_code).map_err(|err| err.into_vm_error())
+}
 
 cached_key! {
     MODULES: SizedCache<(Vec<u8>, u64), Result<wasmer_runtime::Module, VMError>>
         = SizedCache::with_size(CACHE_SIZE);
     Key = {
-        (code_hash, config.non_crypto_hash())
+        (code_hash.clone(), config.non_crypto_hash())
     };
 
-    fn compile_cached_module(code_hash: Vec<u8>, code: &[u8], config: &VMConfig
+    fn compile_cached_module(code_hash: Vec<u8>, code: &[u8], config: &VMConfig,
+        cache: Option<&dyn CompiledContractCache>
         ) -> Result<wasmer_runtime::Module, VMError> = {
-        let prepared_code = prepare::prepare_contract(code, config)?;
-        wasmer_runtime::compile(&prepared_code).map_err(|err| err.into_vm_error())
+        let cache = match cache {
+            Some(cache) => cache,
+            None => return compile_module(code, config),
+        };
+        let key = cache_key(&code_hash, config);
+        // Failures of the persistent cache only cost a compilation.
+        if let Some(module) = cache.get(&key).ok().and_then(|record| deserialize_module(&record?)) {
+            return Ok(module);
+        }
+        let module = compile_module(code, config)?;
+        if let Some(record) = serialize_module(&module) {
+            let _ = cache.put(&key, &record);
+        }
+        Ok(module)
+    }
+}
+
+/// Compiles the contract ahead of its first call, e.g. when it is deployed, and stores it in the
+/// caches. Compilation errors are returned, but they are also returned by calls to the contract.
+pub fn precompile(
+    code_hash: Vec<u8>,
+    code: &[u8],
+    config: &VMConfig,
+    cache: Option<&dyn CompiledContractCache>,
+) -> Result<(), VMError> {
+    compile_cached_module(code_hash, code, config, cache).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MockCache {
+        records: Mutex<HashMap<Vec<u8>, Vec<u8>>>,
+    }
+
+    impl CompiledContractCache for MockCache {
+        fn put(&self, key: &[u8], value: &[u8]) -> Result<(), std::io::Error> {
+            self.records.lock().unwrap().insert(key.to_vec(), value.to_vec());
+            Ok(())
+        }
+
+        fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, std::io::Error> {
+            Ok(self.records.lock().unwrap().get(key).cloned())
+        }
+    }
+
+    const CODE: &str = r#"(module (func (export "main")))"#;
+
+    #[test]
+    fn check_records() {
+        let code = wabt::wat2wasm(CODE).unwrap();
+        let config = VMConfig::default();
+        let module = compile_module(&code, &config).unwrap();
+        let record = serialize_module(&module).unwrap();
+        assert!(deserialize_module(&record).is_some());
+
+        let mut corrupted = record.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(deserialize_module(&corrupted).is_none());
+        let mut old_version = record.clone();
+        old_version[0] ^= 1;
+        assert!(deserialize_module(&old_version).is_none());
+        assert!(deserialize_module(&record[..CACHE_RECORD_HEADER_LEN - 1]).is_none());
+    }
+
+    #[test]
+    fn precompile_stores_module() {
+        let code = wabt::wat2wasm(CODE).unwrap();
+        let config = VMConfig::default();
+        let cache = MockCache::default();
+        let cache: &dyn CompiledContractCache = &cache;
+        precompile(b"precompile_stores_module".to_vec(), &code, &config, Some(cache)).unwrap();
+        let records = cache.records.lock().unwrap();
+        let record = records.get(&cache_key(b"precompile_stores_module", &config)).unwrap();
+        assert!(deserialize_module(record).is_some());
     }
 }
@@ -76,6 +76,14 @@ wrapped_imports! {
     panic_utf8<[len: u64, ptr: u64] -> []>,
     log_utf8<[len: u64, ptr: u64] -> []>,
     log_utf16<[len: u64, ptr: u64] -> []>,
+    emit_event<[
+        name_len: u64,
+        name_ptr: u64,
+        topics_len: u64,
+        topics_ptr: u64,
+        data_len: u64,
+        data_ptr: u64
+    ] -> []>,
     abort<[msg_ptr: u32, filename_ptr: u32, line: u32, col: u32] -> []>,
     // ################
     // # Promises API #
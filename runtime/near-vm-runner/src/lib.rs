@@ -4,8 +4,9 @@ mod imports;
 mod memory;
 mod prepare;
 mod runner;
+pub use cache::{precompile, CompiledContractCache};
 pub use near_vm_errors::VMError;
-pub use runner::run;
+pub use runner::{run, run_with_cache};
 
 #[cfg(feature = "costs_counting")]
 pub use near_vm_logic::EXT_COSTS_COUNTER;
@@ -1,3 +1,4 @@
+use crate::cache::CompiledContractCache;
 use crate::errors::IntoVMError;
 use crate::memory::WasmerMemory;
 use crate::{cache, imports};
@@ -47,6 +48,32 @@ pub fn run<'a>(
     wasm_config: &'a VMConfig,
     fees_config: &'a RuntimeFeesConfig,
     promise_results: &'a [PromiseResult],
+) -> (Option<VMOutcome>, Option<VMError>) {
+    run_with_cache(
+        code_hash,
+        code,
+        method_name,
+        ext,
+        context,
+        wasm_config,
+        fees_config,
+        promise_results,
+        None,
+    )
+}
+
+/// Same as `run`, but looks up the compiled contract in the given persistent cache before
+/// compiling it, and stores it there after compiling.
+pub fn run_with_cache<'a>(
+    code_hash: Vec<u8>,
+    code: &[u8],
+    method_name: &[u8],
+    ext: &mut dyn External,
+    context: VMContext,
+    wasm_config: &'a VMConfig,
+    fees_config: &'a RuntimeFeesConfig,
+    promise_results: &'a [PromiseResult],
+    cache: Option<&'a dyn CompiledContractCache>,
 ) -> (Option<VMOutcome>, Option<VMError>) {
     if method_name.is_empty() {
         return (
@@ -57,7 +84,7 @@ pub fn run<'a>(
         );
     }
 
-    let module = match cache::compile_cached_module(code_hash, code, wasm_config) {
+    let module = match cache::compile_cached_module(code_hash, code, wasm_config, cache) {
         Ok(x) => x,
         Err(err) => return (None, Some(err)),
     };
@@ -1,11 +1,15 @@
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
 use rand::seq::SliceRandom;
 use rand::{Rng, SeedableRng};
 
 use near_crypto::{InMemorySigner, KeyType, PublicKey};
-use near_primitives::account::{AccessKey, AccessKeyPermission, FunctionCallPermission};
+use near_primitives::account::{
+    AccessKey, AccessKeyPermission, FunctionCallPermission, MultisigPermission, WeightedKey,
+    MAX_MULTISIG_KEYS,
+};
 use near_primitives::hash::CryptoHash;
 use near_primitives::transaction::{
     Action, AddKeyAction, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
@@ -64,6 +68,27 @@ fn measure_function(
     measure_transactions(metric, measurements, config, Some(testbed), &mut f, allow_failures)
 }
 
+/// Signers whose keys make up the multisig access key of the given account.
+fn multisig_signers(account_id: &str, num_keys: usize) -> Vec<InMemorySigner> {
+    (0..num_keys)
+        .map(|i| InMemorySigner::from_seed(account_id, KeyType::ED25519, &format!("multisig{}", i)))
+        .collect()
+}
+
+/// Multisig access key that requires all of the given signers.
+fn multisig_access_key(signers: &[InMemorySigner]) -> AccessKey {
+    AccessKey {
+        nonce: 0,
+        permission: AccessKeyPermission::Multisig(MultisigPermission {
+            keys: signers
+                .iter()
+                .map(|signer| WeightedKey { public_key: signer.public_key.clone(), weight: 1 })
+                .collect(),
+            threshold: signers.len() as u64,
+        }),
+    }
+}
+
 macro_rules! calls_helper(
     { $($el:ident => $method_name:ident),* } => {
     {
@@ -86,6 +111,9 @@ pub enum Metric {
     ActionAddFullAccessKey,
     ActionAddFunctionAccessKey1Method,
     ActionAddFunctionAccessKey1000Methods,
+    ActionAddMultisigAccessKey1Key,
+    ActionAddMultisigAccessKeyMaxKeys,
+    ActionTransferMultisig,
     ActionDeleteAccessKey,
     ActionStake,
     ActionDeploy10K,
@@ -291,6 +319,87 @@ pub fn run(mut config: Config) -> RuntimeConfig {
         true,
     );
 
+    // Measure the speed of adding multisig access keys with 1 and with the maximum number of keys.
+    for (metric, num_keys) in vec![
+        (Metric::ActionAddMultisigAccessKey1Key, 1),
+        (Metric::ActionAddMultisigAccessKeyMaxKeys, MAX_MULTISIG_KEYS),
+    ] {
+        measure_actions(
+            metric,
+            &mut m,
+            &config,
+            None,
+            vec![Action::AddKey(AddKeyAction {
+                public_key: serde_json::from_str(
+                    "\"ed25519:DcA2MzgpJbrUATQLLceocVckhhAqrkingax4oJ9kZ847\"",
+                )
+                .unwrap(),
+                access_key: multisig_access_key(&multisig_signers(&get_account_id(0), num_keys)),
+            })],
+            true,
+            true,
+        );
+    }
+
+    // Measure the speed of transfers signed by the maximum number of multisig keys.
+    let mut testbed =
+        RuntimeTestbed::from_state_dump(&PathBuf::from(config.state_dump_path.as_str()));
+    let add_multisig_keys: Vec<_> = (0..config.active_accounts)
+        .map(|account_idx| {
+            let account_id = get_account_id(account_idx);
+            let signer = InMemorySigner::from_seed(&account_id, KeyType::ED25519, &account_id);
+            let key_signers = multisig_signers(&account_id, MAX_MULTISIG_KEYS);
+            SignedTransaction::from_actions(
+                1,
+                account_id.clone(),
+                account_id.clone(),
+                &signer,
+                vec![Action::AddKey(AddKeyAction {
+                    public_key: key_signers[0].public_key.clone(),
+                    access_key: multisig_access_key(&key_signers),
+                })],
+                CryptoHash::default(),
+            )
+        })
+        .collect();
+    for block in add_multisig_keys.chunks(100) {
+        testbed.process_block(block, false);
+    }
+    testbed.process_blocks_until_no_receipts(false);
+    let mut nonces: HashMap<usize, u64> = HashMap::new();
+    let mut f = || {
+        let account_idx = rand::thread_rng().gen::<usize>() % config.active_accounts;
+        let other_account_idx = loop {
+            let x = rand::thread_rng().gen::<usize>() % config.active_accounts;
+            if x != account_idx {
+                break x;
+            }
+        };
+        let account_id = get_account_id(account_idx);
+        let signers = multisig_signers(&account_id, MAX_MULTISIG_KEYS);
+        let nonce = *nonces.entry(account_idx).and_modify(|x| *x += 1).or_insert(1);
+        let mut transaction = SignedTransaction::from_actions(
+            nonce as u64,
+            account_id,
+            get_account_id(other_account_idx),
+            &signers[0],
+            vec![Action::Transfer(TransferAction { deposit: 1 })],
+            CryptoHash::default(),
+        );
+        for signer in &signers[1..] {
+            transaction.add_signature(signer);
+        }
+        transaction
+    };
+    measure_transactions(
+        Metric::ActionTransferMultisig,
+        &mut m,
+        &config,
+        Some(testbed),
+        &mut f,
+        false,
+    );
+
     // Measure the speed of deleting an access key.
     let mut nonces: HashMap<usize, u64> = HashMap::new();
     // Accounts with deleted access keys.
@@ -529,6 +638,8 @@ fn get_runtime_fees_config(measurement: &Measurements) -> RuntimeFeesConfig {
                 full_access_cost: f64_to_fee(pure[&ActionAddFullAccessKey]),
                 function_call_cost: f64_to_fee(pure[&ActionAddFunctionAccessKeyBase]),
                 function_call_cost_per_byte: f64_to_fee(pure[&ActionAddFunctionAccessKeyPerByte]),
+                multisig_cost: f64_to_fee(pure[&ActionAddMultisigAccessKeyBase]),
+                multisig_cost_per_key: f64_to_fee(pure[&ActionAddMultisigAccessKeyPerKey]),
             },
             delete_key_cost: f64_to_fee(pure[&ActionDeleteKey]),
             delete_account_cost: f64_to_fee(pure[&ActionDeleteAccount]),
@@ -540,6 +651,7 @@ fn get_runtime_fees_config(measurement: &Measurements) -> RuntimeFeesConfig {
             value_cost_per_byte: 0,
             code_cost_per_byte: 0,
         },
+        extra_signature_verification_cost: f64_to_gas(pure[&ExtraSignatureVerification]),
         burnt_gas_reward: Fraction { numerator: 1, denominator: 3 },
     }
 }
@@ -566,6 +678,8 @@ fn get_ext_costs_config(measurement: &Measurements) -> ExtCostsConfig {
         sha256_byte: f64_to_gas(pure[&sha256_byte]),
         log_base: f64_to_gas(pure[&log_base]),
         log_byte: f64_to_gas(pure[&log_byte]),
+        event_base: f64_to_gas(pure[&event_base]),
+        event_byte: f64_to_gas(pure[&event_byte]),
         storage_write_base: f64_to_gas(pure[&storage_write_base]),
         storage_write_key_byte: f64_to_gas(pure[&storage_write_key_byte]),
         storage_write_value_byte: f64_to_gas(pure[&storage_write_value_byte]),
@@ -613,6 +727,9 @@ fn get_vm_config(measurement: &Measurements) -> VMConfig {
         max_number_registers: 100,
         max_number_logs: 100,
         max_log_len: 500,
+        max_number_event_topics: 4,
+        max_number_events: 100,
+        max_total_event_length: 16 * 1024,
     }
 }
 
@@ -71,6 +71,8 @@ impl ExtCostsGenerator {
 
         self.result.insert(log_base, 0f64);
         self.result.insert(log_byte, 0f64);
+        self.result.insert(event_base, 0f64);
+        self.result.insert(event_byte, 0f64);
         self.extract(utf8_log_10b_10k, utf8_decoding_base, &[utf8_decoding_byte]);
         // Charge the maximum between non-nul-terminated and nul-terminated costs.
         let utf8_byte = self.extract_value(utf8_log_10kib_10k, utf8_decoding_byte, &[]);
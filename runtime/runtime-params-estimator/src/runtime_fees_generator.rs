@@ -1,5 +1,6 @@
 use crate::cases::Metric;
 use crate::stats::{DataStats, Measurements};
+use near_primitives::account::MAX_MULTISIG_KEYS;
 use std::collections::BTreeMap;
 
 pub struct RuntimeFeesGenerator {
@@ -22,6 +23,9 @@ pub enum ReceiptFeesFloat {
     ActionAddFullAccessKey,
     ActionAddFunctionAccessKeyBase,
     ActionAddFunctionAccessKeyPerByte,
+    ActionAddMultisigAccessKeyBase,
+    ActionAddMultisigAccessKeyPerKey,
+    ExtraSignatureVerification,
     ActionDeleteKey,
     ActionDeleteAccount,
 }
@@ -104,6 +108,24 @@ impl RuntimeFeesGenerator {
                 - self.aggregated[&Metric::ActionAddFunctionAccessKey1Method].upper() as f64)
                 / (1000f64 * 10f64),
         );
+        res.insert(
+            ReceiptFeesFloat::ActionAddMultisigAccessKeyBase,
+            self.aggregated[&Metric::ActionAddMultisigAccessKey1Key].upper() as f64
+                - self.aggregated[&Metric::Receipt].upper() as f64,
+        );
+        res.insert(
+            ReceiptFeesFloat::ActionAddMultisigAccessKeyPerKey,
+            (self.aggregated[&Metric::ActionAddMultisigAccessKeyMaxKeys].upper() as f64
+                - self.aggregated[&Metric::ActionAddMultisigAccessKey1Key].upper() as f64)
+                / (MAX_MULTISIG_KEYS - 1) as f64,
+        );
+        res.insert(
+            ReceiptFeesFloat::ExtraSignatureVerification,
+            // Every multisig transfer carries all but one of the keys as extra signatures.
+            (self.aggregated[&Metric::ActionTransferMultisig].upper() as f64
+                - self.aggregated[&Metric::ActionTransfer].upper() as f64)
+                / (MAX_MULTISIG_KEYS - 1) as f64,
+        );
         res.insert(
             ReceiptFeesFloat::ActionDeleteKey,
             self.aggregated[&Metric::ActionDeleteAccessKey].upper() as f64
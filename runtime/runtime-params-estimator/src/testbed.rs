@@ -66,6 +66,7 @@ impl RuntimeTestbed {
             gas_price: 1,
             block_timestamp: 0,
             gas_limit: None,
+            cache: None,
         };
         Self { workdir, trie, root, runtime, prev_receipts, apply_state }
     }
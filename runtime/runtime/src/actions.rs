@@ -3,13 +3,13 @@ use std::sync::Arc;
 use borsh::BorshSerialize;
 use log::debug;
 
-use near_primitives::account::Account;
+use near_primitives::account::{AccessKeyPermission, Account};
 use near_primitives::contract::ContractCode;
 use near_primitives::hash::CryptoHash;
 use near_primitives::receipt::{ActionReceipt, Receipt};
 use near_primitives::transaction::{
-    Action, AddKeyAction, DeleteAccountAction, DeleteKeyAction, DeployContractAction,
-    FunctionCallAction, StakeAction, TransferAction,
+    Action, AddKeyAction, ContractEvent, DeleteAccountAction, DeleteKeyAction,
+    DeployContractAction, FunctionCallAction, StakeAction, TransferAction,
 };
 use near_primitives::types::{AccountId, Balance, BlockIndex, ValidatorStake};
 use near_primitives::utils::{
@@ -164,7 +164,7 @@ pub(crate) fn action_function_call(
         output_data_receivers,
     };
 
-    let (outcome, err) = near_vm_runner::run(
+    let (outcome, err) = near_vm_runner::run_with_cache(
         code.hash.as_ref().to_vec(),
         &code.code,
         function_call.method_name.as_bytes(),
@@ -173,6 +173,7 @@ pub(crate) fn action_function_call(
         &config.wasm_config,
         &config.transaction_costs,
         promise_results,
+        apply_state.cache.as_ref().map(|cache| &**cache),
     );
     if let Some(err) = err {
         if let VMError::StorageError(storage) = err {
@@ -196,6 +197,13 @@ pub(crate) fn action_function_call(
     }
     let outcome = outcome.unwrap();
     result.logs.extend(outcome.logs.into_iter());
+    // Events of failed calls are dropped together with their state changes.
+    result.events.extend(outcome.events.into_iter().map(|event| ContractEvent {
+        account_id: event.account_id,
+        name: event.name,
+        topics: event.topics,
+        data: event.data,
+    }));
     account.amount = outcome.balance;
     account.storage_usage = outcome.storage_usage;
     result.gas_burnt += outcome.burnt_gas;
@@ -268,7 +276,8 @@ pub(crate) fn action_create_account(
 
 // TODO(#1461): Add Safe Math
 pub(crate) fn action_deploy_contract(
-    fee_config: &RuntimeFeesConfig,
+    config: &RuntimeConfig,
+    apply_state: &ApplyState,
     state_update: &mut TrieUpdate,
     account: &mut Option<Account>,
     account_id: &AccountId,
@@ -278,10 +287,21 @@ pub(crate) fn action_deploy_contract(
     let code = ContractCode::new(deploy_contract.code.clone());
     let prev_code = get_code(state_update, account_id)?;
     let prev_code_length = prev_code.map(|code| code.code.len() as u64).unwrap_or_default();
-    let storage_usage_config = &fee_config.storage_usage_config;
+    let storage_usage_config = &config.transaction_costs.storage_usage_config;
     account.storage_usage -= prev_code_length * storage_usage_config.code_cost_per_byte;
     account.storage_usage += (code.code.len() as u64) * storage_usage_config.code_cost_per_byte;
     account.code_hash = code.get_hash();
+    // Compile the contract now, so the first call doesn't have to. The deploy itself doesn't
+    // depend on the compilation: invalid contracts are still deployed, and their calls fail with
+    // the same compilation error.
+    if let Err(err) = near_vm_runner::precompile(
+        code.hash.as_ref().to_vec(),
+        &code.code,
+        &config.wasm_config,
+        apply_state.cache.as_ref().map(|cache| &**cache),
+    ) {
+        debug!(target: "runtime", "Failed to precompile the contract of {}: {:?}", account_id, err);
+    }
     set_code(state_update, &account_id, &code);
     Ok(())
 }
@@ -351,6 +371,12 @@ pub(crate) fn action_add_key(
         result.result = Err(ActionError::AddKeyAlreadyExists(add_key.public_key.clone()));
         return Ok(());
     }
+    if let AccessKeyPermission::Multisig(multisig) = &add_key.access_key.permission {
+        if !multisig.is_valid() {
+            result.result = Err(ActionError::AddKeyInvalidMultisig(add_key.public_key.clone()));
+            return Ok(());
+        }
+    }
     set_access_key(state_update, account_id, &add_key.public_key, &add_key.access_key);
     let storage_config = &fees_config.storage_usage_config;
     account.storage_usage += (add_key.public_key.try_to_vec().ok().unwrap_or_default().len()
@@ -41,4 +41,13 @@ pub trait ViewRuntimeAdapter {
         account_id: &AccountId,
         prefix: &[u8],
     ) -> Result<ViewStateResult, Box<dyn std::error::Error>>;
+
+    fn view_state_page(
+        &self,
+        state_root: MerkleHash,
+        account_id: &AccountId,
+        prefix: &[u8],
+        start_key: &[u8],
+        limit: usize,
+    ) -> Result<ViewStateResult, Box<dyn std::error::Error>>;
 }
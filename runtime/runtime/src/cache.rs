@@ -1,10 +1,12 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 use cached::{cached_key, SizedCache};
 
 use near_primitives::contract::ContractCode;
 use near_primitives::hash::CryptoHash;
-use near_store::StorageError;
+use near_store::{ColCachedContractCode, StorageError, Store};
+use near_vm_runner::CompiledContractCache;
 
 /// Cache size in number of cached modules to hold.
 const CACHE_SIZE: usize = 1024;
@@ -24,3 +26,45 @@ cached_key! {
         }))
     }
 }
+
+/// Compiled contracts stored in the database, so nodes don't recompile them after a restart.
+/// It's a side cache outside of consensus: records live in their own column, and the writes made
+/// while applying a chunk are buffered and committed by `flush` after the apply, so they never
+/// interleave with the chain's store updates.
+pub struct StoreCompiledContractCache {
+    store: Arc<Store>,
+    pending: Mutex<HashMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl StoreCompiledContractCache {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self { store, pending: Mutex::new(HashMap::new()) }
+    }
+
+    /// Writes the buffered records to the store in one update.
+    pub fn flush(&self) -> Result<(), std::io::Error> {
+        let pending = std::mem::replace(&mut *self.pending.lock().unwrap(), HashMap::new());
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let mut store_update = self.store.store_update();
+        for (key, value) in pending {
+            store_update.set(ColCachedContractCode, &key, &value);
+        }
+        store_update.commit()
+    }
+}
+
+impl CompiledContractCache for StoreCompiledContractCache {
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), std::io::Error> {
+        self.pending.lock().unwrap().insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, std::io::Error> {
+        if let Some(value) = self.pending.lock().unwrap().get(key) {
+            return Ok(Some(value.clone()));
+        }
+        self.store.get(ColCachedContractCode, key)
+    }
+}
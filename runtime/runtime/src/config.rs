@@ -101,6 +101,11 @@ pub fn total_send_fees(
                                 .function_call_cost_per_byte
                                 .send_fee(sender_is_receiver)
                 }
+                AccessKeyPermission::Multisig(multisig) => {
+                    cfg.add_key_cost.multisig_cost.send_fee(sender_is_receiver)
+                        + multisig.keys.len() as u64
+                            * cfg.add_key_cost.multisig_cost_per_key.send_fee(sender_is_receiver)
+                }
                 AccessKeyPermission::FullAccess => {
                     cfg.add_key_cost.full_access_cost.send_fee(sender_is_receiver)
                 }
@@ -141,6 +146,10 @@ pub fn exec_fee(config: &RuntimeFeesConfig, action: &Action) -> Gas {
                 cfg.add_key_cost.function_call_cost.exec_fee()
                     + num_bytes * cfg.add_key_cost.function_call_cost_per_byte.exec_fee()
             }
+            AccessKeyPermission::Multisig(multisig) => {
+                cfg.add_key_cost.multisig_cost.exec_fee()
+                    + multisig.keys.len() as u64 * cfg.add_key_cost.multisig_cost_per_key.exec_fee()
+            }
             AccessKeyPermission::FullAccess => cfg.add_key_cost.full_access_cost.exec_fee(),
         },
         DeleteKey(_) => cfg.delete_key_cost.exec_fee(),
@@ -148,14 +157,22 @@ pub fn exec_fee(config: &RuntimeFeesConfig, action: &Action) -> Gas {
     }
 }
 /// Returns a total amount of gas which was being burnt and total_cost
-/// which is used during incoming transaction verification
+/// which is used during incoming transaction verification.
+/// Verification of the signatures beyond the first one is burnt on top of the send fees.
 pub fn tx_cost(
     config: &RuntimeFeesConfig,
     transaction: &Transaction,
     gas_price: Balance,
     sender_is_receiver: bool,
+    num_extra_signatures: u64,
 ) -> Result<(Gas, Gas, Balance), IntegerOverflowError> {
     let mut gas_burnt: Gas = config.action_receipt_creation_config.send_fee(sender_is_receiver);
+    gas_burnt = safe_add_gas(
+        gas_burnt,
+        num_extra_signatures
+            .checked_mul(config.extra_signature_verification_cost)
+            .ok_or(IntegerOverflowError)?,
+    )?;
     gas_burnt = safe_add_gas(
         gas_burnt,
         total_send_fees(&config, sender_is_receiver, &transaction.actions)?,
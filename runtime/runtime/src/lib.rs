@@ -6,7 +6,7 @@ extern crate log;
 extern crate serde_derive;
 
 use std::cmp::max;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::TryInto;
 use std::sync::Arc;
 
@@ -22,7 +22,8 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::receipt::{ActionReceipt, DataReceipt, Receipt, ReceiptEnum, ReceivedData};
 use near_primitives::serialize::from_base64;
 use near_primitives::transaction::{
-    Action, ExecutionOutcome, ExecutionOutcomeWithId, ExecutionStatus, LogEntry, SignedTransaction,
+    Action, ContractEvent, ExecutionOutcome, ExecutionOutcomeWithId, ExecutionStatus, LogEntry,
+    SignedTransaction,
 };
 use near_primitives::types::{
     AccountId, Balance, BlockIndex, Gas, Nonce, StateRoot, ValidatorStake,
@@ -40,6 +41,7 @@ use near_store::{
 };
 use near_vm_logic::types::PromiseResult;
 use near_vm_logic::ReturnData;
+use near_vm_runner::CompiledContractCache;
 #[cfg(feature = "costs_counting")]
 pub use near_vm_runner::EXT_COSTS_COUNTER;
 
@@ -74,6 +76,8 @@ pub struct ApplyState {
     /// Gas limit for a given chunk.
     /// If None is given, assumes there is no gas limit.
     pub gas_limit: Option<Gas>,
+    /// Persistent cache of compiled contracts, if any.
+    pub cache: Option<Arc<dyn CompiledContractCache>>,
 }
 
 /// Contains information to update validators accounts at the first block of a new epoch.
@@ -116,6 +120,15 @@ pub struct ApplyResult {
     pub stats: ApplyStats,
 }
 
+/// Result of a transaction simulated on top of the state, see `Runtime::simulate_transaction`.
+pub struct SimulationResult {
+    /// Outcomes of the transaction and of all the receipts processed, in the order of execution.
+    pub outcomes: Vec<ExecutionOutcomeWithId>,
+    pub gas_burnt: Gas,
+    /// Receipts to accounts outside of the simulated state, which were not processed.
+    pub unprocessed_receipts: Vec<Receipt>,
+}
+
 /// Stores indices for a persistent queue for delayed receipts that didn't fit into a block.
 #[derive(Default, BorshSerialize, BorshDeserialize)]
 pub struct DelayedReceiptIndices {
@@ -132,6 +145,7 @@ pub struct ActionResult {
     pub gas_used: Gas,
     pub result: Result<ReturnData, ActionError>,
     pub logs: Vec<LogEntry>,
+    pub events: Vec<ContractEvent>,
     pub new_receipts: Vec<Receipt>,
     pub validator_proposals: Vec<ValidatorStake>,
 }
@@ -158,9 +172,11 @@ impl ActionResult {
             *receipt_index += self.new_receipts.len() as u64;
         }
         if self.result.is_ok() {
+            self.events.append(&mut next_result.events);
             self.new_receipts.append(&mut next_result.new_receipts);
             self.validator_proposals.append(&mut next_result.validator_proposals);
         } else {
+            self.events.clear();
             self.new_receipts.clear();
             self.validator_proposals.clear();
         }
@@ -176,6 +192,7 @@ impl Default for ActionResult {
             gas_used: 0,
             result: Ok(ReturnData::None),
             logs: vec![],
+            events: vec![],
             new_receipts: vec![],
             validator_proposals: vec![],
         }
@@ -224,7 +241,7 @@ impl Runtime {
 
         if !signed_transaction
             .signature
-            .verify(signed_transaction.get_hash().as_ref(), &transaction.public_key)
+            .verify(signed_transaction.get_signing_hash().as_ref(), &transaction.public_key)
         {
             return Err(InvalidTxError::InvalidSignature.into());
         }
@@ -252,6 +269,47 @@ impl Runtime {
             return Err(InvalidTxError::InvalidNonce(transaction.nonce, access_key.nonce).into());
         }
 
+        // Extra signatures are only checked to be of distinct keys of the multisig key set here,
+        // they are verified once the transaction is known to be paid for.
+        let signer_keys = std::iter::once(&transaction.public_key)
+            .chain(signed_transaction.extra_signatures.iter().map(|(public_key, _)| public_key))
+            .collect::<Vec<_>>();
+        if !signed_transaction.extra_signatures.is_empty() {
+            let valid = match access_key.permission {
+                AccessKeyPermission::Multisig(ref multisig_permission) => {
+                    signed_transaction.extra_signatures.len() <= multisig_permission.keys.len()
+                        && signer_keys.iter().collect::<HashSet<_>>().len() == signer_keys.len()
+                        && signed_transaction.extra_signatures.iter().all(|(public_key, _)| {
+                            multisig_permission.keys.iter().any(|key| &key.public_key == public_key)
+                        })
+                }
+                _ => false,
+            };
+            if !valid {
+                return Err(InvalidTxError::InvalidAccessKey(
+                    InvalidAccessKeyError::InvalidExtraSignatures(
+                        signer_id.clone(),
+                        transaction.public_key.clone(),
+                    ),
+                )
+                .into());
+            }
+        }
+        if let AccessKeyPermission::Multisig(ref multisig_permission) = access_key.permission {
+            let weight = multisig_permission.signed_weight(&signer_keys);
+            if weight < multisig_permission.threshold {
+                return Err(InvalidTxError::InvalidAccessKey(
+                    InvalidAccessKeyError::NotEnoughSignatureWeight(
+                        signer_id.clone(),
+                        transaction.public_key.clone(),
+                        weight,
+                        multisig_permission.threshold,
+                    ),
+                )
+                .into());
+            }
+        }
+
         let sender_is_receiver = &transaction.receiver_id == signer_id;
 
         let rent_paid = apply_rent(&signer_id, &mut signer, apply_state.block_index, &self.config);
@@ -262,6 +320,7 @@ impl Runtime {
             &transaction,
             apply_state.gas_price,
             sender_is_receiver,
+            signed_transaction.extra_signatures.len() as u64,
         )
         .map_err(|_| InvalidTxError::CostOverflow)?;
 
@@ -269,6 +328,12 @@ impl Runtime {
             InvalidTxError::NotEnoughBalance(signer_id.clone(), signer.amount, total_cost)
         })?;
 
+        for (public_key, signature) in signed_transaction.extra_signatures.iter() {
+            if !signature.verify(signed_transaction.get_signing_hash().as_ref(), public_key) {
+                return Err(InvalidTxError::InvalidSignature.into());
+            }
+        }
+
         if let AccessKeyPermission::FunctionCall(ref mut function_call_permission) =
             access_key.permission
         {
@@ -393,6 +458,7 @@ impl Runtime {
                         logs: vec![],
                         receipt_ids: vec![receipt_id],
                         gas_burnt: verification_result.gas_burnt,
+                        events: vec![],
                     }
                 }
                 Err(e) => {
@@ -455,7 +521,8 @@ impl Runtime {
             Action::DeployContract(deploy_contract) => {
                 near_metrics::inc_counter(&metrics::ACTION_DEPLOY_CONTRACT_TOTAL);
                 action_deploy_contract(
-                    &self.config.transaction_costs,
+                    &self.config,
+                    apply_state,
                     state_update,
                     account,
                     &account_id,
@@ -747,6 +814,7 @@ impl Runtime {
                 logs: result.logs,
                 receipt_ids,
                 gas_burnt: result.gas_burnt,
+                events: result.events,
             },
         })
     }
@@ -1121,6 +1189,61 @@ impl Runtime {
         })
     }
 
+    /// Executes the transaction on top of the state with the given root, without producing any
+    /// changes: the state is only updated in memory and dropped at the end. Receipts to accounts
+    /// for which `is_local` returns true are processed until none is left, so the result includes
+    /// cross-contract calls and refunds, other receipts are returned unprocessed.
+    /// Unlike `apply`, ignores the gas limit and the delayed receipts, and doesn't check balances.
+    pub fn simulate_transaction(
+        &self,
+        trie: Arc<Trie>,
+        root: CryptoHash,
+        apply_state: &ApplyState,
+        transaction: &SignedTransaction,
+        is_local: &dyn Fn(&AccountId) -> bool,
+    ) -> Result<SimulationResult, RuntimeError> {
+        let mut state_update = TrieUpdate::new(trie, root);
+        let mut stats = ApplyStats::default();
+        let mut validator_proposals = vec![];
+        let mut local_receipts = vec![];
+        let mut new_receipts = vec![];
+
+        let outcome_with_id = self.process_transaction(
+            &mut state_update,
+            apply_state,
+            transaction,
+            &mut local_receipts,
+            &mut new_receipts,
+            &mut stats,
+        )?;
+        let mut gas_burnt = outcome_with_id.outcome.gas_burnt;
+        let mut outcomes = vec![outcome_with_id];
+        let mut unprocessed_receipts = vec![];
+        let mut queue: VecDeque<Receipt> =
+            local_receipts.into_iter().chain(new_receipts.drain(..)).collect();
+
+        while let Some(receipt) = queue.pop_front() {
+            if !is_local(&receipt.receiver_id) {
+                unprocessed_receipts.push(receipt);
+                continue;
+            }
+            if let Some(outcome_with_id) = self.process_receipt(
+                &mut state_update,
+                apply_state,
+                &receipt,
+                &mut new_receipts,
+                &mut validator_proposals,
+                &mut stats,
+            )? {
+                gas_burnt = safe_add_gas(gas_burnt, outcome_with_id.outcome.gas_burnt)?;
+                outcomes.push(outcome_with_id);
+            }
+            queue.extend(new_receipts.drain(..));
+        }
+
+        Ok(SimulationResult { outcomes, gas_burnt, unprocessed_receipts })
+    }
+
     // TODO(#1461): Add safe math
     pub fn compute_storage_usage(&self, records: &[StateRecord]) -> HashMap<AccountId, u64> {
         let mut result = HashMap::new();
@@ -1271,7 +1394,8 @@ impl Runtime {
 
 #[cfg(test)]
 mod tests {
-    use near_crypto::KeyType;
+    use near_crypto::{InMemorySigner, KeyType, Signer};
+    use near_primitives::account::{MultisigPermission, WeightedKey};
     use near_primitives::hash::hash;
     use near_primitives::transaction::TransferAction;
     use near_primitives::types::MerkleHash;
@@ -1337,6 +1461,7 @@ mod tests {
             gas_price: GAS_PRICE,
             block_timestamp: 100,
             gas_limit: Some(gas_limit),
+            cache: None,
         };
 
         (runtime, trie, root, apply_state)
@@ -1499,6 +1624,160 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_simulate_transaction() {
+        let initial_balance = 10u128.pow(24);
+        let (runtime, trie, root, apply_state) = setup_runtime(initial_balance, 0, 10_000_000);
+        let signer =
+            InMemorySigner::from_seed(&alice_account(), KeyType::ED25519, &alice_account());
+        let mut state_update = TrieUpdate::new(trie.clone(), root);
+        set_access_key(
+            &mut state_update,
+            &alice_account(),
+            &signer.public_key,
+            &AccessKey::full_access(),
+        );
+        let (store_update, root) = state_update.finalize().unwrap().into(trie.clone()).unwrap();
+        store_update.commit().unwrap();
+
+        let tx = SignedTransaction::send_money(
+            1,
+            alice_account(),
+            alice_account(),
+            &signer,
+            100,
+            CryptoHash::default(),
+        );
+        let result =
+            runtime.simulate_transaction(trie.clone(), root, &apply_state, &tx, &|_| true).unwrap();
+        // The transaction, the receipt and the refund of the gas.
+        assert_eq!(result.outcomes.len(), 3);
+        assert_eq!(result.outcomes[0].id, tx.get_hash());
+        assert_eq!(
+            result.gas_burnt,
+            result.outcomes.iter().map(|outcome| outcome.outcome.gas_burnt).sum::<Gas>()
+        );
+        assert!(result.unprocessed_receipts.is_empty());
+
+        let result = runtime
+            .simulate_transaction(trie.clone(), root, &apply_state, &tx, &|_| false)
+            .unwrap();
+        assert_eq!(result.outcomes.len(), 1);
+        assert_eq!(result.unprocessed_receipts.len(), 1);
+
+        // The state is left intact.
+        let state = TrieUpdate::new(trie.clone(), root);
+        let account = get_account(&state, &alice_account()).unwrap().unwrap();
+        assert_eq!(account.amount, initial_balance);
+    }
+
+    #[test]
+    fn test_verify_multisig_transaction() {
+        let (runtime, trie, root, apply_state) = setup_runtime(10u128.pow(24), 0, 10_000_000);
+        let signers = (0..3)
+            .map(|i| InMemorySigner::from_seed(&alice_account(), KeyType::ED25519, &i.to_string()))
+            .collect::<Vec<_>>();
+        let multisig_permission = MultisigPermission {
+            keys: signers
+                .iter()
+                .zip(vec![1, 1, 2])
+                .map(|(signer, weight)| WeightedKey { public_key: signer.public_key(), weight })
+                .collect(),
+            threshold: 3,
+        };
+        let mut state_update = TrieUpdate::new(trie.clone(), root);
+        set_access_key(
+            &mut state_update,
+            &alice_account(),
+            &signers[0].public_key,
+            &AccessKey { nonce: 0, permission: AccessKeyPermission::Multisig(multisig_permission) },
+        );
+        let full_access_signer =
+            InMemorySigner::from_seed(&alice_account(), KeyType::ED25519, &alice_account());
+        set_access_key(
+            &mut state_update,
+            &alice_account(),
+            &full_access_signer.public_key,
+            &AccessKey::full_access(),
+        );
+        let (store_update, root) = state_update.finalize().unwrap().into(trie.clone()).unwrap();
+        store_update.commit().unwrap();
+        let verify = |tx: &SignedTransaction| {
+            let mut state_update = TrieUpdate::new(trie.clone(), root);
+            runtime.verify_and_charge_transaction(&mut state_update, &apply_state, tx)
+        };
+
+        let mut tx = SignedTransaction::send_money(
+            1,
+            alice_account(),
+            bob_account(),
+            &signers[0],
+            100,
+            CryptoHash::default(),
+        );
+        tx.add_signature(&signers[1]);
+        assert_eq!(
+            verify(&tx).unwrap_err(),
+            RuntimeError::InvalidTxError(InvalidTxError::InvalidAccessKey(
+                InvalidAccessKeyError::NotEnoughSignatureWeight(
+                    alice_account(),
+                    signers[0].public_key(),
+                    2,
+                    3
+                )
+            ))
+        );
+
+        let mut forged_tx = tx.clone();
+        forged_tx.extra_signatures.push((signers[2].public_key(), tx.signature.clone()));
+        assert_eq!(
+            verify(&forged_tx).unwrap_err(),
+            RuntimeError::InvalidTxError(InvalidTxError::InvalidSignature)
+        );
+
+        let invalid_extra_signatures = RuntimeError::InvalidTxError(
+            InvalidTxError::InvalidAccessKey(InvalidAccessKeyError::InvalidExtraSignatures(
+                alice_account(),
+                signers[0].public_key(),
+            )),
+        );
+        let mut duplicate_tx = tx.clone();
+        duplicate_tx.add_signature(&signers[1]);
+        assert_eq!(verify(&duplicate_tx).unwrap_err(), invalid_extra_signatures);
+
+        let outsider = InMemorySigner::from_seed(&bob_account(), KeyType::ED25519, "outsider");
+        let mut outsider_tx = tx.clone();
+        outsider_tx.add_signature(&outsider);
+        assert_eq!(verify(&outsider_tx).unwrap_err(), invalid_extra_signatures);
+
+        let mut full_access_tx = SignedTransaction::send_money(
+            1,
+            alice_account(),
+            bob_account(),
+            &full_access_signer,
+            100,
+            CryptoHash::default(),
+        );
+        full_access_tx.add_signature(&signers[1]);
+        assert_eq!(
+            verify(&full_access_tx).unwrap_err(),
+            RuntimeError::InvalidTxError(InvalidTxError::InvalidAccessKey(
+                InvalidAccessKeyError::InvalidExtraSignatures(
+                    alice_account(),
+                    full_access_signer.public_key(),
+                )
+            ))
+        );
+
+        tx.add_signature(&signers[2]);
+        let fees = &runtime.config.transaction_costs;
+        let (gas_burnt, _, _) = tx_cost(fees, &tx.transaction, GAS_PRICE, false, 0).unwrap();
+        assert_eq!(
+            verify(&tx).unwrap().gas_burnt,
+            gas_burnt + 2 * fees.extra_signature_verification_cost
+        );
+    }
+
     fn generate_receipts(small_transfer: u128, n: u64) -> Vec<Receipt> {
         (0..n)
             .map(|i| Receipt {
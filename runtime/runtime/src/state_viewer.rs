@@ -1,5 +1,7 @@
+use std::cmp::min;
 use std::collections::HashMap;
 use std::str;
+use std::sync::Arc;
 use std::time::Instant;
 
 use borsh::BorshSerialize;
@@ -11,17 +13,28 @@ use near_primitives::types::AccountId;
 use near_primitives::utils::{is_valid_account_id, prefix_for_data};
 use near_primitives::views::ViewStateResult;
 use near_runtime_fees::RuntimeFeesConfig;
-use near_store::{get_access_key, get_account, TrieUpdate};
+use near_store::{get_access_key, get_account, TrieUpdate, TrieUpdateIterator};
 use near_vm_logic::{ReturnData, VMConfig, VMContext};
+use near_vm_runner::CompiledContractCache;
 
 use crate::actions::get_code_with_cache;
 use crate::ext::RuntimeExt;
 
-pub struct TrieViewer {}
+/// Max number of values returned by a single page of `view_state_page`.
+pub const MAX_VIEW_STATE_PAGE_SIZE: usize = 1000;
+
+pub struct TrieViewer {
+    /// Persistent cache of compiled contracts, if any.
+    cache: Option<Arc<dyn CompiledContractCache>>,
+}
 
 impl TrieViewer {
     pub fn new() -> Self {
-        Self {}
+        Self { cache: None }
+    }
+
+    pub fn new_with_cache(cache: Arc<dyn CompiledContractCache>) -> Self {
+        Self { cache: Some(cache) }
     }
 
     pub fn view_account(
@@ -56,21 +69,60 @@ impl TrieViewer {
         state_update: &TrieUpdate,
         account_id: &AccountId,
         prefix: &[u8],
+    ) -> Result<ViewStateResult, Box<dyn std::error::Error>> {
+        self.view_state_range(state_update, account_id, prefix, prefix, usize::max_value())
+    }
+
+    /// Returns at most `limit` values with the given prefix, starting from `start_key`, which must
+    /// have the prefix. If there are more values, `next_key` is the start key of the next page.
+    /// The limit must be positive and is capped at `MAX_VIEW_STATE_PAGE_SIZE`.
+    pub fn view_state_page(
+        &self,
+        state_update: &TrieUpdate,
+        account_id: &AccountId,
+        prefix: &[u8],
+        start_key: &[u8],
+        limit: usize,
+    ) -> Result<ViewStateResult, Box<dyn std::error::Error>> {
+        if limit == 0 {
+            return Err("Limit must be positive".into());
+        }
+        let limit = min(limit, MAX_VIEW_STATE_PAGE_SIZE);
+        self.view_state_range(state_update, account_id, prefix, start_key, limit)
+    }
+
+    fn view_state_range(
+        &self,
+        state_update: &TrieUpdate,
+        account_id: &AccountId,
+        prefix: &[u8],
+        start_key: &[u8],
+        limit: usize,
     ) -> Result<ViewStateResult, Box<dyn std::error::Error>> {
         if !is_valid_account_id(account_id) {
             return Err(format!("Account ID '{}' is not valid", account_id).into());
         }
+        if !start_key.starts_with(prefix) {
+            return Err("Start key must start with the prefix".into());
+        }
         let mut values = HashMap::default();
+        let mut next_key = None;
         let mut query = prefix_for_data(account_id);
         let acc_sep_len = query.len();
         query.extend_from_slice(prefix);
-        state_update.for_keys_with_prefix(&query, |key| {
-            // TODO error
-            if let Ok(Some(value)) = state_update.get(key) {
+        // Seeks the trie to the start key, so the keys before it are not even read.
+        let iter = TrieUpdateIterator::new(state_update, &query, &start_key[prefix.len()..], None)?;
+        for key in iter {
+            let key = key?;
+            if values.len() == limit {
+                next_key = Some(key[acc_sep_len..].to_vec());
+                break;
+            }
+            if let Some(value) = state_update.get(&key)? {
                 values.insert(key[acc_sep_len..].to_vec(), value.to_vec());
             }
-        })?;
-        Ok(ViewStateResult { values })
+        }
+        Ok(ViewStateResult { values, next_key })
     }
 
     pub fn call_function(
@@ -125,7 +177,7 @@ impl TrieViewer {
                 output_data_receivers: vec![],
             };
 
-            near_vm_runner::run(
+            near_vm_runner::run_with_cache(
                 code.hash.as_ref().to_vec(),
                 &code.code,
                 method_name.as_bytes(),
@@ -134,6 +186,7 @@ impl TrieViewer {
                 &VMConfig::default(),
                 &RuntimeFeesConfig::default(),
                 &[],
+                self.cache.as_ref().map(|cache| &**cache),
             )
         };
         let elapsed = now.elapsed();
@@ -250,4 +303,62 @@ mod tests {
             [(b"test123".to_vec(), b"123".to_vec())].iter().cloned().collect()
         )
     }
+
+    #[test]
+    fn test_view_state_page() {
+        let (_, trie, root) = get_runtime_and_trie();
+        let mut state_update = TrieUpdate::new(trie.clone(), root);
+        for i in 0..10u8 {
+            state_update.set(key_for_data(&alice_account(), &[b'k', i]), vec![i]);
+        }
+        state_update.set(key_for_data(&alice_account(), b"z"), b"z".to_vec());
+        let (db_changes, new_root) = state_update.finalize().unwrap().into(trie.clone()).unwrap();
+        db_changes.commit().unwrap();
+
+        let state_update = TrieUpdate::new(trie, new_root);
+        let trie_viewer = TrieViewer::new();
+        let mut values = HashMap::new();
+        let mut start_key = b"k".to_vec();
+        let mut pages = 0;
+        loop {
+            let result = trie_viewer
+                .view_state_page(&state_update, &alice_account(), b"k", &start_key, 3)
+                .unwrap();
+            assert!(result.values.len() <= 3);
+            values.extend(result.values);
+            pages += 1;
+            match result.next_key {
+                Some(next_key) => start_key = next_key,
+                None => break,
+            }
+        }
+        assert_eq!(pages, 4);
+        assert_eq!(values, (0..10u8).map(|i| (vec![b'k', i], vec![i])).collect());
+
+        let result = trie_viewer
+            .view_state_page(&state_update, &alice_account(), b"", &[b'k', 8], 3)
+            .unwrap();
+        assert_eq!(
+            result.values,
+            vec![
+                (vec![b'k', 8], vec![8]),
+                (vec![b'k', 9], vec![9]),
+                (b"z".to_vec(), b"z".to_vec())
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(result.next_key, None);
+        assert!(trie_viewer
+            .view_state_page(&state_update, &alice_account(), b"k", b"a", 3)
+            .is_err());
+        assert!(trie_viewer
+            .view_state_page(&state_update, &alice_account(), b"k", b"k", 0)
+            .is_err());
+        let result = trie_viewer
+            .view_state_page(&state_update, &alice_account(), b"k", b"k", usize::max_value())
+            .unwrap();
+        assert_eq!(result.values.len(), 10);
+        assert_eq!(result.next_key, None);
+    }
 }
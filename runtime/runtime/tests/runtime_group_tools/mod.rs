@@ -56,6 +56,7 @@ impl StandaloneRuntime {
             gas_price: 100,
             block_timestamp: 0,
             gas_limit: None,
+            cache: None,
         };
 
         Self { apply_state, runtime, trie, signer, root: root }
@@ -31,6 +31,8 @@ pub fn random_config() -> RuntimeConfig {
                     full_access_cost: random_fee(),
                     function_call_cost: random_fee(),
                     function_call_cost_per_byte: random_fee(),
+                    multisig_cost: random_fee(),
+                    multisig_cost_per_key: random_fee(),
                 },
                 delete_key_cost: random_fee(),
                 delete_account_cost: random_fee(),
@@ -42,6 +44,7 @@ pub fn random_config() -> RuntimeConfig {
                 value_cost_per_byte: rng.next_u64() % 100,
                 code_cost_per_byte: rng.next_u64() % 100,
             },
+            extra_signature_verification_cost: rng.next_u64() % 1000,
             burnt_gas_reward: Fraction { numerator: rng.next_u64() % 100, denominator: 100 },
         },
         ..Default::default()
@@ -0,0 +1,14 @@
+#!/usr/bin/env python
+
+# Migrate the genesis config from protocol version 4 to 6. Versions 5 and 6 only change the network
+# protocol: all the connections are encrypted with Noise, and the handshake announces whether the
+# node accepts compressed frames, so nodes of version 4 can't connect.
+
+import json
+import os
+
+filename = os.path.join(os.path.dirname(os.path.realpath(__file__)), '../../near/res/testnet.json')
+q = json.loads(open(filename).read())
+assert q['protocol_version'] == 4, q['protocol_version']
+q['protocol_version'] = 6
+open(filename + '.v6', 'w').write(json.dumps(q, indent=2))
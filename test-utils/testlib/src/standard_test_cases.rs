@@ -2,10 +2,13 @@ use std::sync::Arc;
 
 use near::config::{TESTING_INIT_BALANCE, TESTING_INIT_STAKE};
 use near_crypto::{InMemorySigner, KeyType};
-use near_primitives::account::{AccessKey, AccessKeyPermission, FunctionCallPermission};
+use near_primitives::account::{
+    AccessKey, AccessKeyPermission, FunctionCallPermission, MultisigPermission, WeightedKey,
+};
 use near_primitives::errors::{ActionError, InvalidAccessKeyError, InvalidTxError};
 use near_primitives::hash::hash;
 use near_primitives::serialize::to_base64;
+use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::Balance;
 use near_primitives::views::FinalExecutionStatus;
 use near_primitives::views::{AccountView, FinalExecutionOutcomeView};
@@ -583,6 +586,78 @@ pub fn test_add_existing_key(node: impl Node) {
     assert!(node_user.get_access_key(&account_id, &node.signer().public_key()).is_ok());
 }
 
+pub fn test_add_invalid_multisig_key(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let node_user = node.user();
+    let signer2 = InMemorySigner::from_random("".to_string(), KeyType::ED25519);
+    let access_key = AccessKey {
+        nonce: 0,
+        permission: AccessKeyPermission::Multisig(MultisigPermission {
+            keys: vec![WeightedKey { public_key: signer2.public_key.clone(), weight: 1 }],
+            threshold: 2,
+        }),
+    };
+    let transaction_result =
+        node_user.add_key(account_id.clone(), signer2.public_key.clone(), access_key).unwrap();
+    assert_eq!(
+        transaction_result.status,
+        FinalExecutionStatus::Failure(
+            ActionError::AddKeyInvalidMultisig(signer2.public_key.clone()).into()
+        )
+    );
+    assert!(node_user.get_access_key(&account_id, &signer2.public_key).is_err());
+}
+
+pub fn test_multisig_transaction(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let node_user = node.user();
+    let signers: Vec<_> =
+        (0..3).map(|_| InMemorySigner::from_random("".to_string(), KeyType::ED25519)).collect();
+    let access_key = AccessKey {
+        nonce: 0,
+        permission: AccessKeyPermission::Multisig(MultisigPermission {
+            keys: signers
+                .iter()
+                .map(|signer| WeightedKey { public_key: signer.public_key.clone(), weight: 1 })
+                .collect(),
+            threshold: 2,
+        }),
+    };
+    add_access_key(&node, node_user.as_ref(), &access_key, &signers[0]);
+
+    let money_used = 10000;
+    let block_hash = node_user.get_best_block_hash().unwrap_or_default();
+    let mut transaction = SignedTransaction::send_money(
+        1,
+        account_id.clone(),
+        bob_account(),
+        &signers[0],
+        money_used,
+        block_hash,
+    );
+    let transaction_result = node_user.commit_transaction(transaction.clone()).unwrap_err();
+    assert_eq!(
+        transaction_result,
+        format!(
+            "{}",
+            InvalidTxError::InvalidAccessKey(InvalidAccessKeyError::NotEnoughSignatureWeight(
+                account_id.clone(),
+                signers[0].public_key.clone(),
+                1,
+                2
+            ))
+        )
+    );
+
+    transaction.add_signature(&signers[1]);
+    let transaction_result = node_user.commit_transaction(transaction).unwrap();
+    assert_eq!(transaction_result.status, FinalExecutionStatus::SuccessValue(to_base64(&[])));
+    let view_access_key = node_user.get_access_key(account_id, &signers[0].public_key).unwrap();
+    assert_eq!(view_access_key.nonce, 1);
+    let AccountView { amount, .. } = node_user.view_account(&bob_account()).unwrap();
+    assert_eq!(amount, TESTING_INIT_BALANCE + money_used - TESTING_INIT_STAKE);
+}
+
 pub fn test_delete_key(node: impl Node) {
     let account_id = &node.account_id().unwrap();
     let signer2 = InMemorySigner::from_random("".to_string(), KeyType::ED25519);
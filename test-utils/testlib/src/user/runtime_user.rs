@@ -114,6 +114,7 @@ impl RuntimeUser {
             epoch_length: client.epoch_length,
             gas_price: MIN_GAS_PRICE,
             gas_limit: None,
+            cache: None,
         }
     }
 
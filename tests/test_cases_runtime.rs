@@ -194,6 +194,18 @@ mod test {
         test_add_existing_key(node);
     }
 
+    #[test]
+    fn test_add_invalid_multisig_key_runtime() {
+        let node = create_runtime_node();
+        test_add_invalid_multisig_key(node);
+    }
+
+    #[test]
+    fn test_multisig_transaction_runtime() {
+        let node = create_runtime_node();
+        test_multisig_transaction(node);
+    }
+
     #[test]
     fn test_delete_key_runtime() {
         let node = create_runtime_node();
@@ -157,6 +157,16 @@ mod test {
         run_testnet_test!(test_add_existing_key);
     }
 
+    #[test]
+    fn test_add_invalid_multisig_key_testnet() {
+        run_testnet_test!(test_add_invalid_multisig_key);
+    }
+
+    #[test]
+    fn test_multisig_transaction_testnet() {
+        run_testnet_test!(test_multisig_transaction);
+    }
+
     #[test]
     fn test_delete_key_testnet() {
         run_testnet_test!(test_delete_key);